@@ -43,6 +43,72 @@ export function storeCredentials(creds: Credentials | null) {
   localStorage.setItem(storageKeys.creds, JSON.stringify(creds));
 }
 
+export interface ProblemDetails {
+  type: string;
+  title: string;
+  status: number;
+  code: string;
+  detail: string;
+  request_id?: string;
+}
+
+/** Human-readable message for a failed response, from its problem document if any. */
+export async function errorMessage(res: Response, fallback: string): Promise<string> {
+  try {
+    const problem = (await res.json()) as Partial<ProblemDetails>;
+    if (problem.detail) {
+      return problem.request_id
+        ? `${problem.detail} (request id: ${problem.request_id})`
+        : problem.detail;
+    }
+  } catch {
+    // not a problem document
+  }
+  return fallback;
+}
+
+/**
+ * Follow `GET /api/zone/events`, calling `onEvent` with each event name.
+ * EventSource cannot send Basic credentials, so the stream is read via fetch.
+ * Reconnects after a short delay; returns a function that stops listening.
+ */
+export function subscribeZoneEvents(
+  authHeader: string,
+  onEvent: (type: string) => void,
+): () => void {
+  const controller = new AbortController();
+  const run = async () => {
+    while (!controller.signal.aborted) {
+      try {
+        const res = await fetch(joinApiUrl("/api/zone/events"), {
+          headers: { Authorization: authHeader, Accept: "text/event-stream" },
+          signal: controller.signal,
+        });
+        if (!res.ok || !res.body) throw new Error(`event stream failed with ${res.status}`);
+        const reader = res.body.pipeThrough(new TextDecoderStream()).getReader();
+        let buffered = "";
+        for (;;) {
+          const { value, done } = await reader.read();
+          if (done) break;
+          buffered += value;
+          const messages = buffered.split("\n\n");
+          buffered = messages.pop() ?? "";
+          for (const message of messages) {
+            const line = message.split("\n").find((l) => l.startsWith("event:"));
+            if (line) onEvent(line.slice("event:".length).trim());
+          }
+        }
+      } catch (err) {
+        if (controller.signal.aborted) return;
+        console.warn("zone event stream interrupted", err);
+      }
+      await new Promise((resolve) => setTimeout(resolve, 5000));
+    }
+  };
+  void run();
+  return () => controller.abort();
+}
+
 export function compareDomain(d1: string, d2: string): -1 | 0 | 1 {
   const d1Parts = String(d1).replace(/\.$/, '').split('.').reverse();
   const d2Parts = String(d2).replace(/\.$/, '').split('.').reverse();
@@ -95,6 +161,19 @@ export interface ParentSoaResponse {
   soa: string;
 }
 
+export interface DomainStats {
+  delegations: number;
+  internal_ns: number;
+  external_ns: number;
+}
+
+export interface InstanceStats extends DomainStats {
+  domains: Record<string, DomainStats>;
+  newest_signup_at: string | null;
+  newest_signup_age_secs: number | null;
+  computed_at: string;
+}
+
 export const decodeDomain = (value: string) => {
   const trimmed = value.trim();
   if (!trimmed) {
@@ -4,6 +4,8 @@ import {
   API_BASE,
   compareDomain,
   decodeDomain,
+  errorMessage,
+  InstanceStats,
   joinApiUrl,
   NsListEntry,
   ParentSoaResponse,
@@ -29,6 +31,7 @@ export default function LandingPage() {
   const [nsError, setNsError] = useState<string | null>(null);
   const [soaLine, setSoaLine] = useState<string | null>(null);
   const [soaError, setSoaError] = useState<string | null>(null);
+  const [stats, setStats] = useState<InstanceStats | null>(null);
 
   const [subdomain, setSubdomain] = useState("");
   const [availability, setAvailability] = useState<AvailabilityState>({
@@ -53,6 +56,19 @@ export default function LandingPage() {
     loadAbout();
   }, []);
 
+  useEffect(() => {
+    const loadStats = async () => {
+      try {
+        const res = await fetch(joinApiUrl("/api/stats"));
+        if (!res.ok) throw new Error(`Stats request failed: ${res.status}`);
+        setStats((await res.json()) as InstanceStats);
+      } catch (err) {
+        console.error(err);
+      }
+    };
+    loadStats();
+  }, []);
+
   useEffect(() => {
     const loadNs = async () => {
       try {
@@ -173,8 +189,7 @@ export default function LandingPage() {
           body: JSON.stringify({ subdomain: trimmed, password }),
         });
         if (!res.ok) {
-          const text = await res.text();
-          throw new Error(text || "Sign-in failed");
+          throw new Error(await errorMessage(res, "Sign-in failed"));
         }
         signIn({ subdomain: trimmed, password });
         navigate("/manage");
@@ -195,8 +210,7 @@ export default function LandingPage() {
           body: JSON.stringify({ subdomain: trimmed, password }),
         });
         if (!res.ok) {
-          const text = await res.text();
-          throw new Error(text || "Signup failed");
+          throw new Error(await errorMessage(res, "Signup failed"));
         }
         // automatically sign in
         const signinRes = await fetch(joinApiUrl("/api/signin"), {
@@ -231,13 +245,17 @@ export default function LandingPage() {
   }, [baseDomain]);
 
   const delegatedCount = useMemo(() => {
+    const domainStats = baseDomain ? stats?.domains[baseDomain] : undefined;
+    if (domainStats) {
+      return domainStats.delegations;
+    }
     if (!normalizedParentZone) {
       return nsList.length;
     }
     return nsList.filter(
       (entry) => entry.name.trim().toLowerCase() !== normalizedParentZone,
     ).length;
-  }, [nsList, normalizedParentZone]);
+  }, [nsList, normalizedParentZone, stats, baseDomain]);
 
   const manageLabel =
     credentials && decodedBaseDomain
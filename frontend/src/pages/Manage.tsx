@@ -1,13 +1,15 @@
-import { useEffect, useMemo, useState } from "react";
+import { useEffect, useMemo, useRef, useState } from "react";
 import { Link, useNavigate } from "react-router-dom";
 import {
   API_BASE,
   buildBasicAuthHeader,
   decodeDomain,
+  errorMessage,
   joinApiUrl,
   ProfileDto,
   RecordDto,
   RTYPES,
+  subscribeZoneEvents,
 } from "../lib/api.js";
 import { useAuth } from "../App.js";
 import { toASCII, toUnicode } from "punycode";
@@ -222,6 +224,18 @@ export default function ManagePage() {
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [profile?.external_ns, baseDomain]);
 
+  // the stream outlives renders, so it calls the latest fetchers through a ref
+  const refresh = useRef({ fetchZoneRecords, fetchProfile });
+  refresh.current = { fetchZoneRecords, fetchProfile };
+  useEffect(
+    () =>
+      subscribeZoneEvents(authHeader, (type) => {
+        if (type === "records.changed" || type === "lagged") refresh.current.fetchZoneRecords();
+        if (type === "ns_mode.changed" || type === "lagged") refresh.current.fetchProfile();
+      }),
+    [authHeader],
+  );
+
   const handleToggleNsMode = async (checked: boolean) => {
     if (!profile) return;
     setProfileMessage(null);
@@ -231,7 +245,9 @@ export default function ManagePage() {
           method: "POST",
           headers: { Authorization: authHeader },
         });
-        if (!res.ok) throw new Error("Failed to switch to internal NS");
+        if (!res.ok) {
+          throw new Error(await errorMessage(res, "Failed to switch to internal NS"));
+        }
         await fetchProfile();
       } else {
         setProfile((prev) => (prev ? { ...prev, external_ns: true } : prev));
@@ -261,7 +277,9 @@ export default function ManagePage() {
         },
         body: JSON.stringify({ ns: filtered }),
       });
-      if (!res.ok) throw new Error("Failed to update nameservers");
+      if (!res.ok) {
+        throw new Error(await errorMessage(res, "Failed to update nameservers"));
+      }
       await fetchProfile();
       setProfileMessage("Nameservers updated.");
     } catch (err) {
@@ -344,8 +362,7 @@ export default function ManagePage() {
         }),
       });
       if (!res.ok) {
-        const text = await res.text();
-        throw new Error(text || "Failed to change password");
+        throw new Error(await errorMessage(res, "Failed to change password"));
       }
       setPasswordMessage("Password updated.");
       setPasswordForm({ current: "", next: "", confirm: "" });
@@ -386,7 +403,9 @@ export default function ManagePage() {
         },
         body: JSON.stringify({ records: payload }),
       });
-      if (!res.ok) throw new Error("Failed to update DNS zone");
+      if (!res.ok) {
+        throw new Error(await errorMessage(res, "Failed to update DNS zone"));
+      }
       setRecordsMessage("DNS records updated.");
       await fetchZoneRecords();
     } catch (err) {
@@ -0,0 +1,34 @@
+-- migrations/0002_multi_domain.sql
+-- Labels are unique per base domain rather than globally. Existing rows get an
+-- empty base_domain which the server backfills with its default domain on boot.
+CREATE TABLE users_new (
+  id              INTEGER PRIMARY KEY AUTOINCREMENT,
+  subdomain       TEXT NOT NULL,
+  base_domain     TEXT NOT NULL DEFAULT '',
+  password_hash   TEXT NOT NULL,
+  external_ns     INTEGER NOT NULL DEFAULT 0,
+  external_ns1    TEXT,
+  external_ns2    TEXT,
+  external_ns3    TEXT,
+  external_ns4    TEXT,
+  external_ns5    TEXT,
+  external_ns6    TEXT,
+  created_at      TEXT NOT NULL,
+  updated_at      TEXT NOT NULL,
+  last_login_at   TEXT,
+  UNIQUE (subdomain, base_domain)
+);
+
+INSERT INTO users_new (
+  id, subdomain, base_domain, password_hash, external_ns,
+  external_ns1, external_ns2, external_ns3, external_ns4, external_ns5, external_ns6,
+  created_at, updated_at, last_login_at
+)
+SELECT
+  id, subdomain, '', password_hash, external_ns,
+  external_ns1, external_ns2, external_ns3, external_ns4, external_ns5, external_ns6,
+  created_at, updated_at, last_login_at
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
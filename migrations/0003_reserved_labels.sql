@@ -0,0 +1,6 @@
+-- migrations/0003_reserved_labels.sql
+CREATE TABLE IF NOT EXISTS reserved_labels (
+  label       TEXT PRIMARY KEY,
+  reason      TEXT,
+  created_at  TEXT NOT NULL
+);
@@ -0,0 +1,23 @@
+-- migrations/0004_external_nameservers.sql
+-- Move external NS hostnames out of the fixed users.external_ns1..6 columns.
+CREATE TABLE IF NOT EXISTS external_nameservers (
+  user_id   INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  position  INTEGER NOT NULL,
+  hostname  TEXT NOT NULL,
+  PRIMARY KEY (user_id, position)
+);
+
+INSERT INTO external_nameservers (user_id, position, hostname)
+SELECT id, 0, external_ns1 FROM users WHERE external_ns1 IS NOT NULL
+UNION ALL SELECT id, 1, external_ns2 FROM users WHERE external_ns2 IS NOT NULL
+UNION ALL SELECT id, 2, external_ns3 FROM users WHERE external_ns3 IS NOT NULL
+UNION ALL SELECT id, 3, external_ns4 FROM users WHERE external_ns4 IS NOT NULL
+UNION ALL SELECT id, 4, external_ns5 FROM users WHERE external_ns5 IS NOT NULL
+UNION ALL SELECT id, 5, external_ns6 FROM users WHERE external_ns6 IS NOT NULL;
+
+ALTER TABLE users DROP COLUMN external_ns1;
+ALTER TABLE users DROP COLUMN external_ns2;
+ALTER TABLE users DROP COLUMN external_ns3;
+ALTER TABLE users DROP COLUMN external_ns4;
+ALTER TABLE users DROP COLUMN external_ns5;
+ALTER TABLE users DROP COLUMN external_ns6;
@@ -0,0 +1,8 @@
+-- migrations/0005_invites.sql
+CREATE TABLE IF NOT EXISTS invites (
+  code        TEXT PRIMARY KEY,
+  note        TEXT,
+  created_at  TEXT NOT NULL,
+  used_at     TEXT,
+  used_by     TEXT
+);
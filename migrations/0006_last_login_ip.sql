@@ -0,0 +1,2 @@
+-- migrations/0006_last_login_ip.sql
+ALTER TABLE users ADD COLUMN last_login_ip TEXT;
@@ -0,0 +1,22 @@
+-- migrations/0007_webhooks.sql
+CREATE TABLE IF NOT EXISTS webhooks (
+  id          INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id     INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  url         TEXT NOT NULL,
+  secret      TEXT NOT NULL,
+  created_at  TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhooks_user_id ON webhooks (user_id);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  webhook_id   INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+  event_type   TEXT NOT NULL,
+  attempt      INTEGER NOT NULL,
+  status_code  INTEGER,
+  error        TEXT,
+  created_at   TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook_id ON webhook_deliveries (webhook_id, id);
@@ -0,0 +1,11 @@
+-- migrations/0008_change_history.sql
+CREATE TABLE IF NOT EXISTS change_history (
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id      INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  zone         TEXT NOT NULL,
+  event_type   TEXT NOT NULL,
+  payload      TEXT NOT NULL,
+  occurred_at  TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS change_history_user_id ON change_history (user_id, id);
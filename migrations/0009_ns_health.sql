@@ -0,0 +1,13 @@
+-- Latest SOA probe of each external nameserver a user delegates to.
+CREATE TABLE IF NOT EXISTS ns_health (
+  user_id               INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  hostname              TEXT NOT NULL,
+  status                TEXT NOT NULL,
+  detail                TEXT,
+  serial                INTEGER,
+  consecutive_failures  INTEGER NOT NULL DEFAULT 0,
+  failing_since         TEXT,
+  notified              INTEGER NOT NULL DEFAULT 0,
+  checked_at            TEXT NOT NULL,
+  PRIMARY KEY (user_id, hostname)
+);
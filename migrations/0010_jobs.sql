@@ -0,0 +1,12 @@
+-- Run history of the scheduled background jobs.
+CREATE TABLE IF NOT EXISTS jobs (
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  name         TEXT NOT NULL,
+  started_at   TEXT NOT NULL,
+  finished_at  TEXT NOT NULL,
+  duration_ms  INTEGER NOT NULL,
+  outcome      TEXT NOT NULL,
+  detail       TEXT
+);
+
+CREATE INDEX IF NOT EXISTS jobs_name ON jobs (name, id);
@@ -0,0 +1,19 @@
+-- Parent-zone delegations with neither a user nor a sub-PDNS zone, kept
+-- until their quarantine ends.
+CREATE TABLE IF NOT EXISTS dangling_delegations (
+  name           TEXT PRIMARY KEY,
+  base_domain    TEXT NOT NULL,
+  nameservers    TEXT NOT NULL,
+  first_seen_at  TEXT NOT NULL,
+  last_seen_at   TEXT NOT NULL
+);
+
+-- Actions taken by operators and background jobs rather than by users.
+CREATE TABLE IF NOT EXISTS audit_log (
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  actor        TEXT NOT NULL,
+  action       TEXT NOT NULL,
+  target       TEXT NOT NULL,
+  details      TEXT NOT NULL,
+  occurred_at  TEXT NOT NULL
+);
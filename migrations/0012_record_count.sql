@@ -0,0 +1,3 @@
+-- User records per zone as of the last successful update; NULL until the
+-- zone is next written.
+ALTER TABLE users ADD COLUMN record_count INTEGER;
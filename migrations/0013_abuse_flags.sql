@@ -0,0 +1,16 @@
+-- Zones flagged for human review, one row per user and reason. A dismissed
+-- flag keeps its row so the scanner does not raise it again.
+CREATE TABLE IF NOT EXISTS abuse_flags (
+  id               INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id          INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  zone             TEXT NOT NULL,
+  reason           TEXT NOT NULL,
+  details          TEXT NOT NULL,
+  status           TEXT NOT NULL DEFAULT 'open',
+  first_flagged_at TEXT NOT NULL,
+  last_flagged_at  TEXT NOT NULL,
+  reviewed_at      TEXT,
+  UNIQUE (user_id, reason)
+);
+
+CREATE INDEX IF NOT EXISTS abuse_flags_status ON abuse_flags (status, id);
@@ -0,0 +1,13 @@
+-- Client address of each signup in the last day, for the per-address and
+-- per-network caps. Rows outlive their account so deleting it does not free
+-- a slot.
+CREATE TABLE IF NOT EXISTS signup_sources (
+  id         INTEGER PRIMARY KEY AUTOINCREMENT,
+  client_ip  TEXT NOT NULL,
+  subnet     TEXT NOT NULL,
+  zone       TEXT NOT NULL,
+  created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS signup_sources_ip ON signup_sources (client_ip, created_at);
+CREATE INDEX IF NOT EXISTS signup_sources_subnet ON signup_sources (subnet, created_at);
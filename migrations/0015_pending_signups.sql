@@ -0,0 +1,18 @@
+-- Signups still being provisioned. A row holds the label until its saga
+-- either creates the user row or is rolled back; `step` is the last step
+-- that completed.
+CREATE TABLE IF NOT EXISTS pending_signups (
+  id             INTEGER PRIMARY KEY AUTOINCREMENT,
+  subdomain      TEXT NOT NULL,
+  base_domain    TEXT NOT NULL,
+  password_hash  TEXT NOT NULL,
+  invite_code    TEXT,
+  flag_reason    TEXT,
+  flag_similar   TEXT,
+  step           TEXT NOT NULL,
+  attempts       INTEGER NOT NULL DEFAULT 0,
+  last_error     TEXT,
+  created_at     TEXT NOT NULL,
+  updated_at     TEXT NOT NULL,
+  UNIQUE (subdomain, base_domain)
+);
@@ -0,0 +1,11 @@
+-- NS RRsets of the parent zones, mirrored from base-PDNS so public listings
+-- need not read the zones. Written with each delegation change and replaced
+-- wholesale by every reconciliation scan.
+CREATE TABLE IF NOT EXISTS delegations (
+  name         TEXT PRIMARY KEY,
+  base_domain  TEXT NOT NULL,
+  nameservers  TEXT NOT NULL,
+  updated_at   TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS delegations_base_domain ON delegations (base_domain, name);
@@ -0,0 +1,11 @@
+-- Notes and tag labels users attach to their RRsets, keyed by lowercase
+-- owner name and type. Kept here because PowerDNS comments are limited.
+CREATE TABLE IF NOT EXISTS record_notes (
+  user_id     INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name        TEXT NOT NULL,
+  rrtype      TEXT NOT NULL,
+  note        TEXT,
+  labels      TEXT NOT NULL DEFAULT '[]',
+  updated_at  TEXT NOT NULL,
+  PRIMARY KEY (user_id, name, rrtype)
+);
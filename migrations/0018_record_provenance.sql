@@ -0,0 +1,12 @@
+-- Which credential created and last modified each RRset, keyed like
+-- record_notes by lowercase owner name and type.
+CREATE TABLE IF NOT EXISTS record_provenance (
+  user_id      INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name         TEXT NOT NULL,
+  rrtype       TEXT NOT NULL,
+  created_by   TEXT NOT NULL,
+  created_at   TEXT NOT NULL,
+  modified_by  TEXT NOT NULL,
+  modified_at  TEXT NOT NULL,
+  PRIMARY KEY (user_id, name, rrtype)
+);
@@ -0,0 +1,16 @@
+-- Rendered messages waiting to be sent. Sent messages are deleted; those
+-- that failed for good keep failed_at for inspection.
+CREATE TABLE IF NOT EXISTS mail_outbox (
+  id               INTEGER PRIMARY KEY AUTOINCREMENT,
+  recipient        TEXT NOT NULL,
+  template         TEXT NOT NULL,
+  subject          TEXT NOT NULL,
+  body             TEXT NOT NULL,
+  attempts         INTEGER NOT NULL DEFAULT 0,
+  last_error       TEXT,
+  next_attempt_at  TEXT NOT NULL,
+  failed_at        TEXT,
+  created_at       TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS mail_outbox_due ON mail_outbox (failed_at, next_attempt_at);
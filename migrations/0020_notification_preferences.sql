@@ -0,0 +1,11 @@
+-- How each user wants to hear about notable events. `channels` is a JSON
+-- object mapping categories to `email`, `webhook`, or `none`; users without
+-- a row get the defaults.
+CREATE TABLE IF NOT EXISTS notification_preferences (
+  user_id     INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  email       TEXT,
+  channels    TEXT NOT NULL DEFAULT '{}',
+  updated_at  TEXT NOT NULL
+);
+
+ALTER TABLE users ADD COLUMN expiry_reminded_at TEXT;
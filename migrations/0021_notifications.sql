@@ -0,0 +1,14 @@
+-- In-app inbox: every notification a user received, whatever channel
+-- delivered it elsewhere. Only the newest 200 per user are kept.
+CREATE TABLE IF NOT EXISTS notifications (
+  id          INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id     INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  category    TEXT NOT NULL,
+  event_type  TEXT NOT NULL,
+  subject     TEXT NOT NULL,
+  body        TEXT NOT NULL,
+  created_at  TEXT NOT NULL,
+  read_at     TEXT
+);
+
+CREATE INDEX IF NOT EXISTS notifications_user_id ON notifications (user_id, id);
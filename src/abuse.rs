@@ -0,0 +1,196 @@
+//! Heuristic scan of user zones that puts suspicious ones into the admin
+//! abuse queue for a human to review. Nothing is changed automatically.
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::AppState;
+use crate::alerts::{self, Alert, AlertKind};
+use crate::config::{AbuseScanConfig, PolicyConfig};
+use crate::db::{abuse_repo, audit_repo, history_repo, user_repo};
+use crate::jobs::{Job, Schedule};
+use crate::powerdns::types::PdnsRrset;
+use crate::reputation::{self, Target};
+
+/// Audit log actor for flags raised by the scan.
+const ACTOR: &str = "abuse_scan";
+
+/// Owner names listed as evidence of a `similar_names` flag.
+const SIMILAR_NAMES_SAMPLE: usize = 5;
+
+/// Scheduled scans, per `[abuse_scan]` in the policy.
+pub struct AbuseScanJob;
+
+#[async_trait]
+impl Job for AbuseScanJob {
+    fn name(&self) -> &'static str {
+        "abuse_scan"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.abuse_scan;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let (scanned, flagged) = scan(state).await?;
+        Ok(format!(
+            "scanned {scanned} zones, raised {flagged} new flag(s)"
+        ))
+    }
+}
+
+/// Operator alert for a zone newly put into the review queue.
+pub fn flagged_alert(zone: &str, reason: &str, details: &serde_json::Value) -> Alert {
+    Alert::new(
+        AlertKind::AbuseFlagged,
+        zone,
+        format!("{zone} was flagged for review: {reason}"),
+        details.clone(),
+    )
+}
+
+/// Check every user zone; returns the zones scanned and the new flags raised.
+pub async fn scan(state: &AppState) -> anyhow::Result<(usize, usize)> {
+    let config = state.policy().abuse_scan.clone();
+    let since = Utc::now() - chrono::Duration::hours(i64::from(config.churn_window_hours));
+    let changes = history_repo::count_by_user_since(&state.db, "records.changed", since).await?;
+
+    let mut scanned = 0;
+    let mut flagged = 0;
+    for user in user_repo::list_all(&state.db).await? {
+        let zone = state
+            .config
+            .user_zone_name(&user.subdomain, &user.base_domain);
+        let rrsets = match state.sub_pdns_for(&user.base_domain).get_zone(&zone).await {
+            Ok(found) => found.rrsets.unwrap_or_default(),
+            Err(err) => {
+                // a missing zone is the reconciler's business
+                tracing::debug!("abuse scan: skipping {zone}: {err:#}");
+                continue;
+            }
+        };
+        scanned += 1;
+
+        let churn = changes.get(&user.id).copied().unwrap_or_default();
+        for (reason, details) in findings(&config, &zone, &rrsets, churn) {
+            if abuse_repo::flag(&state.db, user.id, &zone, reason, &details).await? {
+                flagged += 1;
+                tracing::warn!("abuse scan flagged {zone}: {reason}");
+                alerts::raise(state, flagged_alert(&zone, reason, &details));
+                audit_repo::insert(&state.db, ACTOR, "abuse.flagged", &zone, &details).await?;
+            }
+        }
+    }
+    Ok((scanned, flagged))
+}
+
+/// The heuristics that fire for one zone, with their evidence.
+fn findings(
+    config: &AbuseScanConfig,
+    zone: &str,
+    rrsets: &[PdnsRrset],
+    churn: i64,
+) -> Vec<(&'static str, serde_json::Value)> {
+    let mut found = Vec::new();
+
+    let mut shapes: HashMap<String, Vec<&str>> = HashMap::new();
+    for rrset in rrsets {
+        if rrset.name.eq_ignore_ascii_case(zone) {
+            continue;
+        }
+        let names = shapes.entry(name_shape(&rrset.name)).or_default();
+        if !names.contains(&rrset.name.as_str()) {
+            names.push(&rrset.name);
+        }
+    }
+    if let Some((shape, names)) = shapes
+        .into_iter()
+        .max_by_key(|(_, names)| names.len())
+        .filter(|(_, names)| names.len() >= config.similar_names)
+    {
+        found.push((
+            "similar_names",
+            json!({
+                "reason": "similar_names",
+                "pattern": shape,
+                "count": names.len(),
+                "examples": &names[..SIMILAR_NAMES_SAMPLE.min(names.len())],
+            }),
+        ));
+    }
+
+    let targets: Vec<String> = rrsets
+        .iter()
+        .flat_map(|rrset| {
+            rrset
+                .records
+                .iter()
+                .filter(|record| is_flagged_target(config, &rrset.rrtype, &record.content))
+                .map(move |record| format!("{} {} {}", rrset.name, rrset.rrtype, record.content))
+        })
+        .collect();
+    if !targets.is_empty() {
+        found.push((
+            "flagged_target",
+            json!({ "reason": "flagged_target", "records": targets }),
+        ));
+    }
+
+    let low_ttl = rrsets
+        .iter()
+        .filter(|rrset| !rrset.name.eq_ignore_ascii_case(zone))
+        .filter(|rrset| rrset.ttl <= config.churn_max_ttl)
+        .count();
+    if low_ttl > 0 && churn >= config.churn_changes {
+        found.push((
+            "ttl_churn",
+            json!({
+                "reason": "ttl_churn",
+                "changes": churn,
+                "window_hours": config.churn_window_hours,
+                "low_ttl_rrsets": low_ttl,
+            }),
+        ));
+    }
+    found
+}
+
+/// The owner name with every run of digits replaced by `#`, so
+/// `host1.` and `host22.` group together.
+fn name_shape(name: &str) -> String {
+    let mut shape = String::with_capacity(name.len());
+    let mut in_digits = false;
+    for c in name.to_ascii_lowercase().chars() {
+        if c.is_ascii_digit() {
+            if !in_digits {
+                shape.push('#');
+            }
+            in_digits = true;
+        } else {
+            shape.push(c);
+            in_digits = false;
+        }
+    }
+    shape
+}
+
+/// Whether one record points at a flagged network or domain.
+fn is_flagged_target(config: &AbuseScanConfig, rrtype: &str, content: &str) -> bool {
+    match reputation::target(rrtype, content) {
+        Some(Target::Address(ip)) => config
+            .flagged_networks
+            .iter()
+            .any(|network| network.contains(&ip)),
+        Some(Target::Host(target)) => config.flagged_domains.iter().any(|domain| {
+            target == *domain
+                || target
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        }),
+        None => false,
+    }
+}
@@ -0,0 +1,291 @@
+//! Automatic certificates for the frontend host via ACME DNS-01, answered by
+//! publishing `_acme-challenge` TXT records in the parent zone on base PowerDNS.
+use anyhow::{Context, bail};
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, OrderStatus, RetryPolicy,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+use crate::powerdns::types::{PdnsRecord, PdnsRrset};
+use crate::tls::TlsFiles;
+use crate::{AppState, SharedState};
+
+/// Let's Encrypt production directory, the default CA.
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// How often the certificate's age is checked.
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+
+/// Wait between publishing a challenge record and asking the CA to check it,
+/// so secondaries serving the parent zone can pick it up.
+const PROPAGATION_DELAY: Duration = Duration::from_secs(10);
+
+const CHALLENGE_TTL: u32 = 60;
+
+/// Certificate management settings taken from the command line.
+#[derive(Clone, Debug)]
+pub struct AcmeSettings {
+    /// Names on the certificate; each must lie within a served base domain.
+    pub domains: Vec<String>,
+    pub directory_url: String,
+    /// Account contact URIs, e.g. `mailto:hostmaster@example.com`.
+    pub contact: Vec<String>,
+    /// Holds `account.json`, `cert.pem`, and `key.pem`.
+    pub storage_dir: PathBuf,
+    /// Certificates older than this are renewed.
+    pub renew_after: Duration,
+}
+
+impl AcmeSettings {
+    /// Certificate and key files for the TLS listener.
+    pub fn tls_files(&self) -> TlsFiles {
+        TlsFiles {
+            cert: self.storage_dir.join("cert.pem"),
+            key: self.storage_dir.join("key.pem"),
+        }
+    }
+
+    /// Normalize the names and check each is under a base domain of `state`.
+    pub fn validate(&mut self, state: &AppState) -> anyhow::Result<()> {
+        if self.domains.is_empty() {
+            bail!("no ACME domains configured");
+        }
+        for domain in &mut self.domains {
+            *domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+            parent_base_domain(state, domain).with_context(|| {
+                format!("ACME domain {domain} is not within a served base domain")
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Obtain a certificate unless a fresh enough one is already stored;
+    /// returns whether a new one was issued.
+    pub async fn ensure_certificate(&self, state: &AppState) -> anyhow::Result<bool> {
+        if !self.needs_renewal() {
+            return Ok(false);
+        }
+        tracing::info!(
+            "requesting ACME certificate for {}",
+            self.domains.join(", ")
+        );
+        let _guard = state.in_flight.begin("ACME certificate issuance");
+        self.issue(state).await?;
+        tracing::info!("stored ACME certificate in {}", self.storage_dir.display());
+        Ok(true)
+    }
+
+    /// Renew in the background; the TLS reloader notices the rewritten files.
+    pub fn spawn_renewer(self, state: SharedState) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RENEWAL_CHECK_INTERVAL);
+            // the first tick fires immediately, right after the startup check
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.ensure_certificate(&state).await {
+                    tracing::error!("ACME renewal failed, will retry: {err:#}");
+                }
+            }
+        })
+    }
+
+    fn needs_renewal(&self) -> bool {
+        let files = self.tls_files();
+        if !files.key.is_file() {
+            return true;
+        }
+        let issued = std::fs::metadata(&files.cert).and_then(|meta| meta.modified());
+        match issued {
+            Ok(issued) => SystemTime::now()
+                .duration_since(issued)
+                .is_ok_and(|age| age >= self.renew_after),
+            Err(_) => true,
+        }
+    }
+
+    async fn issue(&self, state: &AppState) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.storage_dir).with_context(|| {
+            format!(
+                "failed to create ACME directory {}",
+                self.storage_dir.display()
+            )
+        })?;
+        let account = self.account().await?;
+
+        let identifiers: Vec<Identifier> = self
+            .domains
+            .iter()
+            .map(|domain| Identifier::Dns(domain.clone()))
+            .collect();
+        let mut order = account
+            .new_order(&NewOrder::new(&identifiers))
+            .await
+            .context("failed to create ACME order")?;
+
+        // values per challenge owner, so a second name on the same owner keeps the first
+        let mut published: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let outcome = async {
+            let mut authorizations = order.authorizations();
+            while let Some(authz) = authorizations.next().await {
+                let mut authz = authz.context("failed to fetch ACME authorization")?;
+                match authz.status {
+                    AuthorizationStatus::Pending => {}
+                    AuthorizationStatus::Valid => continue,
+                    status => bail!("ACME authorization is {status:?}"),
+                }
+                let mut challenge = authz
+                    .challenge(ChallengeType::Dns01)
+                    .context("CA offered no dns-01 challenge")?;
+                let domain = challenge.identifier().to_string();
+                let domain = domain.trim_start_matches("*.");
+                let owner = format!("_acme-challenge.{domain}.");
+                let values = published.entry(owner.clone()).or_default();
+                values.push(challenge.key_authorization().dns_value());
+                publish_challenge(state, domain, &owner, values).await?;
+                tokio::time::sleep(PROPAGATION_DELAY).await;
+                challenge
+                    .set_ready()
+                    .await
+                    .context("failed to submit ACME challenge")?;
+            }
+
+            let status = order
+                .poll_ready(&RetryPolicy::default())
+                .await
+                .context("ACME order did not become ready")?;
+            if status != OrderStatus::Ready {
+                bail!("ACME order is {status:?}");
+            }
+            let key_pem = order
+                .finalize()
+                .await
+                .context("failed to finalize ACME order")?;
+            let cert_pem = order
+                .poll_certificate(&RetryPolicy::default())
+                .await
+                .context("failed to download certificate")?;
+            Ok::<_, anyhow::Error>((key_pem, cert_pem))
+        }
+        .await;
+
+        for owner in published.keys() {
+            let domain = owner
+                .trim_start_matches("_acme-challenge.")
+                .trim_end_matches('.');
+            if let Err(err) = remove_challenge(state, domain, owner).await {
+                tracing::warn!("failed to remove ACME challenge {owner}: {err:#}");
+            }
+        }
+
+        let (key_pem, cert_pem) = outcome?;
+        let files = self.tls_files();
+        // key first: a reload between the two writes fails and is retried
+        write_private(&files.key, key_pem.as_bytes())?;
+        write_private(&files.cert, cert_pem.as_bytes())?;
+        Ok(())
+    }
+
+    /// Restore the stored ACME account, or register one.
+    async fn account(&self) -> anyhow::Result<Account> {
+        let path = self.storage_dir.join("account.json");
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            let credentials: AccountCredentials = serde_json::from_str(&text)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            return Account::builder()?
+                .from_credentials(credentials)
+                .await
+                .context("failed to restore ACME account");
+        }
+
+        let contact: Vec<&str> = self.contact.iter().map(String::as_str).collect();
+        let (account, credentials) = Account::builder()?
+            .create(
+                &NewAccount {
+                    contact: &contact,
+                    terms_of_service_agreed: true,
+                    only_return_existing: false,
+                },
+                self.directory_url.clone(),
+                None,
+            )
+            .await
+            .context("failed to register ACME account")?;
+        write_private(&path, serde_json::to_string(&credentials)?.as_bytes())?;
+        Ok(account)
+    }
+}
+
+/// The served base domain whose parent zone holds `domain`.
+fn parent_base_domain<'a>(state: &'a AppState, domain: &str) -> Option<&'a str> {
+    let domain = domain.trim_start_matches("*.");
+    state.config.base_domain_roots().find(|root| {
+        domain == *root
+            || domain
+                .strip_suffix(root)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+async fn publish_challenge(
+    state: &AppState,
+    domain: &str,
+    owner: &str,
+    values: &[String],
+) -> anyhow::Result<()> {
+    let base_domain = parent_base_domain(state, domain).context("domain left the base domains")?;
+    let rrset = PdnsRrset {
+        name: owner.to_string(),
+        rrtype: "TXT".into(),
+        ttl: CHALLENGE_TTL,
+        changetype: Some("REPLACE".into()),
+        records: values
+            .iter()
+            .map(|value| PdnsRecord {
+                content: format!("\"{value}\""),
+                disabled: false,
+            })
+            .collect(),
+        comments: Vec::new(),
+    };
+    state
+        .base_pdns_for(base_domain)
+        .patch_rrsets(&state.config.parent_zone_name(base_domain), &[rrset])
+        .await
+}
+
+async fn remove_challenge(state: &AppState, domain: &str, owner: &str) -> anyhow::Result<()> {
+    let base_domain = parent_base_domain(state, domain).context("domain left the base domains")?;
+    let rrset = PdnsRrset {
+        name: owner.to_string(),
+        rrtype: "TXT".into(),
+        ttl: CHALLENGE_TTL,
+        changetype: Some("DELETE".into()),
+        records: Vec::new(),
+        comments: Vec::new(),
+    };
+    state
+        .base_pdns_for(base_domain)
+        .patch_rrsets(&state.config.parent_zone_name(base_domain), &[rrset])
+        .await
+}
+
+/// Replace a file atomically, readable only by the owner.
+fn write_private(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(&tmp)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        std::io::Write::write_all(&mut file, contents)?;
+    }
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+}
@@ -0,0 +1,331 @@
+//! Operator alerts: structured messages about notable events (signup spikes,
+//! PowerDNS failures, reconciliation drift, abuse flags, failed jobs) sent
+//! to the sinks in `[alerts]`, so problems surface without watching logs.
+use chrono::{DateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::config::AlertSink;
+use crate::db::signup_repo;
+use crate::shutdown::InFlight;
+use crate::{AppState, SharedState, health, webhooks};
+
+/// Pause before each retry; a sink is attempted `RETRY_DELAYS.len() + 1` times.
+const RETRY_DELAYS: [Duration; 2] = [Duration::from_secs(5), Duration::from_secs(60)];
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Failed probes in a row before a PowerDNS endpoint is reported down.
+const PDNS_FAILURES_BEFORE_ALERT: u32 = 2;
+
+/// What an alert is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    SignupSpike,
+    PdnsDown,
+    PdnsRecovered,
+    ReconcileDrift,
+    AbuseFlagged,
+    JobFailed,
+}
+
+/// One alert, as sent to webhook sinks.
+#[derive(Clone, Debug, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    /// What the alert concerns, e.g. a zone, endpoint, or job name; repeats
+    /// of the same kind and subject are held back for `alerts.cooldown_secs`.
+    pub subject: String,
+    /// One line for chat sinks.
+    pub summary: String,
+    pub details: serde_json::Value,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl Alert {
+    pub fn new(
+        kind: AlertKind,
+        subject: impl Into<String>,
+        summary: impl Into<String>,
+        details: serde_json::Value,
+    ) -> Self {
+        Self {
+            kind,
+            subject: subject.into(),
+            summary: summary.into(),
+            details,
+            occurred_at: Utc::now(),
+        }
+    }
+}
+
+/// When each kind and subject was last alerted.
+#[derive(Default)]
+pub struct Alerter {
+    last_sent: Mutex<HashMap<(AlertKind, String), Instant>>,
+}
+
+impl Alerter {
+    /// Whether the alert is outside the cooldown; records it if so.
+    fn admit(&self, alert: &Alert, cooldown: Duration) -> bool {
+        let mut last_sent = self.last_sent.lock().expect("alert cooldowns poisoned");
+        let now = Instant::now();
+        last_sent.retain(|_, sent| now.duration_since(*sent) < cooldown);
+        let key = (alert.kind, alert.subject.clone());
+        if last_sent.contains_key(&key) {
+            return false;
+        }
+        last_sent.insert(key, now);
+        true
+    }
+}
+
+/// PowerDNS endpoints the monitor found down; while any is, the API is
+/// read-only unless `maintenance.read_only_when_pdns_down` is switched off.
+#[derive(Default)]
+pub struct PdnsHealth {
+    down: Mutex<BTreeSet<String>>,
+}
+
+impl PdnsHealth {
+    /// Whether any endpoint is down.
+    pub fn degraded(&self) -> bool {
+        !self
+            .down
+            .lock()
+            .expect("PowerDNS health poisoned")
+            .is_empty()
+    }
+
+    fn set(&self, endpoint: &str, down: bool) {
+        let mut set = self.down.lock().expect("PowerDNS health poisoned");
+        let was_degraded = !set.is_empty();
+        if down {
+            set.insert(endpoint.to_string());
+        } else {
+            set.remove(endpoint);
+        }
+        match (was_degraded, !set.is_empty()) {
+            (false, true) => tracing::warn!("{endpoint} is down, entering degraded mode"),
+            (true, false) => tracing::info!("PowerDNS answers again, leaving degraded mode"),
+            _ => {}
+        }
+    }
+
+    fn clear(&self) {
+        self.down.lock().expect("PowerDNS health poisoned").clear();
+    }
+}
+
+/// Log the alert and send it to every sink in the background, unless the
+/// same kind and subject was alerted within the cooldown.
+pub fn raise(state: &AppState, alert: Alert) {
+    tracing::warn!(kind = ?alert.kind, subject = %alert.subject, "alert: {}", alert.summary);
+    let config = state.policy().alerts.clone();
+    if config.sinks.is_empty()
+        || !state
+            .alerts
+            .admit(&alert, Duration::from_secs(config.cooldown_secs))
+    {
+        return;
+    }
+    let service = state
+        .policy()
+        .service_name
+        .clone()
+        .unwrap_or_else(|| state.config.base_domain_root().to_string());
+    for sink in config.sinks {
+        tokio::spawn(deliver(
+            state.in_flight.clone(),
+            sink,
+            service.clone(),
+            alert.clone(),
+        ));
+    }
+}
+
+async fn deliver(in_flight: InFlight, sink: AlertSink, service: String, alert: Alert) {
+    for attempt in 1..=RETRY_DELAYS.len() + 1 {
+        let outcome = {
+            let _guard = in_flight.begin(format!("sending {:?} alert", alert.kind));
+            send(&sink, &service, &alert).await
+        };
+        match (outcome, RETRY_DELAYS.get(attempt - 1)) {
+            (Ok(()), _) => return,
+            (Err(_), Some(delay)) => tokio::time::sleep(*delay).await,
+            (Err(err), None) => {
+                tracing::error!(
+                    "giving up on {:?} alert after {attempt} attempts: {err:#}",
+                    alert.kind
+                );
+            }
+        }
+    }
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("static reqwest configuration is valid")
+    });
+    &CLIENT
+}
+
+async fn send(sink: &AlertSink, service: &str, alert: &Alert) -> anyhow::Result<()> {
+    let text = format!("[{service}] {}", alert.summary);
+    let request = match sink {
+        AlertSink::Slack { url } => http_client()
+            .post(url)
+            .json(&serde_json::json!({ "text": text })),
+        AlertSink::Matrix {
+            homeserver,
+            room_id,
+            access_token,
+        } => {
+            let mut txn = [0u8; 12];
+            OsRng.fill_bytes(&mut txn);
+            let txn: String = txn.iter().map(|byte| format!("{byte:02x}")).collect();
+            let mut url = url::Url::parse(homeserver)?;
+            url.path_segments_mut()
+                .map_err(|()| anyhow::anyhow!("{homeserver} cannot be a base URL"))?
+                .pop_if_empty()
+                .extend([
+                    "_matrix",
+                    "client",
+                    "v3",
+                    "rooms",
+                    room_id,
+                    "send",
+                    "m.room.message",
+                    &txn,
+                ]);
+            http_client()
+                .put(url)
+                .bearer_auth(access_token)
+                .json(&serde_json::json!({ "msgtype": "m.notice", "body": text }))
+        }
+        AlertSink::Webhook { url, secret } => {
+            let mut payload = serde_json::to_value(alert)?;
+            payload["service"] = service.into();
+            let body = serde_json::to_vec(&payload)?;
+            let mut request = http_client()
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            if let Some(secret) = secret {
+                request = request.header(webhooks::SIGNATURE_HEADER, webhooks::sign(secret, &body));
+            }
+            request.body(body)
+        }
+    };
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        anyhow::bail!("sink answered {}", response.status());
+    }
+    Ok(())
+}
+
+/// Raise a `signup_spike` alert when the signups within the configured
+/// window reach `alerts.signup_spike`.
+pub async fn check_signup_spike(state: &AppState) {
+    let (threshold, window) = {
+        let policy = state.policy();
+        (
+            policy.alerts.signup_spike,
+            policy.alerts.signup_spike_window_mins,
+        )
+    };
+    let Some(threshold) = threshold else {
+        return;
+    };
+    let since = Utc::now() - chrono::Duration::minutes(i64::from(window));
+    let signups = match signup_repo::count_since(&state.db, since).await {
+        Ok(signups) => signups,
+        Err(err) => {
+            tracing::error!("failed to count recent signups: {err:?}");
+            return;
+        }
+    };
+    if signups >= i64::from(threshold) {
+        raise(
+            state,
+            Alert::new(
+                AlertKind::SignupSpike,
+                "signups",
+                format!("{signups} signups in the last {window} minutes"),
+                serde_json::json!({ "signups": signups, "window_mins": window }),
+            ),
+        );
+    }
+}
+
+/// Probe every PowerDNS endpoint every `alerts.pdns_check_secs`, alerting
+/// when one keeps failing and again when it recovers, and keeping
+/// `AppState::pdns_health` up to date.
+pub fn spawn_pdns_monitor(state: SharedState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut failures: HashMap<String, u32> = HashMap::new();
+        loop {
+            let interval = state.policy().alerts.pdns_check_secs;
+            if interval == 0 {
+                // switched off; look again after a reload might have changed that
+                failures.clear();
+                state.pdns_health.clear();
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            for (name, outcome) in probe(&state).await {
+                let count = failures.entry(name.clone()).or_default();
+                match outcome {
+                    Ok(_) => {
+                        if *count >= PDNS_FAILURES_BEFORE_ALERT {
+                            raise(
+                                &state,
+                                Alert::new(
+                                    AlertKind::PdnsRecovered,
+                                    name.clone(),
+                                    format!("{name} answers again"),
+                                    serde_json::json!({ "endpoint": name }),
+                                ),
+                            );
+                        }
+                        *count = 0;
+                        state.pdns_health.set(&name, false);
+                    }
+                    Err(err) => {
+                        *count += 1;
+                        if *count >= PDNS_FAILURES_BEFORE_ALERT {
+                            state.pdns_health.set(&name, true);
+                        }
+                        if *count == PDNS_FAILURES_BEFORE_ALERT {
+                            raise(
+                                &state,
+                                Alert::new(
+                                    AlertKind::PdnsDown,
+                                    name.clone(),
+                                    format!("{name} failed {count} checks in a row: {err}"),
+                                    serde_json::json!({ "endpoint": name, "error": err }),
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Check the PowerDNS API of every endpoint, named as in `/readyz`.
+async fn probe(state: &AppState) -> Vec<(String, Result<String, String>)> {
+    let mut outcomes = Vec::new();
+    for (name, client) in state.pdns_endpoints() {
+        outcomes.push((name, health::check_pdns(client).await));
+    }
+    outcomes
+}
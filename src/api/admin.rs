@@ -0,0 +1,718 @@
+//! Operator-only endpoints guarded by the configured admin token.
+use crate::api::public::SIGNUP_WINDOW;
+use crate::config::{IdnMode, MaintenanceConfig, PolicyConfig, SignupMode};
+use crate::dangling;
+use crate::db::job_repo::{self, JobRun};
+use crate::db::{
+    abuse_repo, audit_repo, dangling_repo, invite_repo, mail_outbox_repo, reserved_repo,
+    signup_repo,
+};
+use crate::error::{AppError, ErrorCode, internal};
+use crate::geoip::Location;
+use crate::mail::{self, EnqueueError, Template};
+use crate::notifications;
+use crate::reconcile::{self, RunReport};
+use crate::serial_check;
+use crate::validation::normalize_subdomain_label;
+use crate::{SharedState, auth::AdminAuthenticated};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    http::StatusCode,
+};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Re-read the policy file and swap it into the running server.
+pub async fn reload_policy(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !state.has_policy_sources() {
+        return Err(AppError::bad_request("no policy file configured"));
+    }
+
+    state.reload_policy().map_err(internal)?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// A DB-managed label reservation as shown to operators.
+#[derive(Serialize)]
+pub struct ReservedLabelDto {
+    pub label: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// List operator-managed label reservations.
+pub async fn list_reserved(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<ReservedLabelDto>>, AppError> {
+    let labels = reserved_repo::list(&state.db).await.map_err(internal)?;
+    Ok(Json(
+        labels
+            .into_iter()
+            .map(|entry| ReservedLabelDto {
+                label: entry.label,
+                reason: entry.reason,
+                created_at: entry.created_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Payload reserving a single label.
+#[derive(Deserialize)]
+pub struct ReserveLabelRequest {
+    pub label: String,
+    pub reason: Option<String>,
+}
+
+/// Reserve a label so it can no longer be registered.
+pub async fn add_reserved(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<ReserveLabelRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let label = normalize_subdomain_label(&req.label.trim().to_lowercase(), IdnMode::Any)
+        .map_err(|e| AppError::localized(ErrorCode::InvalidSubdomain, e))?;
+
+    reserved_repo::upsert(&state.db, &label, req.reason.as_deref())
+        .await
+        .map_err(internal)?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Release a DB-managed reservation.
+pub async fn remove_reserved(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Path(label): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !reserved_repo::delete(&state.db, &label)
+        .await
+        .map_err(internal)?
+    {
+        return Err(AppError::not_found());
+    }
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Payload switching the signup mode at runtime.
+#[derive(Deserialize)]
+pub struct SetSignupModeRequest {
+    pub mode: SignupMode,
+}
+
+/// Change the signup mode until the next policy reload.
+pub async fn set_signup_mode(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<SetSignupModeRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    state.policy.rcu(|current| {
+        Arc::new(PolicyConfig {
+            signup_mode: req.mode,
+            ..PolicyConfig::clone(current)
+        })
+    });
+    tracing::info!("signup mode set to {:?}", req.mode);
+
+    Ok(Json(serde_json::json!({ "ok": true, "mode": req.mode })))
+}
+
+/// Payload toggling maintenance mode at runtime.
+#[derive(Deserialize)]
+pub struct SetMaintenanceRequest {
+    pub enabled: bool,
+    pub retry_after_secs: Option<u64>,
+    pub message: Option<String>,
+    pub read_only_when_pdns_down: Option<bool>,
+}
+
+/// Enable or lift maintenance mode until the next policy reload.
+pub async fn set_maintenance(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<SetMaintenanceRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let message = req
+        .message
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty());
+    state.policy.rcu(|current| {
+        let maintenance = MaintenanceConfig {
+            enabled: req.enabled,
+            retry_after_secs: req
+                .retry_after_secs
+                .unwrap_or(current.maintenance.retry_after_secs),
+            message: message.clone(),
+            read_only_when_pdns_down: req
+                .read_only_when_pdns_down
+                .unwrap_or(current.maintenance.read_only_when_pdns_down),
+        };
+        Arc::new(PolicyConfig {
+            maintenance,
+            ..PolicyConfig::clone(current)
+        })
+    });
+    let policy = state.policy();
+    let maintenance = &policy.maintenance;
+    tracing::info!("maintenance mode enabled: {}", maintenance.enabled);
+
+    Ok(Json(
+        serde_json::json!({ "ok": true, "maintenance": maintenance }),
+    ))
+}
+
+/// An invite code as shown to operators.
+#[derive(Serialize)]
+pub struct InviteDto {
+    pub code: String,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub used_by: Option<String>,
+}
+
+impl From<invite_repo::Invite> for InviteDto {
+    fn from(invite: invite_repo::Invite) -> Self {
+        Self {
+            code: invite.code,
+            note: invite.note,
+            created_at: invite.created_at,
+            used_at: invite.used_at,
+            used_by: invite.used_by,
+        }
+    }
+}
+
+/// List every issued invite code.
+pub async fn list_invites(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<InviteDto>>, AppError> {
+    let invites = invite_repo::list(&state.db).await.map_err(internal)?;
+    Ok(Json(invites.into_iter().map(InviteDto::from).collect()))
+}
+
+/// Optional metadata attached to a new invite.
+#[derive(Deserialize, Default)]
+pub struct CreateInviteRequest {
+    pub note: Option<String>,
+}
+
+/// Issue a new random single-use invite code.
+pub async fn create_invite(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    body: Option<Json<CreateInviteRequest>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+
+    let mut bytes = [0u8; 18];
+    OsRng.fill_bytes(&mut bytes);
+    let code = URL_SAFE_NO_PAD.encode(bytes);
+
+    invite_repo::insert(&state.db, &code, req.note.as_deref())
+        .await
+        .map_err(internal)?;
+
+    Ok(Json(serde_json::json!({ "ok": true, "code": code })))
+}
+
+/// Revoke an invite code.
+pub async fn delete_invite(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Path(code): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !invite_repo::delete(&state.db, &code)
+        .await
+        .map_err(internal)?
+    {
+        return Err(AppError::not_found());
+    }
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Report of the latest reconciliation run; 404 until one has finished.
+pub async fn get_reconcile_report(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Arc<RunReport>>, AppError> {
+    state
+        .reconcile
+        .last
+        .load_full()
+        .map(Json)
+        .ok_or_else(AppError::not_found)
+}
+
+/// Run reconciliation now and return its report.
+pub async fn run_reconcile(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Arc<RunReport>>, AppError> {
+    reconcile::run(&state)
+        .await
+        .map(Json)
+        .ok_or_else(|| AppError::conflict("a reconciliation run is already in progress"))
+}
+
+/// Report of the latest SOA serial check; 404 until one has finished.
+pub async fn get_serial_check_report(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Arc<serial_check::Report>>, AppError> {
+    state
+        .serial_check
+        .last
+        .load_full()
+        .map(Json)
+        .ok_or_else(AppError::not_found)
+}
+
+/// Runs returned by `list_job_runs`.
+const JOB_RUNS_LIMIT: i64 = 20;
+
+/// One finished run of a background job.
+#[derive(Serialize)]
+pub struct JobRunDto {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub outcome: String,
+    pub detail: Option<String>,
+}
+
+impl From<JobRun> for JobRunDto {
+    fn from(run: JobRun) -> Self {
+        JobRunDto {
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+            duration_ms: run.duration_ms,
+            outcome: run.outcome,
+            detail: run.detail,
+        }
+    }
+}
+
+/// A background job with its schedule and latest run.
+#[derive(Serialize)]
+pub struct JobDto {
+    pub name: &'static str,
+    pub enabled: bool,
+    pub schedule: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub running_since: Option<DateTime<Utc>>,
+    pub last_run: Option<JobRunDto>,
+}
+
+/// List every background job.
+pub async fn list_jobs(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<JobDto>>, AppError> {
+    let mut latest = job_repo::latest(&state.db).await.map_err(internal)?;
+    let jobs = state
+        .jobs
+        .snapshot()
+        .into_iter()
+        .map(|(name, status)| {
+            let last_run = latest
+                .iter()
+                .position(|run| run.name == name)
+                .map(|index| JobRunDto::from(latest.swap_remove(index)));
+            JobDto {
+                name,
+                enabled: status.schedule.is_some(),
+                schedule: status.schedule,
+                next_run_at: status.next_run_at,
+                running_since: status.running_since,
+                last_run,
+            }
+        })
+        .collect();
+    Ok(Json(jobs))
+}
+
+/// Recent runs of one background job, newest first.
+pub async fn list_job_runs(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<JobRunDto>>, AppError> {
+    if !state.jobs.snapshot().iter().any(|(job, _)| *job == name) {
+        return Err(AppError::not_found());
+    }
+    let runs = job_repo::list_for_job(&state.db, &name, JOB_RUNS_LIMIT)
+        .await
+        .map_err(internal)?;
+    Ok(Json(runs.into_iter().map(JobRunDto::from).collect()))
+}
+
+/// A quarantined delegation and when it becomes due for removal.
+#[derive(Serialize)]
+pub struct DanglingDto {
+    pub name: String,
+    pub base_domain: String,
+    pub nameservers: Vec<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub remove_after: DateTime<Utc>,
+}
+
+/// List delegations currently in quarantine.
+pub async fn list_dangling(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<DanglingDto>>, AppError> {
+    let quarantine = chrono::Duration::days(i64::from(state.policy().dangling.quarantine_days));
+    let entries = dangling_repo::list(&state.db).await.map_err(internal)?;
+    Ok(Json(
+        entries
+            .into_iter()
+            .map(|entry| DanglingDto {
+                remove_after: entry.first_seen_at + quarantine,
+                name: entry.name,
+                base_domain: entry.base_domain,
+                nameservers: entry.nameservers,
+                first_seen_at: entry.first_seen_at,
+                last_seen_at: entry.last_seen_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Run the dangling delegation sweep now, whether or not the job is enabled.
+pub async fn sweep_dangling(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<dangling::Sweep>, AppError> {
+    dangling::sweep(&state).await.map(Json).map_err(internal)
+}
+
+/// Review state of an abuse flag.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbuseStatus {
+    Open,
+    Dismissed,
+}
+
+impl AbuseStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            AbuseStatus::Open => "open",
+            AbuseStatus::Dismissed => "dismissed",
+        }
+    }
+}
+
+/// Filter of `GET /api/admin/abuse`; every flag when `status` is omitted.
+#[derive(Deserialize)]
+pub struct AbuseQuery {
+    #[serde(default)]
+    pub status: Option<AbuseStatus>,
+}
+
+/// A zone in the abuse review queue.
+#[derive(Serialize)]
+pub struct AbuseFlagDto {
+    pub id: i64,
+    pub zone: String,
+    pub reason: String,
+    pub details: serde_json::Value,
+    pub status: String,
+    pub first_flagged_at: DateTime<Utc>,
+    pub last_flagged_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// List the abuse review queue, newest first.
+pub async fn list_abuse(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<AbuseQuery>,
+) -> Result<Json<Vec<AbuseFlagDto>>, AppError> {
+    let flags = abuse_repo::list(&state.db, query.status.map(AbuseStatus::as_str))
+        .await
+        .map_err(internal)?;
+    Ok(Json(
+        flags
+            .into_iter()
+            .map(|flag| AbuseFlagDto {
+                id: flag.id,
+                zone: flag.zone,
+                reason: flag.reason,
+                details: serde_json::from_str(&flag.details).unwrap_or_default(),
+                status: flag.status,
+                first_flagged_at: flag.first_flagged_at,
+                last_flagged_at: flag.last_flagged_at,
+                reviewed_at: flag.reviewed_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Payload recording the review of an abuse flag.
+#[derive(Deserialize)]
+pub struct ReviewAbuseRequest {
+    pub status: AbuseStatus,
+}
+
+/// Dismiss an abuse flag, or reopen a dismissed one.
+pub async fn review_abuse(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    Json(req): Json<ReviewAbuseRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !abuse_repo::set_status(&state.db, id, req.status.as_str())
+        .await
+        .map_err(internal)?
+    {
+        return Err(AppError::not_found());
+    }
+    Ok(Json(
+        serde_json::json!({ "ok": true, "status": req.status }),
+    ))
+}
+
+/// Signups from one network in the last day.
+#[derive(Serialize)]
+pub struct SubnetSignupsDto {
+    pub subnet: String,
+    pub signups: i64,
+    pub addresses: i64,
+    pub last_signup_at: DateTime<Utc>,
+    /// Whether the network has reached `per_subnet_per_day`.
+    pub capped: bool,
+    /// Country and ASN of the network, with `--geoip-db`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo: Option<Location>,
+}
+
+/// Signup counters next to the caps from the policy.
+#[derive(Serialize)]
+pub struct SignupSourcesResponse {
+    pub per_ip_per_day: Option<u32>,
+    pub per_subnet_per_day: Option<u32>,
+    pub subnets: Vec<SubnetSignupsDto>,
+}
+
+/// List signups of the last day per network, busiest first.
+pub async fn list_signup_sources(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<SignupSourcesResponse>, AppError> {
+    let limits = state.policy().signup_limits.clone();
+    let counts = signup_repo::count_by_subnet_since(&state.db, Utc::now() - SIGNUP_WINDOW)
+        .await
+        .map_err(internal)?;
+    let subnets = counts
+        .into_iter()
+        .map(|entry| SubnetSignupsDto {
+            capped: limits
+                .per_subnet_per_day
+                .is_some_and(|limit| entry.signups >= i64::from(limit)),
+            geo: state.geoip.as_ref().and_then(|geoip| {
+                let network = entry.subnet.split('/').next()?.parse().ok()?;
+                geoip.lookup(network)
+            }),
+            subnet: entry.subnet,
+            signups: entry.signups,
+            addresses: entry.addresses,
+            last_signup_at: entry.last_signup_at,
+        })
+        .collect();
+    Ok(Json(SignupSourcesResponse {
+        per_ip_per_day: limits.per_ip_per_day,
+        per_subnet_per_day: limits.per_subnet_per_day,
+        subnets,
+    }))
+}
+
+/// Entries returned by `GET /api/admin/audit` when no `limit` is given.
+const DEFAULT_AUDIT_LIMIT: i64 = 50;
+/// Largest accepted `limit`.
+const MAX_AUDIT_LIMIT: i64 = 200;
+
+/// Pagination of `GET /api/admin/audit`.
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Only entries older than this `id`; pass the previous page's `next_before`.
+    #[serde(default)]
+    pub before: Option<i64>,
+}
+
+/// One operator or background-job action.
+#[derive(Serialize)]
+pub struct AuditEntryDto {
+    pub id: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub details: serde_json::Value,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// A page of the audit log, newest first.
+#[derive(Serialize)]
+pub struct AuditPage {
+    pub items: Vec<AuditEntryDto>,
+    /// Cursor for the next (older) page; absent on the last page.
+    pub next_before: Option<i64>,
+}
+
+/// Return the operator audit log.
+pub async fn list_audit(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, AppError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_LIMIT)
+        .clamp(1, MAX_AUDIT_LIMIT);
+    // one extra row tells whether another page follows
+    let mut entries = audit_repo::list(&state.db, query.before, limit + 1)
+        .await
+        .map_err(internal)?;
+    let has_more = entries.len() as i64 > limit;
+    entries.truncate(limit as usize);
+
+    let next_before = has_more
+        .then(|| entries.last().map(|entry| entry.id))
+        .flatten();
+    let items = entries
+        .into_iter()
+        .map(|entry| AuditEntryDto {
+            id: entry.id,
+            actor: entry.actor,
+            action: entry.action,
+            target: entry.target,
+            details: serde_json::from_str(&entry.details).unwrap_or_default(),
+            occurred_at: entry.occurred_at,
+        })
+        .collect();
+    Ok(Json(AuditPage { items, next_before }))
+}
+
+/// Recipient of `POST /api/admin/mail/test`.
+#[derive(Deserialize)]
+pub struct TestMailRequest {
+    pub to: String,
+}
+
+/// Queue a test message to check the mail transport.
+pub async fn send_test_mail(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<TestMailRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let id = mail::enqueue(&state, &req.to, Template::Test)
+        .await
+        .map_err(|err| match err {
+            EnqueueError::InvalidAddress(_) => AppError::bad_request(err.to_string()),
+            EnqueueError::Db(err) => internal(err),
+        })?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "ok": true, "id": id })),
+    ))
+}
+
+/// Messages listed by `GET /api/admin/mail/outbox`.
+const OUTBOX_LIMIT: i64 = 100;
+
+/// A queued or failed message, without its body.
+#[derive(Serialize)]
+pub struct OutboxMessageDto {
+    pub id: i64,
+    pub recipient: String,
+    pub template: String,
+    pub subject: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    /// Set once the message was given up on.
+    pub failed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Return the messages still in the outbox, newest first.
+pub async fn list_mail_outbox(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<OutboxMessageDto>>, AppError> {
+    let messages = mail_outbox_repo::list(&state.db, OUTBOX_LIMIT)
+        .await
+        .map_err(internal)?;
+    Ok(Json(
+        messages
+            .into_iter()
+            .map(|message| OutboxMessageDto {
+                id: message.id,
+                recipient: message.recipient,
+                template: message.template,
+                subject: message.subject,
+                attempts: message.attempts,
+                last_error: message.last_error,
+                next_attempt_at: message.next_attempt_at,
+                failed_at: message.failed_at,
+                created_at: message.created_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Longest accepted announcement subject, in characters.
+const MAX_ANNOUNCEMENT_SUBJECT: usize = 200;
+/// Longest accepted announcement text, in characters.
+const MAX_ANNOUNCEMENT_TEXT: usize = 10_000;
+
+/// Body of `POST /api/admin/announcements`.
+#[derive(Deserialize)]
+pub struct AnnouncementRequest {
+    pub subject: String,
+    pub text: String,
+}
+
+/// Send a message to every user's inbox, and onwards per their preferences.
+pub async fn create_announcement(
+    _admin: AdminAuthenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<AnnouncementRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let subject = req.subject.trim();
+    let text = req.text.trim();
+    if subject.is_empty() || subject.chars().count() > MAX_ANNOUNCEMENT_SUBJECT {
+        return Err(AppError::bad_request(format!(
+            "subject must be 1 to {MAX_ANNOUNCEMENT_SUBJECT} characters"
+        )));
+    }
+    if text.is_empty() || text.chars().count() > MAX_ANNOUNCEMENT_TEXT {
+        return Err(AppError::bad_request(format!(
+            "text must be 1 to {MAX_ANNOUNCEMENT_TEXT} characters"
+        )));
+    }
+    let recipients = notifications::announce(&state, subject.to_string(), text.to_string())
+        .await
+        .map_err(internal)?;
+    Ok(Json(
+        serde_json::json!({ "ok": true, "recipients": recipients }),
+    ))
+}
@@ -0,0 +1,55 @@
+//! Response compression for the API and the web UI, limited to content
+//! types that shrink; images, archives, and event streams pass through.
+use crate::config::Compression;
+use axum::{
+    Router,
+    http::{Extensions, HeaderMap, StatusCode, Version, header},
+};
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{NotForContentType, Predicate, SizeAbove},
+};
+
+/// Content types worth compressing; anything else is sent as it is.
+const COMPRESSIBLE: &[&str] = &[
+    "text/",
+    "application/json",
+    "application/problem+json",
+    "application/graphql-response+json",
+    "application/manifest+json",
+    "application/javascript",
+    "application/xml",
+    "application/wasm",
+    "image/svg+xml",
+];
+
+/// Compress the responses of `router` with the encodings `config` enables.
+pub fn apply(router: Router, config: &Compression) -> Router {
+    if !(config.gzip || config.br || config.zstd) {
+        return router;
+    }
+    let predicate = SizeAbove::new(config.min_size)
+        // streamed events must reach the client as they are written
+        .and(NotForContentType::SSE)
+        .and(compressible);
+    router.layer(
+        CompressionLayer::new()
+            .gzip(config.gzip)
+            .br(config.br)
+            .zstd(config.zstd)
+            .no_deflate()
+            .quality(config.level)
+            .compress_when(predicate),
+    )
+}
+
+/// Whether the response's content type is in [`COMPRESSIBLE`].
+fn compressible(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            let value = value.to_ascii_lowercase();
+            COMPRESSIBLE.iter().any(|prefix| value.starts_with(prefix))
+        })
+}
@@ -0,0 +1,76 @@
+//! Strong ETags and short-lived caching for cheap-to-compare public responses.
+use crate::error::internal;
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+/// Responses larger than this are served without an ETag.
+const MAX_TAGGED_BODY: usize = 4 * 1024 * 1024;
+
+/// Tag successful GET responses with a content hash, answer matching
+/// `If-None-Match` requests with `304 Not Modified`, and allow clients to
+/// reuse the response for `max_age` seconds.
+pub async fn etag(State(max_age): State<u32>, request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_TAGGED_BODY).await {
+        Ok(bytes) => bytes,
+        Err(err) => return internal(err).into_response(),
+    };
+
+    let digest = Sha256::digest(&bytes);
+    let hex: String = digest[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let tag = format!("\"{hex}\"");
+    let cache_control = format!("public, max-age={max_age}");
+
+    let headers = &mut parts.headers;
+    if let Ok(value) = HeaderValue::from_str(&tag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+
+    if if_none_match.is_some_and(|value| matches_tag(&value, &tag)) {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        copy_validators(&parts.headers, not_modified.headers_mut());
+        return not_modified;
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Weak comparison of `If-None-Match` (a list of tags or `*`) against `tag`.
+fn matches_tag(if_none_match: &HeaderValue, tag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == tag
+    })
+}
+
+/// Headers a 304 must repeat from the full response (RFC 9110 §15.4.5).
+fn copy_validators(from: &HeaderMap, to: &mut HeaderMap) {
+    for name in [header::ETAG, header::CACHE_CONTROL, header::VARY] {
+        if let Some(value) = from.get(&name) {
+            to.insert(name, value.clone());
+        }
+    }
+}
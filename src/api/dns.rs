@@ -1,61 +1,346 @@
 //! Authenticated DNS management endpoints for user-owned zones.
-use super::public::internal;
+use crate::SharedState;
+use crate::auth::{Authenticated, Credential};
+use crate::config::{PolicyConfig, ReputationAction};
+use crate::db::record_note_repo::{self, RecordNote};
+use crate::db::record_provenance_repo::{self, RecordProvenance};
+use crate::db::user_repo::{self, User};
+use crate::db::{abuse_repo, audit_repo};
+use crate::error::{AppError, ErrorCode, internal};
+use crate::events::{Event, EventKind, RrsetRef};
+use crate::i18n::{Message, MessageId};
 use crate::powerdns::types::{PdnsRecord, PdnsRrset};
-use crate::{SharedState, auth::Authenticated};
+use crate::record_data::RecordData;
+use crate::reputation::{self, Target};
+use crate::validation::{is_hostname, validate_name_length, validate_owner_labels};
+use crate::{abuse, alerts, rate_limit, rdata, spf};
+use axum::body::{Body, Bytes};
+use axum::extract::Query;
+use axum::http::{HeaderMap, header};
+use axum::response::{IntoResponse, Response};
 use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, btree_map::Entry};
+use std::collections::{BTreeMap, HashMap, btree_map::Entry};
 
 /// JSON representation of a DNS record row returned to the frontend.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "graphql", graphql(name = "Record"))]
 pub struct RecordDto {
     pub name: String, // relative or FQDN, your choice
+    /// May be left empty when `data` is given.
+    #[serde(default)]
     pub rrtype: String,
-    pub ttl: u32,
+    /// Omitted TTLs take the policy default; all TTLs are clamped to its bounds.
+    pub ttl: Option<u32>,
+    /// May be left empty when `data` or `srv` is given; must match `data` otherwise.
+    #[serde(default)]
     pub content: String,
+    /// Structured alternative to `content` for common types; returned
+    /// whenever the content fits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub data: Option<RecordData>,
     pub priority: Option<u16>, // for MX, SRV if you want
+    /// Structured SRV input composing `name` and `content`; never returned.
+    #[serde(default, skip_serializing)]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub srv: Option<SrvFields>,
+    /// Free-form note on the record's RRset. Omitted on input, the stored
+    /// note is kept; an empty string removes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Tag labels of the record's RRset, such as `prod`. Omitted on input,
+    /// the stored labels are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    /// Credentials that created and last modified the record's RRset;
+    /// ignored on input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub provenance: Option<Provenance>,
+}
+
+/// Which credential created and last modified an RRset. Absent for RRsets
+/// not written through the API since provenance was tracked.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Credential kind, such as `password`.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub modified_by: String,
+    pub modified_at: DateTime<Utc>,
+}
+
+/// Longest note, in characters.
+const MAX_NOTE_CHARS: usize = 1000;
+/// Labels one RRset may carry.
+const MAX_LABELS: usize = 16;
+/// Longest label, in characters.
+const MAX_LABEL_CHARS: usize = 32;
+
+/// Note and labels submitted for one RRset; `None` keeps the stored value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RrsetNotes {
+    pub name: String,
+    pub rrtype: String,
+    pub note: Option<String>,
+    pub labels: Option<Vec<String>>,
+}
+
+/// SRV record fields (RFC 2782). The owner becomes `_service._proto` below
+/// the record's `name`, and the content `priority weight port target`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SrvFields {
+    /// Service name such as `sip`, with or without the leading underscore.
+    pub service: String,
+    /// Transport such as `tcp` or `udp`, with or without the leading underscore.
+    pub proto: String,
+    // wider than the wire fields so out-of-range values get a clear message
+    pub priority: i64,
+    pub weight: i64,
+    pub port: i64,
+    /// Host providing the service, or `.` when it is not available.
+    pub target: String,
+}
+
+impl SrvFields {
+    /// Owner name and content for a record submitted with `name`.
+    fn compose(&self, name: &str) -> Result<(String, String), String> {
+        let service = self
+            .service
+            .trim()
+            .trim_start_matches('_')
+            .to_ascii_lowercase();
+        // RFC 6335 section 5.1 service names
+        if service.is_empty()
+            || service.len() > 15
+            || service.starts_with('-')
+            || service.ends_with('-')
+            || service.contains("--")
+            || !service
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            || !service.bytes().any(|b| b.is_ascii_alphabetic())
+        {
+            return Err(format!("invalid service name {:?}", self.service));
+        }
+        let proto = self
+            .proto
+            .trim()
+            .trim_start_matches('_')
+            .to_ascii_lowercase();
+        if proto.is_empty() || proto.len() > 63 || !proto.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            return Err(format!("invalid protocol {:?}", self.proto));
+        }
+
+        let mut fields = Vec::with_capacity(3);
+        for (field, value) in [
+            ("priority", self.priority),
+            ("weight", self.weight),
+            ("port", self.port),
+        ] {
+            let value = u16::try_from(value)
+                .map_err(|_| format!("{field} must be between 0 and 65535, got {value}"))?;
+            fields.push(value.to_string());
+        }
+
+        let target = self.target.trim().to_ascii_lowercase();
+        let target = if target == "." {
+            target
+        } else if is_hostname(&target) {
+            format!("{}.", target.trim_end_matches('.'))
+        } else {
+            return Err(format!("invalid target {:?}", self.target));
+        };
+
+        let name = name.trim();
+        let owner = if name.is_empty() || name == "@" {
+            format!("_{service}._{proto}")
+        } else {
+            format!("_{service}._{proto}.{name}")
+        };
+        Ok((owner, format!("{} {target}", fields.join(" "))))
+    }
 }
 
-/// Return every user-manageable RRset in the caller's delegated zone.
+/// Records serialized per chunk of a streamed zone listing.
+const RECORDS_PER_CHUNK: usize = 256;
+
+/// Media type of the newline-delimited listing.
+const NDJSON: &str = "application/x-ndjson";
+
+/// Filters of `GET /api/zone`.
+#[derive(Deserialize)]
+pub struct ZoneQuery {
+    /// Only records whose RRset carries this label.
+    pub label: Option<String>,
+}
+
+/// Return every user-manageable RRset in the caller's delegated zone. The
+/// body is streamed as a JSON array, or as one record per line when the
+/// client accepts `application/x-ndjson`.
 pub async fn get_zone(
     Authenticated(user): Authenticated,
     Extension(state): Extension<SharedState>,
-) -> Result<Json<Vec<RecordDto>>, (axum::http::StatusCode, String)> {
-    let zone_name = state.config.user_zone_name(&user.subdomain);
+    Query(query): Query<ZoneQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let ndjson = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|range| range.trim().starts_with(NDJSON))
+        });
+    let label = query.label.map(|label| label.trim().to_ascii_lowercase());
+    let records = fetch_user_records(&state, &user)
+        .await?
+        .filter(move |record| {
+            label.as_ref().is_none_or(|label| {
+                record
+                    .labels
+                    .as_ref()
+                    .is_some_and(|labels| labels.contains(label))
+            })
+        });
+    let content_type = if ndjson { NDJSON } else { "application/json" };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::VARY, "Accept"),
+        ],
+        Body::from_stream(tokio_stream::iter(serialize_chunks(records, ndjson))),
+    )
+        .into_response())
+}
+
+/// Serialize `records` lazily, [`RECORDS_PER_CHUNK`] at a time, so large
+/// zones are never held as one JSON string.
+fn serialize_chunks(
+    records: impl Iterator<Item = RecordDto>,
+    ndjson: bool,
+) -> impl Iterator<Item = Result<Bytes, serde_json::Error>> {
+    let mut records = records.peekable();
+    let mut first = true;
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let mut chunk = Vec::new();
+        if first && !ndjson {
+            chunk.push(b'[');
+        }
+        for record in records.by_ref().take(RECORDS_PER_CHUNK) {
+            if !first && !ndjson {
+                chunk.push(b',');
+            }
+            first = false;
+            if let Err(err) = serde_json::to_writer(&mut chunk, &record) {
+                done = true;
+                return Some(Err(err));
+            }
+            if ndjson {
+                chunk.push(b'\n');
+            }
+        }
+        if records.peek().is_none() {
+            done = true;
+            if !ndjson {
+                chunk.push(b']');
+            }
+        }
+        Some(Ok(Bytes::from(chunk)))
+    })
+}
+
+/// Fetch the user's zone and flatten its user-manageable RRsets into records.
+pub(crate) async fn load_user_records(
+    state: &SharedState,
+    user: &User,
+) -> Result<Vec<RecordDto>, AppError> {
+    Ok(fetch_user_records(state, user).await?.collect())
+}
+
+/// Fetch the user's zone; its records are flattened as the iterator is
+/// consumed.
+async fn fetch_user_records(
+    state: &SharedState,
+    user: &User,
+) -> Result<impl Iterator<Item = RecordDto> + Send + 'static, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
 
     let zone = state
-        .sub_pdns
+        .sub_pdns_for(&user.base_domain)
         .get_zone(&zone_name)
         .await
         .map_err(internal)?;
-    let mut records = Vec::new();
+    let notes: HashMap<(String, String), RecordNote> =
+        record_note_repo::list_for_user(&state.db, user.id)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(|note| ((note.name.clone(), note.rrtype.clone()), note))
+            .collect();
+    let provenance: HashMap<(String, String), RecordProvenance> =
+        record_provenance_repo::list_for_user(&state.db, user.id)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(|written| ((written.name.clone(), written.rrtype.clone()), written))
+            .collect();
 
-    if let Some(rrsets) = zone.rrsets {
-        for rr in rrsets {
-            // skip apex NS and SOA; keep these under server control
-            if rr.rrtype.eq_ignore_ascii_case("SOA") {
-                continue;
-            }
-            if rr.rrtype.eq_ignore_ascii_case("NS") && rr.name.eq_ignore_ascii_case(&zone_name) {
-                continue;
-            }
-            for rec in rr.records {
-                records.push(RecordDto {
-                    name: rr.name.clone(), // TODO: normalize to relative if desired
-                    rrtype: rr.rrtype.clone(),
-                    ttl: rr.ttl,
-                    content: rec.content,
+    Ok(zone
+        .rrsets
+        .unwrap_or_default()
+        .into_iter()
+        // skip apex NS and SOA; keep these under server control
+        .filter(move |rr| !is_server_managed(rr, &zone_name))
+        .flat_map(move |rr| {
+            let is_txt = matches!(rr.rrtype.as_str(), "TXT" | "SPF");
+            let key = (rr.name.to_ascii_lowercase(), rr.rrtype.clone());
+            let stored = notes.get(&key);
+            let note = stored.and_then(|stored| stored.note.clone());
+            let labels = stored
+                .map(|stored| stored.labels.clone())
+                .filter(|labels| !labels.is_empty());
+            let provenance = provenance.get(&key).map(|written| Provenance {
+                created_by: written.created_by.clone(),
+                created_at: written.created_at,
+                modified_by: written.modified_by.clone(),
+                modified_at: written.modified_at,
+            });
+            let (name, rrtype, ttl) = (rr.name, rr.rrtype, rr.ttl);
+            rr.records.into_iter().map(move |rec| {
+                let content = if is_txt {
+                    rdata::join_txt(&rec.content).into_owned()
+                } else {
+                    rec.content
+                };
+                RecordDto {
+                    name: name.clone(), // TODO: normalize to relative if desired
+                    rrtype: rrtype.clone(),
+                    ttl: Some(ttl),
+                    data: RecordData::parse(&rrtype, &content),
+                    content,
                     priority: None, // TODO: parse for MX/SRV if you care
-                });
-            }
-        }
-    }
-
-    Ok(Json(records))
+                    srv: None,
+                    note: note.clone(),
+                    labels: labels.clone(),
+                    provenance: provenance.clone(),
+                }
+            })
+        }))
 }
 
 /// Request payload describing the full set of records to retain.
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ZoneUpdateRequest {
     pub records: Vec<RecordDto>,
 }
@@ -65,56 +350,407 @@ pub async fn put_zone(
     Authenticated(user): Authenticated,
     Extension(state): Extension<SharedState>,
     Json(req): Json<ZoneUpdateRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let zone_name = state.config.user_zone_name(&user.subdomain);
+) -> Result<Json<serde_json::Value>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let (rrsets, notes) = build_rrsets_with_notes(&state.policy(), &zone_name, req.records)?;
+    let mut warnings = lint_rrsets(&state.policy(), &rrsets)?;
+    count_zone_update(&state, &user)?;
 
-    let mut map: BTreeMap<(String, String), (u32, Vec<PdnsRecord>)> = BTreeMap::new();
-    for record in req.records {
-        if record.ttl == 0 {
-            return Err((
-                axum::http::StatusCode::BAD_REQUEST,
-                "ttl must be greater than 0".into(),
-            ));
+    // needed for the limits and for the stored record count
+    let zone = state
+        .sub_pdns_for(&user.base_domain)
+        .get_zone(&zone_name)
+        .await
+        .map_err(internal)?;
+    let existing = zone.rrsets.unwrap_or_default();
+    if state.policy().has_zone_limits() {
+        enforce_zone_limits(&state.policy(), &zone_name, &existing, &rrsets)?;
+    }
+    let hits = screen_targets(&state, &zone_name, &existing, &rrsets).await?;
+
+    state
+        .sub_pdns_for(&user.base_domain)
+        .patch_rrsets(&zone_name, &rrsets)
+        .await
+        .map_err(internal)?;
+    flag_listed_targets(&state, &user, &zone_name, hits).await;
+    record_usage(&state, &user, &zone_name, &existing, &rrsets).await;
+    store_notes(&state, &user, &notes, &rrsets, &mut warnings).await;
+    store_provenance(&state, &user, Credential::Password, &rrsets).await;
+    publish_records_changed(&state, &user, Credential::Password, zone_name, &rrsets);
+
+    let applied: Vec<AppliedRrset> = rrsets.iter().map(AppliedRrset::from).collect();
+    Ok(Json(
+        serde_json::json!({ "ok": true, "warnings": warnings, "rrsets": applied }),
+    ))
+}
+
+/// An RRset as written to the zone, with its absolute owner and final TTL.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppliedRrset {
+    pub name: String,
+    pub rrtype: String,
+    pub ttl: u32,
+    /// Contents as `GET /api/zone` returns them.
+    pub records: Vec<String>,
+}
+
+impl From<&PdnsRrset> for AppliedRrset {
+    fn from(rrset: &PdnsRrset) -> Self {
+        let is_txt = matches!(rrset.rrtype.as_str(), "TXT" | "SPF");
+        AppliedRrset {
+            name: rrset.name.clone(),
+            rrtype: rrset.rrtype.clone(),
+            ttl: rrset.ttl,
+            records: rrset
+                .records
+                .iter()
+                .map(|record| {
+                    if is_txt {
+                        rdata::join_txt(&record.content).into_owned()
+                    } else {
+                        record.content.clone()
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Current size of the caller's zone next to the policy's ceilings.
+#[derive(Serialize)]
+pub struct ZoneUsageResponse {
+    #[serde(flatten)]
+    pub usage: ZoneUsage,
+    /// Zone updates made in the current minute and day.
+    pub updates: rate_limit::Usage,
+    pub limits: ZoneLimits,
+}
+
+/// Ceilings from the policy; `None` means unlimited.
+#[derive(Serialize)]
+pub struct ZoneLimits {
+    pub max_rrsets_per_zone: Option<usize>,
+    pub max_records_per_zone: Option<usize>,
+    pub max_zone_bytes: Option<usize>,
+    pub max_zone_updates_per_minute: Option<u32>,
+    pub max_zone_updates_per_day: Option<u32>,
+}
+
+/// Report how many RRsets, records, and bytes the caller's zone uses.
+pub async fn get_zone_usage(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<ZoneUsageResponse>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let zone = state
+        .sub_pdns_for(&user.base_domain)
+        .get_zone(&zone_name)
+        .await
+        .map_err(internal)?;
+    let usage = usage_after_update(&zone_name, &zone.rrsets.unwrap_or_default(), &[]);
+
+    let policy = state.policy();
+    Ok(Json(ZoneUsageResponse {
+        usage,
+        updates: state.zone_updates.usage(user.id),
+        limits: ZoneLimits {
+            max_rrsets_per_zone: policy.max_rrsets_per_zone,
+            max_records_per_zone: policy.max_records_per_zone,
+            max_zone_bytes: policy.max_zone_bytes,
+            max_zone_updates_per_minute: policy.max_zone_updates_per_minute,
+            max_zone_updates_per_day: policy.max_zone_updates_per_day,
+        },
+    }))
+}
+
+/// The server-managed RRsets at the zone apex.
+#[derive(Serialize)]
+pub struct ZoneApexResponse {
+    pub zone: String,
+    /// Always `true`; `PUT /api/zone` cannot change these RRsets.
+    pub read_only: bool,
+    /// The SOA RRset, then the apex NS RRset.
+    pub rrsets: Vec<AppliedRrset>,
+}
+
+/// Show the SOA and apex NS RRsets that `get_zone` leaves out.
+pub async fn get_zone_apex(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<ZoneApexResponse>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let zone = state
+        .sub_pdns_for(&user.base_domain)
+        .get_zone(&zone_name)
+        .await
+        .map_err(internal)?;
+
+    let mut rrsets: Vec<AppliedRrset> = zone
+        .rrsets
+        .unwrap_or_default()
+        .iter()
+        .filter(|rr| is_server_managed(rr, &zone_name))
+        .map(AppliedRrset::from)
+        .collect();
+    rrsets.sort_by_key(|rr| rr.rrtype != "SOA");
+    Ok(Json(ZoneApexResponse {
+        zone: zone_name,
+        read_only: true,
+        rrsets,
+    }))
+}
+
+/// Count a zone update against the caller's rate limits; refuses it with
+/// `429` once the minute or day allowance is used up.
+pub(crate) fn count_zone_update(state: &SharedState, user: &User) -> Result<(), AppError> {
+    let (per_minute, per_day) = {
+        let policy = state.policy();
+        (
+            policy.max_zone_updates_per_minute,
+            policy.max_zone_updates_per_day,
+        )
+    };
+    if per_minute.is_none() && per_day.is_none() {
+        return Ok(());
+    }
+    match state.zone_updates.acquire(user.id, per_minute, per_day) {
+        Ok(_) => Ok(()),
+        Err(exceeded) => {
+            tracing::warn!(
+                "zone updates of {} exceed {} per {}",
+                user.subdomain,
+                exceeded.limit,
+                exceeded.window
+            );
+            // round up so a client waiting this long is never refused again
+            let retry_after = exceeded.retry_after.as_secs() + 1;
+            Err(AppError::new(
+                ErrorCode::TooManyRequests,
+                format!(
+                    "zone update limit of {} per {} reached; retry in {retry_after} s",
+                    exceeded.limit, exceeded.window
+                ),
+            )
+            .with("window", exceeded.window)
+            .with("limit", exceeded.limit)
+            .with("updates", serde_json::json!(exceeded.usage))
+            .with("retry_after", retry_after))
         }
+    }
+}
+
+/// Store the zone's record count after a successful patch, for the metrics.
+pub(crate) async fn record_usage(
+    state: &SharedState,
+    user: &User,
+    zone_name: &str,
+    existing: &[PdnsRrset],
+    updates: &[PdnsRrset],
+) {
+    let usage = usage_after_update(zone_name, existing, updates);
+    if let Err(err) = user_repo::set_record_count(&state.db, user.id, usage.records).await {
+        tracing::warn!("failed to store record count of {zone_name}: {err:?}");
+    }
+}
+
+/// Announce a successful patch on the event bus.
+pub(crate) fn publish_records_changed(
+    state: &SharedState,
+    user: &User,
+    credential: Credential,
+    zone_name: String,
+    rrsets: &[PdnsRrset],
+) {
+    let changed = rrsets
+        .iter()
+        .map(|rr| RrsetRef {
+            name: rr.name.clone(),
+            rrtype: rr.rrtype.clone(),
+        })
+        .collect();
+    state.events.publish(Event::new(
+        user.id,
+        zone_name,
+        EventKind::RecordsChanged {
+            rrsets: changed,
+            credential: credential.as_str().to_string(),
+        },
+    ));
+}
+
+/// Validate submitted records against the policy and group them into
+/// `REPLACE` RRsets, ordered by owner and type.
+pub(crate) fn build_rrsets(
+    policy: &PolicyConfig,
+    zone_name: &str,
+    records: Vec<RecordDto>,
+) -> Result<Vec<PdnsRrset>, AppError> {
+    build_rrsets_with_notes(policy, zone_name, records).map(|(rrsets, _)| rrsets)
+}
+
+/// Like [`build_rrsets`], also returning the notes and labels submitted for
+/// each RRset that had any.
+pub(crate) fn build_rrsets_with_notes(
+    policy: &PolicyConfig,
+    zone_name: &str,
+    records: Vec<RecordDto>,
+) -> Result<(Vec<PdnsRrset>, Vec<RrsetNotes>), AppError> {
+    let mut map: BTreeMap<(String, String), (u32, Vec<PdnsRecord>)> = BTreeMap::new();
+    let mut notes: BTreeMap<(String, String), RrsetNotes> = BTreeMap::new();
+    for record in records {
+        let (note, labels) = clean_notes(record.note.as_deref(), record.labels.as_deref())?;
+        let ttl = policy.effective_ttl(record.ttl);
+        let (rrtype, content) = match &record.data {
+            None => (record.rrtype.to_uppercase(), record.content),
+            Some(data)
+                if !record.rrtype.is_empty()
+                    && !record.rrtype.eq_ignore_ascii_case(data.rrtype()) =>
+            {
+                return Err(AppError::new(
+                    ErrorCode::InvalidRecord,
+                    format!(
+                        "{} data was given for a {} record",
+                        data.rrtype(),
+                        record.rrtype
+                    ),
+                ));
+            }
+            // records read from the API carry both; they must agree
+            Some(data)
+                if !record.content.trim().is_empty()
+                    && RecordData::parse(data.rrtype(), &record.content).as_ref() != Some(data) =>
+            {
+                return Err(AppError::new(
+                    ErrorCode::InvalidRecord,
+                    format!("content {:?} does not match the given data", record.content),
+                ));
+            }
+            Some(data) => (data.rrtype().to_string(), data.to_content()),
+        };
 
-        let owner = normalize_owner(&record.name, &zone_name)
-            .map_err(|msg| (axum::http::StatusCode::BAD_REQUEST, msg))?;
-        let rrtype = record.rrtype.to_uppercase();
+        let (name, content) = match &record.srv {
+            None => (record.name, content),
+            Some(_) if rrtype != "SRV" => {
+                return Err(AppError::new(
+                    ErrorCode::InvalidRecord,
+                    format!("srv fields were given for a {rrtype} record"),
+                ));
+            }
+            Some(_) if !content.trim().is_empty() => {
+                return Err(AppError::new(
+                    ErrorCode::InvalidRecord,
+                    "give either content or srv fields, not both",
+                ));
+            }
+            Some(srv) => srv.compose(&record.name).map_err(|msg| {
+                AppError::new(
+                    ErrorCode::InvalidRecord,
+                    format!("invalid SRV record: {msg}"),
+                )
+            })?,
+        };
+        let owner = normalize_owner(&name, zone_name)?;
 
         if rrtype == "SOA" {
-            return Err((
-                axum::http::StatusCode::BAD_REQUEST,
-                "SOA records are managed automatically and cannot be modified".into(),
+            return Err(AppError::localized(
+                ErrorCode::InvalidRecord,
+                MessageId::SoaManaged,
+            ));
+        }
+
+        if !policy.is_record_type_allowed(&rrtype) {
+            return Err(AppError::localized(
+                ErrorCode::RecordTypeNotAllowed,
+                Message::new(MessageId::RecordTypeNotAllowed, [&rrtype]),
             ));
         }
 
-        if rrtype == "NS" && owner.eq_ignore_ascii_case(&zone_name) {
-            return Err((
-                axum::http::StatusCode::BAD_REQUEST,
-                "apex NS records must be managed via NS-mode endpoints".into(),
+        let invalid = |msg: String| {
+            AppError::new(
+                ErrorCode::InvalidRecord,
+                format!("invalid {rrtype} record for {owner}: {msg}"),
+            )
+        };
+        let stored = match rrtype.as_str() {
+            "TXT" | "SPF" => rdata::chunk_txt(&content).map_err(invalid)?.into_owned(),
+            _ => content.clone(),
+        };
+        rdata::to_wire(&rrtype, &stored).map_err(invalid)?;
+
+        policy
+            .content
+            .check(&rrtype, &content)
+            .map_err(|reason| AppError::localized(ErrorCode::ContentRejected, reason))?;
+
+        if rrtype == "NS" && owner.eq_ignore_ascii_case(zone_name) {
+            return Err(AppError::localized(
+                ErrorCode::InvalidRecord,
+                MessageId::ApexNsManaged,
             ));
         }
 
+        if note.is_some() || labels.is_some() {
+            let key = (owner.to_ascii_lowercase(), rrtype.clone());
+            let entry = notes.entry(key).or_insert_with(|| RrsetNotes {
+                name: owner.clone(),
+                rrtype: rrtype.clone(),
+                ..RrsetNotes::default()
+            });
+            let conflict = |what: &str| {
+                AppError::new(
+                    ErrorCode::InvalidRecord,
+                    format!("conflicting {what} for {owner} {rrtype}"),
+                )
+            };
+            if let Some(note) = note {
+                if entry
+                    .note
+                    .as_ref()
+                    .is_some_and(|existing| *existing != note)
+                {
+                    return Err(conflict("notes"));
+                }
+                entry.note = Some(note);
+            }
+            if let Some(labels) = labels {
+                if entry
+                    .labels
+                    .as_ref()
+                    .is_some_and(|existing| *existing != labels)
+                {
+                    return Err(conflict("labels"));
+                }
+                entry.labels = Some(labels);
+            }
+        }
+
         match map.entry((owner.clone(), rrtype.clone())) {
             Entry::Vacant(v) => {
                 v.insert((
-                    record.ttl,
+                    ttl,
                     vec![PdnsRecord {
-                        content: record.content,
+                        content: stored,
                         disabled: false,
                     }],
                 ));
             }
             Entry::Occupied(mut o) => {
-                let (ttl, records) = o.get_mut();
-                if *ttl != record.ttl {
-                    return Err((
-                        axum::http::StatusCode::BAD_REQUEST,
-                        format!("conflicting TTLs for {} {}", owner, rrtype),
+                let (existing_ttl, records) = o.get_mut();
+                if *existing_ttl != ttl {
+                    return Err(AppError::localized(
+                        ErrorCode::InvalidRecord,
+                        Message::new(MessageId::ConflictingTtls, [&owner, &rrtype]),
                     ));
                 }
                 records.push(PdnsRecord {
-                    content: record.content,
+                    content: stored,
                     disabled: false,
                 });
             }
@@ -132,37 +768,389 @@ pub async fn put_zone(
             comments: Vec::new(),
         });
     }
+    Ok((rrsets, notes.into_values().collect()))
+}
 
-    state
-        .sub_pdns
-        .patch_rrsets(&zone_name, &rrsets)
+/// Check a submitted note and labels; labels come back lowercase, sorted,
+/// and deduplicated.
+fn clean_notes(
+    note: Option<&str>,
+    labels: Option<&[String]>,
+) -> Result<(Option<String>, Option<Vec<String>>), AppError> {
+    let invalid = |msg: String| AppError::new(ErrorCode::InvalidRecord, msg);
+    let note = note.map(str::trim);
+    if let Some(note) = note
+        && note.chars().count() > MAX_NOTE_CHARS
+    {
+        return Err(invalid(format!(
+            "notes may be at most {MAX_NOTE_CHARS} characters"
+        )));
+    }
+    let labels = match labels {
+        None => None,
+        Some(labels) => {
+            let mut cleaned: Vec<String> = labels
+                .iter()
+                .map(|label| label.trim().to_ascii_lowercase())
+                .collect();
+            cleaned.sort();
+            cleaned.dedup();
+            if cleaned.len() > MAX_LABELS {
+                return Err(invalid(format!(
+                    "an RRset may carry at most {MAX_LABELS} labels"
+                )));
+            }
+            if let Some(label) = cleaned.iter().find(|label| {
+                label.is_empty()
+                    || label.len() > MAX_LABEL_CHARS
+                    || !label
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+            }) {
+                return Err(invalid(format!(
+                    "invalid label {label:?}; use up to {MAX_LABEL_CHARS} letters, digits, '-', '_', or '.'"
+                )));
+            }
+            Some(cleaned)
+        }
+    };
+    Ok((note.map(str::to_string), labels))
+}
+
+/// Save the notes submitted with a successful patch, and drop those of the
+/// RRsets it deleted. The records are already written by then, so a failure
+/// is logged and added to `warnings` rather than failing the request.
+pub(crate) async fn store_notes(
+    state: &SharedState,
+    user: &User,
+    notes: &[RrsetNotes],
+    patch: &[PdnsRrset],
+    warnings: &mut Vec<String>,
+) {
+    let stored = async {
+        for rr in patch
+            .iter()
+            .filter(|rr| rr.changetype.as_deref() == Some("DELETE"))
+        {
+            record_note_repo::delete(&state.db, user.id, &rr.name, &rr.rrtype).await?;
+        }
+        for notes in notes {
+            record_note_repo::set(
+                &state.db,
+                user.id,
+                &notes.name,
+                &notes.rrtype,
+                notes.note.as_deref(),
+                notes.labels.as_deref(),
+            )
+            .await?;
+        }
+        Ok::<_, sqlx::Error>(())
+    };
+    if let Err(err) = stored.await {
+        tracing::warn!("failed to store record notes of user {}: {err:?}", user.id);
+        warnings.push("records were saved, but their notes and labels were not".into());
+    }
+}
+
+/// Stamp the RRsets a successful patch wrote with `credential`, and drop
+/// the provenance of those it deleted. Failures are only logged; the
+/// records are already written.
+pub(crate) async fn store_provenance(
+    state: &SharedState,
+    user: &User,
+    credential: Credential,
+    patch: &[PdnsRrset],
+) {
+    for rr in patch {
+        let stored = if rr.changetype.as_deref() == Some("DELETE") {
+            record_provenance_repo::delete(&state.db, user.id, &rr.name, &rr.rrtype).await
+        } else {
+            record_provenance_repo::touch(
+                &state.db,
+                user.id,
+                &rr.name,
+                &rr.rrtype,
+                credential.as_str(),
+            )
+            .await
+        };
+        if let Err(err) = stored {
+            tracing::warn!(
+                "failed to store provenance of {} {}: {err:?}",
+                rr.name,
+                rr.rrtype
+            );
+        }
+    }
+}
+
+/// Lint the SPF policies among `rrsets` and collect the content policy's
+/// warnings. Findings are returned as warnings; SPF errors are rejected
+/// instead when the content policy says so.
+pub(crate) fn lint_rrsets(
+    policy: &PolicyConfig,
+    rrsets: &[PdnsRrset],
+) -> Result<Vec<String>, AppError> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for rrset in rrsets {
+        warnings.extend(rrset.records.iter().filter_map(|record| {
+            let warning = policy.content.warning(&rrset.rrtype, &record.content)?;
+            Some(format!("{}: {warning}", rrset.name))
+        }));
+        if !matches!(rrset.rrtype.as_str(), "TXT" | "SPF") {
+            continue;
+        }
+        let policies: Vec<String> = rrset
+            .records
+            .iter()
+            .filter_map(|record| rdata::txt_text(&record.content).ok())
+            .map(|text| String::from_utf8_lossy(&text).into_owned())
+            .filter(|text| spf::is_spf(text))
+            .collect();
+        if policies.len() > 1 {
+            errors.push(format!(
+                "{}: {} SPF records, receivers ignore all of them",
+                rrset.name,
+                policies.len()
+            ));
+        }
+        for text in &policies {
+            let lint = spf::lint(text);
+            errors.extend(
+                lint.errors
+                    .iter()
+                    .map(|err| format!("{}: SPF {err}", rrset.name)),
+            );
+            warnings.extend(
+                lint.warnings
+                    .iter()
+                    .map(|warning| format!("{}: SPF {warning}", rrset.name)),
+            );
+        }
+    }
+    if policy.content.reject_invalid_spf && !errors.is_empty() {
+        return Err(AppError::new(ErrorCode::ContentRejected, errors.join("; ")));
+    }
+    errors.extend(warnings);
+    Ok(errors)
+}
+
+/// Look the targets of records that `updates` adds up on the policy's
+/// blocklists. Hits reject the update when the policy says so; otherwise
+/// they are returned for [`flag_listed_targets`].
+pub(crate) async fn screen_targets(
+    state: &SharedState,
+    zone_name: &str,
+    existing: &[PdnsRrset],
+    updates: &[PdnsRrset],
+) -> Result<Vec<reputation::Hit>, AppError> {
+    let config = state.policy().reputation.clone();
+    if !config.enabled {
+        return Ok(Vec::new());
+    }
+    let zone = zone_name.trim_end_matches('.').to_ascii_lowercase();
+    let mut targets = Vec::new();
+    for rrset in updates {
+        let old = existing
+            .iter()
+            .find(|old| old.rrtype == rrset.rrtype && old.name.eq_ignore_ascii_case(&rrset.name));
+        for record in &rrset.records {
+            if old.is_some_and(|old| old.records.iter().any(|r| r.content == record.content)) {
+                continue;
+            }
+            let Some(target) = reputation::target(&rrset.rrtype, &record.content) else {
+                continue;
+            };
+            // names in the caller's own zone are covered by their own records
+            if let Target::Host(host) = &target
+                && (*host == zone || host.ends_with(&format!(".{zone}")))
+            {
+                continue;
+            }
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let hits = reputation::check(&config, targets).await;
+    if config.action == ReputationAction::Reject
+        && let Some(hit) = hits.first()
+    {
+        return Err(AppError::new(
+            ErrorCode::ContentRejected,
+            format!("record target {} is listed on {}", hit.target, hit.list),
+        ));
+    }
+    Ok(hits)
+}
+
+/// Put a zone whose new records point at listed targets into the abuse queue.
+pub(crate) async fn flag_listed_targets(
+    state: &SharedState,
+    user: &User,
+    zone_name: &str,
+    hits: Vec<reputation::Hit>,
+) {
+    if hits.is_empty() {
+        return;
+    }
+    tracing::warn!(
+        "{zone_name} points at {} listed target(s), flagged for review",
+        hits.len()
+    );
+    let details = serde_json::json!({ "reason": "listed_target", "hits": hits });
+    let flagged = async {
+        if abuse_repo::flag(&state.db, user.id, zone_name, "listed_target", &details).await? {
+            alerts::raise(
+                state,
+                abuse::flagged_alert(zone_name, "listed_target", &details),
+            );
+        }
+        audit_repo::insert(
+            &state.db,
+            "reputation",
+            "abuse.flagged",
+            zone_name,
+            &details,
+        )
         .await
-        .map_err(internal)?;
+    };
+    if let Err(err) = flagged.await {
+        tracing::warn!("failed to flag {zone_name}: {err:?}");
+    }
+}
+
+/// Whether the RRset is server-managed (SOA or apex NS) and hidden from users.
+pub(crate) fn is_server_managed(rr: &PdnsRrset, zone_name: &str) -> bool {
+    rr.rrtype.eq_ignore_ascii_case("SOA")
+        || (rr.rrtype.eq_ignore_ascii_case("NS") && rr.name.eq_ignore_ascii_case(zone_name))
+}
+
+/// Size of the user-visible part of a zone (server-managed RRsets excluded).
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ZoneUsage {
+    pub rrsets: usize,
+    pub records: usize,
+    /// Length of the records rendered in zone-file presentation format.
+    pub bytes: usize,
+}
+
+impl ZoneUsage {
+    fn add(&mut self, rr: &PdnsRrset) {
+        if rr.records.is_empty() {
+            return;
+        }
+        self.rrsets += 1;
+        self.records += rr.records.len();
+        // "<name> <ttl> IN <type> <content>\n" per record
+        let prefix = rr.name.len() + rr.ttl.to_string().len() + rr.rrtype.len() + 6;
+        self.bytes += rr
+            .records
+            .iter()
+            .map(|rec| prefix + rec.content.len())
+            .sum::<usize>();
+    }
+}
+
+/// Usage the zone would have after replacing the RRsets in `updates`.
+fn usage_after_update(zone_name: &str, existing: &[PdnsRrset], updates: &[PdnsRrset]) -> ZoneUsage {
+    let replaced = |rr: &PdnsRrset| {
+        updates.iter().any(|update| {
+            update.name.eq_ignore_ascii_case(&rr.name)
+                && update.rrtype.eq_ignore_ascii_case(&rr.rrtype)
+        })
+    };
+
+    let mut usage = ZoneUsage::default();
+    for rr in existing
+        .iter()
+        .filter(|rr| !is_server_managed(rr, zone_name) && !replaced(rr))
+        .chain(updates)
+    {
+        usage.add(rr);
+    }
+    usage
+}
+
+/// Reject updates that would push the zone past the policy's size ceilings.
+pub(crate) fn enforce_zone_limits(
+    policy: &PolicyConfig,
+    zone_name: &str,
+    existing: &[PdnsRrset],
+    updates: &[PdnsRrset],
+) -> Result<(), AppError> {
+    let after = usage_after_update(zone_name, existing, updates);
+    if let Some(max_records) = policy.max_records_per_zone
+        && after.records > max_records
+    {
+        return Err(AppError::localized(
+            ErrorCode::RecordQuotaExceeded,
+            Message::new(MessageId::TooManyRecords, [after.records, max_records]),
+        ));
+    }
 
-    Ok(Json(serde_json::json!({ "ok": true })))
+    let exceeded = match (policy.max_rrsets_per_zone, policy.max_zone_bytes) {
+        (Some(max), _) if after.rrsets > max => Some((MessageId::TooManyRrsets, after.rrsets, max)),
+        (_, Some(max)) if after.bytes > max => Some((MessageId::ZoneTooLarge, after.bytes, max)),
+        _ => None,
+    };
+    if let Some((id, after, max)) = exceeded {
+        let current = usage_after_update(zone_name, existing, &[]);
+        return Err(AppError::localized(
+            ErrorCode::ZoneTooLarge,
+            Message::new(
+                id,
+                [after, max, current.rrsets, current.records, current.bytes],
+            ),
+        ));
+    }
+    Ok(())
 }
 
-/// Convert relative names or shorthands into the absolute owner within the zone.
-fn normalize_owner(name: &str, zone_name: &str) -> Result<String, String> {
+/// Convert relative names or shorthands into the absolute owner within the
+/// zone, enforcing the name length limits and the owner label syntax.
+pub(crate) fn normalize_owner(name: &str, zone_name: &str) -> Result<String, AppError> {
     let trimmed = name.trim();
     if trimmed.is_empty() || trimmed == "@" {
         return Ok(zone_name.to_string());
     }
 
-    if trimmed.ends_with('.') {
+    let (owner, relative) = if trimmed.ends_with('.') {
         let owner_lower = trimmed.to_ascii_lowercase();
         let zone_lower = zone_name.to_ascii_lowercase();
         if owner_lower == zone_lower {
             return Ok(trimmed.to_string());
         }
-        if owner_lower.ends_with(&zone_lower) {
-            let prefix_len = owner_lower.len() - zone_lower.len();
-            if prefix_len > 0 && owner_lower.as_bytes()[prefix_len - 1] == b'.' {
-                return Ok(trimmed.to_string());
-            }
-        }
-        return Err("record name must be within your zone".into());
-    }
+        let Some(prefix) = owner_lower
+            .strip_suffix(&zone_lower)
+            .and_then(|prefix| prefix.strip_suffix('.'))
+        else {
+            return Err(AppError::localized(
+                ErrorCode::InvalidRecord,
+                MessageId::OwnerOutsideZone,
+            ));
+        };
+        let relative = trimmed[..prefix.len()].to_string();
+        (trimmed.to_string(), relative)
+    } else {
+        (format!("{trimmed}.{zone_name}"), trimmed.to_string())
+    };
 
-    Ok(format!("{}.{}", trimmed.trim_end_matches('.'), zone_name))
+    validate_name_length(&owner)
+        .and_then(|()| validate_owner_labels(&relative))
+        .map_err(|err| {
+            let code = if err.is_length_limit() {
+                ErrorCode::NameTooLong
+            } else {
+                ErrorCode::InvalidRecord
+            };
+            AppError::localized(code, err)
+        })?;
+    Ok(owner)
 }
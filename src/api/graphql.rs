@@ -0,0 +1,140 @@
+//! GraphQL view over the caller's profile, zone records, and change history.
+use super::dns::{RecordDto, load_user_records};
+use super::profile::{ProfileDto, load_profile};
+use crate::db::history_repo;
+use crate::db::user_repo::User;
+use crate::error::{AppError, ErrorCode};
+use crate::i18n::MessageId;
+use crate::{SharedState, auth::Authenticated};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{
+    Extension,
+    http::{HeaderMap, header},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::sync::LazyLock;
+
+/// Largest `history(limit:)` accepted.
+const MAX_HISTORY_LIMIT: i32 = 500;
+
+/// Read-only schema; mutations stay on the REST endpoints.
+pub type SatsukiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+static SCHEMA: LazyLock<SatsukiSchema> = LazyLock::new(|| {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(8)
+        .limit_complexity(256)
+        .finish()
+});
+
+/// Serve the GraphiQL explorer.
+pub async fn graphiql() -> Html<String> {
+    Html(
+        GraphiQLSource::build()
+            .endpoint(&format!("{}/graphql", super::API_V1_PREFIX))
+            .finish(),
+    )
+}
+
+/// Execute a GraphQL request; Basic credentials are optional but required by every field.
+pub async fn graphql(
+    Extension(state): Extension<SharedState>,
+    headers: HeaderMap,
+    auth: Result<Authenticated, AppError>,
+    request: GraphQLRequest,
+) -> Response {
+    let caller = match auth {
+        Ok(Authenticated(user)) => Some(user),
+        Err(_) if !headers.contains_key(header::AUTHORIZATION) => None,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let request = request.into_inner().data(state).data(Caller(caller));
+    GraphQLResponse::from(SCHEMA.execute(request).await).into_response()
+}
+
+/// The authenticated user, if any, attached to each request.
+struct Caller(Option<User>);
+
+fn caller<'a>(ctx: &'a Context<'_>) -> async_graphql::Result<&'a User> {
+    ctx.data::<Caller>()?.0.as_ref().ok_or_else(|| {
+        gql_error(AppError::localized(
+            ErrorCode::Unauthorized,
+            MessageId::AuthenticationRequired,
+        ))
+    })
+}
+
+/// Surface an `AppError` with its stable code under `extensions.code`.
+fn gql_error(err: AppError) -> async_graphql::Error {
+    if let AppError::Internal(inner) = &err {
+        tracing::error!("{inner:#}");
+    }
+    let code = err.code();
+    async_graphql::Error::new(err.to_string()).extend_with(|_, ext| ext.set("code", code.as_str()))
+}
+
+/// A recorded change, as also delivered to webhooks.
+#[derive(SimpleObject)]
+pub struct Change {
+    pub id: i64,
+    pub zone: String,
+    pub event_type: String,
+    /// The full event payload.
+    pub payload: async_graphql::Json<serde_json::Value>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Root query type.
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The caller's profile and NS configuration.
+    async fn profile(&self, ctx: &Context<'_>) -> async_graphql::Result<ProfileDto> {
+        let state = ctx.data::<SharedState>()?;
+        load_profile(state, caller(ctx)?.clone())
+            .await
+            .map_err(gql_error)
+    }
+
+    /// Every user-manageable record in the caller's zone.
+    async fn records(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<RecordDto>> {
+        let state = ctx.data::<SharedState>()?;
+        load_user_records(state, caller(ctx)?)
+            .await
+            .map_err(gql_error)
+    }
+
+    /// The caller's most recent changes, newest first.
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 50)] limit: i32,
+    ) -> async_graphql::Result<Vec<Change>> {
+        let state = ctx.data::<SharedState>()?;
+        let user = caller(ctx)?;
+        let limit = limit.clamp(1, MAX_HISTORY_LIMIT);
+        let changes = history_repo::list_for_user(&state.db, user.id, None, i64::from(limit))
+            .await
+            .map_err(|err| gql_error(AppError::internal(err)))?;
+
+        Ok(changes
+            .into_iter()
+            .map(|change| Change {
+                id: change.id,
+                zone: change.zone,
+                event_type: change.event_type,
+                payload: async_graphql::Json(
+                    serde_json::from_str(&change.payload).unwrap_or_default(),
+                ),
+                occurred_at: change.occurred_at,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,269 @@
+//! Importing a zone from another provider by zone transfer, so migrating
+//! does not need an exported zone file.
+use super::dns::RecordDto;
+use super::sync::{Missing, SyncResponse, converge};
+use crate::axfr::{self, TsigAlgorithm, TsigKey};
+use crate::config::PolicyConfig;
+use crate::dns_probe::AnswerRecord;
+use crate::error::{AppError, ErrorCode};
+use crate::validation::special_range;
+use crate::{SharedState, auth::Authenticated};
+use axum::{Extension, Json};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// Time to resolve the server's name.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time one transfer may take, connecting included.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(60);
+/// Types the server signs the zone with itself.
+const DNSSEC_TYPES: &[&str] = &[
+    "DNSKEY",
+    "RRSIG",
+    "NSEC",
+    "NSEC3",
+    "NSEC3PARAM",
+    "CDS",
+    "CDNSKEY",
+];
+
+/// Payload of `POST /api/zone/import/axfr`.
+#[derive(Deserialize)]
+pub struct AxfrImportRequest {
+    /// Primary to transfer from: an address or hostname, optionally with a
+    /// port (default 53).
+    pub server: String,
+    /// Zone to transfer; defaults to the caller's zone. Its records are
+    /// moved below the caller's zone.
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub tsig: Option<TsigRequest>,
+    /// Also delete RRsets the transfer does not contain.
+    #[serde(default)]
+    pub replace: bool,
+    /// Report the changes without making them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// TSIG key the primary requires for transfers.
+#[derive(Deserialize)]
+pub struct TsigRequest {
+    pub name: String,
+    #[serde(default = "default_algorithm")]
+    pub algorithm: String,
+    /// Base64, as in BIND's `key` statements.
+    pub secret: String,
+}
+
+fn default_algorithm() -> String {
+    "hmac-sha256".into()
+}
+
+/// A transferred record that was not imported.
+#[derive(Serialize)]
+pub struct SkippedRecord {
+    pub name: String,
+    pub rrtype: String,
+    pub reason: &'static str,
+}
+
+/// Outcome of an import: the transfer, then the sync it turned into.
+#[derive(Serialize)]
+pub struct AxfrImportResponse {
+    /// Records received, the skipped ones included.
+    pub transferred: usize,
+    pub skipped: Vec<SkippedRecord>,
+    #[serde(flatten)]
+    pub sync: SyncResponse,
+}
+
+/// Transfer a zone from the caller's old primary and load its records.
+pub async fn import_axfr(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<AxfrImportRequest>,
+) -> Result<Json<AxfrImportResponse>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let source = match req.zone.as_deref().map(str::trim) {
+        None | Some("") => zone_name.clone(),
+        Some(zone) => format!("{}.", zone.trim_end_matches('.').to_ascii_lowercase()),
+    };
+    let tsig = req.tsig.as_ref().map(tsig_key).transpose()?;
+    let addrs = resolve_server(&req.server).await?;
+
+    let mut last_err = None;
+    let mut transferred = None;
+    for addr in addrs {
+        match axfr::transfer(addr, &source, tsig.as_ref(), TRANSFER_TIMEOUT).await {
+            Ok(records) => {
+                transferred = Some(records);
+                break;
+            }
+            Err(err) => last_err = Some(err),
+        }
+    }
+    let Some(transferred) = transferred else {
+        let err = last_err.map_or_else(
+            || "no addresses to try".to_string(),
+            |err| format!("{err:#}"),
+        );
+        return Err(AppError::new(
+            ErrorCode::TransferFailed,
+            format!("zone transfer of {source} failed: {err}"),
+        ));
+    };
+
+    let count = transferred.len();
+    let (records, skipped) = sort_records(&state.policy(), &source, transferred);
+    let missing = if req.replace {
+        Missing::Delete
+    } else {
+        Missing::Keep
+    };
+    let sync = converge(&state, &user, records, missing, None, !req.dry_run).await?;
+    Ok(Json(AxfrImportResponse {
+        transferred: count,
+        skipped,
+        sync,
+    }))
+}
+
+/// Split transferred records into those to import, with owners relative
+/// to `source`, and those left out.
+fn sort_records(
+    policy: &PolicyConfig,
+    source: &str,
+    transferred: Vec<AnswerRecord>,
+) -> (Vec<RecordDto>, Vec<SkippedRecord>) {
+    let mut records = Vec::new();
+    let mut skipped = Vec::new();
+    for record in transferred {
+        let name = if record.name == source {
+            Some("@".to_string())
+        } else {
+            record
+                .name
+                .strip_suffix(&format!(".{source}"))
+                .map(str::to_string)
+        };
+        let reason = match &name {
+            None => Some("outside the transferred zone"),
+            Some(name) if name == "@" && matches!(record.rrtype.as_str(), "SOA" | "NS") => {
+                Some("managed by this service")
+            }
+            Some(_) if DNSSEC_TYPES.contains(&record.rrtype.as_str()) => {
+                Some("DNSSEC records are generated by this service")
+            }
+            Some(_) if !policy.is_record_type_allowed(&record.rrtype) => {
+                Some("record type not allowed on this instance")
+            }
+            Some(_) => None,
+        };
+        match (name, reason) {
+            (Some(name), None) => records.push(RecordDto {
+                name,
+                rrtype: record.rrtype,
+                ttl: Some(record.ttl),
+                content: record.content,
+                data: None,
+                priority: None,
+                srv: None,
+                note: None,
+                labels: None,
+                provenance: None,
+            }),
+            (_, reason) => skipped.push(SkippedRecord {
+                name: record.name,
+                rrtype: record.rrtype,
+                reason: reason.unwrap_or_default(),
+            }),
+        }
+    }
+    (records, skipped)
+}
+
+fn tsig_key(req: &TsigRequest) -> Result<TsigKey, AppError> {
+    let algorithm = TsigAlgorithm::parse(&req.algorithm).ok_or_else(|| {
+        AppError::bad_request(format!(
+            "unsupported TSIG algorithm {:?}; use hmac-sha256, hmac-sha384, or hmac-sha512",
+            req.algorithm
+        ))
+    })?;
+    let name = req.name.trim().trim_end_matches('.').to_ascii_lowercase();
+    if name.is_empty()
+        || name
+            .split('.')
+            .any(|label| label.is_empty() || label.len() > 63)
+    {
+        return Err(AppError::bad_request("invalid TSIG key name"));
+    }
+    let secret = BASE64
+        .decode(req.secret.trim())
+        .map_err(|_| AppError::bad_request("TSIG secret must be base64"))?;
+    if secret.is_empty() {
+        return Err(AppError::bad_request("TSIG secret is empty"));
+    }
+    Ok(TsigKey {
+        name: format!("{name}."),
+        algorithm,
+        secret,
+    })
+}
+
+/// Addresses of `server`, which may carry a port; only public addresses
+/// are accepted so imports cannot reach internal hosts.
+async fn resolve_server(server: &str) -> Result<Vec<SocketAddr>, AppError> {
+    let server = server.trim();
+    let addrs: Vec<SocketAddr> = if let Ok(addr) = server.parse::<SocketAddr>() {
+        vec![addr]
+    } else if let Ok(ip) = server.parse::<IpAddr>() {
+        vec![SocketAddr::new(ip, 53)]
+    } else {
+        let (host, port) = match server.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse::<u16>()
+                    .map_err(|_| AppError::bad_request(format!("invalid port in {server:?}")))?,
+            ),
+            None => (server, 53),
+        };
+        let host = host.trim_end_matches('.');
+        if host.is_empty() {
+            return Err(AppError::bad_request("server is required"));
+        }
+        let resolved = tokio::time::timeout(RESOLVE_TIMEOUT, tokio::net::lookup_host((host, port)))
+            .await
+            .map_err(|_| {
+                AppError::new(
+                    ErrorCode::TransferFailed,
+                    format!("resolving {host} timed out"),
+                )
+            })?
+            .map_err(|err| {
+                AppError::new(
+                    ErrorCode::TransferFailed,
+                    format!("failed to resolve {host}: {err}"),
+                )
+            })?;
+        resolved.collect()
+    };
+    if let Some(range) = addrs.iter().find_map(|addr| special_range(addr.ip())) {
+        return Err(AppError::bad_request(format!(
+            "server must have public addresses, not a {range} one"
+        )));
+    }
+    if addrs.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::TransferFailed,
+            format!("{server} has no addresses"),
+        ));
+    }
+    Ok(addrs)
+}
@@ -0,0 +1,138 @@
+//! Authenticated endpoints for the caller's in-app notification inbox.
+use crate::db::inbox_repo::{self, InboxEntry};
+use crate::error::{AppError, internal};
+use crate::{SharedState, auth::Authenticated};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Entries returned by `GET /api/notifications` when no `limit` is given.
+const DEFAULT_LIMIT: i64 = 50;
+/// Largest accepted `limit`.
+const MAX_LIMIT: i64 = 200;
+
+/// Filter and pagination of `GET /api/notifications`.
+#[derive(Deserialize)]
+pub struct InboxQuery {
+    /// Only notifications not marked read yet.
+    #[serde(default)]
+    pub unread: bool,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Only entries older than this `id`; pass the previous page's `next_before`.
+    #[serde(default)]
+    pub before: Option<i64>,
+}
+
+/// One notification as shown to its owner.
+#[derive(Serialize)]
+pub struct NotificationDto {
+    pub id: i64,
+    pub category: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub subject: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+impl From<InboxEntry> for NotificationDto {
+    fn from(entry: InboxEntry) -> Self {
+        Self {
+            id: entry.id,
+            category: entry.category,
+            event_type: entry.event_type,
+            subject: entry.subject,
+            body: entry.body,
+            created_at: entry.created_at,
+            read_at: entry.read_at,
+        }
+    }
+}
+
+/// A page of `GET /api/notifications`.
+#[derive(Serialize)]
+pub struct InboxPage {
+    pub items: Vec<NotificationDto>,
+    /// Cursor for the next (older) page; absent on the last page.
+    pub next_before: Option<i64>,
+    /// Unread notifications in the whole inbox, for a badge.
+    pub unread: i64,
+}
+
+/// Return the caller's notifications, newest first.
+pub async fn list_notifications(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<InboxQuery>,
+) -> Result<Json<InboxPage>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    // one extra row tells whether another page follows
+    let mut entries =
+        inbox_repo::list_for_user(&state.db, user.id, query.unread, query.before, limit + 1)
+            .await
+            .map_err(internal)?;
+    let has_more = entries.len() as i64 > limit;
+    entries.truncate(limit as usize);
+
+    let next_before = has_more
+        .then(|| entries.last().map(|entry| entry.id))
+        .flatten();
+    let unread = inbox_repo::count_unread(&state.db, user.id)
+        .await
+        .map_err(internal)?;
+    Ok(Json(InboxPage {
+        items: entries.into_iter().map(NotificationDto::from).collect(),
+        next_before,
+        unread,
+    }))
+}
+
+/// Mark one notification read.
+pub async fn mark_read(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !inbox_repo::mark_read(&state.db, user.id, id)
+        .await
+        .map_err(internal)?
+    {
+        return Err(AppError::not_found());
+    }
+    let unread = inbox_repo::count_unread(&state.db, user.id)
+        .await
+        .map_err(internal)?;
+    Ok(Json(serde_json::json!({ "ok": true, "unread": unread })))
+}
+
+/// Body of `POST /api/notifications/read`.
+#[derive(Deserialize, Default)]
+pub struct MarkAllReadRequest {
+    /// Newest notification to mark; everything when omitted, so entries
+    /// that arrived after the client's last fetch stay unread if given.
+    #[serde(default)]
+    pub up_to: Option<i64>,
+}
+
+/// Mark all of the caller's notifications read.
+pub async fn mark_all_read(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    body: Option<Json<MarkAllReadRequest>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let marked = inbox_repo::mark_all_read(&state.db, user.id, req.up_to)
+        .await
+        .map_err(internal)?;
+    let unread = inbox_repo::count_unread(&state.db, user.id)
+        .await
+        .map_err(internal)?;
+    Ok(Json(
+        serde_json::json!({ "ok": true, "marked": marked, "unread": unread }),
+    ))
+}
@@ -0,0 +1,66 @@
+//! Concurrency caps that shed excess requests instead of queueing them.
+use crate::error::{ErrorCode, Problem};
+use crate::i18n::MessageId;
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::header,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Seconds clients are told to wait before retrying a shed request.
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// A fixed number of request slots and the error returned once they are taken.
+#[derive(Clone)]
+pub struct ConcurrencyCap {
+    permits: Arc<Semaphore>,
+    code: ErrorCode,
+}
+
+impl ConcurrencyCap {
+    /// Cap for the whole API; saturation means the server is overloaded (503).
+    pub fn global(limit: usize) -> Option<Self> {
+        Self::new(limit, ErrorCode::Overloaded)
+    }
+
+    /// Cap for one route group; saturation means that endpoint is busy (429).
+    pub fn route(limit: usize) -> Option<Self> {
+        Self::new(limit, ErrorCode::TooManyRequests)
+    }
+
+    /// `None` when `limit` is `0`, i.e. the cap is disabled.
+    fn new(limit: usize, code: ErrorCode) -> Option<Self> {
+        (limit > 0).then(|| Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            code,
+        })
+    }
+}
+
+/// Enforce `cap` (if enabled) on every route of `router`.
+///
+/// Routers layered with clones of one cap share its slots.
+pub fn apply(router: Router, cap: Option<&ConcurrencyCap>) -> Router {
+    match cap {
+        Some(cap) => router.layer(middleware::from_fn_with_state(cap.clone(), limit)),
+        None => router,
+    }
+}
+
+/// Run the request if a slot is free, otherwise reject it immediately with `Retry-After`.
+pub async fn limit(State(cap): State<ConcurrencyCap>, request: Request, next: Next) -> Response {
+    let Ok(_permit) = cap.permits.try_acquire() else {
+        tracing::warn!(code = %cap.code, "shedding request: concurrency limit reached");
+        let problem = Problem::localized(cap.code.status(), cap.code, MessageId::Overloaded);
+        return (
+            [(header::RETRY_AFTER, RETRY_AFTER_SECS.to_string())],
+            problem,
+        )
+            .into_response();
+    };
+    next.run(request).await
+}
@@ -0,0 +1,83 @@
+//! Middleware rejecting state-changing requests while maintenance is
+//! enabled or PowerDNS is down, and, if configured, every API request
+//! until the startup checks pass.
+use super::api_relative_path;
+use crate::SharedState;
+use crate::error::{ErrorCode, Problem};
+use crate::health::STARTUP_RETRY;
+use crate::i18n::MessageId;
+use axum::{
+    Extension,
+    extract::Request,
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Answer mutating requests with 503 + `Retry-After`; reads, signin, and
+/// admin endpoints keep working so operators can lift the switch.
+pub async fn guard(
+    Extension(state): Extension<SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.config.hold_api_until_ready
+        && !state.startup.is_ready()
+        && api_relative_path(request.uri().path()).is_some()
+    {
+        return not_ready_response();
+    }
+    let maintenance = state.policy().maintenance.clone();
+    let degraded = maintenance.read_only_when_pdns_down && state.pdns_health.degraded();
+    if !(maintenance.enabled || degraded) || is_exempt(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+    if !maintenance.enabled {
+        return degraded_response(&state);
+    }
+
+    let code = ErrorCode::Maintenance;
+    let problem = Problem::localized(code.status(), code, MessageId::Maintenance)
+        .with("maintenance", true)
+        .with("message", maintenance.message)
+        .with("retry_after", maintenance.retry_after_secs);
+    (
+        [(
+            header::RETRY_AFTER,
+            maintenance.retry_after_secs.to_string(),
+        )],
+        problem,
+    )
+        .into_response()
+}
+
+/// Rejection while PowerDNS is down: changes would fail or time out there,
+/// so they are refused up front until the monitor sees it answer again.
+fn degraded_response(state: &SharedState) -> Response {
+    // the next probe may end degraded mode
+    let retry_after = state.policy().alerts.pdns_check_secs.max(1);
+    let code = ErrorCode::Degraded;
+    let problem = Problem::localized(code.status(), code, MessageId::Degraded)
+        .with("degraded", true)
+        .with("retry_after", retry_after);
+    ([(header::RETRY_AFTER, retry_after.to_string())], problem).into_response()
+}
+
+/// Rejection before the startup checks pass, with `--hold-api-until-ready`.
+fn not_ready_response() -> Response {
+    let retry_after = STARTUP_RETRY.as_secs();
+    let code = ErrorCode::NotReady;
+    let problem = Problem::localized(code.status(), code, MessageId::NotReady)
+        .with("retry_after", retry_after);
+    ([(header::RETRY_AFTER, retry_after.to_string())], problem).into_response()
+}
+
+/// Requests that never change user data.
+fn is_exempt(method: &Method, path: &str) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+    // the GraphQL schema is read-only
+    api_relative_path(path)
+        .is_some_and(|rest| rest == "/signin" || rest == "/graphql" || rest.starts_with("/admin/"))
+}
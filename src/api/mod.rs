@@ -1,44 +1,331 @@
 //! API module wiring together public and authenticated routes.
 
+pub mod admin;
+pub mod compression;
+pub mod conditional;
 pub mod dns;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod import;
+pub mod inbox;
+pub mod load_shed;
+pub mod maintenance;
+pub mod presets;
+pub mod problem;
 pub mod profile;
 pub mod public;
+pub mod query;
+pub mod request_id;
+pub mod sync;
+pub mod webhooks;
+pub mod well_known;
+pub mod whoami;
+pub mod zone_events;
 
 use crate::SharedState;
+use crate::config::BodyLimits;
+use crate::error::AppError;
+use crate::health::{self, CheckResult};
+use axum::http::{HeaderValue, header};
 use axum::{
     Extension, Json, Router,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Request},
+    http::{Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{any, delete, get, post, put},
 };
+use load_shed::ConcurrencyCap;
 use serde::Serialize;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// Prefix of the canonical, versioned API surface.
+pub const API_V1_PREFIX: &str = "/api/v1";
+
+/// Seconds clients may reuse the ETag-tagged public responses.
+const PUBLIC_MAX_AGE: u32 = 60;
+
+/// Which endpoints a listener serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Surface {
+    /// Everything on a single port.
+    All,
+    /// User API, discovery, and well-known files, without operational endpoints.
+    Public,
+    /// Probes, metrics, and admin endpoints, for an `internal` listener.
+    Internal,
+}
 
 /// Build the Axum router tree with every public and authenticated endpoint.
+///
+/// Routes are served under `/api/v1`; the unversioned `/api/...` paths remain
+/// as aliases that advertise their successor via deprecation headers.
 pub fn create_router(state: SharedState) -> Router {
-    use crate::api::{dns, profile, public};
+    create_router_for(state, Surface::All)
+}
+
+/// Build the router for one listener when public and internal endpoints are split.
+pub fn create_router_for(state: SharedState, surface: Surface) -> Router {
+    let cors = cors_layer(&state);
+    let limits = state.config.body_limits;
+    let concurrency = state.config.concurrency;
+    // shared by the versioned routes and their aliases
+    let caps = RouteCaps {
+        auth: ConcurrencyCap::route(concurrency.auth),
+        zone: ConcurrencyCap::route(concurrency.zone),
+    };
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let api = Router::new()
+        .nest(API_V1_PREFIX, api_routes(limits, &caps, surface))
+        .nest(
+            "/api",
+            api_routes(limits, &caps, surface).layer(middleware::from_fn(deprecated_alias)),
+        );
+    // probes and metrics stay outside the global cap so an overloaded
+    // instance is not restarted for being busy
+    let api = load_shed::apply(
+        api,
+        ConcurrencyCap::global(concurrency.max_in_flight).as_ref(),
+    );
 
+    let mut router = Router::new();
+    if surface != Surface::Public {
+        router = router
+            .route("/health", get(health_check))
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/metrics", get(public::metrics));
+    } else {
+        // answered here so the web UI's catch-all does not serve them a page
+        for path in ["/health", "/healthz", "/readyz", "/metrics"] {
+            router = router.route(path, any(internal_only));
+        }
+    }
+    if surface != Surface::Internal {
+        router = router
+            .route("/.well-known/security.txt", get(well_known::security_txt))
+            .route("/robots.txt", get(well_known::robots_txt));
+    }
+    router
+        .merge(api)
+        // the per-group limits replace axum's fixed extractor limit
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn(problem::normalize))
+        .layer(middleware::from_fn(track_mutations))
+        .layer(middleware::from_fn(maintenance::guard))
+        .layer(middleware::from_fn(problem::localize))
+        .layer(middleware::from_fn(request_id::propagate))
+        .layer(cors)
+        .layer(Extension(state))
+}
+
+/// Per-route-group concurrency caps.
+struct RouteCaps {
+    auth: Option<ConcurrencyCap>,
+    zone: Option<ConcurrencyCap>,
+}
+
+/// The API endpoints of `surface`, relative to the version prefix.
+fn api_routes(limits: BodyLimits, caps: &RouteCaps, surface: Surface) -> Router {
+    let mut router = Router::new();
+    if surface != Surface::Internal {
+        router = router.merge(user_routes(limits, caps));
+    }
+    if surface != Surface::Public {
+        router = router.merge(admin_routes().layer(RequestBodyLimitLayer::new(limits.default)));
+    } else {
+        router = router.route("/admin/{*rest}", any(internal_only));
+    }
+    router
+}
+
+/// Stand-in for endpoints moved to an `internal` listener.
+async fn internal_only() -> AppError {
+    AppError::not_found()
+}
+
+/// Operator endpoints, authenticated with the admin token.
+fn admin_routes() -> Router {
     Router::new()
-        .route("/health", get(health_check))
+        .route("/admin/reload", post(admin::reload_policy))
+        .route(
+            "/admin/reserved",
+            get(admin::list_reserved).post(admin::add_reserved),
+        )
+        .route("/admin/reserved/{label}", delete(admin::remove_reserved))
+        .route("/admin/signup-mode", put(admin::set_signup_mode))
+        .route("/admin/signup-sources", get(admin::list_signup_sources))
+        .route("/admin/maintenance", put(admin::set_maintenance))
+        .route(
+            "/admin/invites",
+            get(admin::list_invites).post(admin::create_invite),
+        )
+        .route("/admin/invites/{code}", delete(admin::delete_invite))
+        .route(
+            "/admin/reconcile",
+            get(admin::get_reconcile_report).post(admin::run_reconcile),
+        )
+        .route("/admin/serial-check", get(admin::get_serial_check_report))
+        .route("/admin/jobs", get(admin::list_jobs))
+        .route("/admin/jobs/{name}", get(admin::list_job_runs))
+        .route("/admin/dangling-delegations", get(admin::list_dangling))
+        .route(
+            "/admin/dangling-delegations/sweep",
+            post(admin::sweep_dangling),
+        )
+        .route("/admin/audit", get(admin::list_audit))
+        .route("/admin/mail/test", post(admin::send_test_mail))
+        .route("/admin/mail/outbox", get(admin::list_mail_outbox))
+        .route("/admin/announcements", post(admin::create_announcement))
+        .route("/admin/abuse", get(admin::list_abuse))
+        .route("/admin/abuse/{id}", put(admin::review_abuse))
+}
+
+/// Public and user-authenticated endpoints.
+fn user_routes(limits: BodyLimits, caps: &RouteCaps) -> Router {
+    // credential endpoints get the tightest body limit, and share a cap
+    // since each one hashes a password
+    let auth_routes = Router::new()
+        .route("/signup", post(public::signup))
+        .route("/signin", post(public::signin))
+        .route("/whoami", get(whoami::whoami))
+        .layer(RequestBodyLimitLayer::new(limits.auth));
+    let auth_routes = load_shed::apply(auth_routes, caps.auth.as_ref());
+    let zone_routes = Router::new()
+        .route("/zone", get(dns::get_zone).put(dns::put_zone))
+        .route("/zone/usage", get(dns::get_zone_usage))
+        .route("/zone/apex", get(dns::get_zone_apex))
+        .route("/zone/query", get(query::query))
+        .route("/zone/plan", post(sync::plan))
+        .route("/zone/apply", post(sync::apply))
+        .route("/zone/import/axfr", post(import::import_axfr))
+        .route("/zone/presets/caa", post(presets::caa))
+        .route("/zone/presets/dkim", post(presets::dkim))
+        .route("/zone/presets/dmarc", post(presets::dmarc))
+        .layer(RequestBodyLimitLayer::new(limits.zone));
+    let zone_routes = load_shed::apply(zone_routes, caps.zone.as_ref());
+    // landing-page data that changes rarely; clients revalidate with If-None-Match
+    let cached_routes = Router::new()
+        .route("/about", get(public::about))
+        .route("/subdomain/soa", get(public::parent_zone_soa))
+        .route("/subdomain/list", get(public::list_ns_records))
+        .route("/feed.json", get(public::feed::json_feed))
+        .route("/feed.atom", get(public::feed::atom_feed))
+        .route("/stats", get(public::stats::get_stats))
+        .layer(middleware::from_fn_with_state(
+            PUBLIC_MAX_AGE,
+            conditional::etag,
+        ));
+
+    let router = Router::new()
         // public
-        .route("/api/signup", post(public::signup))
-        .route("/api/signin", post(public::signin))
-        .route("/api/subdomain/check", get(public::check_subdomain))
-        .route("/api/about", get(public::about))
-        .route("/api/subdomain/soa", get(public::parent_zone_soa))
-        .route("/api/subdomain/list", get(public::list_ns_records))
-        .route("/metrics", get(public::metrics))
+        .route("/subdomain/check", get(public::check_subdomain))
+        .merge(cached_routes)
         // authenticated
-        .route("/api/zone", get(dns::get_zone).put(dns::put_zone))
-        .route("/api/ns-mode/internal", post(profile::set_ns_internal))
-        .route("/api/ns-mode/external", post(profile::set_ns_external))
-        .route("/api/password/change", post(profile::change_password))
-        .route("/api/profile", get(profile::get_profile))
-        .layer(cors)
-        .layer(Extension(state))
+        .route("/ns-mode/internal", post(profile::set_ns_internal))
+        .route("/ns-mode/external", post(profile::set_ns_external))
+        .route("/password/change", post(profile::change_password))
+        .route("/profile", get(profile::get_profile))
+        .route("/profile/activity", get(profile::get_activity))
+        .route("/profile/backup", get(profile::get_backup))
+        .route(
+            "/profile/notifications",
+            get(profile::get_notifications).put(profile::put_notifications),
+        )
+        .route("/notifications", get(inbox::list_notifications))
+        .route("/notifications/read", post(inbox::mark_all_read))
+        .route("/notifications/{id}/read", post(inbox::mark_read))
+        // long-lived, so outside the zone group's concurrency cap
+        .route("/zone/events", get(zone_events::zone_events))
+        .route(
+            "/webhooks",
+            get(webhooks::list_webhooks).post(webhooks::create_webhook),
+        )
+        .route("/webhooks/{id}", delete(webhooks::delete_webhook))
+        .route("/webhooks/{id}/deliveries", get(webhooks::list_deliveries));
+
+    #[cfg(feature = "graphql")]
+    let router = router.route("/graphql", get(graphql::graphiql).post(graphql::graphql));
+
+    router
+        .layer(RequestBodyLimitLayer::new(limits.default))
+        .merge(auth_routes)
+        .merge(zone_routes)
+}
+
+/// Strip `/api/v1` or the legacy `/api` prefix, yielding the route-relative path.
+pub(crate) fn api_relative_path(path: &str) -> Option<&str> {
+    path.strip_prefix(API_V1_PREFIX)
+        .or_else(|| path.strip_prefix("/api"))
+        .filter(|rest| rest.starts_with('/'))
+}
+
+/// Mark responses of a deprecated route (RFC 9745) and link its `/api/v1` successor.
+async fn deprecated_alias(request: Request, next: Next) -> Response {
+    // inside the nested router the URI is already relative to `/api`
+    let successor = format!(
+        "<{API_V1_PREFIX}{}>; rel=\"successor-version\"",
+        request.uri().path()
+    );
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, link);
+    }
+    response
+}
+
+/// Register state-changing requests so shutdown waits for them to finish.
+async fn track_mutations(
+    Extension(state): Extension<SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+    let mut description = format!("{} {}", request.method(), request.uri().path());
+    if let Some(request_id::RequestId(id)) = request.extensions().get() {
+        description.push_str(&format!(" (request {id})"));
+    }
+    let _guard = state.in_flight.begin(description);
+    next.run(request).await
+}
+
+/// Build the CORS policy from the configured origin allowlist.
+fn cors_layer(state: &SharedState) -> CorsLayer {
+    let config = &state.config;
+    if config.cors_allowed_origins.is_empty() {
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers([request_id::REQUEST_ID_HEADER]);
+    }
+
+    let origins = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect::<Vec<_>>();
+
+    // wildcards are not permitted together with credentials, so mirror instead
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(config.cors_allow_credentials)
+        .expose_headers([request_id::REQUEST_ID_HEADER])
+        .vary([
+            axum::http::header::ORIGIN,
+            axum::http::header::ACCESS_CONTROL_REQUEST_METHOD,
+            axum::http::header::ACCESS_CONTROL_REQUEST_HEADERS,
+        ])
 }
 
 /// Simple response body for `/health`.
@@ -51,3 +338,34 @@ struct HealthResponse {
 async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse { status: "ok" })
 }
+
+/// Body of `/healthz` and `/readyz`.
+#[derive(Serialize)]
+struct ProbeResponse {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<CheckResult>,
+}
+
+/// Liveness probe: the process is up and serving requests.
+async fn healthz() -> Json<ProbeResponse> {
+    Json(ProbeResponse {
+        status: "ok",
+        checks: Vec::new(),
+    })
+}
+
+/// Readiness probe: every dependency needed to serve traffic is usable.
+async fn readyz(Extension(state): Extension<SharedState>) -> Response {
+    let report = health::readiness(&state).await;
+    let (status, label) = if report.ok() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+    let body = ProbeResponse {
+        status: label,
+        checks: report.checks,
+    };
+    (status, Json(body)).into_response()
+}
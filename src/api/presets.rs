@@ -0,0 +1,388 @@
+//! Builders that turn a few structured options into correctly formatted
+//! records. Nothing is applied: the records are returned for the client to
+//! review and submit with `PUT /api/zone`.
+use crate::api::dns::{RecordDto, build_rrsets, normalize_owner};
+use crate::dkim::{self, PublicKey};
+use crate::error::{AppError, internal};
+use crate::rdata;
+use crate::record_data::RecordData;
+use crate::validation::{is_email_address, is_hostname};
+use crate::{SharedState, auth::Authenticated};
+use axum::{Extension, Json, http::header};
+use serde::{Deserialize, Serialize};
+
+/// Records built by a preset.
+#[derive(Serialize)]
+pub struct PresetResponse {
+    pub records: Vec<RecordDto>,
+    /// Things to set up elsewhere for the records to work.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// PKCS#8 PEM of a key generated for the request; it is not kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+}
+
+/// A single value or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Options of `POST /api/zone/presets/caa`.
+#[derive(Deserialize)]
+pub struct CaaPreset {
+    /// Domains of the CAs allowed to issue, such as `letsencrypt.org`;
+    /// an empty list forbids issuance altogether.
+    pub ca: OneOrMany,
+    /// `true` lets the same CAs issue wildcard certificates and `false`
+    /// forbids them; when omitted, wildcards follow the `issue` records.
+    #[serde(default)]
+    pub wildcard: Option<bool>,
+    /// Address CAs report refused requests to.
+    #[serde(default)]
+    pub report_email: Option<String>,
+    /// Owner name; defaults to the zone apex.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub ttl: Option<u32>,
+}
+
+/// Build the CAA records restricting certificate issuance to the given CAs.
+pub async fn caa(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<CaaPreset>,
+) -> Result<Json<PresetResponse>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let owner = normalize_owner(req.name.as_deref().unwrap_or("@"), &zone_name)?;
+
+    let mut cas: Vec<String> = Vec::new();
+    for ca in req.ca.into_vec() {
+        let ca = ca.trim().trim_end_matches('.').to_ascii_lowercase();
+        if !is_hostname(&ca) {
+            return Err(AppError::bad_request(format!("invalid CA domain {ca:?}")));
+        }
+        if !cas.contains(&ca) {
+            cas.push(ca);
+        }
+    }
+
+    let mut contents: Vec<String> = Vec::new();
+    if cas.is_empty() {
+        contents.push("0 issue \";\"".into());
+    }
+    contents.extend(cas.iter().map(|ca| format!("0 issue \"{ca}\"")));
+    match req.wildcard {
+        Some(true) if !cas.is_empty() => {
+            contents.extend(cas.iter().map(|ca| format!("0 issuewild \"{ca}\"")));
+        }
+        Some(_) => contents.push("0 issuewild \";\"".into()),
+        None => {}
+    }
+    if let Some(email) = req.report_email {
+        let email = email.trim();
+        if !is_email_address(email) {
+            return Err(AppError::bad_request(format!(
+                "invalid report email {email:?}"
+            )));
+        }
+        contents.push(format!("0 iodef \"mailto:{email}\""));
+    }
+
+    let records = checked_records(&state, &zone_name, &owner, "CAA", req.ttl, contents)?;
+    Ok(Json(PresetResponse {
+        records,
+        warnings: Vec::new(),
+        private_key: None,
+    }))
+}
+
+/// What receivers do with mail failing DMARC.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DmarcPolicy {
+    None,
+    Quarantine,
+    Reject,
+}
+
+impl DmarcPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            DmarcPolicy::None => "none",
+            DmarcPolicy::Quarantine => "quarantine",
+            DmarcPolicy::Reject => "reject",
+        }
+    }
+}
+
+/// DKIM or SPF identifier alignment mode.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Alignment {
+    #[serde(alias = "r")]
+    Relaxed,
+    #[serde(alias = "s")]
+    Strict,
+}
+
+impl Alignment {
+    fn as_str(self) -> &'static str {
+        match self {
+            Alignment::Relaxed => "r",
+            Alignment::Strict => "s",
+        }
+    }
+}
+
+/// Options of `POST /api/zone/presets/dmarc`.
+#[derive(Deserialize)]
+pub struct DmarcPreset {
+    pub policy: DmarcPolicy,
+    /// Policy for subdomains; defaults to `policy`.
+    #[serde(default)]
+    pub subdomain_policy: Option<DmarcPolicy>,
+    /// Addresses receiving aggregate reports, as `mailto:` URIs or bare addresses.
+    #[serde(default)]
+    pub rua: Option<OneOrMany>,
+    /// Addresses receiving failure reports.
+    #[serde(default)]
+    pub ruf: Option<OneOrMany>,
+    /// Percentage of failing mail the policy applies to.
+    #[serde(default)]
+    pub pct: Option<u8>,
+    #[serde(default)]
+    pub adkim: Option<Alignment>,
+    #[serde(default)]
+    pub aspf: Option<Alignment>,
+    /// Mail domain the policy is for, relative to the zone; defaults to the apex.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub ttl: Option<u32>,
+}
+
+/// Build the `_dmarc` TXT record publishing a DMARC policy (RFC 7489).
+pub async fn dmarc(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<DmarcPreset>,
+) -> Result<Json<PresetResponse>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let domain = normalize_owner(req.name.as_deref().unwrap_or("@"), &zone_name)?;
+    let owner = normalize_owner(&format!("_dmarc.{domain}"), &zone_name)?;
+
+    let mut tags = vec![format!("v=DMARC1; p={}", req.policy.as_str())];
+    if let Some(policy) = req.subdomain_policy {
+        tags.push(format!("sp={}", policy.as_str()));
+    }
+    if let Some(pct) = req.pct {
+        if pct > 100 {
+            return Err(AppError::bad_request("pct must be between 0 and 100"));
+        }
+        tags.push(format!("pct={pct}"));
+    }
+    if let Some(mode) = req.adkim {
+        tags.push(format!("adkim={}", mode.as_str()));
+    }
+    if let Some(mode) = req.aspf {
+        tags.push(format!("aspf={}", mode.as_str()));
+    }
+
+    let mut external: Vec<String> = Vec::new();
+    for (tag, uris) in [("rua", req.rua), ("ruf", req.ruf)] {
+        let uris: Vec<String> = uris.map(OneOrMany::into_vec).unwrap_or_default();
+        if uris.is_empty() {
+            continue;
+        }
+        let mut normalized = Vec::with_capacity(uris.len());
+        for uri in &uris {
+            let (uri, destination) = report_uri(uri)
+                .ok_or_else(|| AppError::bad_request(format!("invalid {tag} address {uri:?}")))?;
+            if !within(&destination, &domain) && !external.contains(&destination) {
+                external.push(destination);
+            }
+            normalized.push(uri);
+        }
+        tags.push(format!("{tag}={}", normalized.join(",")));
+    }
+    // RFC 7489 section 7.1: other domains must opt in to receiving reports
+    let warnings = external
+        .iter()
+        .map(|destination| {
+            format!(
+                "{destination} only accepts reports for {domain} if it publishes \
+                 the TXT record \"v=DMARC1\" at {domain}_report._dmarc.{destination}"
+            )
+        })
+        .collect();
+
+    let content = format!("\"{}\"", tags.join("; "));
+    let records = checked_records(&state, &zone_name, &owner, "TXT", req.ttl, vec![content])?;
+    Ok(Json(PresetResponse {
+        records,
+        warnings,
+        private_key: None,
+    }))
+}
+
+/// Options of `POST /api/zone/presets/dkim`.
+#[derive(Deserialize)]
+pub struct DkimPreset {
+    pub selector: String,
+    /// PEM or base64 DER public key; omit it and set `generate` to have one made.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// Generate an RSA key pair and return the private key once.
+    #[serde(default)]
+    pub generate: bool,
+    /// Size of a generated key.
+    #[serde(default = "default_dkim_bits")]
+    pub bits: usize,
+    /// Mail domain the key signs for, relative to the zone; defaults to the apex.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub ttl: Option<u32>,
+}
+
+fn default_dkim_bits() -> usize {
+    2048
+}
+
+/// Build the `selector._domainkey` TXT record publishing a DKIM public key.
+pub async fn dkim(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<DkimPreset>,
+) -> Result<
+    (
+        [(header::HeaderName, &'static str); 1],
+        Json<PresetResponse>,
+    ),
+    AppError,
+> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let selector = req.selector.trim().to_ascii_lowercase();
+    if !is_hostname(&selector) || selector.ends_with('.') {
+        return Err(AppError::bad_request(format!(
+            "invalid selector {selector:?}"
+        )));
+    }
+    let domain = normalize_owner(req.name.as_deref().unwrap_or("@"), &zone_name)?;
+    let owner = normalize_owner(&format!("{selector}._domainkey.{domain}"), &zone_name)?;
+
+    let (public, private_key) = match (req.public_key, req.generate) {
+        (Some(key), false) => (PublicKey::parse(&key).map_err(AppError::bad_request)?, None),
+        (None, true) => {
+            let bits = req.bits;
+            let (public, private) = tokio::task::spawn_blocking(move || dkim::generate_rsa(bits))
+                .await
+                .map_err(internal)?
+                .map_err(AppError::bad_request)?;
+            (public, Some(private))
+        }
+        _ => {
+            return Err(AppError::bad_request(
+                "give either public_key or generate: true",
+            ));
+        }
+    };
+
+    let text = format!("\"{}\"", public.record());
+    let content = rdata::chunk_txt(&text)
+        .map_err(AppError::bad_request)?
+        .into_owned();
+    let records = checked_records(&state, &zone_name, &owner, "TXT", req.ttl, vec![content])?;
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(PresetResponse {
+            records,
+            warnings: Vec::new(),
+            private_key,
+        }),
+    ))
+}
+
+/// A DMARC report URI (`mailto:` with an optional `!size` limit) or a bare
+/// address, normalized to a URI, and the domain of its address.
+fn report_uri(uri: &str) -> Option<(String, String)> {
+    let uri = uri.trim();
+    let address = match uri.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &uri[7..],
+        _ => uri,
+    };
+    let (address, limit) = match address.split_once('!') {
+        Some((address, limit)) => (address, Some(limit)),
+        None => (address, None),
+    };
+    let valid_limit = limit.is_none_or(|limit| {
+        let digits = limit.trim_end_matches(['k', 'm', 'g', 't']);
+        !digits.is_empty()
+            && digits.bytes().all(|b| b.is_ascii_digit())
+            && limit.len() - digits.len() <= 1
+    });
+    if !valid_limit || !is_email_address(address) {
+        return None;
+    }
+    let (_, domain) = address.rsplit_once('@')?;
+    let domain = format!("{}.", domain.trim_end_matches('.').to_ascii_lowercase());
+    let limit = limit.map(|limit| format!("!{limit}")).unwrap_or_default();
+    Some((format!("mailto:{address}{limit}"), domain))
+}
+
+/// Whether `name` is `domain` or below it; both fully qualified.
+fn within(name: &str, domain: &str) -> bool {
+    let domain = domain.to_ascii_lowercase();
+    name == domain
+        || name
+            .strip_suffix(domain.as_str())
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Records of one RRset, checked like `PUT /api/zone` would check them.
+fn checked_records(
+    state: &SharedState,
+    zone_name: &str,
+    owner: &str,
+    rrtype: &str,
+    ttl: Option<u32>,
+    contents: Vec<String>,
+) -> Result<Vec<RecordDto>, AppError> {
+    let records: Vec<RecordDto> = contents
+        .into_iter()
+        .map(|content| RecordDto {
+            name: owner.to_string(),
+            rrtype: rrtype.to_string(),
+            ttl,
+            data: RecordData::parse(rrtype, &content),
+            content,
+            priority: None,
+            srv: None,
+            note: None,
+            labels: None,
+            provenance: None,
+        })
+        .collect();
+    build_rrsets(&state.policy(), zone_name, records.clone())?;
+    Ok(records)
+}
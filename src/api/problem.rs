@@ -0,0 +1,116 @@
+//! Fallback turning bare error responses into problem documents, and their
+//! localization.
+use crate::error::{ErrorCode, PROBLEM_JSON, Problem};
+use crate::i18n::{Locale, Message, MessageId};
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// Plain-text bodies larger than this are replaced by the status reason.
+const MAX_TEXT_BODY: usize = 16 * 1024;
+
+/// Problem bodies larger than this are served untranslated.
+const MAX_PROBLEM_BODY: usize = 64 * 1024;
+
+/// Rewrite error responses that did not come from `AppError` (extractor
+/// rejections, body limits, unmatched routes) as `application/problem+json`.
+pub async fn normalize(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.is_empty() && !content_type.starts_with("text/plain") {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let text = to_bytes(body, MAX_TEXT_BODY)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .unwrap_or_default();
+    let code = ErrorCode::for_status(status);
+    let problem = match (text.is_empty(), status) {
+        (true, StatusCode::NOT_FOUND) => Problem::localized(status, code, MessageId::NotFound),
+        (true, StatusCode::INTERNAL_SERVER_ERROR) => {
+            Problem::localized(status, code, MessageId::Internal)
+        }
+        (true, _) => Problem::new(
+            status,
+            code,
+            status
+                .canonical_reason()
+                .unwrap_or("error")
+                .to_ascii_lowercase(),
+        ),
+        (false, _) => Problem::new(status, code, text),
+    };
+    let body = serde_json::to_vec(&problem).unwrap_or_default();
+    if let Some(message) = problem.message {
+        parts.extensions.insert(message);
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Translate the `title` of problem documents into the language negotiated
+/// from `Accept-Language`, and their `detail` when the response carries its
+/// [`Message`]; `code` and `type` stay unchanged.
+pub async fn localize(request: Request, next: Next) -> Response {
+    let locale = Locale::negotiate(
+        request
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok()),
+    );
+    let response = next.run(request).await;
+    let is_problem = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(PROBLEM_JSON.as_bytes()));
+    if !is_problem {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("accept-language"));
+    parts.headers.insert(
+        header::CONTENT_LANGUAGE,
+        HeaderValue::from_static(locale.tag()),
+    );
+    if locale == Locale::En {
+        return Response::from_parts(parts, body);
+    }
+
+    let Ok(bytes) = to_bytes(body, MAX_PROBLEM_BODY).await else {
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    };
+    let Ok(serde_json::Value::Object(mut problem)) = serde_json::from_slice(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    if let Some(title) = locale.title(parts.status) {
+        problem.insert("title".into(), title.into());
+    }
+    if let Some(message) = parts.extensions.get::<Message>() {
+        problem.insert("detail".into(), message.render(locale).into());
+    }
+    let body = serde_json::to_vec(&problem).unwrap_or_default();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
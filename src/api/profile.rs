@@ -1,57 +1,136 @@
 //! Authenticated profile endpoints for viewing and updating NS delegation.
-use super::public::internal;
-use crate::db::user_repo;
+use super::dns::load_user_records;
+use super::public::require_feature;
+use crate::config::Feature;
+use crate::db::user_repo::{self, User};
+use crate::db::{history_repo, notification_repo, ns_health_repo};
+use crate::error::{AppError, ErrorCode, internal};
+use crate::events::{Event, EventKind};
+use crate::i18n::{Message, MessageId};
+use crate::notifications::{self, Channels};
 use crate::powerdns::types::{PdnsRecord, PdnsRrset};
-use crate::validation::validate_fqdn_ascii;
+use crate::tar::TarBuilder;
+use crate::validation::is_email_address;
+use crate::validation::{label_to_unicode, validate_fqdn_ascii};
 use crate::{
     SharedState,
     auth::{self, Authenticated},
 };
-use axum::{Extension, Json};
+use crate::{delegations, ns_preflight};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json, extract::Query};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Public profile information returned to signed-in users.
 #[derive(Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "graphql", graphql(name = "Profile"))]
 pub struct ProfileDto {
+    /// The label as stored in DNS, in ACE form for Unicode labels.
     pub subdomain: String,
+    pub subdomain_unicode: String,
+    pub base_domain: String,
     pub external_ns: bool,
+    pub external_nameservers: Vec<String>,
+    // first six entries of `external_nameservers`, kept for older clients
     pub external_ns1: Option<String>,
     pub external_ns2: Option<String>,
     pub external_ns3: Option<String>,
     pub external_ns4: Option<String>,
     pub external_ns5: Option<String>,
     pub external_ns6: Option<String>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub last_login_ip: Option<String>,
+    /// Latest check of each external nameserver; empty in internal mode.
+    pub external_ns_health: Vec<NsHealthDto>,
+}
+
+/// Result of the latest SOA check of one external nameserver.
+#[derive(Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "graphql", graphql(name = "NsHealth"))]
+pub struct NsHealthDto {
+    pub hostname: String,
+    /// `ok`, `lame`, or `unreachable`.
+    pub status: String,
+    pub detail: Option<String>,
+    pub serial: Option<i64>,
+    pub consecutive_failures: i64,
+    pub failing_since: Option<DateTime<Utc>>,
+    pub checked_at: DateTime<Utc>,
 }
 
 /// Return the caller's profile metadata and NS configuration.
 pub async fn get_profile(
     Authenticated(user): Authenticated,
-    Extension(_state): Extension<SharedState>,
-) -> Result<Json<ProfileDto>, (axum::http::StatusCode, String)> {
-    Ok(Json(ProfileDto {
-        subdomain: user.subdomain.clone(),
-        external_ns: user.external_ns,
-        external_ns1: user.external_ns1.clone(),
-        external_ns2: user.external_ns2.clone(),
-        external_ns3: user.external_ns3.clone(),
-        external_ns4: user.external_ns4.clone(),
-        external_ns5: user.external_ns5.clone(),
-        external_ns6: user.external_ns6.clone(),
-    }))
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<ProfileDto>, AppError> {
+    Ok(Json(load_profile(&state, user).await?))
+}
+
+/// The profile with the nameserver health recorded for the current NS list.
+pub(crate) async fn load_profile(state: &SharedState, user: User) -> Result<ProfileDto, AppError> {
+    let health = if user.external_ns {
+        ns_health_repo::list_for_user(&state.db, user.id)
+            .await
+            .map_err(internal)?
+    } else {
+        Vec::new()
+    };
+    let mut profile = ProfileDto::from(user);
+    profile.external_ns_health = health
+        .into_iter()
+        .filter(|health| profile.external_nameservers.contains(&health.hostname))
+        .map(|health| NsHealthDto {
+            hostname: health.hostname,
+            status: health.status,
+            detail: health.detail,
+            serial: health.serial,
+            consecutive_failures: health.consecutive_failures,
+            failing_since: health.failing_since,
+            checked_at: health.checked_at,
+        })
+        .collect();
+    Ok(profile)
+}
+
+impl From<User> for ProfileDto {
+    fn from(user: User) -> Self {
+        ProfileDto {
+            subdomain_unicode: label_to_unicode(&user.subdomain),
+            subdomain: user.subdomain,
+            base_domain: user.base_domain,
+            external_ns: user.external_ns,
+            external_ns1: user.external_nameservers.first().cloned(),
+            external_ns2: user.external_nameservers.get(1).cloned(),
+            external_ns3: user.external_nameservers.get(2).cloned(),
+            external_ns4: user.external_nameservers.get(3).cloned(),
+            external_ns5: user.external_nameservers.get(4).cloned(),
+            external_ns6: user.external_nameservers.get(5).cloned(),
+            external_nameservers: user.external_nameservers,
+            last_login_at: user.last_login_at,
+            last_login_ip: user.last_login_ip,
+            external_ns_health: Vec::new(),
+        }
+    }
 }
 
 /// Switch the caller back to the operator-managed nameservers.
 pub async fn set_ns_internal(
     Authenticated(user): Authenticated,
     Extension(state): Extension<SharedState>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let zone_name = state.config.user_zone_name(&user.subdomain);
-    let parent_zone = state.config.parent_zone_name();
+) -> Result<Json<serde_json::Value>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
 
     let ns_rrset = PdnsRrset {
         name: zone_name.clone(),
         rrtype: "NS".into(),
-        ttl: 300,
+        ttl: state.config.ns_ttl,
         changetype: Some("REPLACE".into()),
         records: state
             .config
@@ -64,25 +143,36 @@ pub async fn set_ns_internal(
             .collect(),
         comments: Vec::new(),
     };
-    state
-        .base_pdns
-        .patch_rrsets(&parent_zone, &[ns_rrset])
+    delegations::patch(&state, &user.base_domain, &[ns_rrset])
         .await
         .map_err(internal)?;
 
-    user_repo::set_external_ns(
-        &state.db, user.id, false, None, None, None, None, None, None,
-    )
-    .await
-    .map_err(internal)?;
+    user_repo::set_external_ns(&state.db, user.id, false, &[])
+        .await
+        .map_err(internal)?;
+    state.events.publish(Event::new(
+        user.id,
+        zone_name,
+        EventKind::NsModeChanged {
+            external: false,
+            nameservers: state.config.internal_ns.clone(),
+        },
+    ));
 
-    Ok(Json(serde_json::json!({ "ok": true })))
+    Ok(Json(serde_json::json!({
+        "ok": true,
+        "mode": "internal",
+        "nameservers": state.config.internal_ns,
+    })))
 }
 
 /// Payload describing the external NS list the user wants to delegate to.
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SetExternalNsRequest {
     pub ns: Vec<String>, // validate to be FQDNs with trailing dots
+    /// Delegate even when the pre-flight check finds problems.
+    #[serde(default)]
+    pub force: bool,
 }
 
 /// Configure custom nameservers for the caller and persist them in PDNS.
@@ -90,41 +180,84 @@ pub async fn set_ns_external(
     Authenticated(user): Authenticated,
     Extension(state): Extension<SharedState>,
     Json(req): Json<SetExternalNsRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    if req.ns.is_empty() {
-        return Err((
-            axum::http::StatusCode::BAD_REQUEST,
-            "at least one NS required".into(),
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_feature(&state, Feature::ExternalNs)?;
+
+    let (min_ns, max_ns) = {
+        let policy = state.policy();
+        (policy.min_external_ns, policy.max_external_ns)
+    };
+
+    if req.ns.len() < min_ns {
+        return Err(AppError::localized(
+            ErrorCode::InvalidNameservers,
+            Message::new(MessageId::TooFewNameservers, [min_ns]),
         ));
     }
 
-    let zone_name = state.config.user_zone_name(&user.subdomain);
-    let parent_zone = state.config.parent_zone_name();
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
 
-    if req.ns.len() > 6 {
-        return Err((
-            axum::http::StatusCode::BAD_REQUEST,
-            "up to six nameservers supported".into(),
+    if req.ns.len() > max_ns {
+        return Err(AppError::localized(
+            ErrorCode::InvalidNameservers,
+            Message::new(MessageId::TooManyNameservers, [max_ns]),
         ));
     }
 
     let mut validated_ns = Vec::with_capacity(req.ns.len());
     for ns in req.ns {
         if !ns.ends_with('.') {
-            return Err((
-                axum::http::StatusCode::BAD_REQUEST,
-                "nameservers must end with '.'".into(),
+            return Err(AppError::localized(
+                ErrorCode::InvalidNameservers,
+                MessageId::NameserverNotAbsolute,
+            ));
+        }
+        validate_fqdn_ascii(&ns).map_err(|e| {
+            let code = if e.is_length_limit() {
+                ErrorCode::NameTooLong
+            } else {
+                ErrorCode::InvalidNameservers
+            };
+            AppError::localized(code, e)
+        })?;
+        let ns = ns.to_ascii_lowercase();
+        if validated_ns.contains(&ns) {
+            return Err(AppError::localized(
+                ErrorCode::InvalidNameservers,
+                Message::new(MessageId::DuplicateNameserver, [ns]),
             ));
         }
-        validate_fqdn_ascii(&ns)
-            .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
         validated_ns.push(ns);
     }
 
+    let preflight = {
+        let policy = state.policy();
+        policy
+            .ns_preflight
+            .enabled
+            .then(|| Duration::from_secs(policy.ns_preflight.timeout_secs))
+    };
+    let report = match preflight {
+        Some(timeout) => Some(ns_preflight::run(&zone_name, &validated_ns, timeout).await),
+        None => None,
+    };
+    if let Some(report) = &report
+        && !report.ok
+        && !req.force
+    {
+        return Err(AppError::localized(
+            ErrorCode::NameserversNotReady,
+            MessageId::NameserversNotReady,
+        )
+        .with("preflight", serde_json::to_value(report).map_err(internal)?));
+    }
+
     let ns_rrset = PdnsRrset {
         name: zone_name.clone(),
         rrtype: "NS".into(),
-        ttl: 300,
+        ttl: state.config.ns_ttl,
         changetype: Some("REPLACE".into()),
         records: validated_ns
             .iter()
@@ -135,24 +268,28 @@ pub async fn set_ns_external(
             .collect(),
         comments: Vec::new(),
     };
-    state
-        .base_pdns
-        .patch_rrsets(&parent_zone, &[ns_rrset])
+    delegations::patch(&state, &user.base_domain, &[ns_rrset])
         .await
         .map_err(internal)?;
 
-    let ns1 = validated_ns.get(0).cloned();
-    let ns2 = validated_ns.get(1).cloned();
-    let ns3 = validated_ns.get(2).cloned();
-    let ns4 = validated_ns.get(3).cloned();
-    let ns5 = validated_ns.get(4).cloned();
-    let ns6 = validated_ns.get(5).cloned();
-
-    user_repo::set_external_ns(&state.db, user.id, true, ns1, ns2, ns3, ns4, ns5, ns6)
+    user_repo::set_external_ns(&state.db, user.id, true, &validated_ns)
         .await
         .map_err(internal)?;
+    state.events.publish(Event::new(
+        user.id,
+        zone_name,
+        EventKind::NsModeChanged {
+            external: true,
+            nameservers: validated_ns.clone(),
+        },
+    ));
 
-    Ok(Json(serde_json::json!({ "ok": true })))
+    Ok(Json(serde_json::json!({
+        "ok": true,
+        "mode": "external",
+        "nameservers": validated_ns,
+        "preflight": report,
+    })))
 }
 
 /// Request body for updating the user's password.
@@ -167,21 +304,23 @@ pub async fn change_password(
     Authenticated(user): Authenticated,
     Extension(state): Extension<SharedState>,
     Json(req): Json<ChangePasswordRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_feature(&state, Feature::PasswordChange)?;
+
     if req.new_password.trim().len() < 8 {
-        return Err((
-            axum::http::StatusCode::BAD_REQUEST,
-            "new password must be at least 8 characters".into(),
+        return Err(AppError::localized(
+            ErrorCode::WeakPassword,
+            MessageId::WeakPassword,
         ));
     }
 
-    let valid_current = auth::verify_password(&user.password_hash, &req.current_password)
-        .map_err(internal)?;
+    let valid_current =
+        auth::verify_password(&user.password_hash, &req.current_password).map_err(internal)?;
 
     if !valid_current {
-        return Err((
-            axum::http::StatusCode::UNAUTHORIZED,
-            "current password is incorrect".into(),
+        return Err(AppError::localized(
+            ErrorCode::InvalidCredentials,
+            MessageId::WrongPassword,
         ));
     }
 
@@ -189,6 +328,204 @@ pub async fn change_password(
     user_repo::update_password(&state.db, user.id, &new_hash)
         .await
         .map_err(internal)?;
+    state.events.publish(Event::new(
+        user.id,
+        state
+            .config
+            .user_zone_name(&user.subdomain, &user.base_domain),
+        EventKind::PasswordChanged,
+    ));
 
     Ok(Json(serde_json::json!({ "ok": true })))
 }
+
+/// Entries returned by `GET /api/profile/activity` when no `limit` is given.
+const DEFAULT_ACTIVITY_LIMIT: i64 = 50;
+/// Largest accepted `limit`.
+const MAX_ACTIVITY_LIMIT: i64 = 200;
+
+/// Pagination of `GET /api/profile/activity`.
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Only entries older than this `id`; pass the previous page's `next_before`.
+    #[serde(default)]
+    pub before: Option<i64>,
+}
+
+/// One audited action on the caller's account or zone.
+#[derive(Serialize)]
+pub struct ActivityEntry {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub zone: String,
+    pub occurred_at: DateTime<Utc>,
+    /// The recorded event, as delivered to webhooks.
+    pub details: serde_json::Value,
+}
+
+/// A page of activity, newest first.
+#[derive(Serialize)]
+pub struct ActivityPage {
+    pub items: Vec<ActivityEntry>,
+    /// Cursor for the next (older) page; absent on the last page.
+    pub next_before: Option<i64>,
+}
+
+/// Return the caller's own sign-ins, record changes, and NS switches.
+pub async fn get_activity(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<Json<ActivityPage>, AppError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_ACTIVITY_LIMIT)
+        .clamp(1, MAX_ACTIVITY_LIMIT);
+    // one extra row tells whether another page follows
+    let mut changes = history_repo::list_for_user(&state.db, user.id, query.before, limit + 1)
+        .await
+        .map_err(internal)?;
+    let has_more = changes.len() as i64 > limit;
+    changes.truncate(limit as usize);
+
+    let next_before = has_more
+        .then(|| changes.last().map(|change| change.id))
+        .flatten();
+    let items = changes
+        .into_iter()
+        .map(|change| ActivityEntry {
+            id: change.id,
+            event_type: change.event_type,
+            zone: change.zone,
+            occurred_at: change.occurred_at,
+            details: serde_json::from_str(&change.payload).unwrap_or_default(),
+        })
+        .collect();
+    Ok(Json(ActivityPage { items, next_before }))
+}
+
+/// `GET /api/profile/backup`: the zone file, records with their notes,
+/// profile, and change history of the caller as one tar archive.
+pub async fn get_backup(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Response, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let zone_file = state
+        .sub_pdns_for(&user.base_domain)
+        .export_zone(&zone_name)
+        .await
+        .map_err(internal)?;
+    let records = load_user_records(&state, &user).await?;
+    let history: Vec<ActivityEntry> = history_repo::list_all_for_user(&state.db, user.id)
+        .await
+        .map_err(internal)?
+        .into_iter()
+        .map(|change| ActivityEntry {
+            id: change.id,
+            event_type: change.event_type,
+            zone: change.zone,
+            occurred_at: change.occurred_at,
+            details: serde_json::from_str(&change.payload).unwrap_or_default(),
+        })
+        .collect();
+    let profile = load_profile(&state, user).await?;
+
+    let now = Utc::now();
+    let mut archive = TarBuilder::new(now.timestamp().max(0) as u64);
+    archive.append("zone.txt", zone_file.as_bytes());
+    for (path, json) in [
+        ("records.json", serde_json::to_vec_pretty(&records)),
+        ("profile.json", serde_json::to_vec_pretty(&profile)),
+        ("history.json", serde_json::to_vec_pretty(&history)),
+    ] {
+        archive.append(path, &json.map_err(internal)?);
+    }
+
+    let filename = format!(
+        "{}-{}.tar",
+        zone_name.trim_end_matches('.'),
+        now.format("%Y%m%d")
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        archive.finish(),
+    )
+        .into_response())
+}
+
+/// Body of `PUT /api/profile/notifications`, and its response.
+#[derive(Deserialize, Serialize)]
+pub struct NotificationPreferencesDto {
+    /// Address the `email` channel sends to.
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub channels: Channels,
+}
+
+/// Return the caller's notification preferences, or the defaults.
+pub async fn get_notifications(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<NotificationPreferencesDto>, AppError> {
+    let preferences = notifications::load(&state, user.id)
+        .await
+        .map_err(internal)?;
+    Ok(Json(NotificationPreferencesDto {
+        email: preferences.email,
+        channels: preferences.channels,
+    }))
+}
+
+/// Replace the caller's notification preferences.
+pub async fn put_notifications(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<NotificationPreferencesDto>,
+) -> Result<Json<NotificationPreferencesDto>, AppError> {
+    let email = req
+        .email
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty());
+    match &email {
+        Some(email) if !is_email_address(email) => {
+            return Err(AppError::bad_request(format!(
+                "invalid email address {email:?}"
+            )));
+        }
+        Some(email) => {
+            state
+                .policy()
+                .email_domains
+                .check(email)
+                .map_err(|reason| AppError::localized(ErrorCode::EmailDomainRejected, reason))?;
+        }
+        None if req.channels.uses_email() => {
+            return Err(AppError::bad_request(
+                "the email channel needs an email address",
+            ));
+        }
+        _ => {}
+    }
+    let channels = serde_json::to_string(&req.channels).map_err(internal)?;
+    notification_repo::set(&state.db, user.id, email.as_deref(), &channels)
+        .await
+        .map_err(internal)?;
+    Ok(Json(NotificationPreferencesDto {
+        email,
+        channels: req.channels,
+    }))
+}
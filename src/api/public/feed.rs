@@ -0,0 +1,122 @@
+//! Public feeds of newly registered subdomains (label and date only).
+use super::require_feature;
+use crate::SharedState;
+use crate::config::Feature;
+use crate::db::user_repo::{self, Delegation};
+use crate::error::{AppError, internal};
+use axum::{Extension, Json, http::header, response::IntoResponse};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+/// Entries included in either feed.
+const FEED_LENGTH: i64 = 50;
+
+/// JSON Feed 1.1 document.
+#[derive(Serialize)]
+pub struct JsonFeed {
+    pub version: &'static str,
+    pub title: String,
+    pub items: Vec<JsonFeedItem>,
+}
+
+/// One registration in the JSON Feed.
+#[derive(Serialize)]
+pub struct JsonFeedItem {
+    pub id: String,
+    pub title: String,
+    pub content_text: String,
+    pub date_published: DateTime<Utc>,
+}
+
+/// `GET /api/feed.json`: recent registrations as a JSON Feed.
+pub async fn json_feed(
+    Extension(state): Extension<SharedState>,
+) -> Result<impl IntoResponse, AppError> {
+    let (title, delegations) = load(&state).await?;
+    let items = delegations
+        .iter()
+        .map(|delegation| {
+            let fqdn = fqdn(&state, delegation);
+            JsonFeedItem {
+                id: format!("dns:{fqdn}"),
+                content_text: format!("{fqdn} was registered"),
+                title: fqdn,
+                date_published: delegation.created_at,
+            }
+        })
+        .collect();
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title,
+        items,
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "application/feed+json")],
+        Json(feed),
+    ))
+}
+
+/// `GET /api/feed.atom`: recent registrations as an Atom feed.
+pub async fn atom_feed(
+    Extension(state): Extension<SharedState>,
+) -> Result<impl IntoResponse, AppError> {
+    let (title, delegations) = load(&state).await?;
+    let updated = delegations
+        .first()
+        .map_or(DateTime::<Utc>::UNIX_EPOCH, |delegation| {
+            delegation.created_at
+        });
+
+    let mut body = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    body.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    body.push_str(&format!(
+        "  <id>urn:satsuki:delegations:{}</id>\n  <title>{}</title>\n  <updated>{}</updated>\n",
+        xml_escape(state.config.base_domain_root()),
+        xml_escape(&title),
+        rfc3339(updated)
+    ));
+    for delegation in &delegations {
+        let fqdn = xml_escape(&fqdn(&state, delegation));
+        body.push_str(&format!(
+            "  <entry>\n    <id>dns:{fqdn}</id>\n    <title>{fqdn}</title>\n    <updated>{}</updated>\n    <summary>{fqdn} was registered</summary>\n  </entry>\n",
+            rfc3339(delegation.created_at)
+        ));
+    }
+    body.push_str("</feed>\n");
+
+    Ok(([(header::CONTENT_TYPE, "application/atom+xml")], body))
+}
+
+/// Feed title and the newest registrations, if the operator enabled the feed.
+async fn load(state: &SharedState) -> Result<(String, Vec<Delegation>), AppError> {
+    require_feature(state, Feature::DelegationFeed)?;
+    let delegations = user_repo::list_recent(&state.db, FEED_LENGTH)
+        .await
+        .map_err(internal)?;
+    let service = state
+        .policy()
+        .service_name
+        .clone()
+        .unwrap_or_else(|| state.config.base_domain_root().to_string());
+    Ok((format!("New subdomains on {service}"), delegations))
+}
+
+/// Registered name without the trailing dot.
+fn fqdn(state: &SharedState, delegation: &Delegation) -> String {
+    state
+        .config
+        .user_zone_name(&delegation.subdomain, &delegation.base_domain)
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
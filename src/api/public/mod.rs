@@ -1,151 +1,287 @@
 //! Public-facing API handlers for signup, authentication, and discovery.
 
-use crate::config::AppConfig;
-use crate::db::user_repo;
-use crate::error::AppError;
-use crate::powerdns::types::{PdnsRecord, PdnsRrset, PdnsZoneCreate};
-use crate::validation::validate_subdomain_name;
-use crate::{SharedState, auth::hash_password};
-use axum::{Extension, Json, http::header, response::IntoResponse};
-use chrono::Utc;
+pub mod feed;
+pub mod stats;
+
+use crate::config::{AppConfig, ConfusableMode, Feature, MaintenanceConfig, SignupMode};
+use crate::db::{
+    abuse_repo, audit_repo, delegation_repo, invite_repo, pending_signup_repo, reserved_repo,
+    signup_repo, user_repo,
+};
+use crate::error::{AppError, ErrorCode, internal};
+use crate::i18n::{Message, MessageId};
+use crate::powerdns::breaker;
+use crate::powerdns::types::{PdnsRecord, PdnsRrset};
+use crate::signup_saga::{self, ReserveError};
+use crate::validation::{
+    confusable_skeleton, label_to_ascii, label_to_unicode, normalize_subdomain_label,
+    one_edit_apart,
+};
+use crate::{AppState, SharedState, auth::hash_password, client_ip::ClientIp};
+use crate::{abuse, alerts};
+use axum::http::{StatusCode, header};
+use axum::{Extension, Json, response::IntoResponse};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::Error as SqlxError;
-use std::collections::BTreeSet;
+use std::net::IpAddr;
 
 /// Payload for creating a brand-new delegated subdomain.
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SignupRequest {
     pub subdomain: String,
     pub password: String,
+    /// One of the configured base domains; defaults to the first.
+    #[serde(default)]
+    pub base_domain: Option<String>,
+    /// Required when the instance runs in invite-only mode.
+    #[serde(default)]
+    pub invite_code: Option<String>,
 }
 
 /// Create a user account and delegate the requested subdomain if available.
 pub async fn signup(
     Extension(state): Extension<SharedState>,
+    ClientIp(client_ip): ClientIp,
     Json(req): Json<SignupRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let signup_mode = state.policy().signup_mode;
+    if signup_mode == SignupMode::Closed {
+        return Err(AppError::localized(
+            ErrorCode::SignupClosed,
+            MessageId::SignupClosed,
+        ));
+    }
+
+    let invite_code = req
+        .invite_code
+        .as_deref()
+        .map(str::trim)
+        .filter(|code| !code.is_empty());
+    if signup_mode == SignupMode::Invite && invite_code.is_none() {
+        return Err(AppError::localized(
+            ErrorCode::InviteRequired,
+            MessageId::InviteRequired,
+        ));
+    }
+
+    let source_subnet = check_signup_sources(&state, client_ip).await?;
+
     // 1) validate subdomain syntax
-    crate::validation::validate_subdomain_name(&req.subdomain)
-        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+    let subdomain = normalize_subdomain_label(&req.subdomain, state.policy().idn_labels)
+        .map_err(|e| AppError::localized(ErrorCode::InvalidSubdomain, e))?;
 
-    if state.config.is_disallowed_subdomain(&req.subdomain) {
-        return Err((
-            axum::http::StatusCode::BAD_REQUEST,
-            "requested subdomain is reserved".into(),
+    if label_reserved(&state, &subdomain).await.map_err(internal)? {
+        return Err(AppError::localized(
+            ErrorCode::SubdomainReserved,
+            MessageId::SubdomainReserved,
         ));
     }
 
+    let Some(base_domain) = state
+        .config
+        .resolve_base_domain(req.base_domain.as_deref())
+        .map(str::to_string)
+    else {
+        return Err(unknown_base_domain());
+    };
+
     // 2) check if exists
-    if user_repo::exists(&state.db, &req.subdomain)
+    if user_repo::exists(&state.db, &subdomain, &base_domain)
         .await
         .map_err(internal)?
     {
-        return Err((axum::http::StatusCode::CONFLICT, "already exists".into()));
+        return Err(AppError::localized(
+            ErrorCode::SubdomainTaken,
+            MessageId::SubdomainTaken,
+        ));
     }
 
-    if dns_label_occupied(&state, &req.subdomain)
+    if dns_label_occupied(&state, &subdomain, &base_domain)
         .await
         .map_err(internal)?
     {
-        return Err((axum::http::StatusCode::CONFLICT, "already exists".into()));
+        return Err(AppError::localized(
+            ErrorCode::SubdomainTaken,
+            MessageId::SubdomainTaken,
+        ));
+    }
+
+    let confusable_mode = state.policy().confusable_labels;
+    let lookalike = confusable_with(&state, confusable_mode, &subdomain, &base_domain)
+        .await
+        .map_err(internal)?;
+    if let Some(similar) = &lookalike
+        && confusable_mode == ConfusableMode::Reject
+    {
+        return Err(confusable(similar));
+    }
+
+    let typosquat_mode = state.policy().typosquat.mode;
+    let typosquat = match &lookalike {
+        Some(_) => None,
+        None => typosquat_of(&state, &subdomain, &base_domain)
+            .await
+            .map_err(internal)?,
+    };
+    if let Some(popular) = &typosquat
+        && typosquat_mode == ConfusableMode::Reject
+    {
+        return Err(confusable(popular));
     }
 
     if state.config.internal_ns.is_empty() {
-        return Err((
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            "no internal nameservers configured".into(),
-        ));
+        return Err(internal("no internal nameservers configured"));
     }
 
     let hash = hash_password(&req.password).map_err(internal)?;
+    let zone_name = state.config.user_zone_name(&subdomain, &base_domain);
 
-    // 3) prepare PDNS zone & NS
-    let zone_name = state.config.user_zone_name(&req.subdomain);
-    let parent_zone = state.config.parent_zone_name();
-
-    // create zone in sub-PDNS
-    let z = PdnsZoneCreate {
-        name: zone_name.clone(),
-        kind: "Native".into(),
-        nameservers: state.config.internal_ns.clone(),
+    let claimed_invite = match (signup_mode, invite_code) {
+        (SignupMode::Invite, Some(code)) => {
+            if !invite_repo::claim(&state.db, code, &zone_name)
+                .await
+                .map_err(internal)?
+            {
+                return Err(AppError::localized(
+                    ErrorCode::InvalidInvite,
+                    MessageId::InvalidInvite,
+                ));
+            }
+            Some(code)
+        }
+        _ => None,
     };
-    state.sub_pdns.create_zone(&z).await.map_err(internal)?;
-
-    let sub_zone_rrsets = vec![
-        build_apex_ns_rrset(&state.config, &zone_name),
-        build_apex_soa_rrset(&state.config, &zone_name),
-    ];
 
-    if let Err(err) = state
-        .sub_pdns
-        .patch_rrsets(&zone_name, &sub_zone_rrsets)
-        .await
+    let flag = lookalike
+        .map(|similar| ("confusable_label", similar))
+        .or(typosquat.map(|popular| ("typosquat_label", popular)));
+    let saga = match signup_saga::reserve(
+        &state,
+        &subdomain,
+        &base_domain,
+        &hash,
+        claimed_invite,
+        flag.as_ref()
+            .map(|(reason, similar)| (*reason, similar.as_str())),
+    )
+    .await
     {
-        cleanup_partial_signup(&state, &parent_zone, &zone_name).await;
-        return Err(internal(err));
-    }
-
-    // 4) create NS delegation in base-PDNS
-    if let Err(err) = state
-        .base_pdns
-        .patch_rrsets(
-            &parent_zone,
-            &[build_apex_ns_rrset(&state.config, &zone_name)],
-        )
-        .await
+        Ok(saga) => saga,
+        Err(err) => {
+            if let Some(code) = claimed_invite
+                && let Err(release_err) = invite_repo::release(&state.db, code).await
+            {
+                tracing::error!("failed to release invite after failed signup: {release_err:?}");
+            }
+            return Err(match err {
+                ReserveError::Taken => {
+                    AppError::localized(ErrorCode::SubdomainTaken, MessageId::SubdomainTaken)
+                }
+                ReserveError::Db(err) => internal(err),
+            });
+        }
+    };
+    // past this point the signup is kept and finished by the recovery job
+    // if a step fails
+    let pending = match signup_saga::advance(&state, saga).await {
+        Ok(_) => {
+            tracing::info!("registered {zone_name} from {client_ip}");
+            false
+        }
+        Err(err) => {
+            tracing::warn!("{err:#}; left to the signup_recovery job");
+            true
+        }
+    };
+    if let Err(err) = signup_repo::record(
+        &state.db,
+        &client_ip.to_string(),
+        &source_subnet,
+        &zone_name,
+        Utc::now() - SIGNUP_WINDOW,
+    )
+    .await
     {
-        cleanup_partial_signup(&state, &parent_zone, &zone_name).await;
-        return Err(internal(err));
+        tracing::error!("failed to log signup source of {zone_name}: {err:?}");
     }
+    alerts::check_signup_spike(&state).await;
 
-    // 5) insert into DB
-    if let Err(err) = user_repo::insert(&state.db, &req.subdomain, &hash).await {
-        cleanup_partial_signup(&state, &parent_zone, &zone_name).await;
-        if is_unique_violation(&err) {
-            return Err((axum::http::StatusCode::CONFLICT, "already exists".into()));
-        }
-        return Err(internal(err));
-    }
+    let status = if pending {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::OK
+    };
+    Ok((
+        status,
+        Json(serde_json::json!({
+            "ok": true,
+            "pending": pending,
+            "subdomain": subdomain,
+            "subdomain_unicode": label_to_unicode(&subdomain),
+            "base_domain": base_domain,
+            "zone": zone_name,
+            "nameservers": state.config.internal_ns,
+        })),
+    ))
+}
 
-    Ok(Json(serde_json::json!({ "ok": true })))
+/// Reject the request with 403 when the operator disabled `feature`.
+pub(crate) fn require_feature(state: &SharedState, feature: Feature) -> Result<(), AppError> {
+    if state.policy().features.is_enabled(feature) {
+        return Ok(());
+    }
+    Err(AppError::localized(
+        ErrorCode::FeatureDisabled,
+        Message::new(MessageId::FeatureDisabled, [feature.name()]),
+    ))
 }
 
-pub(crate) fn internal<E: std::fmt::Debug + std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    tracing::error!("{e:?}");
-    (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+fn unknown_base_domain() -> AppError {
+    AppError::localized(ErrorCode::UnknownBaseDomain, MessageId::UnknownBaseDomain)
 }
 
 /// Credentials used to authenticate an existing subdomain owner.
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SigninRequest {
     pub subdomain: String,
     pub password: String,
+    #[serde(default)]
+    pub base_domain: Option<String>,
 }
 
 /// Authenticate a user against the stored password hash.
 pub async fn signin(
     Extension(state): Extension<SharedState>,
+    ClientIp(client_ip): ClientIp,
     Json(req): Json<SigninRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
     use crate::auth::verify_password;
     use crate::db::user_repo;
 
-    let user = user_repo::find_by_subdomain(&state.db, &req.subdomain)
+    let invalid =
+        || AppError::localized(ErrorCode::InvalidCredentials, MessageId::InvalidCredentials);
+    let (subdomain, base_domain) = match req.base_domain.as_deref() {
+        Some(domain) => state
+            .config
+            .resolve_base_domain(Some(domain))
+            .map(|domain| (req.subdomain.as_str(), domain)),
+        None => state.config.split_username(&req.subdomain),
+    }
+    .ok_or_else(invalid)?;
+
+    let user = user_repo::find_by_subdomain(&state.db, &label_to_ascii(subdomain), base_domain)
         .await
         .map_err(internal)?
-        .ok_or((
-            axum::http::StatusCode::UNAUTHORIZED,
-            "invalid credentials".into(),
-        ))?;
+        .ok_or_else(invalid)?;
 
     if !verify_password(&user.password_hash, &req.password).map_err(internal)? {
-        return Err((
-            axum::http::StatusCode::UNAUTHORIZED,
-            "invalid credentials".into(),
-        ));
+        return Err(invalid());
     }
 
-    user_repo::update_last_login(&state.db, user.id)
+    user_repo::update_last_login(&state.db, user.id, &client_ip.to_string())
+        .await
+        .map_err(internal)?;
+    crate::history::record_signin(&state, &user, client_ip)
         .await
         .map_err(internal)?;
 
@@ -156,6 +292,9 @@ pub async fn signin(
 #[derive(Serialize)]
 pub struct CheckSubdomainResponse {
     available: bool,
+    /// The label as it would be stored, in ACE form for Unicode labels.
+    subdomain: String,
+    subdomain_unicode: String,
 }
 
 /// Validate syntax, reservation list, database, and DNS occupancy for a label.
@@ -164,65 +303,133 @@ pub async fn check_subdomain(
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> Result<Json<CheckSubdomainResponse>, AppError> {
     let Some(name) = params.get("name") else {
-        return Err(AppError::bad_request("missing 'name' parameter"));
+        return Err(AppError::localized(
+            ErrorCode::InvalidRequest,
+            MessageId::MissingNameParameter,
+        ));
     };
 
-    validate_subdomain_name(name).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let name = normalize_subdomain_label(name, state.policy().idn_labels)
+        .map_err(|e| AppError::localized(ErrorCode::InvalidSubdomain, e))?;
 
-    if state.config.is_disallowed_subdomain(name) {
-        return Err(AppError::bad_request("requested subdomain is reserved"));
+    if label_reserved(&state, &name)
+        .await
+        .map_err(AppError::internal)?
+    {
+        return Err(AppError::localized(
+            ErrorCode::SubdomainReserved,
+            MessageId::SubdomainReserved,
+        ));
     }
 
-    let exists = user_repo::exists(&state.db, name)
+    let base_domain = state
+        .config
+        .resolve_base_domain(params.get("domain").map(String::as_str))
+        .ok_or_else(unknown_base_domain)?;
+
+    let exists = user_repo::exists(&state.db, &name, base_domain)
         .await
-        .map_err(AppError::internal)?;
+        .map_err(AppError::internal)?
+        || pending_signup_repo::exists(&state.db, &name, base_domain)
+            .await
+            .map_err(AppError::internal)?;
 
-    let dns_exists = dns_label_occupied(&state, name)
+    let dns_exists = dns_label_occupied(&state, &name, base_domain)
         .await
         .map_err(AppError::internal_anyhow)?;
 
+    let confusable_mode = state.policy().confusable_labels;
+    if confusable_mode == ConfusableMode::Reject
+        && let Some(similar) = confusable_with(&state, confusable_mode, &name, base_domain)
+            .await
+            .map_err(AppError::internal)?
+    {
+        return Err(confusable(&similar));
+    }
+    if state.policy().typosquat.mode == ConfusableMode::Reject
+        && let Some(popular) = typosquat_of(&state, &name, base_domain)
+            .await
+            .map_err(AppError::internal)?
+    {
+        return Err(confusable(&popular));
+    }
+
     Ok(Json(CheckSubdomainResponse {
         available: !(exists || dns_exists),
+        subdomain_unicode: label_to_unicode(&name),
+        subdomain: name,
     }))
 }
 
-fn is_unique_violation(err: &SqlxError) -> bool {
-    match err {
-        SqlxError::Database(db_err) => db_err.message().contains("UNIQUE"),
-        _ => false,
-    }
-}
-
-/// Best-effort cleanup if any step of signup fails after DNS writes.
-async fn cleanup_partial_signup(state: &SharedState, parent_zone: &str, zone_name: &str) {
-    let delete_rrset = PdnsRrset {
-        name: zone_name.to_string(),
-        rrtype: "NS".into(),
-        ttl: 300,
-        changetype: Some("DELETE".into()),
-        records: Vec::new(),
-        comments: Vec::new(),
-    };
-
-    let _ = state
-        .base_pdns
-        .patch_rrsets(parent_zone, &[delete_rrset])
-        .await;
-    let _ = state.sub_pdns.delete_zone(zone_name).await;
-}
-
-/// Public description of the base domain the service manages.
+/// Public description of the instance, its domains, and its policies.
 #[derive(Serialize)]
 pub struct AboutResponse {
     pub base_domain: String,
+    pub base_domains: Vec<String>,
+    pub service_name: Option<String>,
+    pub contact_email: Option<String>,
+    pub terms_url: Option<String>,
+    pub privacy_url: Option<String>,
+    pub signup_mode: SignupMode,
+    pub announcement: Option<String>,
+    pub maintenance: MaintenanceConfig,
+    /// PowerDNS is down; with `maintenance.read_only_when_pdns_down`,
+    /// changes are rejected until it recovers.
+    pub degraded: bool,
+    pub limits: AboutLimits,
+    pub allowed_record_types: Vec<String>,
+    pub features: Vec<&'static str>,
 }
 
-/// Return the base domain so clients can build FQDNs locally.
+/// Quotas advertised so clients can validate before submitting.
+#[derive(Serialize)]
+pub struct AboutLimits {
+    pub max_records_per_zone: Option<usize>,
+    pub max_rrsets_per_zone: Option<usize>,
+    pub max_zone_bytes: Option<usize>,
+    pub max_zone_updates_per_minute: Option<u32>,
+    pub max_zone_updates_per_day: Option<u32>,
+    pub default_ttl: u32,
+    pub min_ttl: u32,
+    pub max_ttl: u32,
+    pub min_external_ns: usize,
+    pub max_external_ns: usize,
+}
+
+/// Return instance metadata so clients can build FQDNs and adapt their UI.
 pub async fn about(
     Extension(state): Extension<SharedState>,
-) -> Result<Json<AboutResponse>, (axum::http::StatusCode, String)> {
+) -> Result<Json<AboutResponse>, AppError> {
+    let policy = state.policy();
     Ok(Json(AboutResponse {
         base_domain: state.config.base_domain_root().to_string(),
+        base_domains: state
+            .config
+            .base_domain_roots()
+            .map(str::to_string)
+            .collect(),
+        service_name: policy.service_name.clone(),
+        contact_email: policy.contact_email.clone(),
+        terms_url: policy.terms_url.clone(),
+        privacy_url: policy.privacy_url.clone(),
+        signup_mode: policy.signup_mode,
+        announcement: policy.announcement.clone(),
+        maintenance: policy.maintenance.clone(),
+        degraded: state.pdns_health.degraded(),
+        limits: AboutLimits {
+            max_records_per_zone: policy.max_records_per_zone,
+            max_rrsets_per_zone: policy.max_rrsets_per_zone,
+            max_zone_bytes: policy.max_zone_bytes,
+            max_zone_updates_per_minute: policy.max_zone_updates_per_minute,
+            max_zone_updates_per_day: policy.max_zone_updates_per_day,
+            default_ttl: policy.default_ttl,
+            min_ttl: policy.min_ttl,
+            max_ttl: policy.max_ttl,
+            min_external_ns: policy.min_external_ns,
+            max_external_ns: policy.max_external_ns,
+        },
+        allowed_record_types: policy.effective_allowed_record_types(),
+        features: policy.features.enabled_names(),
     }))
 }
 
@@ -239,117 +446,109 @@ pub struct ParentSoaResponse {
     pub soa: String,
 }
 
-/// Enumerate all NS delegations under the parent zone.
+/// Enumerate all NS delegations under every parent zone, as recorded in the
+/// delegation inventory.
 pub async fn list_ns_records(
     Extension(state): Extension<SharedState>,
-) -> Result<Json<Vec<SubdomainListResponse>>, (axum::http::StatusCode, String)> {
-    use std::collections::BTreeMap;
-
-    let parent_zone = state.config.parent_zone_name();
-    let zone = state
-        .base_pdns
-        .get_zone(&parent_zone)
-        .await
-        .map_err(internal)?;
-
-    let mut map: BTreeMap<String, Vec<String>> = BTreeMap::new();
-    if let Some(rrsets) = zone.rrsets {
-        for rr in rrsets
-            .into_iter()
-            .filter(|rr| rr.rrtype.eq_ignore_ascii_case("NS"))
-        {
-            let entry = map.entry(rr.name).or_default();
-            entry.extend(rr.records.into_iter().map(|rec| rec.content));
-        }
-    }
-
-    let mut grouped = Vec::with_capacity(map.len());
-    for (name, records) in map {
-        grouped.push(SubdomainListResponse { name, records });
+) -> Result<Json<Vec<SubdomainListResponse>>, AppError> {
+    require_feature(&state, Feature::PublicListing)?;
+
+    let mut grouped = Vec::new();
+    for base_domain in state.config.base_domain_roots() {
+        let delegations = delegation_repo::list(&state.db, base_domain)
+            .await
+            .map_err(internal)?;
+        grouped.extend(
+            delegations
+                .into_iter()
+                .map(|delegation| SubdomainListResponse {
+                    name: delegation.name,
+                    records: delegation.nameservers,
+                }),
+        );
     }
 
     Ok(Json(grouped))
 }
 
+/// Query string for endpoints that target a single base domain.
+#[derive(Deserialize)]
+pub struct DomainQuery {
+    pub domain: Option<String>,
+}
+
 /// Return the parent zone's SOA record so clients can copy/paste it.
 pub async fn parent_zone_soa(
     Extension(state): Extension<SharedState>,
-) -> Result<Json<ParentSoaResponse>, (axum::http::StatusCode, String)> {
-    let parent_zone = state.config.parent_zone_name();
+    axum::extract::Query(query): axum::extract::Query<DomainQuery>,
+) -> Result<Json<ParentSoaResponse>, AppError> {
+    require_feature(&state, Feature::PublicListing)?;
+
+    let base_domain = state
+        .config
+        .resolve_base_domain(query.domain.as_deref())
+        .ok_or_else(unknown_base_domain)?;
+    let parent_zone = state.config.parent_zone_name(base_domain);
     let zone = state
-        .base_pdns
+        .base_pdns_for(base_domain)
         .get_zone(&parent_zone)
         .await
         .map_err(internal)?;
 
     if let Some(rrsets) = zone.rrsets {
         for rr in rrsets {
-            if rr
-                .rrtype
-                .eq_ignore_ascii_case("SOA")
+            if rr.rrtype.eq_ignore_ascii_case("SOA")
                 && normalize_dns_name(&rr.name) == normalize_dns_name(&parent_zone)
+                && let Some(record) = rr.records.into_iter().next()
             {
-                if let Some(record) = rr.records.into_iter().next() {
-                    return Ok(Json(ParentSoaResponse { soa: record.content }));
-                }
+                return Ok(Json(ParentSoaResponse {
+                    soa: record.content,
+                }));
             }
         }
     }
 
-    Err((
-        axum::http::StatusCode::NOT_FOUND,
-        "SOA record not found".into(),
+    Err(AppError::localized(
+        ErrorCode::NotFound,
+        MessageId::SoaNotFound,
     ))
 }
 
 /// Prometheus metrics endpoint exporting subdomain counts.
 pub async fn metrics(
     Extension(state): Extension<SharedState>,
-) -> Result<impl IntoResponse, (axum::http::StatusCode, String)> {
-    let parent_zone = state.config.parent_zone_name();
-    let zone = state
-        .base_pdns
-        .get_zone(&parent_zone)
-        .await
-        .map_err(internal)?;
-
-    let mut subdomains: BTreeSet<String> = BTreeSet::new();
-    if let Some(rrsets) = zone.rrsets {
-        for rr in rrsets.into_iter().filter(|rr| rr.rrtype.eq_ignore_ascii_case("NS")) {
-            let owner = normalize_dns_name(&rr.name);
-            if owner == normalize_dns_name(&parent_zone) {
-                continue;
-            }
-            subdomains.insert(owner);
-        }
-    }
-
-    let body = format!(
+) -> Result<impl IntoResponse, AppError> {
+    let mut body = String::from(
         "# TYPE satsuki_subdomains_total gauge\n\
-# HELP satsuki_subdomains_total Number of delegated subdomains\n\
-satsuki_subdomains_total{{domain=\"{}\"}} {}\n",
-        normalize_dns_name(&parent_zone),
-        subdomains.len()
+# HELP satsuki_subdomains_total Number of delegated subdomains\n",
     );
-    Ok((
-        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
-        body,
-    ))
-}
 
-const NS_TTL: u32 = 300;
-const SOA_TTL: u32 = 3600;
-const SOA_REFRESH: u32 = 7200;
-const SOA_RETRY: u32 = 900;
-const SOA_EXPIRE: u32 = 1_209_600;
-const SOA_MINIMUM: u32 = 300;
+    for base_domain in state.config.base_domain_roots() {
+        let parent_zone = state.config.parent_zone_name(base_domain);
+        let subdomains = delegation_repo::count_delegated(&state.db, base_domain, &parent_zone)
+            .await
+            .map_err(internal)?;
+        body.push_str(&format!(
+            "satsuki_subdomains_total{{domain=\"{}\"}} {subdomains}\n",
+            normalize_dns_name(&parent_zone),
+        ));
+    }
+    if let Some(stats) = state.stats.load_full() {
+        body.push_str(&stats.metrics());
+    }
+    body.push_str(&state.reconcile.metrics());
+    body.push_str(&state.serial_check.metrics());
+    body.push_str(&state.pdns_latency.metrics());
+    body.push_str(&breaker::metrics(&state.pdns_endpoints()));
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
 
 /// Helper to construct the canonical NS RRset for a user zone.
-fn build_apex_ns_rrset(config: &AppConfig, zone_name: &str) -> PdnsRrset {
+pub(crate) fn build_apex_ns_rrset(config: &AppConfig, zone_name: &str) -> PdnsRrset {
     PdnsRrset {
         name: zone_name.to_string(),
         rrtype: "NS".into(),
-        ttl: NS_TTL,
+        ttl: config.ns_ttl,
         changetype: Some("REPLACE".into()),
         records: config
             .internal_ns
@@ -363,11 +562,157 @@ fn build_apex_ns_rrset(config: &AppConfig, zone_name: &str) -> PdnsRrset {
     }
 }
 
+/// Check a label against the static list, the live policy, and DB reservations.
+async fn label_reserved(state: &SharedState, label: &str) -> sqlx::Result<bool> {
+    if state.is_disallowed_subdomain(label) {
+        return Ok(true);
+    }
+    reserved_repo::is_reserved(&state.db, label).await
+}
+
+/// A reserved label or a delegation under `base_domain` that `label` can be
+/// mistaken for; identical labels are left to the reservation and
+/// availability checks.
+async fn confusable_with(
+    state: &SharedState,
+    mode: ConfusableMode,
+    label: &str,
+    base_domain: &str,
+) -> sqlx::Result<Option<String>> {
+    if mode == ConfusableMode::Off {
+        return Ok(None);
+    }
+    let mut candidates = state.config.effective_disallowed_subdomains().into_owned();
+    {
+        let policy = state.policy();
+        candidates.extend(policy.disallowed_subdomains.iter().cloned());
+        candidates.extend(policy.reserved_labels.exact.iter().cloned());
+    }
+    candidates.extend(
+        reserved_repo::list(&state.db)
+            .await?
+            .into_iter()
+            .map(|r| r.label),
+    );
+    candidates.extend(user_repo::list_subdomains(&state.db, base_domain).await?);
+
+    let skeleton = confusable_skeleton(label);
+    Ok(candidates.into_iter().find(|candidate| {
+        !candidate.eq_ignore_ascii_case(label) && confusable_skeleton(candidate) == skeleton
+    }))
+}
+
+/// A popular delegation under `base_domain` that `label` is one typo away
+/// from, comparing skeletons so digit and lookalike substitutions count as
+/// no edit. `None` while the check is off.
+async fn typosquat_of(
+    state: &SharedState,
+    label: &str,
+    base_domain: &str,
+) -> sqlx::Result<Option<String>> {
+    let config = state.policy().typosquat.clone();
+    if config.mode == ConfusableMode::Off {
+        return Ok(None);
+    }
+    let popular =
+        user_repo::list_popular_subdomains(&state.db, base_domain, config.min_records).await?;
+    let skeleton = confusable_skeleton(label);
+    Ok(popular.into_iter().find(|candidate| {
+        let other = confusable_skeleton(candidate);
+        candidate != label
+            && other.chars().count() >= config.min_length
+            && (other == skeleton || one_edit_apart(&other, &skeleton))
+    }))
+}
+
+fn confusable(similar: &str) -> AppError {
+    AppError::localized(
+        ErrorCode::SubdomainConfusable,
+        Message::new(MessageId::SubdomainConfusable, [label_to_unicode(similar)]),
+    )
+}
+
+/// Window the signup caps count over.
+pub(crate) const SIGNUP_WINDOW: chrono::Duration = chrono::Duration::days(1);
+
+/// Refuse a signup once the client address or its network has used up its
+/// daily cap; returns the network the signup counts against.
+async fn check_signup_sources(state: &SharedState, client_ip: IpAddr) -> Result<String, AppError> {
+    let limits = state.policy().signup_limits.clone();
+    let address = client_ip.to_string();
+    let subnet = limits.subnet(client_ip).to_string();
+    let since = Utc::now() - SIGNUP_WINDOW;
+    if let Some(limit) = limits.per_ip_per_day {
+        let recent = signup_repo::recent_by_ip(&state.db, &address, since, limit.into())
+            .await
+            .map_err(internal)?;
+        ensure_below_cap("address", &address, limit, &recent)?;
+    }
+    if let Some(limit) = limits.per_subnet_per_day {
+        let recent = signup_repo::recent_by_subnet(&state.db, &subnet, since, limit.into())
+            .await
+            .map_err(internal)?;
+        ensure_below_cap("subnet", &subnet, limit, &recent)?;
+    }
+    Ok(subnet)
+}
+
+/// `429` when `recent`, the newest signups from one source, fill its cap.
+fn ensure_below_cap(
+    scope: &'static str,
+    source: &str,
+    limit: u32,
+    recent: &[DateTime<Utc>],
+) -> Result<(), AppError> {
+    // the oldest of the newest `limit` signups frees the next slot
+    let Some(oldest) = recent.get(limit as usize - 1) else {
+        return Ok(());
+    };
+    tracing::warn!("signup from {source} refused: {limit} per day reached");
+    let retry_after = (*oldest + SIGNUP_WINDOW - Utc::now()).num_seconds().max(0) + 1;
+    Err(AppError::new(
+        ErrorCode::TooManyRequests,
+        format!("too many signups from this {scope}; retry in {retry_after} s"),
+    )
+    .with("scope", scope)
+    .with("limit", limit)
+    .with("retry_after", retry_after))
+}
+
+/// Put a zone whose label resembles `similar` into the abuse review queue
+/// with `reason`, `confusable_label` or `typosquat_label`.
+pub(crate) async fn flag_lookalike(
+    state: &AppState,
+    user_id: i64,
+    zone_name: &str,
+    subdomain: &str,
+    similar: &str,
+    reason: &str,
+) -> sqlx::Result<()> {
+    let details = serde_json::json!({
+        "reason": reason,
+        "label": label_to_unicode(subdomain),
+        "resembles": label_to_unicode(similar),
+    });
+    tracing::warn!("signup of {zone_name} resembles {similar}, flagged for review");
+    if abuse_repo::flag(&state.db, user_id, zone_name, reason, &details).await? {
+        alerts::raise(state, abuse::flagged_alert(zone_name, reason, &details));
+    }
+    audit_repo::insert(&state.db, "signup", "abuse.flagged", zone_name, &details).await
+}
+
 /// Inspect PowerDNS to determine if the label already has any RRsets.
-async fn dns_label_occupied(state: &SharedState, subdomain: &str) -> anyhow::Result<bool> {
-    let parent_zone = state.config.parent_zone_name();
-    let desired = normalize_dns_name(&state.config.user_zone_name(subdomain));
-    let zone = state.base_pdns.get_zone(&parent_zone).await?;
+async fn dns_label_occupied(
+    state: &SharedState,
+    subdomain: &str,
+    base_domain: &str,
+) -> anyhow::Result<bool> {
+    let parent_zone = state.config.parent_zone_name(base_domain);
+    let desired = normalize_dns_name(&state.config.user_zone_name(subdomain, base_domain));
+    let zone = state
+        .base_pdns_for(base_domain)
+        .get_zone(&parent_zone)
+        .await?;
 
     if let Some(rrsets) = zone.rrsets {
         for rr in rrsets {
@@ -386,20 +731,26 @@ fn normalize_dns_name(name: &str) -> String {
 }
 
 /// Helper to build the authoritative SOA RRset for a user zone.
-fn build_apex_soa_rrset(config: &AppConfig, zone_name: &str) -> PdnsRrset {
+pub(crate) fn build_apex_soa_rrset(config: &AppConfig, zone_name: &str) -> PdnsRrset {
     let mname = config.internal_main_ns.clone();
     let contact = config.internal_contact.clone();
     let serial = Utc::now().format("%Y%m%d01").to_string();
 
     let content = format!(
         "{} {} {} {} {} {} {}",
-        mname, contact, serial, SOA_REFRESH, SOA_RETRY, SOA_EXPIRE, SOA_MINIMUM
+        mname,
+        contact,
+        serial,
+        config.soa.refresh,
+        config.soa.retry,
+        config.soa.expire,
+        config.soa.minimum
     );
 
     PdnsRrset {
         name: zone_name.to_string(),
         rrtype: "SOA".into(),
-        ttl: SOA_TTL,
+        ttl: config.soa_ttl,
         changetype: Some("REPLACE".into()),
         records: vec![PdnsRecord {
             content,
@@ -0,0 +1,19 @@
+//! Cached aggregate numbers about the instance.
+use crate::SharedState;
+use crate::error::{AppError, internal};
+use crate::stats::{self, InstanceStats};
+use axum::{Extension, Json};
+use std::sync::Arc;
+
+/// `GET /api/stats`: registration counts as of the last scheduled refresh.
+pub async fn get_stats(
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Arc<InstanceStats>>, AppError> {
+    let cached = state.stats.load_full();
+    let stats = match cached {
+        Some(stats) => stats,
+        // only before the refresher's first run
+        None => stats::refresh(&state).await.map_err(internal)?,
+    };
+    Ok(Json(stats))
+}
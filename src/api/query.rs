@@ -0,0 +1,102 @@
+//! Direct queries to the internal nameservers, so users can confirm that a
+//! change they just applied is being served.
+use crate::api::dns::normalize_owner;
+use crate::dns_probe::{self, Response};
+use crate::error::AppError;
+use crate::rdata;
+use crate::{SharedState, auth::Authenticated};
+use axum::{Extension, Json, extract::Query};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// Time each nameserver gets to answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Query string of `GET /api/zone/query`.
+#[derive(Deserialize)]
+pub struct QueryParams {
+    /// Name in the caller's zone, relative or fully qualified.
+    pub name: String,
+    /// Record type; defaults to `A`.
+    #[serde(rename = "type", default = "default_type")]
+    pub rrtype: String,
+}
+
+fn default_type() -> String {
+    "A".into()
+}
+
+/// What one internal nameserver answered.
+#[derive(Serialize)]
+pub struct NameserverAnswer {
+    pub nameserver: String,
+    #[serde(flatten)]
+    pub response: Option<Response>,
+    /// Why there is no response: resolution failed or the server did not answer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Answers of every internal nameserver for one question.
+#[derive(Serialize)]
+pub struct QueryResponse {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub rrtype: String,
+    pub nameservers: Vec<NameserverAnswer>,
+}
+
+/// Ask every internal nameserver directly, without recursion or caches,
+/// for a name in the caller's zone.
+pub async fn query(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Query(params): Query<QueryParams>,
+) -> Result<Json<QueryResponse>, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let name = normalize_owner(&params.name, &zone_name)?;
+    let qtype = rdata::type_code(params.rrtype.trim())
+        // 0 is reserved; 128 and up are meta and question-only types such as AXFR and ANY
+        .filter(|&code| code != 0 && !(128..=255).contains(&code))
+        .ok_or_else(|| AppError::bad_request(format!("unsupported type {:?}", params.rrtype)))?;
+
+    let mut probes = JoinSet::new();
+    for (index, hostname) in state.config.internal_ns.iter().cloned().enumerate() {
+        let name = name.clone();
+        probes.spawn(async move {
+            let answer = async {
+                let addrs = dns_probe::resolve(&hostname, QUERY_TIMEOUT).await?;
+                dns_probe::query_any(&addrs, &name, qtype, QUERY_TIMEOUT).await
+            }
+            .await;
+            let (response, error) = match answer {
+                Ok(response) => (Some(response), None),
+                Err(err) => (None, Some(format!("{err:#}"))),
+            };
+            (
+                index,
+                NameserverAnswer {
+                    nameserver: hostname,
+                    response,
+                    error,
+                },
+            )
+        });
+    }
+    let mut answers = Vec::new();
+    while let Some(joined) = probes.join_next().await {
+        if let Ok(answer) = joined {
+            answers.push(answer);
+        }
+    }
+    answers.sort_by_key(|(index, _)| *index);
+
+    Ok(Json(QueryResponse {
+        name,
+        rrtype: rdata::type_name(qtype),
+        nameservers: answers.into_iter().map(|(_, answer)| answer).collect(),
+    }))
+}
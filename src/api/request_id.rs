@@ -0,0 +1,109 @@
+//! Per-request correlation IDs shared by logs, response headers, and error bodies.
+use crate::error::PROBLEM_JSON;
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use rand_core::{OsRng, RngCore};
+use tracing::Instrument;
+
+/// Header carrying the correlation ID in both directions.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest inbound ID accepted from a client or proxy.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Error bodies larger than this are passed through untouched.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Correlation ID of the current request, available as a request extension.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Adopt the caller's `X-Request-Id` (or mint one), run the request inside a
+/// span carrying it, and echo it in the response headers and error bodies.
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(generate_id);
+
+    let span = tracing::info_span!(
+        "request",
+        id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        // filled in by the auth extractors
+        user = tracing::field::Empty,
+    );
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let response = next.run(request).instrument(span).await;
+    let mut response = if response.status().is_client_error() || response.status().is_server_error()
+    {
+        annotate_error(response, &id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Printable ASCII without spaces, so it is safe to log and echo back.
+fn is_valid_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+fn generate_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Add the ID to a JSON error body as a `request_id` member.
+async fn annotate_error(response: Response, id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.starts_with(PROBLEM_JSON) || value.starts_with("application/json")
+        });
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        // the body is gone at this point; fall back to a bare error
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    };
+
+    let annotated = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut object)) => {
+            object.insert("request_id".into(), id.into());
+            serde_json::to_vec(&object).ok()
+        }
+        _ => None,
+    };
+
+    let body = match annotated {
+        Some(annotated) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(annotated)
+        }
+        None => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
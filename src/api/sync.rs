@@ -0,0 +1,292 @@
+//! Declarative zone sync with a plan/apply contract for provider plugins
+//! (octoDNS, libdns): the client sends the complete desired record set and
+//! every user-manageable RRset not in it is deleted.
+use super::dns::{
+    RecordDto, build_rrsets_with_notes, count_zone_update, enforce_zone_limits,
+    flag_listed_targets, is_server_managed, lint_rrsets, publish_records_changed, record_usage,
+    screen_targets, store_notes, store_provenance,
+};
+use crate::SharedState;
+use crate::auth::{Authenticated, Credential};
+use crate::db::user_repo::User;
+use crate::error::{AppError, ErrorCode, internal};
+use crate::i18n::MessageId;
+use crate::powerdns::types::PdnsRrset;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Desired state of the caller's zone.
+#[derive(Deserialize)]
+pub struct SyncRequest {
+    pub records: Vec<RecordDto>,
+    /// `fingerprint` from a previous plan; apply fails if the zone changed since.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+/// Kind of change to one RRset.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// Contents of one RRset before or after a change.
+#[derive(Serialize)]
+pub struct RrsetState {
+    pub ttl: u32,
+    pub records: Vec<String>,
+}
+
+/// One planned or applied RRset change.
+#[derive(Serialize)]
+pub struct SyncChange {
+    pub action: ChangeAction,
+    pub name: String,
+    pub rrtype: String,
+    /// Current contents; absent for creates.
+    pub existing: Option<RrsetState>,
+    /// Desired contents; absent for deletes.
+    pub desired: Option<RrsetState>,
+}
+
+/// Result of a plan or apply.
+#[derive(Serialize)]
+pub struct SyncResponse {
+    /// Hash of the zone's user-manageable contents the plan was computed against.
+    pub fingerprint: String,
+    pub applied: bool,
+    pub changes: Vec<SyncChange>,
+    /// Lint findings on the desired records, such as SPF mistakes.
+    pub warnings: Vec<String>,
+}
+
+/// Compute the changes needed to reach the desired state without applying them.
+pub async fn plan(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<SyncRequest>,
+) -> Result<Json<SyncResponse>, AppError> {
+    converge(&state, &user, req.records, Missing::Delete, None, false)
+        .await
+        .map(Json)
+}
+
+/// Bring the zone to the desired state, optionally guarded by a plan fingerprint.
+pub async fn apply(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<SyncRequest>,
+) -> Result<Json<SyncResponse>, AppError> {
+    converge(
+        &state,
+        &user,
+        req.records,
+        Missing::Delete,
+        req.fingerprint,
+        true,
+    )
+    .await
+    .map(Json)
+}
+
+/// What a sync does with RRsets the desired records leave out.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Missing {
+    Delete,
+    Keep,
+}
+
+/// Diff `records` against the user's zone and, with `apply`, make the
+/// changes. `expected` is a plan fingerprint the zone must still match.
+pub(crate) async fn converge(
+    state: &SharedState,
+    user: &User,
+    records: Vec<RecordDto>,
+    missing: Missing,
+    expected: Option<String>,
+    apply: bool,
+) -> Result<SyncResponse, AppError> {
+    let zone_name = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let (desired, notes) = build_rrsets_with_notes(&state.policy(), &zone_name, records)?;
+    let mut warnings = lint_rrsets(&state.policy(), &desired)?;
+    let existing = load_manageable(state, &user.base_domain, &zone_name).await?;
+
+    let current = fingerprint(&existing);
+    if expected.is_some_and(|expected| expected != current) {
+        return Err(AppError::localized(
+            ErrorCode::ZoneChanged,
+            MessageId::ZoneChanged,
+        ));
+    }
+
+    let mut changes = diff(&existing, &desired);
+    if missing == Missing::Keep {
+        changes.retain(|change| change.action != ChangeAction::Delete);
+    }
+    if !apply {
+        return Ok(SyncResponse {
+            fingerprint: current,
+            applied: false,
+            changes,
+            warnings,
+        });
+    }
+    let patch = patch_for(&changes, desired);
+    if !patch.is_empty() {
+        count_zone_update(state, user)?;
+        enforce_zone_limits(&state.policy(), &zone_name, &existing, &patch)?;
+        let hits = screen_targets(state, &zone_name, &existing, &patch).await?;
+        state
+            .sub_pdns_for(&user.base_domain)
+            .patch_rrsets(&zone_name, &patch)
+            .await
+            .map_err(internal)?;
+        flag_listed_targets(state, user, &zone_name, hits).await;
+        record_usage(state, user, &zone_name, &existing, &patch).await;
+        store_provenance(state, user, Credential::Password, &patch).await;
+        publish_records_changed(state, user, Credential::Password, zone_name, &patch);
+    }
+    store_notes(state, user, &notes, &patch, &mut warnings).await;
+
+    Ok(SyncResponse {
+        fingerprint: current,
+        applied: true,
+        changes,
+        warnings,
+    })
+}
+
+/// The zone's RRsets minus the server-managed SOA and apex NS.
+async fn load_manageable(
+    state: &SharedState,
+    base_domain: &str,
+    zone_name: &str,
+) -> Result<Vec<PdnsRrset>, AppError> {
+    let zone = state
+        .sub_pdns_for(base_domain)
+        .get_zone(zone_name)
+        .await
+        .map_err(internal)?;
+    Ok(zone
+        .rrsets
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|rr| !is_server_managed(rr, zone_name) && !rr.records.is_empty())
+        .collect())
+}
+
+/// Case-insensitive `(owner, type)` key.
+fn key(rr: &PdnsRrset) -> (String, String) {
+    (rr.name.to_ascii_lowercase(), rr.rrtype.to_ascii_uppercase())
+}
+
+fn rrset_state(rr: &PdnsRrset) -> RrsetState {
+    let mut records: Vec<String> = rr.records.iter().map(|rec| rec.content.clone()).collect();
+    records.sort();
+    records.dedup();
+    RrsetState {
+        ttl: rr.ttl,
+        records,
+    }
+}
+
+/// Stable hash over the sorted RRsets so unrelated ordering changes don't matter.
+fn fingerprint(existing: &[PdnsRrset]) -> String {
+    let sorted: BTreeMap<_, _> = existing
+        .iter()
+        .map(|rr| (key(rr), rrset_state(rr)))
+        .collect();
+    let mut hasher = Sha256::new();
+    for ((name, rrtype), state) in &sorted {
+        hasher.update(format!("{name} {rrtype} {}\n", state.ttl));
+        for record in &state.records {
+            hasher.update(format!("\t{record}\n"));
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Changes turning `existing` into `desired`, ordered by owner and type.
+fn diff(existing: &[PdnsRrset], desired: &[PdnsRrset]) -> Vec<SyncChange> {
+    let current: BTreeMap<_, _> = existing.iter().map(|rr| (key(rr), rr)).collect();
+    let wanted: BTreeMap<_, _> = desired.iter().map(|rr| (key(rr), rr)).collect();
+
+    let mut changes = Vec::new();
+    for (rr_key, rr) in &wanted {
+        let desired = rrset_state(rr);
+        let (action, existing) = match current.get(rr_key) {
+            None => (ChangeAction::Create, None),
+            Some(old) => {
+                let existing = rrset_state(old);
+                if existing.ttl == desired.ttl && existing.records == desired.records {
+                    continue;
+                }
+                (ChangeAction::Update, Some(existing))
+            }
+        };
+        changes.push(SyncChange {
+            action,
+            name: rr.name.clone(),
+            rrtype: rr.rrtype.clone(),
+            existing,
+            desired: Some(desired),
+        });
+    }
+    for (rr_key, rr) in &current {
+        if !wanted.contains_key(rr_key) {
+            changes.push(SyncChange {
+                action: ChangeAction::Delete,
+                name: rr.name.clone(),
+                rrtype: rr.rrtype.clone(),
+                existing: Some(rrset_state(rr)),
+                desired: None,
+            });
+        }
+    }
+    changes.sort_by(|a, b| {
+        (a.name.to_ascii_lowercase(), &a.rrtype).cmp(&(b.name.to_ascii_lowercase(), &b.rrtype))
+    });
+    changes
+}
+
+/// PowerDNS patch realizing `changes`: the changed desired RRsets (already
+/// `REPLACE`) plus a `DELETE` for each removed one.
+fn patch_for(changes: &[SyncChange], desired: Vec<PdnsRrset>) -> Vec<PdnsRrset> {
+    let is_change = |rr: &PdnsRrset, action: ChangeAction| {
+        changes.iter().any(|change| {
+            change.action == action
+                && change.name.eq_ignore_ascii_case(&rr.name)
+                && change.rrtype.eq_ignore_ascii_case(&rr.rrtype)
+        })
+    };
+
+    let mut patch: Vec<PdnsRrset> = desired
+        .into_iter()
+        .filter(|rr| is_change(rr, ChangeAction::Create) || is_change(rr, ChangeAction::Update))
+        .collect();
+    patch.extend(
+        changes
+            .iter()
+            .filter(|change| change.action == ChangeAction::Delete)
+            .map(|change| PdnsRrset {
+                name: change.name.clone(),
+                rrtype: change.rrtype.clone(),
+                ttl: change.existing.as_ref().map_or(0, |state| state.ttl),
+                changetype: Some("DELETE".into()),
+                records: Vec::new(),
+                comments: Vec::new(),
+            }),
+    );
+    patch
+}
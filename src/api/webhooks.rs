@@ -0,0 +1,177 @@
+//! Authenticated endpoints for managing the caller's webhooks.
+use super::public::require_feature;
+use crate::config::Feature;
+use crate::db::webhook_repo::{self, Delivery, Webhook};
+use crate::error::{AppError, ErrorCode, internal};
+use crate::i18n::{Message, MessageId};
+use crate::validation::special_range;
+use crate::{SharedState, auth::Authenticated};
+use axum::{Extension, Json, extract::Path};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Webhooks a single user may register.
+const MAX_WEBHOOKS_PER_USER: usize = 5;
+
+/// A webhook as shown to its owner; the secret is only revealed on creation.
+#[derive(Serialize)]
+pub struct WebhookDto {
+    pub id: i64,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Webhook> for WebhookDto {
+    fn from(hook: Webhook) -> Self {
+        Self {
+            id: hook.id,
+            url: hook.url,
+            created_at: hook.created_at,
+        }
+    }
+}
+
+/// One logged delivery attempt.
+#[derive(Serialize)]
+pub struct DeliveryDto {
+    pub id: i64,
+    pub event_type: String,
+    pub attempt: i64,
+    pub status_code: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Delivery> for DeliveryDto {
+    fn from(delivery: Delivery) -> Self {
+        Self {
+            id: delivery.id,
+            event_type: delivery.event_type,
+            attempt: delivery.attempt,
+            status_code: delivery.status_code,
+            error: delivery.error,
+            created_at: delivery.created_at,
+        }
+    }
+}
+
+/// List the caller's webhooks.
+pub async fn list_webhooks(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<WebhookDto>>, AppError> {
+    require_feature(&state, Feature::Webhooks)?;
+
+    let hooks = webhook_repo::list_for_user(&state.db, user.id)
+        .await
+        .map_err(internal)?;
+    Ok(Json(hooks.into_iter().map(WebhookDto::from).collect()))
+}
+
+/// Payload registering a new webhook.
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+}
+
+/// Register a webhook and return the signing secret once.
+pub async fn create_webhook(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_feature(&state, Feature::Webhooks)?;
+
+    let url = validate_webhook_url(&req.url)
+        .map_err(|reason| AppError::localized(ErrorCode::InvalidWebhook, reason))?;
+    let existing = webhook_repo::list_for_user(&state.db, user.id)
+        .await
+        .map_err(internal)?;
+    if existing.len() >= MAX_WEBHOOKS_PER_USER {
+        return Err(AppError::localized(
+            ErrorCode::WebhookLimitReached,
+            Message::new(MessageId::TooManyWebhooks, [MAX_WEBHOOKS_PER_USER]),
+        ));
+    }
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = URL_SAFE_NO_PAD.encode(bytes);
+
+    let id = webhook_repo::insert(&state.db, user.id, &url, &secret)
+        .await
+        .map_err(internal)?;
+
+    Ok(Json(serde_json::json!({
+        "ok": true,
+        "id": id,
+        "url": url,
+        "secret": secret,
+    })))
+}
+
+/// Remove one of the caller's webhooks.
+pub async fn delete_webhook(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_feature(&state, Feature::Webhooks)?;
+
+    if !webhook_repo::delete(&state.db, user.id, id)
+        .await
+        .map_err(internal)?
+    {
+        return Err(AppError::not_found());
+    }
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Show the recent delivery attempts of one of the caller's webhooks.
+pub async fn list_deliveries(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<DeliveryDto>>, AppError> {
+    require_feature(&state, Feature::Webhooks)?;
+
+    let hook = webhook_repo::find_for_user(&state.db, user.id, id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(AppError::not_found)?;
+    let deliveries = webhook_repo::list_deliveries(&state.db, hook.id)
+        .await
+        .map_err(internal)?;
+    Ok(Json(
+        deliveries.into_iter().map(DeliveryDto::from).collect(),
+    ))
+}
+
+/// Accept only absolute http(s) URLs that do not obviously target internal
+/// hosts; names are checked again at delivery, once resolved.
+fn validate_webhook_url(input: &str) -> Result<String, MessageId> {
+    let url = reqwest::Url::parse(input.trim()).map_err(|_| MessageId::InvalidWebhookUrl)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(MessageId::WebhookScheme);
+    }
+    let internal_target = match url.host() {
+        None => true,
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => special_range(ip.into()).is_some(),
+        Some(url::Host::Ipv6(ip)) => special_range(ip.into()).is_some(),
+    };
+    if internal_target {
+        return Err(MessageId::WebhookNotPublic);
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(MessageId::WebhookCredentials);
+    }
+    Ok(url.to_string())
+}
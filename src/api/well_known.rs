@@ -0,0 +1,104 @@
+//! Operator-specific crawler and disclosure files generated from the policy,
+//! so changing them does not require rebuilding the frontend.
+use crate::SharedState;
+use crate::error::AppError;
+use axum::{Extension, http::header, response::IntoResponse};
+use chrono::{Duration, SecondsFormat, Utc};
+use std::fmt::Write;
+
+const TEXT_PLAIN: &str = "text/plain; charset=utf-8";
+
+/// `GET /.well-known/security.txt`; 404 until a contact is configured.
+pub async fn security_txt(
+    Extension(state): Extension<SharedState>,
+) -> Result<impl IntoResponse, AppError> {
+    let policy = state.policy();
+    let config = &policy.security_txt;
+    let contacts: Vec<String> = if config.contact.is_empty() {
+        policy
+            .contact_email
+            .iter()
+            .map(|email| format!("mailto:{email}"))
+            .collect()
+    } else {
+        config.contact.clone()
+    };
+    if contacts.is_empty() {
+        return Err(AppError::not_found());
+    }
+
+    let expires = config.expires.clone().unwrap_or_else(|| {
+        (Utc::now() + Duration::days(365)).to_rfc3339_opts(SecondsFormat::Secs, true)
+    });
+
+    let mut body = String::new();
+    for contact in &contacts {
+        field(&mut body, "Contact", contact);
+    }
+    field(&mut body, "Expires", &expires);
+    for key in &config.encryption {
+        field(&mut body, "Encryption", key);
+    }
+    if let Some(url) = &config.acknowledgments {
+        field(&mut body, "Acknowledgments", url);
+    }
+    if !config.preferred_languages.is_empty() {
+        field(
+            &mut body,
+            "Preferred-Languages",
+            &config.preferred_languages.join(", "),
+        );
+    }
+    for url in &config.canonical {
+        field(&mut body, "Canonical", url);
+    }
+    if let Some(url) = &config.policy {
+        field(&mut body, "Policy", url);
+    }
+    if let Some(url) = &config.hiring {
+        field(&mut body, "Hiring", url);
+    }
+
+    Ok(([(header::CONTENT_TYPE, TEXT_PLAIN)], body))
+}
+
+/// `GET /robots.txt`.
+pub async fn robots_txt(Extension(state): Extension<SharedState>) -> impl IntoResponse {
+    let policy = state.policy();
+    let robots = &policy.robots;
+
+    let mut body = String::new();
+    for (index, rule) in robots.rules.iter().enumerate() {
+        if index > 0 {
+            body.push('\n');
+        }
+        field(&mut body, "User-agent", &rule.user_agent);
+        for path in &rule.allow {
+            field(&mut body, "Allow", path);
+        }
+        for path in &rule.disallow {
+            field(&mut body, "Disallow", path);
+        }
+        if rule.allow.is_empty() && rule.disallow.is_empty() {
+            // an empty Disallow allows everything
+            field(&mut body, "Disallow", "");
+        }
+        if let Some(delay) = rule.crawl_delay {
+            field(&mut body, "Crawl-delay", &delay.to_string());
+        }
+    }
+    if !robots.sitemaps.is_empty() {
+        body.push('\n');
+        for url in &robots.sitemaps {
+            field(&mut body, "Sitemap", url);
+        }
+    }
+
+    ([(header::CONTENT_TYPE, TEXT_PLAIN)], body)
+}
+
+/// Append one `Name: value` line, dropping line breaks from operator input.
+fn field(body: &mut String, name: &str, value: &str) {
+    let value: String = value.chars().filter(|c| !c.is_control()).collect();
+    let _ = writeln!(body, "{name}: {}", value.trim());
+}
@@ -0,0 +1,80 @@
+//! Credential check reporting who the caller is, without loading their zone.
+use crate::auth::{AdminAuthenticated, Authenticated};
+use crate::error::AppError;
+use crate::validation::label_to_unicode;
+use axum::{
+    Json,
+    extract::{FromRequestParts, Request},
+    http::header,
+};
+use serde::Serialize;
+
+/// Scopes implied by user Basic credentials, which grant the whole user API.
+const USER_SCOPES: &[&str] = &[
+    "profile:read",
+    "profile:write",
+    "zone:read",
+    "zone:write",
+    "webhooks",
+];
+
+/// Scopes implied by the operator admin token.
+const ADMIN_SCOPES: &[&str] = &["admin"];
+
+/// How the caller authenticated.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Basic,
+    AdminToken,
+}
+
+/// Identity of the caller.
+#[derive(Serialize)]
+pub struct WhoamiResponse {
+    pub auth_method: AuthMethod,
+    /// Fully qualified delegated name for users, `admin` for the admin token.
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subdomain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subdomain_unicode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_domain: Option<String>,
+    pub scopes: &'static [&'static str],
+    pub roles: Vec<&'static str>,
+}
+
+/// `GET /api/whoami`: accepts user Basic credentials or the admin Bearer token.
+pub async fn whoami(request: Request) -> Result<Json<WhoamiResponse>, AppError> {
+    let (mut parts, _) = request.into_parts();
+    let is_bearer = parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Bearer "));
+
+    if is_bearer {
+        AdminAuthenticated::from_request_parts(&mut parts, &()).await?;
+        return Ok(Json(WhoamiResponse {
+            auth_method: AuthMethod::AdminToken,
+            subject: "admin".into(),
+            subdomain: None,
+            subdomain_unicode: None,
+            base_domain: None,
+            scopes: ADMIN_SCOPES,
+            roles: vec!["admin"],
+        }));
+    }
+
+    let Authenticated(user) = Authenticated::from_request_parts(&mut parts, &()).await?;
+    Ok(Json(WhoamiResponse {
+        auth_method: AuthMethod::Basic,
+        subject: format!("{}.{}", user.subdomain, user.base_domain),
+        subdomain_unicode: Some(label_to_unicode(&user.subdomain)),
+        subdomain: Some(user.subdomain),
+        base_domain: Some(user.base_domain),
+        scopes: USER_SCOPES,
+        roles: vec!["user"],
+    }))
+}
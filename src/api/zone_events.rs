@@ -0,0 +1,40 @@
+//! Server-sent event stream of changes to the caller's zone.
+use crate::{SharedState, auth::Authenticated};
+use axum::{
+    Extension,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+};
+use std::convert::Infallible;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+
+/// Stream every change event for the caller's zone as it is applied.
+///
+/// Each SSE message is named after the event type and carries the same JSON
+/// payload as webhooks. A `lagged` message means events were dropped and the
+/// client should refetch the zone.
+pub async fn zone_events(
+    Authenticated(user): Authenticated,
+    Extension(state): Extension<SharedState>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let user_id = user.id;
+    let stream =
+        BroadcastStream::new(state.events.subscribe()).filter_map(move |item| match item {
+            Ok(event) if event.user_id == user_id => {
+                let message = SseEvent::default().event(event.type_name());
+                match message.json_data(&event) {
+                    Ok(message) => Some(Ok(message)),
+                    Err(err) => {
+                        tracing::error!("failed to encode zone event: {err}");
+                        None
+                    }
+                }
+            }
+            Ok(_) => None,
+            Err(BroadcastStreamRecvError::Lagged(missed)) => Some(Ok(SseEvent::default()
+                .event("lagged")
+                .data(format!("{{\"missed\":{missed}}}")))),
+        });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
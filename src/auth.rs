@@ -1,9 +1,5 @@
 //! Basic-auth based authentication extractor plus password helpers.
-use axum::{
-    Extension,
-    extract::FromRequestParts,
-    http::{StatusCode, request::Parts},
-};
+use axum::{Extension, extract::FromRequestParts, http::request::Parts};
 use std::future::Future;
 
 use argon2::password_hash::SaltString;
@@ -11,9 +7,12 @@ use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use base64::Engine as _;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use rand_core::OsRng;
+use subtle::ConstantTimeEq;
 
 use crate::SharedState;
 use crate::db::user_repo::User;
+use crate::error::{AppError, ErrorCode};
+use crate::i18n::MessageId;
 
 /// Axum extractor that verifies Basic credentials against the database.
 pub struct Authenticated(pub User);
@@ -22,7 +21,7 @@ impl<S> FromRequestParts<S> for Authenticated
 where
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = AppError;
 
     fn from_request_parts(
         parts: &mut Parts,
@@ -33,48 +32,139 @@ where
             let Extension(app_state): axum::extract::Extension<SharedState> =
                 Extension::from_request_parts(parts, state)
                     .await
-                    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "missing state"))?;
+                    .map_err(|_| AppError::Internal(anyhow::anyhow!("missing state")))?;
 
             let auth_header = parts
                 .headers
                 .get(axum::http::header::AUTHORIZATION)
-                .ok_or((StatusCode::UNAUTHORIZED, "missing Authorization header"))?
+                .ok_or_else(|| {
+                    AppError::localized(ErrorCode::Unauthorized, MessageId::MissingAuthorization)
+                })?
                 .to_str()
-                .map_err(|_| (StatusCode::BAD_REQUEST, "invalid Authorization header"))?;
+                .map_err(|_| {
+                    AppError::localized(ErrorCode::InvalidRequest, MessageId::InvalidAuthorization)
+                })?;
 
             if !auth_header.starts_with("Basic ") {
-                return Err((StatusCode::UNAUTHORIZED, "expected Basic auth"));
+                return Err(AppError::localized(
+                    ErrorCode::Unauthorized,
+                    MessageId::ExpectedBasicAuth,
+                ));
             }
 
             let b64 = &auth_header[6..];
             let decoded = BASE64
                 .decode(b64)
-                .map_err(|_| (StatusCode::BAD_REQUEST, "invalid Base64"))?;
-            let decoded = String::from_utf8(decoded)
-                .map_err(|_| (StatusCode::BAD_REQUEST, "invalid UTF-8"))?;
+                .map_err(|_| AppError::bad_request("invalid Base64"))?;
+            let decoded =
+                String::from_utf8(decoded).map_err(|_| AppError::bad_request("invalid UTF-8"))?;
 
             let (username, password) = decoded
                 .split_once(':')
-                .ok_or((StatusCode::BAD_REQUEST, "invalid Basic payload"))?;
+                .ok_or_else(|| AppError::bad_request("invalid Basic payload"))?;
 
-            // lookup user by subdomain (username)
-            let user = crate::db::user_repo::find_by_subdomain(&app_state.db, username)
-                .await
-                .map_err(|_| (StatusCode::UNAUTHORIZED, "invalid credentials"))?
-                .ok_or((StatusCode::UNAUTHORIZED, "invalid credentials"))?;
+            let invalid = || {
+                AppError::localized(ErrorCode::InvalidCredentials, MessageId::InvalidCredentials)
+            };
+
+            // lookup user by subdomain (username), optionally qualified by base domain
+            let (subdomain, base_domain) = app_state
+                .config
+                .split_username(username)
+                .ok_or_else(invalid)?;
+            let subdomain = crate::validation::label_to_ascii(subdomain);
+            let user =
+                crate::db::user_repo::find_by_subdomain(&app_state.db, &subdomain, base_domain)
+                    .await
+                    .map_err(|_| invalid())?
+                    .ok_or_else(invalid)?;
 
             // verify password
             if !crate::auth::verify_password(&user.password_hash, password)
-                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "hash error"))?
+                .map_err(AppError::internal_anyhow)?
             {
-                return Err((StatusCode::UNAUTHORIZED, "invalid credentials"));
+                return Err(invalid());
             }
 
+            tracing::Span::current().record(
+                "user",
+                tracing::field::display(format_args!("{}.{}", user.subdomain, user.base_domain)),
+            );
             Ok(Authenticated(user))
         })
     }
 }
 
+/// Kind of credential a user request authenticated with, recorded as the
+/// provenance of the records it writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Credential {
+    /// The account password, as checked by [`Authenticated`].
+    Password,
+}
+
+impl Credential {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Credential::Password => "password",
+        }
+    }
+}
+
+/// Axum extractor that only admits callers presenting the operator admin token.
+pub struct AdminAuthenticated;
+
+impl<S> FromRequestParts<S> for AdminAuthenticated
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> impl Future<Output = Result<Self, Self::Rejection>> {
+        Box::pin(async move {
+            let Extension(app_state): axum::extract::Extension<SharedState> =
+                Extension::from_request_parts(parts, state)
+                    .await
+                    .map_err(|_| AppError::Internal(anyhow::anyhow!("missing state")))?;
+
+            // admin endpoints are disabled entirely unless a token is configured
+            let expected = app_state
+                .config
+                .admin_token
+                .as_deref()
+                .ok_or_else(AppError::not_found)?;
+
+            let auth_header = parts
+                .headers
+                .get(axum::http::header::AUTHORIZATION)
+                .ok_or_else(|| {
+                    AppError::localized(ErrorCode::Unauthorized, MessageId::MissingAuthorization)
+                })?
+                .to_str()
+                .map_err(|_| {
+                    AppError::localized(ErrorCode::InvalidRequest, MessageId::InvalidAuthorization)
+                })?;
+
+            let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
+                AppError::localized(ErrorCode::Unauthorized, MessageId::ExpectedBearerToken)
+            })?;
+
+            if !bool::from(token.as_bytes().ct_eq(expected.as_bytes())) {
+                return Err(AppError::localized(
+                    ErrorCode::InvalidCredentials,
+                    MessageId::InvalidAdminToken,
+                ));
+            }
+
+            tracing::Span::current().record("user", "admin");
+            Ok(AdminAuthenticated)
+        })
+    }
+}
+
 /// Hash a plaintext password using Argon2 + random salt.
 pub fn hash_password(plain: &str) -> anyhow::Result<String> {
     let salt = SaltString::generate(&mut OsRng);
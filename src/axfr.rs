@@ -0,0 +1,163 @@
+//! Zone transfer client (AXFR, RFC 5936) with optional TSIG authentication
+//! (RFC 8945), for importing a zone from another primary. The transport,
+//! message parsing, and signing are hickory-client's; this module only
+//! checks that the records form the transfer of the zone asked for.
+use anyhow::{Context, anyhow, bail};
+use hickory_client::ClientErrorKind;
+use hickory_client::client::{Client, ClientHandle};
+use hickory_proto::dnssec::rdata::tsig::TsigAlgorithm as HickoryTsigAlgorithm;
+use hickory_proto::dnssec::tsig::TSigner;
+use hickory_proto::op::{MessageFinalizer, ResponseCode};
+use hickory_proto::rr::Name;
+use hickory_proto::runtime::TokioRuntimeProvider;
+use hickory_proto::tcp::TcpClientStream;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+use crate::dns_probe::{self, AnswerRecord};
+
+/// Allowed clock difference between us and the server, in seconds.
+const TSIG_FUDGE: u16 = 300;
+/// Records accepted from one transfer before it is aborted.
+const MAX_RECORDS: usize = 50_000;
+
+/// HMAC algorithms accepted for TSIG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TsigAlgorithm {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// Parse an algorithm name such as `hmac-sha256`, with or without the trailing dot.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim_end_matches('.').to_ascii_lowercase().as_str() {
+            "hmac-sha256" => Some(Self::HmacSha256),
+            "hmac-sha384" => Some(Self::HmacSha384),
+            "hmac-sha512" => Some(Self::HmacSha512),
+            _ => None,
+        }
+    }
+
+    fn hickory(self) -> HickoryTsigAlgorithm {
+        match self {
+            Self::HmacSha256 => HickoryTsigAlgorithm::HmacSha256,
+            Self::HmacSha384 => HickoryTsigAlgorithm::HmacSha384,
+            Self::HmacSha512 => HickoryTsigAlgorithm::HmacSha512,
+        }
+    }
+}
+
+/// Shared secret the primary expects transfers to be signed with.
+#[derive(Clone, Debug)]
+pub struct TsigKey {
+    /// Key name, lowercase with a trailing dot.
+    pub name: String,
+    pub algorithm: TsigAlgorithm,
+    pub secret: Vec<u8>,
+}
+
+impl TsigKey {
+    /// Signer for the query that also verifies every response message.
+    fn signer(&self) -> anyhow::Result<Arc<dyn MessageFinalizer>> {
+        let name = Name::from_ascii(&self.name).context("invalid TSIG key name")?;
+        let signer = TSigner::new(
+            self.secret.clone(),
+            self.algorithm.hickory(),
+            name,
+            TSIG_FUDGE,
+        )
+        .context("unsupported TSIG key")?;
+        Ok(Arc::new(signer))
+    }
+}
+
+/// Transfer `zone` from `server`. Returns every record in transfer order,
+/// starting with the SOA and without the closing copy of it.
+pub async fn transfer(
+    server: SocketAddr,
+    zone: &str,
+    tsig: Option<&TsigKey>,
+    timeout: Duration,
+) -> anyhow::Result<Vec<AnswerRecord>> {
+    tokio::time::timeout(timeout, run(server, zone, tsig, timeout))
+        .await
+        .with_context(|| format!("transfer from {server} timed out"))?
+}
+
+async fn run(
+    server: SocketAddr,
+    zone: &str,
+    tsig: Option<&TsigKey>,
+    timeout: Duration,
+) -> anyhow::Result<Vec<AnswerRecord>> {
+    let origin = Name::from_ascii(zone).with_context(|| format!("invalid zone name {zone}"))?;
+    let signer = tsig.map(TsigKey::signer).transpose()?;
+    let signed = signer.is_some();
+    let (stream, handle) =
+        TcpClientStream::new(server, None, Some(timeout), TokioRuntimeProvider::new());
+    let (mut client, background) = Client::with_timeout(stream, handle, timeout, signer)
+        .await
+        .with_context(|| format!("failed to connect to {server}"))?;
+    let background = tokio::spawn(background);
+    let result = collect(&mut client, origin, zone, signed).await;
+    background.abort();
+    result
+}
+
+async fn collect(
+    client: &mut Client,
+    origin: Name,
+    zone: &str,
+    signed: bool,
+) -> anyhow::Result<Vec<AnswerRecord>> {
+    let zone = zone.to_ascii_lowercase();
+    let mut responses = client.zone_transfer(origin, None);
+    let mut records: Vec<AnswerRecord> = Vec::new();
+    while let Some(response) = responses.next().await {
+        let response = response.map_err(|err| {
+            let reason = match err.kind() {
+                // hickory-client's own message for these is just "proto error"
+                ClientErrorKind::Proto(proto) => proto.to_string(),
+                _ => err.to_string(),
+            };
+            if signed {
+                // answers failing TSIG verification end up here too
+                anyhow!("transfer failed: {reason}; check the TSIG key")
+            } else {
+                anyhow!("transfer failed: {reason}")
+            }
+        })?;
+        match response.response_code() {
+            ResponseCode::NoError => {}
+            code => bail!("server answered {}", rcode_name(code)),
+        }
+        for record in response.answers() {
+            let record = AnswerRecord::from(record);
+            let is_soa = record.rrtype == "SOA";
+            if records.is_empty() && (!is_soa || record.name != zone) {
+                bail!("transfer does not start with the SOA of {zone}");
+            }
+            if is_soa && !records.is_empty() {
+                // the SOA again closes the transfer
+                return Ok(records);
+            }
+            if records.len() == MAX_RECORDS {
+                bail!("zone has more than {MAX_RECORDS} records");
+            }
+            records.push(record);
+        }
+    }
+    bail!("server closed the connection before the transfer ended")
+}
+
+fn rcode_name(rcode: ResponseCode) -> String {
+    match rcode {
+        ResponseCode::Refused => "REFUSED (transfers not allowed from this address?)".into(),
+        ResponseCode::NotAuth => "NOTAUTH (the zone or the TSIG key is not known)".into(),
+        other => dns_probe::rcode_name(other),
+    }
+}
@@ -1,40 +1,252 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{Context, Result, bail};
+use arc_swap::ArcSwap;
 use axum::{
     Router,
     body::Body,
-    extract::OriginalUri,
+    extract::{OriginalUri, State},
     http::{Method, Response, StatusCode, header},
     response::IntoResponse,
     routing::get,
 };
-use clap::Parser;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use ipnet::IpNet;
+#[cfg(feature = "embedded-frontend")]
 use rust_embed::RustEmbed;
+#[cfg(feature = "acme")]
+use satsuki::acme;
+#[cfg(feature = "tls")]
+use satsuki::tls;
 use satsuki::{
-    AppState, SharedState, api, config::AppConfig, db, powerdns::client::PowerDnsClient,
+    AppState, PdnsBackend, SharedState, abuse, alerts,
+    api::{self, Surface},
+    config::{
+        AppConfig, BackendsConfig, BodyLimits, Compression, ConcurrencyLimits, DEFAULT_NS_TTL,
+        DEFAULT_SOA_TTL, MailConfig, MailTransport, PdnsEndpointConfig, PolicyConfig, SmtpConfig,
+        SoaTimers,
+    },
+    dangling, db, delegations,
+    events::EventBus,
+    expiry,
+    geoip::GeoIp,
+    health::{self, CheckReport},
+    history, jobs, mail, notifications, ns_health,
+    powerdns::{client::PowerDnsClient, latency::Latency as PdnsLatency},
+    provision, reconcile, retention, serial_check,
+    shutdown::{self, Shutdown},
+    signup_saga, snapshots, stats, systemd,
+    validation::is_email_address,
+    webhooks,
 };
-use tokio::{net::TcpListener, signal};
-use tracing::{error, info};
+use std::future::IntoFuture;
+use tokio::net::TcpListener;
+use tower_http::CompressionLevel;
+use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, rename_all = "kebab-case")]
+#[command(author, version, about)]
 struct Cli {
-    /// Base domain (e.g. example.com)
-    #[arg(long, value_name = "DOMAIN")]
-    base_domain: String,
+    #[command(subcommand)]
+    command: Command,
+    /// Log output format
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "FORMAT",
+        env = "SATSUKI_LOG_FORMAT",
+        default_value_t = LogFormat::Text
+    )]
+    log_format: LogFormat,
+}
+
+/// What `--dry-run` simulates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DryRun {
+    /// PowerDNS writes only; the database is updated as usual
+    Pdns,
+    /// PowerDNS writes, and database writes land in a throwaway copy
+    All,
+}
+
+/// How log lines are written to stderr.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, with the enclosing spans' fields
+    Json,
+}
+
+/// A response encoding for `--compression`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Encoding {
+    Gzip,
+    Br,
+    Zstd,
+    /// No compression at all
+    None,
+}
+
+/// `--compression-level`: a named level or a number.
+fn parse_compression_level(value: &str) -> Result<CompressionLevel, String> {
+    match value {
+        "fastest" => Ok(CompressionLevel::Fastest),
+        "default" => Ok(CompressionLevel::Default),
+        "best" => Ok(CompressionLevel::Best),
+        number => number
+            .parse()
+            .map(CompressionLevel::Precise)
+            .map_err(|_| format!("expected fastest, default, best, or a number, not {number:?}")),
+    }
+}
+
+/// One `--listen` value: an address and how it is served.
+#[derive(Clone, Copy, Debug)]
+struct ListenSpec {
+    addr: SocketAddr,
+    /// Endpoints served; by default everything, or only the public ones
+    /// when another listener serves the internal ones.
+    surface: Option<Surface>,
+    /// HTTPS or plain HTTP; by default HTTPS when a certificate is configured.
+    tls: Option<bool>,
+}
+
+impl std::str::FromStr for ListenSpec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.split(',');
+        let addr = parts.next().unwrap_or_default().trim();
+        let addr = addr
+            .parse()
+            .map_err(|err| format!("invalid address {addr:?}: {err}"))?;
+        let mut spec = ListenSpec {
+            addr,
+            surface: None,
+            tls: None,
+        };
+        for option in parts {
+            let (surface, tls) = match option.trim() {
+                "all" => (Some(Surface::All), None),
+                "public" => (Some(Surface::Public), None),
+                "internal" => (Some(Surface::Internal), None),
+                "tls" => (None, Some(true)),
+                "http" => (None, Some(false)),
+                other => {
+                    return Err(format!(
+                        "unknown listener option {other:?}; use all, public, internal, tls, or http"
+                    ));
+                }
+            };
+            if surface.is_some() && spec.surface.is_some() || tls.is_some() && spec.tls.is_some() {
+                return Err(format!("conflicting listener options in {value:?}"));
+            }
+            spec.surface = spec.surface.or(surface);
+            spec.tls = spec.tls.or(tls);
+        }
+        Ok(spec)
+    }
+}
+
+/// `--ns-address`: `NAME=ADDR`, an internal nameserver and one of its addresses.
+fn parse_ns_address(value: &str) -> Result<(String, IpAddr), String> {
+    let (name, addr) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=ADDR, not {value:?}"))?;
+    let name = name.trim().trim_end_matches('.').to_ascii_lowercase();
+    if name.is_empty() {
+        return Err(format!("missing nameserver name in {value:?}"));
+    }
+    let addr = addr
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid address in {value:?}"))?;
+    Ok((format!("{name}."), addr))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the HTTP server (the default when no subcommand is given)
+    Serve(Box<ServeArgs>),
+    /// Apply pending database migrations and exit
+    Migrate(MigrateArgs),
+    /// Compare users with the parent zones and sub-PDNS once and report drift
+    Reconcile {
+        #[command(flatten)]
+        args: Box<ServeArgs>,
+        /// Recreate missing zones and delegations; orphans are only reported
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Create the parent zones on the base PowerDNS with SOA, apex NS, and glue
+    Init {
+        #[command(flatten)]
+        args: Box<ServeArgs>,
+        /// Glue address of an internal nameserver inside a parent zone, as NAME=ADDR (repeatable)
+        #[arg(long, value_name = "NAME=ADDR", value_parser = parse_ns_address)]
+        ns_address: Vec<(String, IpAddr)>,
+    },
+    /// Write every user zone to a BIND-format file
+    Export {
+        #[command(flatten)]
+        args: Box<ServeArgs>,
+        /// Directory receiving one `<zone>zone` file per user
+        #[arg(long, value_name = "DIR")]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(Args, Debug)]
+struct MigrateArgs {
     /// Path to the SQLite database file
     #[arg(long, value_name = "PATH")]
     db_path: PathBuf,
-    /// Listen address for the HTTP server
-    #[arg(long, value_name = "ADDR", default_value = "0.0.0.0:8080")]
-    listen: SocketAddr,
+}
+
+/// Server options; `reconcile` and `export` accept the same set, so one
+/// environment file or unit drop-in serves every subcommand.
+#[derive(Args, Debug)]
+#[command(rename_all = "kebab-case")]
+struct ServeArgs {
+    /// Base domain (e.g. example.com); repeat to serve several, the first is the default
+    #[arg(long = "base-domain", value_name = "DOMAIN", required = true)]
+    base_domain: Vec<String>,
+    /// Path to the SQLite database file
+    #[arg(long, value_name = "PATH")]
+    db_path: PathBuf,
+    /// Listen address, optionally followed by ",public", ",internal", or ",all" and ",tls" or ",http" (repeat for multiple)
+    #[arg(long, value_name = "ADDR[,OPTION...]", default_value = "0.0.0.0:8080")]
+    listen: Vec<ListenSpec>,
+    /// Serve probes, metrics, and admin endpoints only here (plain HTTP); same as --listen ADDR,internal,http
+    #[arg(long, value_name = "ADDR")]
+    internal_listen: Option<SocketAddr>,
     /// Base PowerDNS API URL
     #[arg(long, value_name = "URL")]
     base_pdns_url: String,
     /// Base PowerDNS API key
-    #[arg(long, value_name = "KEY")]
-    base_pdns_key: String,
+    #[arg(
+        long,
+        value_name = "KEY",
+        env = "SATSUKI_BASE_PDNS_KEY",
+        hide_env_values = true
+    )]
+    base_pdns_key: Option<String>,
+    /// File containing the base PowerDNS API key (e.g. a mounted secret)
+    #[arg(
+        long,
+        value_name = "PATH",
+        env = "SATSUKI_BASE_PDNS_KEY_FILE",
+        conflicts_with = "base_pdns_key"
+    )]
+    base_pdns_key_file: Option<PathBuf>,
     /// Base PowerDNS server ID
     #[arg(long, value_name = "ID", default_value = "localhost")]
     base_pdns_server_id: String,
@@ -42,11 +254,33 @@ struct Cli {
     #[arg(long, value_name = "URL")]
     sub_pdns_url: String,
     /// Subdomain PowerDNS API key
-    #[arg(long, value_name = "KEY")]
-    sub_pdns_key: String,
+    #[arg(
+        long,
+        value_name = "KEY",
+        env = "SATSUKI_SUB_PDNS_KEY",
+        hide_env_values = true
+    )]
+    sub_pdns_key: Option<String>,
+    /// File containing the subdomain PowerDNS API key
+    #[arg(
+        long,
+        value_name = "PATH",
+        env = "SATSUKI_SUB_PDNS_KEY_FILE",
+        conflicts_with = "sub_pdns_key"
+    )]
+    sub_pdns_key_file: Option<PathBuf>,
     /// Subdomain PowerDNS server ID
     #[arg(long, value_name = "ID", default_value = "localhost")]
     sub_pdns_server_id: String,
+    /// Log PowerDNS API calls slower than this at WARN (0 disables)
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pdns_slow_ms: u64,
+    /// Failed PowerDNS calls in a row after which calls to that endpoint fail at once (0 disables)
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pdns_breaker_failures: u32,
+    /// Seconds the PowerDNS circuit stays open before a trial call
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    pdns_breaker_cooldown: u64,
     /// Internal nameserver FQDN (repeat for multiple values)
     #[arg(long = "internal-ns", value_name = "FQDN", required = true)]
     internal_ns: Vec<String>,
@@ -56,70 +290,891 @@ struct Cli {
     /// Override for SOA rname/contact (defaults to hostmaster.<base-domain>.)
     #[arg(long, value_name = "FQDN")]
     internal_contact: Option<String>,
+    /// SOA refresh interval in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = SoaTimers::default().refresh)]
+    soa_refresh: u32,
+    /// SOA retry interval in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = SoaTimers::default().retry)]
+    soa_retry: u32,
+    /// SOA expire interval in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = SoaTimers::default().expire)]
+    soa_expire: u32,
+    /// SOA minimum (negative-caching TTL) in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = SoaTimers::default().minimum)]
+    soa_minimum: u32,
+    /// TTL of the SOA RRset in user zones
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_SOA_TTL)]
+    soa_ttl: u32,
+    /// TTL of apex and delegation NS RRsets
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_NS_TTL)]
+    ns_ttl: u32,
     /// Additional reserved subdomain labels
     #[arg(long = "disallow-subdomain", value_name = "LABEL")]
     disallow_subdomain: Vec<String>,
+    /// TOML file with reloadable policy settings (re-read on SIGHUP)
+    #[arg(long, value_name = "PATH")]
+    policy_file: Option<PathBuf>,
+    /// File of reserved labels and `^regex` patterns, one per line (re-read on SIGHUP)
+    #[arg(long, value_name = "PATH")]
+    reserved_labels_file: Option<PathBuf>,
+    /// TOML file mapping base domains to their own base/sub PowerDNS endpoints
+    #[arg(long, value_name = "PATH")]
+    pdns_backends_file: Option<PathBuf>,
+    /// Origin allowed to call the API cross-site (repeat for multiple; default: any)
+    #[arg(long = "cors-origin", value_name = "ORIGIN")]
+    cors_origin: Vec<String>,
+    /// Allow credentialed CORS requests (requires --cors-origin)
+    #[arg(long)]
+    cors_allow_credentials: bool,
+    /// Reverse proxy address or CIDR whose forwarding headers are trusted (repeat for multiple)
+    #[arg(long = "trusted-proxy", value_name = "CIDR")]
+    trusted_proxy: Vec<String>,
+    /// Body size limit for /api/signup and /api/signin
+    #[arg(long, value_name = "BYTES", default_value_t = BodyLimits::default().auth)]
+    auth_body_limit: usize,
+    /// Body size limit for bulk zone writes (PUT /api/zone)
+    #[arg(long, value_name = "BYTES", default_value_t = BodyLimits::default().zone)]
+    zone_body_limit: usize,
+    /// Body size limit for every other route
+    #[arg(long, value_name = "BYTES", default_value_t = BodyLimits::default().default)]
+    body_limit: usize,
+    /// Requests handled at once before the API answers 503 (0 disables)
+    #[arg(long, value_name = "N", default_value_t = ConcurrencyLimits::default().max_in_flight)]
+    max_in_flight: usize,
+    /// Concurrent /api/signup and /api/signin requests before 429 (0 disables)
+    #[arg(long, value_name = "N", default_value_t = ConcurrencyLimits::default().auth)]
+    auth_concurrency: usize,
+    /// Concurrent /api/zone requests before 429 (0 disables)
+    #[arg(long, value_name = "N", default_value_t = ConcurrencyLimits::default().zone)]
+    zone_concurrency: usize,
+    /// Response encodings to offer, comma-separated; "none" disables compression
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "gzip,br,zstd"
+    )]
+    compression: Vec<Encoding>,
+    /// Compression level: fastest, default, best, or a number on each encoding's own scale
+    #[arg(long, value_name = "LEVEL", default_value = "default", value_parser = parse_compression_level)]
+    compression_level: CompressionLevel,
+    /// Responses smaller than this many bytes are sent uncompressed
+    #[arg(long, value_name = "BYTES", default_value_t = Compression::default().min_size)]
+    compression_min_size: u16,
+    /// Answer API requests with 503 until the database and PowerDNS pass a first check
+    #[arg(long)]
+    hold_api_until_ready: bool,
+    /// Bearer token enabling the /api/admin endpoints
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "SATSUKI_ADMIN_TOKEN",
+        hide_env_values = true
+    )]
+    admin_token: Option<String>,
+    /// File containing the admin token
+    #[arg(
+        long,
+        value_name = "PATH",
+        env = "SATSUKI_ADMIN_TOKEN_FILE",
+        conflicts_with = "admin_token"
+    )]
+    admin_token_file: Option<PathBuf>,
+    /// SMTP server for outgoing mail: smtp://[user@]host[:port] (STARTTLS),
+    /// smtps:// (TLS), or smtp+insecure:// (local relays); mail is only logged without it
+    #[arg(long, value_name = "URL", env = "SATSUKI_SMTP_URL")]
+    smtp_url: Option<String>,
+    /// Password for the SMTP user
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        env = "SATSUKI_SMTP_PASSWORD",
+        hide_env_values = true
+    )]
+    smtp_password: Option<String>,
+    /// File containing the SMTP password
+    #[arg(
+        long,
+        value_name = "PATH",
+        env = "SATSUKI_SMTP_PASSWORD_FILE",
+        conflicts_with = "smtp_password"
+    )]
+    smtp_password_file: Option<PathBuf>,
+    /// Sender address of outgoing mail (default: noreply@<first base domain>)
+    #[arg(long, value_name = "ADDRESS")]
+    mail_from: Option<String>,
+    /// MaxMind-format database (.mmdb) for country/ASN annotations of sign-ins (repeat for multiple)
+    #[arg(long = "geoip-db", value_name = "PATH")]
+    geoip_db: Vec<PathBuf>,
+    /// PEM certificate chain; switches listeners not marked ",http" to HTTPS (HTTP/1.1 and h2)
+    #[cfg(feature = "tls")]
+    #[arg(long, value_name = "PATH", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key for --tls-cert; both files are reloaded when they change
+    #[cfg(feature = "tls")]
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Obtain the listeners' certificate via ACME DNS-01 for this name (repeat for SANs)
+    #[cfg(feature = "acme")]
+    #[arg(long = "acme-domain", value_name = "FQDN", conflicts_with_all = ["tls_cert", "tls_key"])]
+    acme_domain: Vec<String>,
+    /// ACME account contact, e.g. mailto:hostmaster@example.com (repeat for multiple)
+    #[cfg(feature = "acme")]
+    #[arg(long = "acme-contact", value_name = "URI")]
+    acme_contact: Vec<String>,
+    /// ACME directory URL of the CA
+    #[cfg(feature = "acme")]
+    #[arg(long, value_name = "URL", default_value = acme::LETS_ENCRYPT_DIRECTORY)]
+    acme_directory: String,
+    /// Directory for the ACME account, certificate, and key (default: next to the database)
+    #[cfg(feature = "acme")]
+    #[arg(long, value_name = "PATH")]
+    acme_dir: Option<PathBuf>,
+    /// Renew the ACME certificate once it is this many days old
+    #[cfg(feature = "acme")]
+    #[arg(long, value_name = "DAYS", default_value_t = 60)]
+    acme_renew_days: u64,
+    /// Agree to the CA's terms of service (required with --acme-domain)
+    #[cfg(feature = "acme")]
+    #[arg(long)]
+    acme_accept_tos: bool,
+    /// Serve the web UI from this directory instead of the embedded bundle
+    #[arg(long, value_name = "PATH")]
+    frontend_dir: Option<PathBuf>,
+    /// Seconds to wait for in-flight requests and background jobs on SIGTERM
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    shutdown_timeout: u64,
+    /// Log PowerDNS writes instead of sending them; `all` also writes to a scratch copy of the database
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SCOPE",
+        num_args = 0..=1,
+        default_missing_value = "pdns"
+    )]
+    dry_run: Option<DryRun>,
+    /// Create parent zones missing from the base PowerDNS at startup
+    #[arg(long)]
+    bootstrap_parent_zone: bool,
+    /// Refuse to start with pending migrations instead of applying them
+    #[arg(long)]
+    no_migrate: bool,
+    /// Validate configuration and dependencies, print a report, and exit
+    #[arg(long)]
+    check_config: bool,
 }
 
+#[cfg(feature = "embedded-frontend")]
 #[derive(RustEmbed)]
 #[folder = "dist"]
 struct EmbeddedDist;
 
+/// Where the web UI's files come from.
+enum FrontendSource {
+    #[cfg(feature = "embedded-frontend")]
+    Embedded,
+    /// Canonicalized `--frontend-dir`.
+    Directory(PathBuf),
+}
+
+impl FrontendSource {
+    /// `None` when there is no UI to serve: no `--frontend-dir` in a build
+    /// without the `embedded-frontend` feature.
+    fn from_cli(cli: &ServeArgs) -> Result<Option<Self>> {
+        let Some(dir) = &cli.frontend_dir else {
+            #[cfg(feature = "embedded-frontend")]
+            return Ok(Some(FrontendSource::Embedded));
+            #[cfg(not(feature = "embedded-frontend"))]
+            return Ok(None);
+        };
+        let root = dir
+            .canonicalize()
+            .with_context(|| format!("failed to open frontend directory {}", dir.display()))?;
+        if !root.is_dir() {
+            bail!("--frontend-dir {} is not a directory", dir.display());
+        }
+        if !root.join("index.html").is_file() {
+            bail!("--frontend-dir {} has no index.html", dir.display());
+        }
+        Ok(Some(FrontendSource::Directory(root)))
+    }
+
+    /// Contents of a file relative to the UI root.
+    async fn read(&self, path: &str) -> Option<Vec<u8>> {
+        match self {
+            #[cfg(feature = "embedded-frontend")]
+            FrontendSource::Embedded => {
+                EmbeddedDist::get(path).map(|asset| asset.data.into_owned())
+            }
+            FrontendSource::Directory(root) => {
+                let file = root.join(path).canonicalize().ok()?;
+                // symlinks must not lead outside the directory
+                if !file.starts_with(root) || !file.is_file() {
+                    return None;
+                }
+                tokio::fs::read(file).await.ok()
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    init_tracing();
+    let cli = parse_cli();
+    init_tracing(cli.log_format);
 
-    let cli = Cli::parse();
+    match cli.command {
+        Command::Serve(args) => serve(*args).await,
+        Command::Migrate(args) => migrate(&args).await,
+        Command::Reconcile { args, repair } => reconcile_once(&args, repair).await,
+        Command::Init { args, ns_address } => init(&args, &ns_address).await,
+        Command::Export { args, out_dir } => export(&args, &out_dir).await,
+    }
+}
+
+/// Parse the command line; arguments without a leading subcommand are
+/// taken as `serve`'s, so invocations from before subcommands keep working.
+fn parse_cli() -> Cli {
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let explicit = args.get(1).and_then(|arg| arg.to_str()).is_none_or(|arg| {
+        matches!(arg, "help" | "-h" | "--help" | "-V" | "--version")
+            || Cli::command().find_subcommand(arg).is_some()
+    });
+    if !explicit {
+        args.insert(1, "serve".into());
+    }
+    Cli::parse_from(args)
+}
+
+async fn serve(cli: ServeArgs) -> Result<()> {
     let config = build_app_config(&cli)?;
-    let state = init_shared_state(&cli, config).await?;
 
-    let spa_routes = get(frontend_handler).head(frontend_handler);
-    let app = Router::new()
-        .merge(api::create_router(state))
-        .route("/", spa_routes.clone())
-        .route("/{*path}", spa_routes);
+    if cli.check_config {
+        let report = check_config(&cli, &config).await;
+        for check in &report.checks {
+            let status = if check.ok { "ok" } else { "FAIL" };
+            println!("[{status}] {}: {}", check.name, check.detail);
+        }
+        if !report.ok() {
+            eprintln!("configuration check failed");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let listener = TcpListener::bind(cli.listen)
-        .await
-        .with_context(|| format!("failed to bind to {}", cli.listen))?;
+    let frontend = FrontendSource::from_cli(&cli)?.map(Arc::new);
+    match frontend.as_deref() {
+        Some(FrontendSource::Directory(root)) => {
+            info!("serving the web UI from {}", root.display());
+        }
+        #[cfg(feature = "embedded-frontend")]
+        Some(FrontendSource::Embedded) => {}
+        None => info!(
+            "built without the embedded web UI and no --frontend-dir given; serving the API only"
+        ),
+    }
+    let plan = plan_listeners(&cli)?;
+    // bound before migrating, so connections queue instead of being refused;
+    // /readyz fails until the startup checks pass
+    let mut activated = systemd::take_listeners().context("invalid socket from systemd")?;
+    if activated.len() > cli.listen.len() {
+        warn!(
+            "systemd passed {} sockets for {} --listen addresses, the rest are unused",
+            activated.len(),
+            cli.listen.len()
+        );
+    }
+    activated.truncate(cli.listen.len());
+    let mut activated = activated.into_iter();
+    let listeners = plan
+        .into_iter()
+        // --internal-listen comes last and is never socket-activated
+        .map(|spec| Ok((spec, bind_listener(spec.addr, activated.next())?)))
+        .collect::<Result<Vec<_>>>()?;
+    let state = init_shared_state(&cli, config, !cli.no_migrate).await?;
+    let shutdown = Shutdown::on_signal(Duration::from_secs(cli.shutdown_timeout));
+    #[cfg(feature = "acme")]
+    let acme_files = init_acme(&cli, &state).await?;
+    health::verify_parent_zones(&state, cli.bootstrap_parent_zone).await;
+    health::spawn_startup_checks(state.clone());
+    spawn_reload_on_sighup(state.clone());
+    webhooks::spawn_dispatcher(state.clone());
+    history::spawn_recorder(state.clone());
+    mail::spawn_sender(state.clone());
+    notifications::spawn_dispatcher(state.clone());
+    alerts::spawn_pdns_monitor(state.clone());
+    stats::spawn_refresher(state.clone());
+    delegations::spawn_startup_refresh(state.clone());
+    jobs::spawn(state.clone(), reconcile::ReconcileJob);
+    jobs::spawn(state.clone(), ns_health::NsHealthJob);
+    jobs::spawn(state.clone(), serial_check::SerialCheckJob);
+    jobs::spawn(state.clone(), expiry::ExpiryJob);
+    jobs::spawn(state.clone(), dangling::DanglingJob);
+    jobs::spawn(state.clone(), snapshots::SnapshotJob);
+    jobs::spawn(state.clone(), retention::RetentionJob);
+    jobs::spawn(state.clone(), abuse::AbuseScanJob);
+    jobs::spawn(state.clone(), signup_saga::SignupRecoveryJob);
+
+    let spa = match frontend {
+        Some(frontend) => {
+            let spa_routes = get(frontend_handler).head(frontend_handler);
+            Router::new()
+                .route("/", spa_routes.clone())
+                .route("/{*path}", spa_routes)
+                .with_state(frontend)
+        }
+        None => Router::new(),
+    };
+    #[cfg(feature = "tls")]
+    let tls_config = {
+        #[cfg(feature = "acme")]
+        let files = acme_files.or_else(|| certificate_files(&cli));
+        #[cfg(not(feature = "acme"))]
+        let files = certificate_files(&cli);
+        match files {
+            Some(files) => {
+                let config = files.load().await?;
+                files.spawn_reloader(config.clone());
+                Some(config)
+            }
+            None => None,
+        }
+    };
+
+    let mut routers: HashMap<Surface, Router> = HashMap::new();
+    let mut servers = tokio::task::JoinSet::new();
+    for (spec, listener) in listeners {
+        let surface = spec.surface.unwrap_or(Surface::All);
+        let app = routers
+            .entry(surface)
+            .or_insert_with(|| {
+                let api = api::create_router_for(state.clone(), surface);
+                let app = match surface {
+                    Surface::Internal => api,
+                    Surface::All | Surface::Public => api.merge(spa.clone()),
+                };
+                api::compression::apply(app, &state.config.compression)
+            })
+            .clone();
+        let endpoints = match surface {
+            Surface::All => "all endpoints",
+            Surface::Public => "public endpoints",
+            Surface::Internal => "internal endpoints",
+        };
+        #[cfg(feature = "tls")]
+        if let (Some(true), Some(config)) = (spec.tls, &tls_config) {
+            info!(
+                "listening on https://{} ({endpoints})",
+                listener.local_addr()?
+            );
+            servers.spawn(serve_tls(listener, config.clone(), app, shutdown.clone()));
+            continue;
+        }
+        info!(
+            "listening on http://{} ({endpoints})",
+            listener.local_addr()?
+        );
+        servers.spawn(serve_http(listener, app, shutdown.clone()));
+    }
+    systemd::spawn_watchdog();
+    systemd::notify("READY=1");
 
-    info!("listening on http://{}", listener.local_addr()?);
+    while let Some(served) = servers.join_next().await {
+        served.context("listener task panicked")??;
+    }
+    finish(&state, &shutdown).await
+}
 
-    axum::serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
+/// The certificate given with `--tls-cert` and `--tls-key`, if any.
+#[cfg(feature = "tls")]
+fn certificate_files(cli: &ServeArgs) -> Option<tls::TlsFiles> {
+    let (cert, key) = (cli.tls_cert.clone()?, cli.tls_key.clone()?);
+    Some(tls::TlsFiles { cert, key })
+}
+
+/// Every listener to open, `--internal-listen` last, with the surface and
+/// TLS defaults filled in.
+fn plan_listeners(cli: &ServeArgs) -> Result<Vec<ListenSpec>> {
+    #[cfg(feature = "acme")]
+    let have_certificate = cli.tls_cert.is_some() || !cli.acme_domain.is_empty();
+    #[cfg(all(feature = "tls", not(feature = "acme")))]
+    let have_certificate = cli.tls_cert.is_some();
+    #[cfg(not(feature = "tls"))]
+    let have_certificate = false;
+    let mut plan = cli.listen.clone();
+    if let Some(addr) = cli.internal_listen {
+        plan.push(ListenSpec {
+            addr,
+            surface: Some(Surface::Internal),
+            tls: Some(false),
+        });
+    }
+    let split = plan
+        .iter()
+        .any(|spec| spec.surface == Some(Surface::Internal));
+    for spec in &mut plan {
+        spec.surface
+            .get_or_insert(if split { Surface::Public } else { Surface::All });
+        if *spec.tls.get_or_insert(have_certificate) && !have_certificate {
+            bail!(
+                "--listen {} asks for TLS, but no certificate is configured (--tls-cert or --acme-domain)",
+                spec.addr
+            );
+        }
+    }
+    Ok(plan)
+}
+
+/// Serve plain HTTP until shutdown; connections still open at the
+/// deadline are closed.
+async fn serve_http(
+    listener: std::net::TcpListener,
+    app: Router,
+    shutdown: Shutdown,
+) -> Result<()> {
+    let listener = TcpListener::from_std(listener)?;
+    let addr = listener.local_addr()?;
+    let stop = shutdown.clone();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        stop.requested().await;
+    });
+    tokio::select! {
+        result = server.into_future() => result.with_context(|| format!("server on {addr} exited with error"))?,
+        _ = shutdown.expired() => warn!("requests on {addr} still running at the shutdown deadline, closing them"),
+    }
+    Ok(())
+}
+
+/// Once the listener has stopped, wait for background work up to the deadline.
+async fn finish(state: &SharedState, shutdown: &Shutdown) -> Result<()> {
+    let deadline = shutdown.requested().await;
+    if shutdown::drain(&state.in_flight, deadline).await {
+        info!("in-flight work finished, exiting");
+    }
+    Ok(())
+}
+
+/// Make sure an ACME certificate exists and keep renewing it, if configured.
+#[cfg(feature = "acme")]
+async fn init_acme(cli: &ServeArgs, state: &SharedState) -> Result<Option<tls::TlsFiles>> {
+    if cli.acme_domain.is_empty() {
+        return Ok(None);
+    }
+    if !cli.acme_accept_tos {
+        bail!("--acme-domain requires --acme-accept-tos to agree to the CA's terms of service");
+    }
+    let storage_dir = match &cli.acme_dir {
+        Some(dir) => dir.clone(),
+        None => cli
+            .db_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("acme"),
+    };
+    let mut settings = acme::AcmeSettings {
+        domains: cli.acme_domain.clone(),
+        directory_url: cli.acme_directory.clone(),
+        contact: cli.acme_contact.clone(),
+        storage_dir,
+        renew_after: Duration::from_secs(cli.acme_renew_days * 86_400),
+    };
+    settings.validate(state)?;
+    settings.ensure_certificate(state).await?;
+    let files = settings.tls_files();
+    settings.spawn_renewer(state.clone());
+    Ok(Some(files))
+}
+
+/// The socket passed by systemd socket activation, or a fresh one on `listen`.
+fn bind_listener(
+    listen: SocketAddr,
+    activated: Option<std::net::TcpListener>,
+) -> Result<std::net::TcpListener> {
+    if let Some(listener) = activated {
+        info!("using the listening socket passed by systemd for {listen}");
+        return Ok(listener);
+    }
+    let listener = std::net::TcpListener::bind(listen)
+        .with_context(|| format!("failed to bind to {listen}"))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Serve HTTPS with rustls; the certificate is hot-reloaded through `config`.
+#[cfg(feature = "tls")]
+async fn serve_tls(
+    listener: std::net::TcpListener,
+    config: axum_server::tls_rustls::RustlsConfig,
+    app: Router,
+    shutdown: Shutdown,
+) -> Result<()> {
+    let addr = listener.local_addr()?;
+    let handle = axum_server::Handle::new();
+    let stop = handle.clone();
+    tokio::spawn(async move {
+        let deadline = shutdown.requested().await;
+        // connections still open at the deadline are closed
+        stop.graceful_shutdown(Some(
+            deadline.saturating_duration_since(tokio::time::Instant::now()),
+        ));
+    });
+
+    axum_server::from_tcp_rustls(listener, config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
-        .context("server exited with error")?;
+        .with_context(|| format!("server on {addr} exited with error"))
+}
+
+/// `migrate`: bring the database schema up to date.
+async fn migrate(args: &MigrateArgs) -> Result<()> {
+    create_db_dir(&args.db_path)?;
+    let db = db::connect(&args.db_path).await?;
+    let status = db::migration_status(&db).await?;
+    if !status.unknown.is_empty() {
+        bail!(
+            "database has migrations unknown to this binary: {:?}",
+            status.unknown
+        );
+    }
+    if status.pending.is_empty() {
+        println!("database is up to date");
+        return Ok(());
+    }
+    db::MIGRATOR.run(&db).await.context("migration failed")?;
+    for version in &status.pending {
+        println!("applied migration {version}");
+    }
+    db.close().await;
+    Ok(())
+}
 
+/// `reconcile`: print drift between the database and PowerDNS, optionally
+/// repairing it; exits non-zero while unrepaired drift remains.
+async fn reconcile_once(cli: &ServeArgs, repair: bool) -> Result<()> {
+    let config = build_app_config(cli)?;
+    let state = init_shared_state(cli, config, false).await?;
+    let report = reconcile::scan(&state).await?;
+
+    let mut remaining = 0;
+    for drift in &report.drift {
+        let description = serde_json::to_string(&drift.kind)?;
+        if repair && drift.kind.repairable() {
+            match reconcile::repair(&state, drift).await {
+                Ok(()) => {
+                    println!("[repaired] {}: {description}", drift.zone);
+                    continue;
+                }
+                Err(err) => println!("[FAIL] {}: {description}: {err:#}", drift.zone),
+            }
+        } else {
+            println!("[drift] {}: {description}", drift.zone);
+        }
+        remaining += 1;
+    }
+    println!(
+        "checked {} users, {} drift(s), {remaining} remaining",
+        report.users_checked,
+        report.drift.len()
+    );
+    if remaining > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `init`: provision the parent zones, applying migrations on the way;
+/// exits non-zero if any step failed.
+async fn init(cli: &ServeArgs, glue: &[(String, IpAddr)]) -> Result<()> {
+    let config = build_app_config(cli)?;
+    let state = init_shared_state(cli, config, true).await?;
+    let report = provision::init(&state, glue).await;
+    for check in &report.checks {
+        let status = if check.ok { "ok" } else { "FAIL" };
+        println!("[{status}] {}: {}", check.name, check.detail);
+    }
+    if !report.ok() {
+        eprintln!("initialization incomplete");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `export`: write each user zone as `<out_dir>/<zone>zone`.
+async fn export(cli: &ServeArgs, out_dir: &Path) -> Result<()> {
+    let config = build_app_config(cli)?;
+    let state = init_shared_state(cli, config, false).await?;
+    let export = snapshots::export_all(&state, out_dir).await?;
+    for (zone, err) in &export.failed {
+        eprintln!("failed to export {zone}: {err}");
+    }
+    println!(
+        "exported {} of {} zones to {}",
+        export.exported,
+        export.exported + export.failed.len(),
+        out_dir.display()
+    );
+    if !export.failed.is_empty() {
+        std::process::exit(1);
+    }
     Ok(())
 }
 
-async fn init_shared_state(cli: &Cli, config: AppConfig) -> Result<SharedState> {
-    if let Some(parent) = cli.db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+fn create_db_dir(db_path: &Path) -> Result<()> {
+    if let Some(parent) = db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
         std::fs::create_dir_all(parent)
             .with_context(|| format!("failed to create db directory {}", parent.display()))?;
     }
+    Ok(())
+}
 
-    let db = db::init_db(&cli.db_path).await?;
-    let base_pdns = PowerDnsClient::new(
-        &cli.base_pdns_url,
-        &cli.base_pdns_key,
-        &cli.base_pdns_server_id,
-    );
-    let sub_pdns = PowerDnsClient::new(
-        &cli.sub_pdns_url,
-        &cli.sub_pdns_key,
-        &cli.sub_pdns_server_id,
-    );
+/// Open the database and assemble the shared state; with `migrate` unset,
+/// pending migrations are an error pointing at the `migrate` subcommand.
+async fn init_shared_state(
+    cli: &ServeArgs,
+    config: AppConfig,
+    migrate: bool,
+) -> Result<SharedState> {
+    create_db_dir(&cli.db_path)?;
+
+    let policy = PolicyConfig::from_sources(&config)?;
+
+    let db = if cli.dry_run == Some(DryRun::All) {
+        let (db, scratch) = db::scratch_copy(&cli.db_path).await?;
+        warn!(
+            "dry run: database writes go to a scratch copy at {}",
+            scratch.display()
+        );
+        db
+    } else {
+        db::connect(&cli.db_path).await?
+    };
+    if migrate {
+        db::MIGRATOR.run(&db).await.context("migration failed")?;
+    } else {
+        let pending = db::migration_status(&db).await?.pending;
+        if !pending.is_empty() {
+            bail!(
+                "{} pending migration(s); run `satsuki-pdns-frontend migrate --db-path {}` first",
+                pending.len(),
+                cli.db_path.display()
+            );
+        }
+    }
+    if cli.dry_run.is_some() {
+        warn!("dry run: PowerDNS writes are logged, not sent");
+    }
+    let pdns_latency = Arc::new(PdnsLatency::new(cli.pdns_slow_ms));
+    let (base_pdns, sub_pdns) = build_pdns_clients(cli, &pdns_latency)?;
+    let domain_backends = build_domain_backends(cli, &config, &pdns_latency)?;
+    let backfilled = db::user_repo::backfill_base_domain(&db, config.base_domain_root()).await?;
+    if backfilled > 0 {
+        info!(
+            "assigned {backfilled} existing users to {}",
+            config.base_domain_root()
+        );
+    }
+    let geoip = if cli.geoip_db.is_empty() {
+        None
+    } else {
+        let geoip = GeoIp::open(&cli.geoip_db)?;
+        info!("loaded GeoIP databases: {}", geoip.describe());
+        Some(geoip)
+    };
 
     Ok(Arc::new(AppState {
         config,
+        policy: ArcSwap::from_pointee(policy),
         db,
         base_pdns,
         sub_pdns,
+        domain_backends,
+        pdns_latency,
+        events: EventBus::default(),
+        stats: Default::default(),
+        in_flight: Default::default(),
+        reconcile: Default::default(),
+        serial_check: Default::default(),
+        jobs: Default::default(),
+        zone_updates: Default::default(),
+        alerts: Default::default(),
+        pdns_health: Default::default(),
+        startup: Default::default(),
+        geoip,
     }))
 }
 
-fn build_app_config(cli: &Cli) -> Result<AppConfig> {
+fn build_pdns_clients(
+    cli: &ServeArgs,
+    latency: &Arc<PdnsLatency>,
+) -> Result<(PowerDnsClient, PowerDnsClient)> {
+    let base_pdns_key = resolve_secret(
+        "--base-pdns-key",
+        cli.base_pdns_key.as_deref(),
+        cli.base_pdns_key_file.as_deref(),
+    )?
+    .context("one of --base-pdns-key or --base-pdns-key-file is required")?;
+    let sub_pdns_key = resolve_secret(
+        "--sub-pdns-key",
+        cli.sub_pdns_key.as_deref(),
+        cli.sub_pdns_key_file.as_deref(),
+    )?
+    .context("one of --sub-pdns-key or --sub-pdns-key-file is required")?;
+
+    let base_pdns =
+        PowerDnsClient::new(&cli.base_pdns_url, base_pdns_key, &cli.base_pdns_server_id)
+            .dry_run(cli.dry_run.is_some())
+            .latency(latency.clone())
+            .circuit_breaker(cli.pdns_breaker_failures, breaker_cooldown(cli));
+    let sub_pdns = PowerDnsClient::new(&cli.sub_pdns_url, sub_pdns_key, &cli.sub_pdns_server_id)
+        .dry_run(cli.dry_run.is_some())
+        .latency(latency.clone())
+        .circuit_breaker(cli.pdns_breaker_failures, breaker_cooldown(cli));
+    Ok((base_pdns, sub_pdns))
+}
+
+/// Build the per-domain PowerDNS overrides listed in `--pdns-backends-file`.
+fn build_domain_backends(
+    cli: &ServeArgs,
+    config: &AppConfig,
+    latency: &Arc<PdnsLatency>,
+) -> Result<HashMap<String, PdnsBackend>> {
+    let Some(path) = &cli.pdns_backends_file else {
+        return Ok(HashMap::new());
+    };
+    let backends = BackendsConfig::load(path)?;
+    let mut built = HashMap::new();
+    for (domain, backend) in backends.domains {
+        if !config.base_domain_roots().any(|root| root == domain) {
+            bail!("backend configured for {domain}, which is not a --base-domain");
+        }
+        let base = build_endpoint_client(&format!("{domain} base key"), &backend.base)?
+            .dry_run(cli.dry_run.is_some())
+            .latency(latency.clone())
+            .circuit_breaker(cli.pdns_breaker_failures, breaker_cooldown(cli));
+        let sub = build_endpoint_client(&format!("{domain} sub key"), &backend.sub)?
+            .dry_run(cli.dry_run.is_some())
+            .latency(latency.clone())
+            .circuit_breaker(cli.pdns_breaker_failures, breaker_cooldown(cli));
+        built.insert(domain, PdnsBackend { base, sub });
+    }
+    Ok(built)
+}
+
+fn breaker_cooldown(cli: &ServeArgs) -> Duration {
+    Duration::from_secs(cli.pdns_breaker_cooldown)
+}
+
+fn build_endpoint_client(name: &str, endpoint: &PdnsEndpointConfig) -> Result<PowerDnsClient> {
+    let key = resolve_secret(name, endpoint.key.as_deref(), endpoint.key_file.as_deref())?
+        .with_context(|| format!("{name} is missing (set key or key_file)"))?;
+    Ok(PowerDnsClient::new(&endpoint.url, key, &endpoint.server_id))
+}
+
+/// Return a secret given inline or read from a file, trimming surrounding whitespace.
+fn resolve_secret(name: &str, value: Option<&str>, file: Option<&Path>) -> Result<Option<String>> {
+    let secret = match (value, file) {
+        (Some(_), Some(_)) => bail!("{name} is given both inline and as a file"),
+        (Some(value), None) => value.to_string(),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {name} from {}", path.display()))?,
+        (None, None) => return Ok(None),
+    };
+    let secret = secret.trim();
+    if secret.is_empty() {
+        bail!("{name} must not be empty");
+    }
+    Ok(Some(secret.to_string()))
+}
+
+/// Validate everything `serve` depends on without modifying any state.
+async fn check_config(cli: &ServeArgs, config: &AppConfig) -> CheckReport {
+    let mut report = CheckReport::default();
+    report.record(
+        "config",
+        Ok(format!(
+            "{} base domain(s), {} internal NS",
+            config.base_domains.len(),
+            config.internal_ns.len()
+        )),
+    );
+    report.record(
+        "policy",
+        PolicyConfig::from_sources(config)
+            .map(|_| "loaded".to_string())
+            .map_err(|e| format!("{e:#}")),
+    );
+
+    let db_outcome = if !cli.db_path.exists() {
+        Ok("not created yet, will be initialized on first start".to_string())
+    } else {
+        match db::open_read_only(&cli.db_path).await {
+            Ok(db) => {
+                let outcome = health::check_database(&db, false).await;
+                db.close().await;
+                outcome
+            }
+            Err(err) => Err(format!("failed to open read-only: {err:#}")),
+        }
+    };
+    report.record("database", db_outcome);
+    if !cli.geoip_db.is_empty() {
+        report.record(
+            "geoip",
+            GeoIp::open(&cli.geoip_db)
+                .map(|geoip| geoip.describe())
+                .map_err(|e| format!("{e:#}")),
+        );
+    }
+
+    // checks are not slow calls worth reporting
+    let latency = Arc::new(PdnsLatency::new(0));
+    let (base_pdns, sub_pdns) = match build_pdns_clients(cli, &latency) {
+        Ok(clients) => clients,
+        Err(err) => {
+            report.record("pdns_keys", Err(format!("{err:#}")));
+            return report;
+        }
+    };
+    let domain_backends = match build_domain_backends(cli, config, &latency) {
+        Ok(backends) => backends,
+        Err(err) => {
+            report.record("pdns_backends", Err(format!("{err:#}")));
+            return report;
+        }
+    };
+    report.record("base_pdns", health::check_pdns(&base_pdns).await);
+    report.record("sub_pdns", health::check_pdns(&sub_pdns).await);
+    for (domain, backend) in &domain_backends {
+        report.record(
+            format!("base_pdns {domain}"),
+            health::check_pdns(&backend.base).await,
+        );
+        report.record(
+            format!("sub_pdns {domain}"),
+            health::check_pdns(&backend.sub).await,
+        );
+    }
+    for base_domain in config.base_domain_roots() {
+        let zone = config.parent_zone_name(base_domain);
+        let client = domain_backends
+            .get(base_domain)
+            .map_or(&base_pdns, |backend| &backend.base);
+        report.record(
+            format!("parent_zone {zone}"),
+            health::check_parent_zone(client, &zone, &config.internal_ns).await,
+        );
+    }
+
+    report
+}
+
+fn build_app_config(cli: &ServeArgs) -> Result<AppConfig> {
     if cli.internal_ns.is_empty() {
         bail!("at least one --internal-ns value is required");
     }
@@ -140,15 +1195,71 @@ fn build_app_config(cli: &Cli) -> Result<AppConfig> {
             .expect("internal_ns already validated"),
     };
 
-    let default_contact = format!("hostmaster.{}", cli.base_domain.trim_end_matches('.'));
-    let internal_contact_source = cli
-        .internal_contact
-        .as_ref()
-        .map(|s| s.as_str())
-        .unwrap_or(&default_contact);
+    let base_domains = cli
+        .base_domain
+        .iter()
+        .map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .collect::<Vec<_>>();
+    let Some(default_domain) = base_domains.first() else {
+        bail!("at least one --base-domain value is required");
+    };
+    for (idx, domain) in base_domains.iter().enumerate() {
+        if base_domains[..idx].contains(domain) {
+            bail!("duplicate --base-domain value '{domain}'");
+        }
+    }
+
+    let default_contact = format!("hostmaster.{default_domain}");
+    let internal_contact_source = cli.internal_contact.as_deref().unwrap_or(&default_contact);
     let internal_contact = normalize_fqdn(internal_contact_source)
         .with_context(|| format!("invalid internal-contact '{}'", internal_contact_source))?;
 
+    for (flag, value) in [
+        ("soa-refresh", cli.soa_refresh),
+        ("soa-retry", cli.soa_retry),
+        ("soa-expire", cli.soa_expire),
+        ("soa-ttl", cli.soa_ttl),
+        ("ns-ttl", cli.ns_ttl),
+    ] {
+        if value == 0 {
+            bail!("--{flag} must be greater than 0");
+        }
+    }
+    for (flag, value) in [
+        ("auth-body-limit", cli.auth_body_limit),
+        ("zone-body-limit", cli.zone_body_limit),
+        ("body-limit", cli.body_limit),
+    ] {
+        if value == 0 {
+            bail!("--{flag} must be greater than 0");
+        }
+    }
+    if cli.compression.contains(&Encoding::None) && cli.compression.len() > 1 {
+        bail!("--compression none cannot be combined with encodings");
+    }
+    if cli.soa_retry >= cli.soa_refresh {
+        bail!("--soa-retry must be smaller than --soa-refresh");
+    }
+    if cli.soa_expire <= cli.soa_refresh {
+        bail!("--soa-expire must be larger than --soa-refresh");
+    }
+
+    let cors_allowed_origins = cli
+        .cors_origin
+        .iter()
+        .map(|origin| normalize_origin(origin))
+        .collect::<Result<Vec<_>>>()?;
+    if cli.cors_allow_credentials && cors_allowed_origins.is_empty() {
+        bail!("--cors-allow-credentials requires at least one --cors-origin");
+    }
+
+    let trusted_proxies = cli
+        .trusted_proxy
+        .iter()
+        .map(|proxy| parse_proxy_net(proxy))
+        .collect::<Result<Vec<_>>>()?;
+
     let disallowed_subdomains = cli
         .disallow_subdomain
         .iter()
@@ -156,15 +1267,102 @@ fn build_app_config(cli: &Cli) -> Result<AppConfig> {
         .filter(|label| !label.is_empty())
         .collect();
 
+    let mail = build_mail_config(cli, &base_domains)?;
+
     Ok(AppConfig {
-        base_domain: cli.base_domain.trim_end_matches('.').to_string(),
+        base_domains,
         internal_ns,
         internal_main_ns,
         internal_contact,
+        soa: SoaTimers {
+            refresh: cli.soa_refresh,
+            retry: cli.soa_retry,
+            expire: cli.soa_expire,
+            minimum: cli.soa_minimum,
+        },
+        soa_ttl: cli.soa_ttl,
+        ns_ttl: cli.ns_ttl,
         disallowed_subdomains,
+        policy_file: cli.policy_file.clone(),
+        reserved_labels_file: cli.reserved_labels_file.clone(),
+        admin_token: resolve_secret(
+            "--admin-token",
+            cli.admin_token.as_deref(),
+            cli.admin_token_file.as_deref(),
+        )?,
+        cors_allowed_origins,
+        trusted_proxies,
+        cors_allow_credentials: cli.cors_allow_credentials,
+        body_limits: BodyLimits {
+            auth: cli.auth_body_limit,
+            zone: cli.zone_body_limit,
+            default: cli.body_limit,
+        },
+        concurrency: ConcurrencyLimits {
+            max_in_flight: cli.max_in_flight,
+            auth: cli.auth_concurrency,
+            zone: cli.zone_concurrency,
+        },
+        compression: Compression {
+            gzip: cli.compression.contains(&Encoding::Gzip),
+            br: cli.compression.contains(&Encoding::Br),
+            zstd: cli.compression.contains(&Encoding::Zstd),
+            level: cli.compression_level,
+            min_size: cli.compression_min_size,
+        },
+        hold_api_until_ready: cli.hold_api_until_ready,
+        mail,
     })
 }
 
+/// Sender and transport of outgoing mail; dry runs only log messages.
+fn build_mail_config(cli: &ServeArgs, base_domains: &[String]) -> Result<MailConfig> {
+    let from = match &cli.mail_from {
+        Some(from) => from.trim().to_string(),
+        None => format!("noreply@{}", base_domains[0].trim_end_matches('.')),
+    };
+    if !is_email_address(&from) {
+        bail!("invalid --mail-from address {from:?}");
+    }
+    let password = resolve_secret(
+        "--smtp-password",
+        cli.smtp_password.as_deref(),
+        cli.smtp_password_file.as_deref(),
+    )?;
+    let transport = match &cli.smtp_url {
+        Some(_) if cli.dry_run.is_some() => MailTransport::Log,
+        Some(url) => MailTransport::Smtp(SmtpConfig::from_url(url, password)?),
+        None => MailTransport::Log,
+    };
+    Ok(MailConfig { from, transport })
+}
+
+fn normalize_origin(input: &str) -> Result<String> {
+    let origin = input.trim().trim_end_matches('/');
+    let Some((scheme, host)) = origin.split_once("://") else {
+        bail!("invalid --cors-origin '{input}': expected scheme://host[:port]");
+    };
+    if !matches!(scheme, "http" | "https") || host.is_empty() || host.contains('/') {
+        bail!("invalid --cors-origin '{input}': expected scheme://host[:port]");
+    }
+    if axum::http::HeaderValue::from_str(origin).is_err() {
+        bail!("invalid --cors-origin '{input}'");
+    }
+    Ok(origin.to_ascii_lowercase())
+}
+
+/// Parse a CIDR, treating a bare address as a single-host network.
+fn parse_proxy_net(input: &str) -> Result<IpNet> {
+    let input = input.trim();
+    if let Ok(net) = input.parse::<IpNet>() {
+        return Ok(net.trunc());
+    }
+    input
+        .parse::<IpAddr>()
+        .map(IpNet::from)
+        .with_context(|| format!("invalid --trusted-proxy '{input}'"))
+}
+
 fn normalize_fqdn(input: &str) -> Result<String> {
     let trimmed = input.trim().trim_end_matches('.');
     if trimmed.is_empty() {
@@ -173,14 +1371,35 @@ fn normalize_fqdn(input: &str) -> Result<String> {
     Ok(format!("{}.", trimmed))
 }
 
-async fn shutdown_signal() {
-    if let Err(err) = signal::ctrl_c().await {
-        error!("failed to install CTRL+C handler: {err}");
-    }
-    info!("shutdown signal received");
+#[cfg(unix)]
+fn spawn_reload_on_sighup(state: SharedState) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(stream) => stream,
+            Err(err) => {
+                error!("failed to install SIGHUP handler: {err}");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading policy");
+            if let Err(err) = state.reload_policy() {
+                error!("policy reload failed, keeping previous policy: {err:#}");
+            }
+        }
+    });
 }
 
-async fn frontend_handler(method: Method, OriginalUri(uri): OriginalUri) -> impl IntoResponse {
+#[cfg(not(unix))]
+fn spawn_reload_on_sighup(_state: SharedState) {}
+
+async fn frontend_handler(
+    State(frontend): State<Arc<FrontendSource>>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+) -> impl IntoResponse {
     if method != Method::GET && method != Method::HEAD {
         return StatusCode::METHOD_NOT_ALLOWED.into_response();
     }
@@ -191,22 +1410,26 @@ async fn frontend_handler(method: Method, OriginalUri(uri): OriginalUri) -> impl
     }
 
     let candidate = if path.is_empty() { "index.html" } else { path };
-    if let Some(resp) = embedded_response(candidate, &method) {
+    if let Some(resp) = frontend_response(&frontend, candidate, &method).await {
         return resp;
     }
-    if let Some(resp) = embedded_response("index.html", &method) {
+    if let Some(resp) = frontend_response(&frontend, "index.html", &method).await {
         return resp;
     }
 
     StatusCode::NOT_FOUND.into_response()
 }
 
-fn embedded_response(path: &str, method: &Method) -> Option<Response<Body>> {
-    let asset = EmbeddedDist::get(path)?;
+async fn frontend_response(
+    frontend: &FrontendSource,
+    path: &str,
+    method: &Method,
+) -> Option<Response<Body>> {
+    let data = frontend.read(path).await?;
     let body = if method == Method::HEAD {
         Body::empty()
     } else {
-        Body::from(asset.data.into_owned())
+        Body::from(data)
     };
     let mime = mime_guess::from_path(path).first_or_octet_stream();
 
@@ -233,8 +1456,17 @@ fn embedded_response(path: &str, method: &Method) -> Option<Response<Body>> {
         .ok()
 }
 
-fn init_tracing() {
+fn init_tracing(format: LogFormat) {
     let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| "info,tower_http=info".into());
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    let builder = tracing_subscriber::fmt().with_env_filter(env_filter);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+    }
 }
@@ -0,0 +1,229 @@
+//! Typed async client for the satsuki HTTP API, for scripts and tools that
+//! manage a delegated zone.
+use reqwest::{Client, Method, RequestBuilder, Response};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::api::dns::{AppliedRrset, RecordDto, ZoneUpdateRequest};
+use crate::api::profile::SetExternalNsRequest;
+use crate::api::public::{SigninRequest, SignupRequest};
+
+/// Failure of a client call.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// The server answered with a problem document.
+    #[error("{status} {}: {}", .problem.code, .problem.detail)]
+    Api { status: u16, problem: ApiProblem },
+
+    /// The request could not be sent or the response not read.
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// The call needs credentials, but the client has none.
+    #[error("no credentials configured")]
+    MissingCredentials,
+}
+
+impl ClientError {
+    /// Stable error code of an API error, e.g. `subdomain_taken`.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Api { problem, .. } => Some(&problem.code),
+            _ => None,
+        }
+    }
+}
+
+/// RFC 7807 problem document as served by the API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiProblem {
+    pub code: String,
+    #[serde(default)]
+    pub detail: String,
+    /// Extension members such as `retry_after` or `limit`.
+    #[serde(flatten)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Body of a successful signup.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignupResponse {
+    /// The zone is still being set up and the account cannot sign in yet.
+    #[serde(default)]
+    pub pending: bool,
+    /// Label as stored, in punycode for Unicode labels.
+    pub subdomain: String,
+    pub subdomain_unicode: String,
+    pub base_domain: String,
+    /// Name of the new zone, with a trailing dot.
+    pub zone: String,
+    /// Nameservers the zone is delegated to.
+    pub nameservers: Vec<String>,
+}
+
+/// Body of a successful zone update.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneUpdateResponse {
+    /// Lint findings that did not block the update.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// The RRsets as written, with absolute owners and final TTLs.
+    pub rrsets: Vec<AppliedRrset>,
+}
+
+/// Body of a successful NS-mode switch.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetNsResponse {
+    /// `internal` or `external`.
+    pub mode: String,
+    /// The delegation now in place.
+    pub nameservers: Vec<String>,
+    /// Pre-flight report of the new nameservers, when the check ran.
+    #[serde(default)]
+    pub preflight: Option<serde_json::Value>,
+}
+
+/// Basic Auth credentials of an account.
+#[derive(Clone)]
+struct Credentials {
+    login: String,
+    password: String,
+}
+
+/// Client for one satsuki instance, optionally signed in as one account.
+#[derive(Clone)]
+pub struct SatsukiClient {
+    http: Client,
+    base_url: String, // e.g. "https://dns.example.com", without "/api"
+    credentials: Option<Credentials>,
+}
+
+impl SatsukiClient {
+    /// Construct a client for the instance at `base_url`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            credentials: None,
+        }
+    }
+
+    /// Authenticate later calls as `login`, the label or its qualified form
+    /// (`alice.example.org`) on instances serving several base domains.
+    pub fn with_credentials(
+        mut self,
+        login: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some(Credentials {
+            login: login.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Build a request for an API path such as `/zone`.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}/api{}", self.base_url, path))
+    }
+
+    /// Like `request`, with the configured credentials attached.
+    fn authed(&self, method: Method, path: &str) -> Result<RequestBuilder, ClientError> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or(ClientError::MissingCredentials)?;
+        Ok(self
+            .request(method, path)
+            .basic_auth(&credentials.login, Some(&credentials.password)))
+    }
+
+    /// Register a new subdomain. Does not sign the client in.
+    pub async fn signup(&self, req: &SignupRequest) -> Result<SignupResponse, ClientError> {
+        let res = self
+            .request(Method::POST, "/signup")
+            .json(req)
+            .send()
+            .await?;
+        parse(res).await
+    }
+
+    /// Check the configured credentials; also records the sign-in time.
+    pub async fn signin(&self) -> Result<(), ClientError> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or(ClientError::MissingCredentials)?;
+        let req = SigninRequest {
+            subdomain: credentials.login.clone(),
+            password: credentials.password.clone(),
+            base_domain: None,
+        };
+        let res = self
+            .request(Method::POST, "/signin")
+            .json(&req)
+            .send()
+            .await?;
+        parse::<serde_json::Value>(res).await.map(|_| ())
+    }
+
+    /// Every user-manageable record of the zone.
+    pub async fn get_zone(&self) -> Result<Vec<RecordDto>, ClientError> {
+        let res = self.authed(Method::GET, "/zone")?.send().await?;
+        parse(res).await
+    }
+
+    /// Replace the RRsets the given records belong to.
+    pub async fn put_records(
+        &self,
+        records: Vec<RecordDto>,
+    ) -> Result<ZoneUpdateResponse, ClientError> {
+        let req = ZoneUpdateRequest { records };
+        let res = self.authed(Method::PUT, "/zone")?.json(&req).send().await?;
+        parse(res).await
+    }
+
+    /// Delegate the zone to the instance's own nameservers.
+    pub async fn set_ns_internal(&self) -> Result<SetNsResponse, ClientError> {
+        let res = self
+            .authed(Method::POST, "/ns-mode/internal")?
+            .send()
+            .await?;
+        parse(res).await
+    }
+
+    /// Delegate the zone to external nameservers (FQDNs with trailing dots).
+    /// `force` delegates even when the pre-flight check finds problems.
+    pub async fn set_ns_external(
+        &self,
+        ns: Vec<String>,
+        force: bool,
+    ) -> Result<SetNsResponse, ClientError> {
+        let req = SetExternalNsRequest { ns, force };
+        let res = self
+            .authed(Method::POST, "/ns-mode/external")?
+            .json(&req)
+            .send()
+            .await?;
+        parse(res).await
+    }
+}
+
+/// Decode a success body, or turn an error status into `ClientError::Api`.
+async fn parse<T: DeserializeOwned>(res: Response) -> Result<T, ClientError> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res.json::<T>().await?);
+    }
+    let text = res.text().await?;
+    let problem = serde_json::from_str(&text).unwrap_or_else(|_| ApiProblem {
+        code: "unknown".into(),
+        detail: text,
+        extensions: serde_json::Map::new(),
+    });
+    Err(ClientError::Api {
+        status: status.as_u16(),
+        problem,
+    })
+}
@@ -0,0 +1,117 @@
+//! Client address resolution honouring `Forwarded` / `X-Forwarded-For` from trusted proxies.
+use axum::{
+    Extension,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, request::Parts},
+};
+use ipnet::IpNet;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use crate::SharedState;
+use crate::error::AppError;
+
+/// Axum extractor yielding the originating client address of a request.
+///
+/// Forwarding headers are only believed when the TCP peer is a trusted proxy;
+/// the chain is then walked right to left and the first untrusted hop wins.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> impl Future<Output = Result<Self, Self::Rejection>> {
+        Box::pin(async move {
+            let Extension(app_state): Extension<SharedState> =
+                Extension::from_request_parts(parts, state)
+                    .await
+                    .map_err(|_| AppError::Internal(anyhow::anyhow!("missing state")))?;
+            let peer = parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|info| info.0.ip())
+                .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+            Ok(ClientIp(resolve_client_ip(
+                peer,
+                &parts.headers,
+                &app_state.config.trusted_proxies,
+            )))
+        })
+    }
+}
+
+/// Determine the client address from the TCP peer and forwarding headers.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    // RFC 7239 takes precedence over the de-facto X-Forwarded-For header
+    let chain = if headers.contains_key("forwarded") {
+        forwarded_chain(headers)
+    } else {
+        x_forwarded_for_chain(headers)
+    };
+
+    let mut client = peer;
+    for hop in chain.into_iter().rev() {
+        let Some(hop) = hop else {
+            // unparsable or obfuscated hop: stop at the last address we trust
+            break;
+        };
+        client = hop;
+        if !is_trusted(&hop) {
+            break;
+        }
+    }
+    client
+}
+
+/// Addresses listed in every `X-Forwarded-For` header, in order.
+fn x_forwarded_for_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| parse_node(hop.trim()))
+        .collect()
+}
+
+/// `for=` addresses listed in every `Forwarded` header, in order.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| parse_node(value.trim().trim_matches('"')))
+            })
+        })
+        .collect()
+}
+
+/// Parse a node such as `192.0.2.1`, `192.0.2.1:8080`, or `[2001:db8::1]:443`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
@@ -1,5 +1,16 @@
 //! Static application configuration and helpers around DNS naming.
+use anyhow::Context;
+use ipnet::IpNet;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use tower_http::CompressionLevel;
+
+use crate::i18n::{Message, MessageId};
 
 /// Default label blacklist applied when no custom list is supplied.
 pub const DEFAULT_DISALLOWED_SUBDOMAINS: &[&str] = &[
@@ -23,30 +34,280 @@ pub const DEFAULT_DISALLOWED_SUBDOMAINS: &[&str] = &[
     "test",
 ];
 
+/// Record types users may create when the policy does not list its own.
+pub const DEFAULT_ALLOWED_RECORD_TYPES: &[&str] = &[
+    "A",
+    "AAAA",
+    "CAA",
+    "CNAME",
+    "CSYNC",
+    "DNAME",
+    "DS",
+    "HTTPS",
+    "LOC",
+    "MX",
+    "NAPTR",
+    "NS",
+    "OPENPGPKEY",
+    "PTR",
+    "SMIMEA",
+    "SRV",
+    "SSHFP",
+    "SVCB",
+    "TLSA",
+    "TXT",
+    "URI",
+];
+
+/// Default TTL of apex and delegation NS RRsets.
+pub const DEFAULT_NS_TTL: u32 = 300;
+/// Default TTL of the apex SOA RRset in user zones.
+pub const DEFAULT_SOA_TTL: u32 = 3600;
+
+/// SOA timer fields written into every user zone.
+#[derive(Clone, Debug)]
+pub struct SoaTimers {
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    /// Negative-caching TTL (RFC 2308).
+    pub minimum: u32,
+}
+
+impl Default for SoaTimers {
+    fn default() -> Self {
+        Self {
+            refresh: 7200,
+            retry: 900,
+            expire: 1_209_600,
+            minimum: 300,
+        }
+    }
+}
+
+/// Maximum request body sizes in bytes, per route group.
+#[derive(Clone, Copy, Debug)]
+pub struct BodyLimits {
+    /// `/api/signup` and `/api/signin`, reachable without credentials.
+    pub auth: usize,
+    /// Bulk zone writes such as `PUT /api/zone`.
+    pub zone: usize,
+    /// Every other route.
+    pub default: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            auth: 4 * 1024,
+            zone: 1024 * 1024,
+            default: 64 * 1024,
+        }
+    }
+}
+
+/// Caps on requests handled at once; `0` disables a cap.
+#[derive(Clone, Copy, Debug)]
+pub struct ConcurrencyLimits {
+    /// Every API request; beyond this the server sheds load with 503.
+    pub max_in_flight: usize,
+    /// `/api/signup` and `/api/signin`, whose password hashing is CPU-bound.
+    pub auth: usize,
+    /// Zone reads and writes, which each cost PowerDNS round trips.
+    pub zone: usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 512,
+            auth: 8,
+            zone: 32,
+        }
+    }
+}
+
+/// Response compression, negotiated with each request's `Accept-Encoding`.
+#[derive(Clone, Copy, Debug)]
+pub struct Compression {
+    pub gzip: bool,
+    pub br: bool,
+    pub zstd: bool,
+    /// Applied to every encoding; `Precise` levels are on the encoding's own scale.
+    pub level: CompressionLevel,
+    /// Responses with a smaller `Content-Length` are sent as they are.
+    pub min_size: u16,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            gzip: true,
+            br: true,
+            zstd: true,
+            level: CompressionLevel::Default,
+            min_size: 1024,
+        }
+    }
+}
+
+/// Outgoing mail, sent from the outbox by `mail::spawn_sender`.
+#[derive(Clone)]
+pub struct MailConfig {
+    /// `From` address of every message.
+    pub from: String,
+    pub transport: MailTransport,
+}
+
+/// Where queued messages go.
+#[derive(Clone)]
+pub enum MailTransport {
+    /// Write messages to the log instead of sending them, for development.
+    Log,
+    Smtp(SmtpConfig),
+}
+
+/// An SMTP submission server.
+#[derive(Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// How the SMTP connection is protected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with `STARTTLS`, which must be offered.
+    StartTls,
+    /// TLS from the first byte (SMTPS).
+    Tls,
+    /// No encryption; only for relays on the same host or network.
+    Insecure,
+}
+
+impl SmtpConfig {
+    /// Parse `smtp://[user@]host[:port]` (STARTTLS, port 587),
+    /// `smtps://...` (TLS, port 465), or `smtp+insecure://...` (port 25).
+    pub fn from_url(input: &str, password: Option<String>) -> anyhow::Result<Self> {
+        let url = url::Url::parse(input).context("invalid SMTP URL")?;
+        let (security, default_port) = match url.scheme() {
+            "smtp" => (SmtpSecurity::StartTls, 587),
+            "smtps" => (SmtpSecurity::Tls, 465),
+            "smtp+insecure" => (SmtpSecurity::Insecure, 25),
+            other => anyhow::bail!("unsupported SMTP URL scheme {other:?}"),
+        };
+        if url.password().is_some() {
+            anyhow::bail!(
+                "put the SMTP password in --smtp-password or --smtp-password-file, not the URL"
+            );
+        }
+        let host = url.host_str().context("SMTP URL has no host")?.to_string();
+        let username = Some(url.username())
+            .filter(|user| !user.is_empty())
+            // URLs carry user names such as `me%40example.com`
+            .map(|user| {
+                percent_decode_str(user)
+                    .decode_utf8()
+                    .map(String::from)
+                    .context("invalid SMTP user name")
+            })
+            .transpose()?;
+        if password.is_some() && username.is_none() {
+            anyhow::bail!("an SMTP password needs a user name in the URL");
+        }
+        Ok(Self {
+            host,
+            port: url.port().unwrap_or(default_port),
+            security,
+            username,
+            password,
+        })
+    }
+}
+
 /// Strongly-typed representation of server configuration.
 #[derive(Clone)]
 pub struct AppConfig {
-    pub base_domain: String,
-    pub internal_ns: Vec<String>, // "ns1.example.net.", ...
-    pub internal_main_ns: String, // "ns1.example.net.", used in SOA
-    pub internal_contact: String, // "hostmaster.example.net.", used in SOA
+    pub base_domains: Vec<String>, // "example.com", ...; the first one is the default
+    pub internal_ns: Vec<String>,  // "ns1.example.net.", ...
+    pub internal_main_ns: String,  // "ns1.example.net.", used in SOA
+    pub internal_contact: String,  // "hostmaster.example.net.", used in SOA
+    pub soa: SoaTimers,
+    pub soa_ttl: u32,
+    pub ns_ttl: u32,
     pub disallowed_subdomains: Vec<String>,
+    pub policy_file: Option<PathBuf>,
+    pub reserved_labels_file: Option<PathBuf>,
+    pub admin_token: Option<String>,
+    /// Allowed CORS origins (e.g. "https://dns.example.com"); empty allows any.
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allow_credentials: bool,
+    pub body_limits: BodyLimits,
+    pub concurrency: ConcurrencyLimits,
+    pub compression: Compression,
+    /// Answer API requests with 503 until the startup checks pass.
+    pub hold_api_until_ready: bool,
+    /// Proxies whose `Forwarded` / `X-Forwarded-For` headers are believed.
+    pub trusted_proxies: Vec<IpNet>,
+    pub mail: MailConfig,
 }
 
 impl AppConfig {
-    /// Canonical base domain without trailing dot.
+    /// Canonical default base domain without trailing dot.
     pub fn base_domain_root(&self) -> &str {
-        self.base_domain.trim_end_matches('.')
+        self.base_domains
+            .first()
+            .map(|domain| domain.trim_end_matches('.'))
+            .unwrap_or_default()
+    }
+
+    /// Every configured base domain without trailing dots, default first.
+    pub fn base_domain_roots(&self) -> impl Iterator<Item = &str> {
+        self.base_domains
+            .iter()
+            .map(|domain| domain.trim_end_matches('.'))
+    }
+
+    /// Resolve a client-supplied base domain, falling back to the default.
+    ///
+    /// Returns `None` when the requested domain is not served by this instance.
+    pub fn resolve_base_domain(&self, requested: Option<&str>) -> Option<&str> {
+        let requested = requested
+            .map(|domain| domain.trim().trim_end_matches('.'))
+            .filter(|domain| !domain.is_empty());
+        match requested {
+            None => Some(self.base_domain_root()),
+            Some(domain) => self
+                .base_domain_roots()
+                .find(|candidate| candidate.eq_ignore_ascii_case(domain)),
+        }
+    }
+
+    /// Split a login name into `(label, base_domain)`.
+    ///
+    /// A bare label refers to the default base domain; `label.base-domain`
+    /// selects any other configured domain.
+    pub fn split_username<'a>(&'a self, username: &'a str) -> Option<(&'a str, &'a str)> {
+        let username = username.trim_end_matches('.');
+        match username.split_once('.') {
+            None => Some((username, self.base_domain_root())),
+            Some((label, domain)) => self
+                .resolve_base_domain(Some(domain))
+                .map(|domain| (label, domain)),
+        }
     }
 
     /// Fully-qualified parent zone name (e.g. example.com.).
-    pub fn parent_zone_name(&self) -> String {
-        format!("{}.", self.base_domain_root())
+    pub fn parent_zone_name(&self, base_domain: &str) -> String {
+        format!("{}.", base_domain.trim_end_matches('.'))
     }
 
     /// Fully-qualified user zone name for the provided label.
-    pub fn user_zone_name(&self, subdomain: &str) -> String {
-        format!("{}.{}.", subdomain, self.base_domain_root())
+    pub fn user_zone_name(&self, subdomain: &str, base_domain: &str) -> String {
+        format!("{}.{}.", subdomain, base_domain.trim_end_matches('.'))
     }
 
     /// Check whether the user-provided label is on the reserved list.
@@ -71,3 +332,1258 @@ impl AppConfig {
         }
     }
 }
+
+/// Whether new accounts may be registered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignupMode {
+    #[default]
+    Open,
+    Closed,
+    /// Signup requires a single-use invite code issued by an admin.
+    Invite,
+}
+
+/// Which internationalized (Unicode) subdomain labels signup accepts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdnMode {
+    /// ASCII labels only.
+    Off,
+    /// Labels in a single script, or a usual CJK combination with Latin.
+    #[default]
+    Restricted,
+    /// Any label IDNA accepts.
+    Any,
+}
+
+/// What signup does with a label that looks like a reserved label or an
+/// existing delegation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfusableMode {
+    /// No check.
+    Off,
+    /// Accept the signup and put the zone into the abuse review queue.
+    #[default]
+    Flag,
+    /// Refuse the label.
+    Reject,
+}
+
+/// What record validation does with A/AAAA records pointing at private,
+/// loopback, link-local, documentation, or other non-public ranges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivateAddressMode {
+    /// Accept them silently.
+    #[default]
+    Allow,
+    /// Accept them and return a warning.
+    Warn,
+    /// Reject the update.
+    Deny,
+}
+
+/// Optional subsystems that operators can switch off without recompiling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Delegating a zone to user-provided nameservers.
+    ExternalNs,
+    /// Rotating passwords via `/api/password/change`.
+    PasswordChange,
+    /// PowerDNS LUA records (arbitrary server-side scripting; off by default).
+    LuaRecords,
+    /// The public delegation list and parent SOA endpoints.
+    PublicListing,
+    /// User-registered webhooks receiving signed change events.
+    Webhooks,
+    /// Public JSON Feed / Atom feed of newly registered subdomains.
+    DelegationFeed,
+}
+
+impl Feature {
+    /// Every known feature, in advertisement order.
+    pub const ALL: &'static [Feature] = &[
+        Feature::ExternalNs,
+        Feature::PasswordChange,
+        Feature::LuaRecords,
+        Feature::PublicListing,
+        Feature::Webhooks,
+        Feature::DelegationFeed,
+    ];
+
+    /// Stable identifier used in `/api/about` and error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::ExternalNs => "external_ns",
+            Feature::PasswordChange => "password_change",
+            Feature::LuaRecords => "lua_records",
+            Feature::PublicListing => "public_listing",
+            Feature::Webhooks => "webhooks",
+            Feature::DelegationFeed => "delegation_feed",
+        }
+    }
+}
+
+/// Per-instance on/off switches for each [`Feature`], read from `[features]`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureFlags {
+    pub external_ns: bool,
+    pub password_change: bool,
+    pub lua_records: bool,
+    pub public_listing: bool,
+    pub webhooks: bool,
+    pub delegation_feed: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            external_ns: true,
+            password_change: true,
+            lua_records: false,
+            public_listing: true,
+            webhooks: true,
+            delegation_feed: true,
+        }
+    }
+}
+
+impl FeatureFlags {
+    /// Check whether the feature is switched on.
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::ExternalNs => self.external_ns,
+            Feature::PasswordChange => self.password_change,
+            Feature::LuaRecords => self.lua_records,
+            Feature::PublicListing => self.public_listing,
+            Feature::Webhooks => self.webhooks,
+            Feature::DelegationFeed => self.delegation_feed,
+        }
+    }
+
+    /// Names of every enabled feature.
+    pub fn enabled_names(&self) -> Vec<&'static str> {
+        Feature::ALL
+            .iter()
+            .filter(|feature| self.is_enabled(**feature))
+            .map(|feature| feature.name())
+            .collect()
+    }
+}
+
+/// Maintenance switch; while enabled, mutating requests are answered with 503.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaintenanceConfig {
+    pub enabled: bool,
+    /// Seconds advertised to clients via `Retry-After`.
+    pub retry_after_secs: u64,
+    pub message: Option<String>,
+    /// Also reject mutations while the PowerDNS monitor finds an endpoint down.
+    pub read_only_when_pdns_down: bool,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retry_after_secs: 300,
+            message: None,
+            read_only_when_pdns_down: true,
+        }
+    }
+}
+
+/// Periodic comparison of user rows with PowerDNS.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconcileConfig {
+    pub enabled: bool,
+    /// Seconds between runs.
+    pub interval_secs: u64,
+    /// Recreate missing zones and rewrite wrong or missing delegations.
+    pub repair: bool,
+    pub orphans: OrphanPolicy,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 3600,
+            repair: true,
+            orphans: OrphanPolicy::Report,
+            schedule: None,
+        }
+    }
+}
+
+/// Periodic SOA checks of the nameservers external-mode users delegate to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NsHealthConfig {
+    pub enabled: bool,
+    /// Seconds between rounds of checks.
+    pub interval_secs: u64,
+    /// Per-query timeout, also applied to resolving the nameserver.
+    pub timeout_secs: u64,
+    /// Consecutive failed checks before the user is notified.
+    pub notify_after: u32,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for NsHealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 900,
+            timeout_secs: 5,
+            notify_after: 4,
+            schedule: None,
+        }
+    }
+}
+
+/// Checks of the submitted nameservers before a zone switches to external NS.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NsPreflightConfig {
+    pub enabled: bool,
+    /// Per-query timeout, also applied to resolving the nameserver.
+    pub timeout_secs: u64,
+}
+
+impl Default for NsPreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_secs: 5,
+        }
+    }
+}
+
+/// Periodic comparison of user zone SOA serials on the internal nameservers
+/// with the serials PowerDNS serves.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SerialCheckConfig {
+    /// Off by default, since the internal nameservers must be reachable from this host.
+    pub enabled: bool,
+    /// Seconds between rounds of checks.
+    pub interval_secs: u64,
+    /// Per-query timeout, also applied to resolving the nameserver.
+    pub timeout_secs: u64,
+    /// Zones checked per run, picked at random; all of them when unset.
+    pub sample: Option<usize>,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for SerialCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 900,
+            timeout_secs: 5,
+            sample: None,
+            schedule: None,
+        }
+    }
+}
+
+/// Removal of accounts nobody ever signed into, changed, or added records to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExpiryConfig {
+    /// Off by default, since it deletes accounts.
+    pub enabled: bool,
+    /// Age after which an unused account is removed.
+    pub unused_after_days: u32,
+    /// Days before removal the owner is reminded; 0 sends no reminder.
+    pub remind_before_days: u32,
+    /// Seconds between sweeps.
+    pub interval_secs: u64,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            unused_after_days: 30,
+            remind_before_days: 7,
+            interval_secs: 86_400,
+            schedule: None,
+        }
+    }
+}
+
+/// Removal of parent-zone delegations with neither a user nor a sub-PDNS zone.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DanglingConfig {
+    /// Off by default, since it deletes delegations.
+    pub enabled: bool,
+    /// Days a delegation must stay dangling before it is removed.
+    pub quarantine_days: u32,
+    /// Seconds between sweeps.
+    pub interval_secs: u64,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for DanglingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            quarantine_days: 7,
+            interval_secs: 86_400,
+            schedule: None,
+        }
+    }
+}
+
+/// Periodic BIND-format exports of every user zone.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+    /// Each snapshot is a timestamped subdirectory of this one.
+    pub dir: Option<PathBuf>,
+    /// Snapshots kept; older ones are deleted after each run.
+    pub keep: usize,
+    /// Seconds between snapshots.
+    pub interval_secs: u64,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            keep: 14,
+            interval_secs: 86_400,
+            schedule: None,
+        }
+    }
+}
+
+/// Pruning of the audit log, change history, and webhook deliveries.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    /// Days rows are kept per table; unset keeps them forever.
+    pub audit_log_days: Option<u32>,
+    pub change_history_days: Option<u32>,
+    pub webhook_deliveries_days: Option<u32>,
+    /// Pruned rows are first written here as gzipped NDJSON files.
+    pub archive_dir: Option<PathBuf>,
+    /// Pruned rows are first uploaded to this bucket as well.
+    pub s3: Option<S3Config>,
+    /// Seconds between runs.
+    pub interval_secs: u64,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            audit_log_days: None,
+            change_history_days: None,
+            webhook_deliveries_days: None,
+            archive_dir: None,
+            s3: None,
+            interval_secs: 86_400,
+            schedule: None,
+        }
+    }
+}
+
+/// An S3-compatible bucket, addressed path-style and signed with SigV4.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com`; `https` needs the
+    /// `https-client` feature.
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// Prepended to every object key, e.g. `satsuki/`.
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".into()
+}
+
+/// Signup check for labels one typo away from a popular delegation.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TyposquatConfig {
+    pub mode: ConfusableMode,
+    /// Records a zone needs before its label is protected.
+    pub min_records: usize,
+    /// Shorter labels are not protected; too many names lie one edit from them.
+    pub min_length: usize,
+}
+
+impl Default for TyposquatConfig {
+    fn default() -> Self {
+        Self {
+            mode: ConfusableMode::Off,
+            min_records: 10,
+            min_length: 4,
+        }
+    }
+}
+
+/// Background completion of signups whose provisioning failed midway.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SignupRecoveryConfig {
+    /// Seconds between runs.
+    pub interval_secs: u64,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+    /// Failed attempts at one step before the signup is rolled back.
+    pub max_attempts: u32,
+}
+
+impl Default for SignupRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            schedule: None,
+            max_attempts: 10,
+        }
+    }
+}
+
+/// Caps on signups from one client address or network, checked by `signup`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SignupLimitsConfig {
+    /// Signups one client address may make per day; unset means unlimited.
+    pub per_ip_per_day: Option<u32>,
+    /// Signups one network may make per day; unset means unlimited.
+    pub per_subnet_per_day: Option<u32>,
+    /// Prefix length grouping IPv4 clients into networks.
+    pub ipv4_prefix: u8,
+    /// Prefix length grouping IPv6 clients into networks.
+    pub ipv6_prefix: u8,
+}
+
+impl Default for SignupLimitsConfig {
+    fn default() -> Self {
+        Self {
+            per_ip_per_day: None,
+            per_subnet_per_day: None,
+            ipv4_prefix: 24,
+            ipv6_prefix: 48,
+        }
+    }
+}
+
+impl SignupLimitsConfig {
+    /// The network `ip` is counted in, e.g. `203.0.113.0/24`.
+    pub fn subnet(&self, ip: IpAddr) -> IpNet {
+        let ip = ip.to_canonical();
+        let prefix = match ip {
+            IpAddr::V4(_) => self.ipv4_prefix,
+            IpAddr::V6(_) => self.ipv6_prefix,
+        };
+        IpNet::new(ip, prefix)
+            .map(|net| net.trunc())
+            .unwrap_or_else(|_| IpNet::from(ip))
+    }
+}
+
+/// Heuristic scan of user zones that queues suspicious ones for review.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AbuseScanConfig {
+    pub enabled: bool,
+    /// Seconds between scans.
+    pub interval_secs: u64,
+    /// Owner names differing only in their digits before a zone is flagged.
+    pub similar_names: usize,
+    /// Networks (e.g. known sinkholes or phishing hosts) A/AAAA records may not point into.
+    pub flagged_networks: Vec<IpNet>,
+    /// Domains (and their subdomains) that record targets may not point at.
+    pub flagged_domains: Vec<String>,
+    /// `records.changed` events within `churn_window_hours` before a zone with
+    /// records at or below `churn_max_ttl` is flagged.
+    pub churn_changes: i64,
+    pub churn_window_hours: u32,
+    pub churn_max_ttl: u32,
+    /// Cron expression (UTC) used instead of `interval_secs`.
+    pub schedule: Option<String>,
+}
+
+impl Default for AbuseScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 3600,
+            similar_names: 50,
+            flagged_networks: Vec::new(),
+            flagged_domains: Vec::new(),
+            churn_changes: 100,
+            churn_window_hours: 24,
+            churn_max_ttl: 60,
+            schedule: None,
+        }
+    }
+}
+
+/// What happens to a record update whose targets are on a blocklist.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReputationAction {
+    /// Apply the update and put the zone into the abuse review queue.
+    #[default]
+    Flag,
+    /// Refuse the update.
+    Reject,
+}
+
+/// Blocklist lookups of the addresses and hostnames new records point at.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReputationConfig {
+    pub enabled: bool,
+    /// DNSBL zones queried with reversed addresses, e.g. `zen.spamhaus.org`.
+    pub ip_blocklists: Vec<String>,
+    /// Domain blocklist zones queried with hostnames, e.g. `dbl.spamhaus.org`.
+    pub domain_blocklists: Vec<String>,
+    /// Google Safe Browsing (v4 Lookup API) key; needs the `https-client` feature.
+    pub safe_browsing_key: Option<String>,
+    pub action: ReputationAction,
+    /// Time the lookups of one update may take together; unfinished ones count as clean.
+    pub timeout_secs: u64,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ip_blocklists: Vec::new(),
+            domain_blocklists: Vec::new(),
+            safe_browsing_key: None,
+            action: ReputationAction::Flag,
+            timeout_secs: 3,
+        }
+    }
+}
+
+/// Structured messages to the operators about notable events.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Where alerts go; no alerts are raised without any.
+    pub sinks: Vec<AlertSink>,
+    /// Seconds an alert of the same kind and subject is not repeated for.
+    pub cooldown_secs: u64,
+    /// Seconds between PowerDNS API probes; 0 disables them.
+    pub pdns_check_secs: u64,
+    /// Signups within `signup_spike_window_mins` that count as a spike.
+    pub signup_spike: Option<u32>,
+    pub signup_spike_window_mins: u32,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            sinks: Vec::new(),
+            cooldown_secs: 900,
+            pdns_check_secs: 60,
+            signup_spike: None,
+            signup_spike_window_mins: 60,
+        }
+    }
+}
+
+/// One alert destination; `https` URLs need the `https-client` feature.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum AlertSink {
+    /// A Slack (or compatible, e.g. Mattermost) incoming webhook.
+    Slack { url: String },
+    /// A Matrix room, posted to as the user of `access_token`.
+    Matrix {
+        homeserver: String,
+        room_id: String,
+        access_token: String,
+    },
+    /// The alert as JSON, signed like user webhooks when `secret` is set.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+}
+
+/// What the reconciler does with delegations and zones that have no user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanPolicy {
+    /// Only list them in the report and metrics.
+    #[default]
+    Report,
+    /// Delete them once two consecutive runs found them.
+    Remove,
+}
+
+/// Which domains account email addresses may use, e.g. an institution's
+/// own domain only, or anything but disposable-mail providers.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailDomainPolicy {
+    /// Domains (and their subdomains) addresses must belong to; empty allows any.
+    pub allowed: Vec<String>,
+    /// Domains (and their subdomains) addresses may not belong to.
+    pub blocked: Vec<String>,
+}
+
+impl EmailDomainPolicy {
+    /// Check the domain of a syntactically valid address; returns a
+    /// client-facing reason on rejection.
+    pub fn check(&self, address: &str) -> Result<(), Message> {
+        let domain = address
+            .rsplit_once('@')
+            .map_or(address, |(_, domain)| domain)
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        let within = |list: &[String]| {
+            list.iter().any(|listed| {
+                domain == *listed
+                    || domain
+                        .strip_suffix(listed.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            })
+        };
+        if within(&self.blocked) || !(self.allowed.is_empty() || within(&self.allowed)) {
+            return Err(Message::new(MessageId::EmailDomainRejected, [domain]));
+        }
+        Ok(())
+    }
+}
+
+/// Guardrails on record contents, protecting the shared parent domain's reputation.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentPolicy {
+    /// A/AAAA records pointing at private, loopback, or other bogon ranges.
+    pub private_addresses: Option<PrivateAddressMode>,
+    /// Older spelling of `private_addresses = "deny"`, used when that is unset.
+    pub reject_private_addresses: bool,
+    /// Domains (and their subdomains) that CNAME/DNAME records may not target.
+    pub blocked_cname_targets: Vec<String>,
+    /// Longest accepted TXT record, in bytes of presentation format.
+    pub max_txt_length: Option<usize>,
+    /// Reject SPF records with errors instead of returning them as warnings.
+    pub reject_invalid_spf: bool,
+}
+
+impl ContentPolicy {
+    /// Effective handling of records pointing at non-public addresses.
+    pub fn private_address_mode(&self) -> PrivateAddressMode {
+        self.private_addresses
+            .unwrap_or(if self.reject_private_addresses {
+                PrivateAddressMode::Deny
+            } else {
+                PrivateAddressMode::Allow
+            })
+    }
+
+    /// Check one record's content; returns a client-facing reason on rejection.
+    pub fn check(&self, rrtype: &str, content: &str) -> Result<(), Message> {
+        match rrtype {
+            "A" | "AAAA" if self.private_address_mode() == PrivateAddressMode::Deny => {
+                if let Some(reason) = private_address(rrtype, content) {
+                    return Err(reason);
+                }
+            }
+            "CNAME" | "DNAME" => {
+                let target = content.trim().trim_end_matches('.').to_ascii_lowercase();
+                let blocked = self.blocked_cname_targets.iter().any(|domain| {
+                    target == *domain
+                        || target
+                            .strip_suffix(domain.as_str())
+                            .is_some_and(|prefix| prefix.ends_with('.'))
+                });
+                if blocked {
+                    return Err(Message::new(MessageId::TargetNotAllowed, [rrtype, &target]));
+                }
+            }
+            "TXT" => {
+                if let Some(max) = self.max_txt_length
+                    && content.len() > max
+                {
+                    return Err(Message::new(MessageId::TxtTooLong, [max]));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Warning about one accepted record's content, if the policy asks for one.
+    pub fn warning(&self, rrtype: &str, content: &str) -> Option<String> {
+        match rrtype {
+            "A" | "AAAA" if self.private_address_mode() == PrivateAddressMode::Warn => {
+                private_address(rrtype, content).map(|reason| reason.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Why an A/AAAA record's address is not public, if it is not.
+fn private_address(rrtype: &str, content: &str) -> Option<Message> {
+    let ip: IpAddr = content.trim().parse().ok()?;
+    let range = crate::validation::special_range(ip)?;
+    Some(Message::new(
+        MessageId::PrivateAddress,
+        [rrtype, &ip.to_string(), range],
+    ))
+}
+
+/// Fields of the generated `/.well-known/security.txt` (RFC 9116).
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityTxtConfig {
+    /// `Contact` URIs; defaults to `mailto:` the policy's `contact_email`.
+    pub contact: Vec<String>,
+    /// Fixed `Expires` timestamp (RFC 3339); otherwise one year after the request.
+    pub expires: Option<String>,
+    pub policy: Option<String>,
+    pub encryption: Vec<String>,
+    pub acknowledgments: Option<String>,
+    pub hiring: Option<String>,
+    pub canonical: Vec<String>,
+    pub preferred_languages: Vec<String>,
+}
+
+/// One `User-agent` group of the generated `/robots.txt`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotsRule {
+    #[serde(default = "default_robots_user_agent")]
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+    #[serde(default)]
+    pub crawl_delay: Option<u32>,
+}
+
+fn default_robots_user_agent() -> String {
+    "*".into()
+}
+
+/// Crawl rules served at `/robots.txt`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotsConfig {
+    pub rules: Vec<RobotsRule>,
+    /// Absolute sitemap URLs.
+    pub sitemaps: Vec<String>,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        // the SPA may be indexed; the API is not useful to crawlers
+        Self {
+            rules: vec![RobotsRule {
+                user_agent: default_robots_user_agent(),
+                allow: Vec::new(),
+                disallow: vec!["/api/".into()],
+                crawl_delay: None,
+            }],
+            sitemaps: Vec::new(),
+        }
+    }
+}
+
+/// Operator policy that can be reloaded at runtime (SIGHUP or admin endpoint).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Human-readable instance name shown by clients.
+    pub service_name: Option<String>,
+    pub contact_email: Option<String>,
+    pub terms_url: Option<String>,
+    pub privacy_url: Option<String>,
+    pub signup_mode: SignupMode,
+    pub signup_limits: SignupLimitsConfig,
+    pub signup_recovery: SignupRecoveryConfig,
+    pub idn_labels: IdnMode,
+    pub confusable_labels: ConfusableMode,
+    pub typosquat: TyposquatConfig,
+    /// Maximum number of records (not counting apex SOA/NS) per user zone.
+    pub max_records_per_zone: Option<usize>,
+    /// Ceiling on user RRsets per zone; exceeding it yields 413.
+    pub max_rrsets_per_zone: Option<usize>,
+    /// Ceiling on the zone's presentation-format size in bytes; exceeding it yields 413.
+    pub max_zone_bytes: Option<usize>,
+    /// Zone updates (`PUT /api/zone`, sync apply) one user may make per minute.
+    pub max_zone_updates_per_minute: Option<u32>,
+    /// Zone updates one user may make per day.
+    pub max_zone_updates_per_day: Option<u32>,
+    /// TTL applied to submitted records that omit one.
+    pub default_ttl: u32,
+    /// Submitted TTLs are clamped to `min_ttl..=max_ttl`.
+    pub min_ttl: u32,
+    pub max_ttl: u32,
+    /// Record types accepted by `put_zone`; empty means the built-in default.
+    pub allowed_record_types: Vec<String>,
+    pub features: FeatureFlags,
+    /// Fewest nameservers accepted by `set_ns_external`.
+    pub min_external_ns: usize,
+    /// Most nameservers accepted by `set_ns_external`.
+    pub max_external_ns: usize,
+    /// Labels reserved in addition to the static `AppConfig` list.
+    pub disallowed_subdomains: Vec<String>,
+    /// Free-form notice shown to users (e.g. upcoming maintenance).
+    pub announcement: Option<String>,
+    pub maintenance: MaintenanceConfig,
+    pub content: ContentPolicy,
+    pub email_domains: EmailDomainPolicy,
+    pub security_txt: SecurityTxtConfig,
+    pub robots: RobotsConfig,
+    pub reconcile: ReconcileConfig,
+    pub ns_health: NsHealthConfig,
+    pub ns_preflight: NsPreflightConfig,
+    pub serial_check: SerialCheckConfig,
+    pub expiry: ExpiryConfig,
+    pub dangling: DanglingConfig,
+    pub snapshots: SnapshotConfig,
+    pub retention: RetentionConfig,
+    pub abuse_scan: AbuseScanConfig,
+    pub reputation: ReputationConfig,
+    pub alerts: AlertsConfig,
+    /// Contents of `AppConfig::reserved_labels_file`, loaded alongside the policy.
+    #[serde(skip)]
+    pub reserved_labels: ReservedLabels,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            service_name: None,
+            contact_email: None,
+            terms_url: None,
+            privacy_url: None,
+            signup_mode: SignupMode::Open,
+            signup_limits: SignupLimitsConfig::default(),
+            signup_recovery: SignupRecoveryConfig::default(),
+            idn_labels: IdnMode::Restricted,
+            confusable_labels: ConfusableMode::Flag,
+            typosquat: TyposquatConfig::default(),
+            max_records_per_zone: None,
+            max_rrsets_per_zone: None,
+            max_zone_bytes: None,
+            max_zone_updates_per_minute: None,
+            max_zone_updates_per_day: None,
+            default_ttl: 3600,
+            min_ttl: 1,
+            max_ttl: 604_800,
+            allowed_record_types: Vec::new(),
+            features: FeatureFlags::default(),
+            min_external_ns: 1,
+            max_external_ns: 6,
+            disallowed_subdomains: Vec::new(),
+            announcement: None,
+            maintenance: MaintenanceConfig::default(),
+            content: ContentPolicy::default(),
+            email_domains: EmailDomainPolicy::default(),
+            security_txt: SecurityTxtConfig::default(),
+            robots: RobotsConfig::default(),
+            reconcile: ReconcileConfig::default(),
+            ns_health: NsHealthConfig::default(),
+            ns_preflight: NsPreflightConfig::default(),
+            serial_check: SerialCheckConfig::default(),
+            expiry: ExpiryConfig::default(),
+            dangling: DanglingConfig::default(),
+            snapshots: SnapshotConfig::default(),
+            retention: RetentionConfig::default(),
+            abuse_scan: AbuseScanConfig::default(),
+            reputation: ReputationConfig::default(),
+            alerts: AlertsConfig::default(),
+            reserved_labels: ReservedLabels::default(),
+        }
+    }
+}
+
+impl PolicyConfig {
+    /// Load every reloadable source referenced by the static configuration.
+    pub fn from_sources(config: &AppConfig) -> anyhow::Result<Self> {
+        let mut policy = match &config.policy_file {
+            Some(path) => PolicyConfig::load(path)?,
+            None => PolicyConfig::default(),
+        };
+        if let Some(path) = &config.reserved_labels_file {
+            policy.reserved_labels = ReservedLabels::load(path)?;
+        }
+        Ok(policy)
+    }
+
+    /// Parse a TOML policy file from disk.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read policy file {}", path.display()))?;
+        let mut policy: PolicyConfig = toml::from_str(&text)
+            .with_context(|| format!("failed to parse policy file {}", path.display()))?;
+        policy.disallowed_subdomains = policy
+            .disallowed_subdomains
+            .iter()
+            .map(|label| label.trim().to_ascii_lowercase())
+            .filter(|label| !label.is_empty())
+            .collect();
+        for field in [
+            &mut policy.service_name,
+            &mut policy.contact_email,
+            &mut policy.terms_url,
+            &mut policy.privacy_url,
+            &mut policy.announcement,
+            &mut policy.maintenance.message,
+        ] {
+            *field = field
+                .take()
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty());
+        }
+        policy.allowed_record_types = policy
+            .allowed_record_types
+            .iter()
+            .map(|rrtype| rrtype.trim().to_ascii_uppercase())
+            .filter(|rrtype| !rrtype.is_empty())
+            .collect();
+        policy.content.blocked_cname_targets = policy
+            .content
+            .blocked_cname_targets
+            .iter()
+            .map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+        policy.abuse_scan.flagged_domains = policy
+            .abuse_scan
+            .flagged_domains
+            .iter()
+            .map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+        for list in [
+            &mut policy.reputation.ip_blocklists,
+            &mut policy.reputation.domain_blocklists,
+            &mut policy.email_domains.allowed,
+            &mut policy.email_domains.blocked,
+        ] {
+            *list = list
+                .iter()
+                .map(|zone| zone.trim().trim_end_matches('.').to_ascii_lowercase())
+                .filter(|zone| !zone.is_empty())
+                .collect();
+        }
+        if let Some(expires) = &policy.security_txt.expires {
+            chrono::DateTime::parse_from_rfc3339(expires)
+                .context("security_txt.expires must be an RFC 3339 timestamp")?;
+        }
+        if policy.reconcile.interval_secs < 60 {
+            anyhow::bail!("reconcile.interval_secs must be at least 60");
+        }
+        if policy.ns_health.interval_secs < 60 {
+            anyhow::bail!("ns_health.interval_secs must be at least 60");
+        }
+        if policy.ns_health.timeout_secs == 0 || policy.ns_health.notify_after == 0 {
+            anyhow::bail!("ns_health.timeout_secs and ns_health.notify_after must be at least 1");
+        }
+        if policy.ns_preflight.timeout_secs == 0 {
+            anyhow::bail!("ns_preflight.timeout_secs must be at least 1");
+        }
+        if policy.serial_check.interval_secs < 60 || policy.serial_check.timeout_secs == 0 {
+            anyhow::bail!(
+                "serial_check.interval_secs must be at least 60 and timeout_secs at least 1"
+            );
+        }
+        if policy.serial_check.sample == Some(0) {
+            anyhow::bail!("serial_check.sample must be at least 1");
+        }
+        if policy.expiry.interval_secs < 60 || policy.expiry.unused_after_days == 0 {
+            anyhow::bail!(
+                "expiry.interval_secs must be at least 60 and unused_after_days at least 1"
+            );
+        }
+        if policy.expiry.remind_before_days >= policy.expiry.unused_after_days {
+            anyhow::bail!("expiry.remind_before_days must be less than unused_after_days");
+        }
+        if policy.dangling.interval_secs < 60 || policy.dangling.quarantine_days == 0 {
+            anyhow::bail!(
+                "dangling.interval_secs must be at least 60 and quarantine_days at least 1"
+            );
+        }
+        if policy.snapshots.enabled && policy.snapshots.dir.is_none() {
+            anyhow::bail!("snapshots.enabled requires snapshots.dir");
+        }
+        if policy.snapshots.interval_secs < 60 || policy.snapshots.keep == 0 {
+            anyhow::bail!("snapshots.interval_secs must be at least 60 and keep at least 1");
+        }
+        if policy.retention.interval_secs < 60 {
+            anyhow::bail!("retention.interval_secs must be at least 60");
+        }
+        if [
+            policy.retention.audit_log_days,
+            policy.retention.change_history_days,
+            policy.retention.webhook_deliveries_days,
+        ]
+        .contains(&Some(0))
+        {
+            anyhow::bail!("retention.*_days must be at least 1");
+        }
+        if let Some(s3) = &policy.retention.s3 {
+            url::Url::parse(&s3.endpoint).context("invalid retention.s3.endpoint")?;
+        }
+        if policy.abuse_scan.interval_secs < 60
+            || policy.abuse_scan.similar_names < 2
+            || policy.abuse_scan.churn_changes < 1
+            || policy.abuse_scan.churn_window_hours == 0
+        {
+            anyhow::bail!(
+                "abuse_scan.interval_secs must be at least 60, similar_names at least 2, \
+                 and churn_changes and churn_window_hours at least 1"
+            );
+        }
+        if policy.reputation.timeout_secs == 0 {
+            anyhow::bail!("reputation.timeout_secs must be at least 1");
+        }
+        if policy.alerts.pdns_check_secs != 0 && policy.alerts.pdns_check_secs < 10 {
+            anyhow::bail!("alerts.pdns_check_secs must be 0 or at least 10");
+        }
+        if policy.alerts.signup_spike == Some(0)
+            || !(1..=1440).contains(&policy.alerts.signup_spike_window_mins)
+        {
+            anyhow::bail!(
+                "alerts.signup_spike must be at least 1 and signup_spike_window_mins 1 to 1440"
+            );
+        }
+        for sink in &policy.alerts.sinks {
+            let url = match sink {
+                AlertSink::Slack { url } | AlertSink::Webhook { url, .. } => url,
+                AlertSink::Matrix { homeserver, .. } => homeserver,
+            };
+            url::Url::parse(url).with_context(|| format!("invalid alert sink URL {url:?}"))?;
+        }
+        for (job, schedule) in [
+            ("reconcile", &policy.reconcile.schedule),
+            ("ns_health", &policy.ns_health.schedule),
+            ("serial_check", &policy.serial_check.schedule),
+            ("expiry", &policy.expiry.schedule),
+            ("dangling", &policy.dangling.schedule),
+            ("snapshots", &policy.snapshots.schedule),
+            ("retention", &policy.retention.schedule),
+            ("abuse_scan", &policy.abuse_scan.schedule),
+            ("signup_recovery", &policy.signup_recovery.schedule),
+        ] {
+            if let Some(schedule) = schedule {
+                crate::jobs::Cron::parse(schedule)
+                    .with_context(|| format!("invalid {job}.schedule"))?;
+            }
+        }
+        if policy.signup_limits.per_ip_per_day == Some(0)
+            || policy.signup_limits.per_subnet_per_day == Some(0)
+        {
+            anyhow::bail!("signup_limits.per_ip_per_day and per_subnet_per_day must be at least 1");
+        }
+        if policy.signup_limits.ipv4_prefix > 32 || policy.signup_limits.ipv6_prefix > 128 {
+            anyhow::bail!(
+                "signup_limits.ipv4_prefix must be at most 32 and ipv6_prefix at most 128"
+            );
+        }
+        if policy.signup_recovery.interval_secs < 60 || policy.signup_recovery.max_attempts == 0 {
+            anyhow::bail!(
+                "signup_recovery.interval_secs must be at least 60 and max_attempts at least 1"
+            );
+        }
+        if policy.max_zone_updates_per_minute == Some(0)
+            || policy.max_zone_updates_per_day == Some(0)
+        {
+            anyhow::bail!(
+                "max_zone_updates_per_minute and max_zone_updates_per_day must be at least 1"
+            );
+        }
+        if policy.min_ttl == 0 {
+            anyhow::bail!("min_ttl must be at least 1");
+        }
+        if !(policy.min_ttl..=policy.max_ttl).contains(&policy.default_ttl) {
+            anyhow::bail!("default_ttl must lie between min_ttl and max_ttl");
+        }
+        if policy.min_external_ns == 0 {
+            anyhow::bail!("min_external_ns must be at least 1");
+        }
+        if policy.max_external_ns < policy.min_external_ns {
+            anyhow::bail!("max_external_ns must not be smaller than min_external_ns");
+        }
+        Ok(policy)
+    }
+
+    /// Whether any zone size ceiling requires fetching the zone before writes.
+    pub fn has_zone_limits(&self) -> bool {
+        self.max_records_per_zone.is_some()
+            || self.max_rrsets_per_zone.is_some()
+            || self.max_zone_bytes.is_some()
+    }
+
+    /// TTL to store for a submitted record: the default when omitted, clamped to the bounds.
+    pub fn effective_ttl(&self, requested: Option<u32>) -> u32 {
+        requested
+            .unwrap_or(self.default_ttl)
+            .clamp(self.min_ttl, self.max_ttl)
+    }
+
+    /// Return the record types users may create, honouring feature flags.
+    pub fn effective_allowed_record_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = if self.allowed_record_types.is_empty() {
+            DEFAULT_ALLOWED_RECORD_TYPES
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        } else {
+            self.allowed_record_types
+                .iter()
+                .filter(|rrtype| !rrtype.eq_ignore_ascii_case("LUA"))
+                .cloned()
+                .collect()
+        };
+        if self.features.is_enabled(Feature::LuaRecords) {
+            types.push("LUA".into());
+        }
+        types
+    }
+
+    /// Check whether users may create records of the given (uppercase) type.
+    pub fn is_record_type_allowed(&self, rrtype: &str) -> bool {
+        self.effective_allowed_record_types()
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(rrtype))
+    }
+
+    /// Check whether the label is reserved by the reloadable policy.
+    pub fn is_disallowed_subdomain(&self, label: &str) -> bool {
+        self.disallowed_subdomains
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(label))
+            || self.reserved_labels.matches(label)
+    }
+}
+
+/// Reserved labels read from a file: exact labels plus regex patterns.
+#[derive(Clone, Debug, Default)]
+pub struct ReservedLabels {
+    pub exact: Vec<String>,
+    pub patterns: Vec<Regex>,
+}
+
+impl ReservedLabels {
+    /// Parse a reserved-labels file.
+    ///
+    /// One entry per line; blank lines and `#` comments are ignored. Lines
+    /// starting with `^` are regular expressions matched against the
+    /// lowercased label, everything else is an exact label.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read reserved labels file {}", path.display()))?;
+        let mut reserved = ReservedLabels::default();
+        for (idx, line) in text.lines().enumerate() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            if entry.starts_with('^') {
+                let pattern = Regex::new(entry).with_context(|| {
+                    format!("invalid pattern on line {} of {}", idx + 1, path.display())
+                })?;
+                reserved.patterns.push(pattern);
+            } else {
+                reserved.exact.push(entry.to_ascii_lowercase());
+            }
+        }
+        Ok(reserved)
+    }
+
+    /// Check whether the label equals a listed label or matches a pattern.
+    pub fn matches(&self, label: &str) -> bool {
+        let needle = label.to_ascii_lowercase();
+        self.exact.contains(&needle)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&needle))
+    }
+}
+
+/// Connection settings for a single PowerDNS API endpoint.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PdnsEndpointConfig {
+    pub url: String,
+    pub key: Option<String>,
+    pub key_file: Option<PathBuf>,
+    #[serde(default = "default_server_id")]
+    pub server_id: String,
+}
+
+fn default_server_id() -> String {
+    "localhost".to_string()
+}
+
+/// Base/sub PowerDNS pair serving one base domain.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DomainBackendConfig {
+    pub base: PdnsEndpointConfig,
+    pub sub: PdnsEndpointConfig,
+}
+
+/// Per-base-domain PowerDNS overrides; unlisted domains use the CLI endpoints.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackendsConfig {
+    #[serde(default)]
+    pub domains: BTreeMap<String, DomainBackendConfig>,
+}
+
+impl BackendsConfig {
+    /// Parse a TOML backends file, normalizing domain keys.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read backends file {}", path.display()))?;
+        let parsed: BackendsConfig = toml::from_str(&text)
+            .with_context(|| format!("failed to parse backends file {}", path.display()))?;
+        let mut domains = BTreeMap::new();
+        for (domain, backend) in parsed.domains {
+            let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+            if domains.insert(domain.clone(), backend).is_some() {
+                anyhow::bail!("backend for {domain} is listed twice");
+            }
+        }
+        Ok(BackendsConfig { domains })
+    }
+}
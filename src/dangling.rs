@@ -0,0 +1,189 @@
+//! Cleanup of parent-zone delegations that point nowhere: no user owns the
+//! label and sub-PDNS has no zone for it. Each is quarantined first and only
+//! removed once it stayed dangling for `dangling.quarantine_days`.
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::config::PolicyConfig;
+use crate::db::{audit_repo, dangling_repo, user_repo};
+use crate::jobs::{Job, Schedule};
+use crate::powerdns::types::PdnsRrset;
+use crate::{AppState, delegations};
+
+/// Audit log actor for removals made by the cleanup.
+const ACTOR: &str = "dangling_delegations";
+
+/// Serializes scheduled and operator-triggered sweeps.
+static RUNNING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Outcome of one sweep.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Sweep {
+    /// Dangling delegations found, including those still in quarantine.
+    pub found: usize,
+    /// Delegations removed because their quarantine ended.
+    pub removed: Vec<String>,
+}
+
+/// Scheduled sweeps, per `[dangling]` in the policy.
+pub struct DanglingJob;
+
+#[async_trait]
+impl Job for DanglingJob {
+    fn name(&self) -> &'static str {
+        "dangling_delegations"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.dangling;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let sweep = sweep(state).await?;
+        Ok(format!(
+            "found {} dangling delegation(s), removed {}",
+            sweep.found,
+            sweep.removed.len()
+        ))
+    }
+}
+
+/// Quarantine newly found dangling delegations and remove the expired ones.
+pub async fn sweep(state: &AppState) -> anyhow::Result<Sweep> {
+    let _running = RUNNING.lock().await;
+    let started_at = Utc::now();
+    let quarantine = chrono::Duration::days(i64::from(state.policy().dangling.quarantine_days));
+
+    let found = scan(state).await?;
+    for delegation in &found {
+        dangling_repo::mark_seen(
+            &state.db,
+            &delegation.name,
+            &delegation.base_domain,
+            &delegation.nameservers,
+            started_at,
+        )
+        .await?;
+    }
+    // anything not seen this time got a user, a zone, or was removed by hand
+    dangling_repo::prune(&state.db, started_at).await?;
+
+    let mut sweep = Sweep {
+        found: found.len(),
+        removed: Vec::new(),
+    };
+    for entry in dangling_repo::list(&state.db).await? {
+        if started_at - entry.first_seen_at < quarantine {
+            continue;
+        }
+        remove(
+            state,
+            &entry.name,
+            &entry.base_domain,
+            &entry.nameservers,
+            entry.first_seen_at,
+        )
+        .await?;
+        sweep.removed.push(entry.name);
+    }
+    Ok(sweep)
+}
+
+/// A delegation found by `scan`.
+struct Found {
+    name: String,
+    base_domain: String,
+    nameservers: Vec<String>,
+}
+
+/// Delegations one label below a parent zone with no user and no sub-PDNS
+/// zone. Reserved labels are skipped, since operators delegate those by hand.
+async fn scan(state: &AppState) -> anyhow::Result<Vec<Found>> {
+    let users: HashSet<(String, String)> = user_repo::list_all(&state.db)
+        .await?
+        .into_iter()
+        .map(|user| (user.subdomain, user.base_domain))
+        .collect();
+
+    let mut found = Vec::new();
+    for base_domain in state.config.base_domain_roots() {
+        let parent_zone = state.config.parent_zone_name(base_domain);
+        let suffix = format!(".{}", parent_zone.to_ascii_lowercase());
+        let zones: HashSet<String> = state
+            .sub_pdns_for(base_domain)
+            .list_zones()
+            .await?
+            .into_iter()
+            .map(|zone| zone.name.to_ascii_lowercase())
+            .collect();
+        let parent = state
+            .base_pdns_for(base_domain)
+            .get_zone(&parent_zone)
+            .await?;
+
+        for rrset in parent.rrsets.unwrap_or_default() {
+            if !rrset.rrtype.eq_ignore_ascii_case("NS") {
+                continue;
+            }
+            let name = rrset.name.to_ascii_lowercase();
+            let Some(label) = name.strip_suffix(&suffix) else {
+                continue;
+            };
+            if label.is_empty()
+                || label.contains('.')
+                || state.is_disallowed_subdomain(label)
+                || users.contains(&(label.to_string(), base_domain.to_string()))
+                || zones.contains(&name)
+            {
+                continue;
+            }
+            found.push(Found {
+                name: rrset.name,
+                base_domain: base_domain.to_string(),
+                nameservers: rrset
+                    .records
+                    .into_iter()
+                    .map(|record| record.content)
+                    .collect(),
+            });
+        }
+    }
+    Ok(found)
+}
+
+async fn remove(
+    state: &AppState,
+    name: &str,
+    base_domain: &str,
+    nameservers: &[String],
+    first_seen_at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let _guard = state
+        .in_flight
+        .begin(format!("removing dangling delegation {name}"));
+    let rrset = PdnsRrset {
+        name: name.to_string(),
+        rrtype: "NS".into(),
+        ttl: state.config.ns_ttl,
+        changetype: Some("DELETE".into()),
+        records: Vec::new(),
+        comments: Vec::new(),
+    };
+    delegations::patch(state, base_domain, &[rrset]).await?;
+    dangling_repo::delete(&state.db, name).await?;
+    audit_repo::insert(
+        &state.db,
+        ACTOR,
+        "delegation.removed",
+        name,
+        &serde_json::json!({ "nameservers": nameservers, "first_seen_at": first_seen_at }),
+    )
+    .await?;
+    tracing::warn!("removed dangling delegation {name}, first seen {first_seen_at}");
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Repository functions for the abuse review queue.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// A zone flagged for review.
+#[derive(Debug, Clone)]
+pub struct AbuseFlag {
+    pub id: i64,
+    pub user_id: i64,
+    pub zone: String,
+    /// Heuristic that raised the flag, e.g. `similar_names`.
+    pub reason: String,
+    /// JSON object with the evidence.
+    pub details: String,
+    /// `open` or `dismissed`.
+    pub status: String,
+    pub first_flagged_at: DateTime<Utc>,
+    pub last_flagged_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Raise a flag, or refresh the evidence of an open one. Returns `true` when
+/// a new flag was created; dismissed flags are left alone.
+pub async fn flag(
+    db: &SqlitePool,
+    user_id: i64,
+    zone: &str,
+    reason: &str,
+    details: &serde_json::Value,
+) -> sqlx::Result<bool> {
+    let now = Utc::now();
+    let res = sqlx::query(
+        r#"
+        INSERT INTO abuse_flags (user_id, zone, reason, details, first_flagged_at, last_flagged_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT (user_id, reason) DO NOTHING
+        "#,
+    )
+    .bind(user_id)
+    .bind(zone)
+    .bind(reason)
+    .bind(details.to_string())
+    .bind(now)
+    .bind(now)
+    .execute(db)
+    .await?;
+    if res.rows_affected() > 0 {
+        return Ok(true);
+    }
+    sqlx::query(
+        r#"
+        UPDATE abuse_flags SET details = ?, last_flagged_at = ?
+        WHERE user_id = ? AND reason = ? AND status = 'open'
+        "#,
+    )
+    .bind(details.to_string())
+    .bind(now)
+    .bind(user_id)
+    .bind(reason)
+    .execute(db)
+    .await?;
+    Ok(false)
+}
+
+/// Flags with the given status (all when `None`), newest first.
+pub async fn list(db: &SqlitePool, status: Option<&str>) -> sqlx::Result<Vec<AbuseFlag>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, user_id, zone, reason, details, status,
+               first_flagged_at, last_flagged_at, reviewed_at
+        FROM abuse_flags
+        WHERE ? IS NULL OR status = ?
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(status)
+    .bind(status)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| AbuseFlag {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            zone: row.get("zone"),
+            reason: row.get("reason"),
+            details: row.get("details"),
+            status: row.get("status"),
+            first_flagged_at: row.get::<DateTime<Utc>, _>("first_flagged_at"),
+            last_flagged_at: row.get::<DateTime<Utc>, _>("last_flagged_at"),
+            reviewed_at: row.get::<Option<DateTime<Utc>>, _>("reviewed_at"),
+        })
+        .collect())
+}
+
+/// Set a flag's review status; returns `false` if it does not exist.
+pub async fn set_status(db: &SqlitePool, id: i64, status: &str) -> sqlx::Result<bool> {
+    let res = sqlx::query("UPDATE abuse_flags SET status = ?, reviewed_at = ? WHERE id = ?")
+        .bind(status)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
@@ -0,0 +1,75 @@
+//! Repository functions for the operator audit log.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// One action taken by an operator or a background job.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// `admin`, or the name of the job that acted.
+    pub actor: String,
+    pub action: String,
+    /// Usually the affected zone or delegation.
+    pub target: String,
+    /// JSON object describing the action.
+    pub details: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Append an entry.
+pub async fn insert(
+    db: &SqlitePool,
+    actor: &str,
+    action: &str,
+    target: &str,
+    details: &serde_json::Value,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (actor, action, target, details, occurred_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(actor)
+    .bind(action)
+    .bind(target)
+    .bind(details.to_string())
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// The most recent entries, newest first, optionally only those older than `before`.
+pub async fn list(
+    db: &SqlitePool,
+    before: Option<i64>,
+    limit: i64,
+) -> sqlx::Result<Vec<AuditEntry>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, actor, action, target, details, occurred_at
+        FROM audit_log
+        WHERE ? IS NULL OR id < ?
+        ORDER BY id DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(before)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditEntry {
+            id: row.get("id"),
+            actor: row.get("actor"),
+            action: row.get("action"),
+            target: row.get("target"),
+            details: row.get("details"),
+            occurred_at: row.get::<DateTime<Utc>, _>("occurred_at"),
+        })
+        .collect())
+}
@@ -0,0 +1,83 @@
+//! Repository functions for quarantined dangling delegations.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// A parent-zone delegation with no user and no zone behind it.
+#[derive(Debug, Clone)]
+pub struct DanglingDelegation {
+    /// Fully qualified owner name with trailing dot.
+    pub name: String,
+    pub base_domain: String,
+    pub nameservers: Vec<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Every quarantined delegation, oldest first.
+pub async fn list(db: &SqlitePool) -> sqlx::Result<Vec<DanglingDelegation>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name, base_domain, nameservers, first_seen_at, last_seen_at
+        FROM dangling_delegations
+        ORDER BY first_seen_at, name
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DanglingDelegation {
+            name: row.get("name"),
+            base_domain: row.get("base_domain"),
+            nameservers: serde_json::from_str(row.get("nameservers")).unwrap_or_default(),
+            first_seen_at: row.get::<DateTime<Utc>, _>("first_seen_at"),
+            last_seen_at: row.get::<DateTime<Utc>, _>("last_seen_at"),
+        })
+        .collect())
+}
+
+/// Record a sighting, keeping the first one's timestamp.
+pub async fn mark_seen(
+    db: &SqlitePool,
+    name: &str,
+    base_domain: &str,
+    nameservers: &[String],
+    seen_at: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO dangling_delegations (name, base_domain, nameservers, first_seen_at, last_seen_at)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT (name) DO UPDATE SET
+          nameservers = excluded.nameservers,
+          last_seen_at = excluded.last_seen_at
+        "#,
+    )
+    .bind(name)
+    .bind(base_domain)
+    .bind(serde_json::to_string(nameservers).unwrap_or_else(|_| "[]".into()))
+    .bind(seen_at)
+    .bind(seen_at)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Drop entries not seen since `seen_before`, i.e. no longer dangling.
+pub async fn prune(db: &SqlitePool, seen_before: DateTime<Utc>) -> sqlx::Result<u64> {
+    let res = sqlx::query("DELETE FROM dangling_delegations WHERE last_seen_at < ?")
+        .bind(seen_before)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// Forget one entry once its delegation is removed.
+pub async fn delete(db: &SqlitePool, name: &str) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM dangling_delegations WHERE name = ?")
+        .bind(name)
+        .execute(db)
+        .await?;
+    Ok(())
+}
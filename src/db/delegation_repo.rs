@@ -0,0 +1,105 @@
+//! Repository functions for the delegation inventory, the database mirror
+//! of the NS RRsets in the parent zones.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqliteExecutor, SqlitePool};
+
+/// An NS RRset of a parent zone.
+#[derive(Debug, Clone)]
+pub struct Delegation {
+    /// Fully qualified owner name with trailing dot.
+    pub name: String,
+    pub base_domain: String,
+    pub nameservers: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Every delegation under `base_domain`, by name.
+pub async fn list(db: &SqlitePool, base_domain: &str) -> sqlx::Result<Vec<Delegation>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name, base_domain, nameservers, updated_at
+        FROM delegations
+        WHERE base_domain = ?
+        ORDER BY name
+        "#,
+    )
+    .bind(base_domain)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Delegation {
+            name: row.get("name"),
+            base_domain: row.get("base_domain"),
+            nameservers: serde_json::from_str(row.get("nameservers")).unwrap_or_default(),
+            updated_at: row.get::<DateTime<Utc>, _>("updated_at"),
+        })
+        .collect())
+}
+
+/// Delegations under `base_domain` other than the parent zone's own NS RRset.
+pub async fn count_delegated(
+    db: &SqlitePool,
+    base_domain: &str,
+    parent_zone: &str,
+) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM delegations WHERE base_domain = ? AND name != ?")
+        .bind(base_domain)
+        .bind(parent_zone)
+        .fetch_one(db)
+        .await
+}
+
+/// Record the nameservers `name` is delegated to.
+pub async fn upsert(
+    db: impl SqliteExecutor<'_>,
+    name: &str,
+    base_domain: &str,
+    nameservers: &[String],
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO delegations (name, base_domain, nameservers, updated_at)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT (name) DO UPDATE SET
+          base_domain = excluded.base_domain,
+          nameservers = excluded.nameservers,
+          updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(name)
+    .bind(base_domain)
+    .bind(serde_json::to_string(nameservers).unwrap_or_else(|_| "[]".into()))
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Forget the delegation of `name`.
+pub async fn delete(db: &SqlitePool, name: &str) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM delegations WHERE name = ?")
+        .bind(name)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Replace the inventory of `base_domain` with `delegations`, as read from
+/// the parent zone.
+pub async fn replace_all(
+    db: &SqlitePool,
+    base_domain: &str,
+    delegations: &[(String, Vec<String>)],
+) -> sqlx::Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM delegations WHERE base_domain = ?")
+        .bind(base_domain)
+        .execute(&mut *tx)
+        .await?;
+    for (name, nameservers) in delegations {
+        upsert(&mut *tx, name, base_domain, nameservers).await?;
+    }
+    tx.commit().await
+}
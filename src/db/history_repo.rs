@@ -0,0 +1,142 @@
+//! Repository functions for the per-user change history.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+
+/// One recorded change to a user's zone or account.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub id: i64,
+    pub zone: String,
+    pub event_type: String,
+    /// The event as delivered to webhooks, serialized as JSON.
+    pub payload: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Append a change to the user's history.
+pub async fn insert(
+    db: &SqlitePool,
+    user_id: i64,
+    zone: &str,
+    event_type: &str,
+    payload: &str,
+    occurred_at: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO change_history (user_id, zone, event_type, payload, occurred_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(user_id)
+    .bind(zone)
+    .bind(event_type)
+    .bind(payload)
+    .bind(occurred_at)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Number of `event_type` changes per user since `since`, for users with any.
+pub async fn count_by_user_since(
+    db: &SqlitePool,
+    event_type: &str,
+    since: DateTime<Utc>,
+) -> sqlx::Result<HashMap<i64, i64>> {
+    let rows: Vec<(i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT user_id, COUNT(*)
+        FROM change_history
+        WHERE event_type = ? AND occurred_at >= ?
+        GROUP BY user_id
+        "#,
+    )
+    .bind(event_type)
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// The user's most recent changes, newest first, optionally only those older than `before`.
+pub async fn list_for_user(
+    db: &SqlitePool,
+    user_id: i64,
+    before: Option<i64>,
+    limit: i64,
+) -> sqlx::Result<Vec<Change>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, zone, event_type, payload, occurred_at
+        FROM change_history
+        WHERE user_id = ? AND (? IS NULL OR id < ?)
+        ORDER BY id DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(user_id)
+    .bind(before)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Change {
+            id: row.get("id"),
+            zone: row.get("zone"),
+            event_type: row.get("event_type"),
+            payload: row.get("payload"),
+            occurred_at: row.get::<DateTime<Utc>, _>("occurred_at"),
+        })
+        .collect())
+}
+
+/// The user's whole history, oldest first.
+pub async fn list_all_for_user(db: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<Change>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, zone, event_type, payload, occurred_at
+        FROM change_history
+        WHERE user_id = ?
+        ORDER BY id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Change {
+            id: row.get("id"),
+            zone: row.get("zone"),
+            event_type: row.get("event_type"),
+            payload: row.get("payload"),
+            occurred_at: row.get::<DateTime<Utc>, _>("occurred_at"),
+        })
+        .collect())
+}
+
+/// Distinct `geo.country` values recorded in the user's `event_type` entries.
+pub async fn list_countries(
+    db: &SqlitePool,
+    user_id: i64,
+    event_type: &str,
+) -> sqlx::Result<Vec<String>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT json_extract(payload, '$.geo.country')
+        FROM change_history
+        WHERE user_id = ? AND event_type = ?
+          AND json_extract(payload, '$.geo.country') IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(event_type)
+    .fetch_all(db)
+    .await
+}
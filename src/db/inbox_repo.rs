@@ -0,0 +1,168 @@
+//! Repository functions for the in-app notification inbox.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// Notifications kept per user; older ones are dropped on insert.
+const KEEP_PER_USER: i64 = 200;
+
+/// One notification in a user's inbox.
+#[derive(Debug, Clone)]
+pub struct InboxEntry {
+    pub id: i64,
+    /// Notification category, e.g. `delegation_health`.
+    pub category: String,
+    pub event_type: String,
+    pub subject: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// Add a notification to the inbox of `user_id`, dropping the oldest beyond
+/// the per-user limit.
+pub async fn insert(
+    db: &SqlitePool,
+    user_id: i64,
+    category: &str,
+    event_type: &str,
+    subject: &str,
+    body: &str,
+) -> sqlx::Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO notifications (user_id, category, event_type, subject, body, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(user_id)
+    .bind(category)
+    .bind(event_type)
+    .bind(subject)
+    .bind(body)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        DELETE FROM notifications
+        WHERE user_id = ? AND id <= (
+            SELECT id FROM notifications WHERE user_id = ?
+            ORDER BY id DESC LIMIT 1 OFFSET ?
+        )
+        "#,
+    )
+    .bind(user_id)
+    .bind(user_id)
+    .bind(KEEP_PER_USER)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Add the same notification to every user's inbox; returns how many
+/// received it. Rare enough that the per-user limit is left to later inserts.
+pub async fn insert_for_all(
+    db: &SqlitePool,
+    category: &str,
+    event_type: &str,
+    subject: &str,
+    body: &str,
+) -> sqlx::Result<u64> {
+    let res = sqlx::query(
+        r#"
+        INSERT INTO notifications (user_id, category, event_type, subject, body, created_at)
+        SELECT id, ?, ?, ?, ?, ? FROM users
+        "#,
+    )
+    .bind(category)
+    .bind(event_type)
+    .bind(subject)
+    .bind(body)
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// A page of the inbox, newest first, optionally only unread entries.
+pub async fn list_for_user(
+    db: &SqlitePool,
+    user_id: i64,
+    unread_only: bool,
+    before: Option<i64>,
+    limit: i64,
+) -> sqlx::Result<Vec<InboxEntry>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, category, event_type, subject, body, created_at, read_at
+        FROM notifications
+        WHERE user_id = ? AND (? = 0 OR read_at IS NULL) AND (? IS NULL OR id < ?)
+        ORDER BY id DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(user_id)
+    .bind(unread_only)
+    .bind(before)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| InboxEntry {
+            id: row.get("id"),
+            category: row.get("category"),
+            event_type: row.get("event_type"),
+            subject: row.get("subject"),
+            body: row.get("body"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+            read_at: row.get::<Option<DateTime<Utc>>, _>("read_at"),
+        })
+        .collect())
+}
+
+/// Number of unread notifications of `user_id`.
+pub async fn count_unread(db: &SqlitePool, user_id: i64) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = ? AND read_at IS NULL")
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+}
+
+/// Mark one notification read; returns `false` when `user_id` has no such
+/// notification.
+pub async fn mark_read(db: &SqlitePool, user_id: i64, id: i64) -> sqlx::Result<bool> {
+    let res = sqlx::query(
+        r#"
+        UPDATE notifications SET read_at = COALESCE(read_at, ?)
+        WHERE user_id = ? AND id = ?
+        "#,
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Mark every unread notification of `user_id` up to `up_to` (all when
+/// `None`) read; returns how many changed.
+pub async fn mark_all_read(db: &SqlitePool, user_id: i64, up_to: Option<i64>) -> sqlx::Result<u64> {
+    let res = sqlx::query(
+        r#"
+        UPDATE notifications SET read_at = ?
+        WHERE user_id = ? AND read_at IS NULL AND (? IS NULL OR id <= ?)
+        "#,
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(up_to)
+    .bind(up_to)
+    .execute(db)
+    .await?;
+    Ok(res.rows_affected())
+}
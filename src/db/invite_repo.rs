@@ -0,0 +1,76 @@
+//! Repository functions for single-use signup invite codes.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// An invite code issued by an operator.
+#[derive(Debug, Clone)]
+pub struct Invite {
+    pub code: String,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    /// Zone name of the account that redeemed the code.
+    pub used_by: Option<String>,
+}
+
+/// Store a freshly generated invite code.
+pub async fn insert(db: &SqlitePool, code: &str, note: Option<&str>) -> sqlx::Result<()> {
+    sqlx::query("INSERT INTO invites (code, note, created_at) VALUES (?, ?, ?)")
+        .bind(code)
+        .bind(note)
+        .bind(Utc::now())
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// List every invite, newest first.
+pub async fn list(db: &SqlitePool) -> sqlx::Result<Vec<Invite>> {
+    let rows = sqlx::query(
+        "SELECT code, note, created_at, used_at, used_by FROM invites ORDER BY created_at DESC",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Invite {
+            code: row.get("code"),
+            note: row.get("note"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+            used_at: row.get("used_at"),
+            used_by: row.get("used_by"),
+        })
+        .collect())
+}
+
+/// Atomically mark an unused invite as redeemed; returns false if unavailable.
+pub async fn claim(db: &SqlitePool, code: &str, used_by: &str) -> sqlx::Result<bool> {
+    let res = sqlx::query(
+        "UPDATE invites SET used_at = ?, used_by = ? WHERE code = ? AND used_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(used_by)
+    .bind(code)
+    .execute(db)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Return a claimed invite to the pool after a failed signup.
+pub async fn release(db: &SqlitePool, code: &str) -> sqlx::Result<()> {
+    sqlx::query("UPDATE invites SET used_at = NULL, used_by = NULL WHERE code = ?")
+        .bind(code)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Delete an invite; returns whether a row was removed.
+pub async fn delete(db: &SqlitePool, code: &str) -> sqlx::Result<bool> {
+    let res = sqlx::query("DELETE FROM invites WHERE code = ?")
+        .bind(code)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
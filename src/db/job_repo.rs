@@ -0,0 +1,105 @@
+//! Repository functions for the background job run history.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+
+/// Runs kept per job; older rows are pruned on insert.
+const RUN_HISTORY_LIMIT: i64 = 100;
+
+/// One finished run of a scheduled job.
+#[derive(Debug, Clone)]
+pub struct JobRun {
+    pub id: i64,
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    /// `ok` or `failed`.
+    pub outcome: String,
+    /// Summary on success, the error on failure.
+    pub detail: Option<String>,
+}
+
+fn run_from_row(row: SqliteRow) -> JobRun {
+    JobRun {
+        id: row.get("id"),
+        name: row.get("name"),
+        started_at: row.get::<DateTime<Utc>, _>("started_at"),
+        finished_at: row.get::<DateTime<Utc>, _>("finished_at"),
+        duration_ms: row.get("duration_ms"),
+        outcome: row.get("outcome"),
+        detail: row.get("detail"),
+    }
+}
+
+/// Append a run and prune the job's history to the newest entries.
+pub async fn record(
+    db: &SqlitePool,
+    name: &str,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    outcome: &str,
+    detail: Option<&str>,
+) -> sqlx::Result<()> {
+    let duration_ms = (finished_at - started_at).num_milliseconds();
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO jobs (name, started_at, finished_at, duration_ms, outcome, detail)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(name)
+    .bind(started_at)
+    .bind(finished_at)
+    .bind(duration_ms)
+    .bind(outcome)
+    .bind(detail)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        DELETE FROM jobs
+        WHERE name = ? AND id NOT IN (
+            SELECT id FROM jobs WHERE name = ? ORDER BY id DESC LIMIT ?
+        )
+        "#,
+    )
+    .bind(name)
+    .bind(name)
+    .bind(RUN_HISTORY_LIMIT)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// The latest run of every job that has run at least once.
+pub async fn latest(db: &SqlitePool) -> sqlx::Result<Vec<JobRun>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, name, started_at, finished_at, duration_ms, outcome, detail
+        FROM jobs
+        WHERE id IN (SELECT MAX(id) FROM jobs GROUP BY name)
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(run_from_row).collect())
+}
+
+/// Most recent runs of one job, newest first.
+pub async fn list_for_job(db: &SqlitePool, name: &str, limit: i64) -> sqlx::Result<Vec<JobRun>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, name, started_at, finished_at, duration_ms, outcome, detail
+        FROM jobs
+        WHERE name = ?
+        ORDER BY id DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(name)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(run_from_row).collect())
+}
@@ -0,0 +1,148 @@
+//! Repository functions for the outgoing mail queue.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// One queued message.
+#[derive(Debug, Clone)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub recipient: String,
+    /// Name of the template it was rendered from, e.g. `verification`.
+    pub template: String,
+    pub subject: String,
+    pub body: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub failed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn row_from(row: sqlx::sqlite::SqliteRow) -> OutboxMessage {
+    OutboxMessage {
+        id: row.get("id"),
+        recipient: row.get("recipient"),
+        template: row.get("template"),
+        subject: row.get("subject"),
+        body: row.get("body"),
+        attempts: row.get("attempts"),
+        last_error: row.get("last_error"),
+        next_attempt_at: row.get::<DateTime<Utc>, _>("next_attempt_at"),
+        failed_at: row.get::<Option<DateTime<Utc>>, _>("failed_at"),
+        created_at: row.get::<DateTime<Utc>, _>("created_at"),
+    }
+}
+
+/// Queue a message for immediate sending; returns its id.
+pub async fn insert(
+    db: &SqlitePool,
+    recipient: &str,
+    template: &str,
+    subject: &str,
+    body: &str,
+) -> sqlx::Result<i64> {
+    let now = Utc::now();
+    let result = sqlx::query(
+        r#"
+        INSERT INTO mail_outbox (recipient, template, subject, body, next_attempt_at, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(recipient)
+    .bind(template)
+    .bind(subject)
+    .bind(body)
+    .bind(now)
+    .bind(now)
+    .execute(db)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Messages due for an attempt at `now`, oldest first.
+pub async fn list_due(
+    db: &SqlitePool,
+    now: DateTime<Utc>,
+    limit: i64,
+) -> sqlx::Result<Vec<OutboxMessage>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, recipient, template, subject, body, attempts, last_error,
+               next_attempt_at, failed_at, created_at
+        FROM mail_outbox
+        WHERE failed_at IS NULL AND next_attempt_at <= ?
+        ORDER BY next_attempt_at, id
+        LIMIT ?
+        "#,
+    )
+    .bind(now)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(row_from).collect())
+}
+
+/// Queued and failed messages, newest first.
+pub async fn list(db: &SqlitePool, limit: i64) -> sqlx::Result<Vec<OutboxMessage>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, recipient, template, subject, body, attempts, last_error,
+               next_attempt_at, failed_at, created_at
+        FROM mail_outbox
+        ORDER BY id DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(row_from).collect())
+}
+
+/// Drop a message once it was sent.
+pub async fn delete(db: &SqlitePool, id: i64) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM mail_outbox WHERE id = ?")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Count a failed attempt and schedule the next one.
+pub async fn reschedule(
+    db: &SqlitePool,
+    id: i64,
+    error: &str,
+    next_attempt_at: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE mail_outbox
+        SET attempts = attempts + 1, last_error = ?, next_attempt_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(error)
+    .bind(next_attempt_at)
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Give up on a message; it stays queued for inspection.
+pub async fn mark_failed(db: &SqlitePool, id: i64, error: &str) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE mail_outbox
+        SET attempts = attempts + 1, last_error = ?, failed_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(error)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -1,19 +1,112 @@
 //! Database initialization helpers and repositories.
 
+pub mod abuse_repo;
+pub mod audit_repo;
+pub mod dangling_repo;
+pub mod delegation_repo;
+pub mod history_repo;
+pub mod inbox_repo;
+pub mod invite_repo;
+pub mod job_repo;
+pub mod mail_outbox_repo;
+pub mod notification_repo;
+pub mod ns_health_repo;
+pub mod pending_signup_repo;
+pub mod record_note_repo;
+pub mod record_provenance_repo;
+pub mod reserved_repo;
+pub mod retention_repo;
+pub mod signup_repo;
 pub mod user_repo;
+pub mod webhook_repo;
 
 // src/db/mod.rs (add this)
 use sqlx::SqlitePool;
+use sqlx::migrate::Migrator;
 use sqlx::sqlite::SqliteConnectOptions;
 
 pub type Db = SqlitePool;
 
 /// Initialize the SQLite connection pool and run pending migrations.
 pub async fn init_db(path: &std::path::Path) -> anyhow::Result<Db> {
+    let pool = connect(path).await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
+
+/// Open (creating if needed) the database without touching its schema.
+pub async fn connect(path: &std::path::Path) -> anyhow::Result<Db> {
     let options = SqliteConnectOptions::new()
         .filename(path)
         .create_if_missing(true);
-    let pool = SqlitePool::connect_with(options).await?;
-    sqlx::migrate!().run(&pool).await?;
-    Ok(pool)
+    Ok(SqlitePool::connect_with(options).await?)
+}
+
+/// Migrations embedded from `migrations/`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Copy the database (if it exists) to a throwaway file and open the copy,
+/// so a dry run can write freely without touching the original.
+pub async fn scratch_copy(path: &std::path::Path) -> anyhow::Result<(Db, std::path::PathBuf)> {
+    let scratch =
+        std::env::temp_dir().join(format!("satsuki-dry-run-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&scratch);
+    if path.exists() {
+        let source = open_read_only(path).await?;
+        sqlx::query("VACUUM INTO ?")
+            .bind(scratch.to_string_lossy().into_owned())
+            .execute(&source)
+            .await?;
+        source.close().await;
+    }
+    Ok((connect(&scratch).await?, scratch))
+}
+
+/// Open an existing database without creating it or running migrations.
+pub async fn open_read_only(path: &std::path::Path) -> anyhow::Result<Db> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    Ok(SqlitePool::connect_with(options).await?)
+}
+
+/// Migration state of a database compared with the embedded migrations.
+#[derive(Debug, Default)]
+pub struct MigrationStatus {
+    /// Embedded migrations that have not been applied yet.
+    pub pending: Vec<i64>,
+    /// Applied migrations this binary does not know about (DB is newer).
+    pub unknown: Vec<i64>,
+}
+
+/// Compare `_sqlx_migrations` with the migrations compiled into the binary.
+pub async fn migration_status(db: &Db) -> anyhow::Result<MigrationStatus> {
+    let table: Option<(String,)> = sqlx::query_as(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let applied: Vec<i64> = if table.is_some() {
+        sqlx::query_as::<_, (i64,)>("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .map(|(version,)| version)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let known: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    Ok(MigrationStatus {
+        pending: known
+            .iter()
+            .copied()
+            .filter(|v| !applied.contains(v))
+            .collect(),
+        unknown: applied
+            .iter()
+            .copied()
+            .filter(|v| !known.contains(v))
+            .collect(),
+    })
 }
@@ -0,0 +1,118 @@
+//! Repository functions for per-user notification preferences.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// Stored preferences of one user.
+#[derive(Debug, Clone)]
+pub struct NotificationPreferences {
+    /// Address used by the `email` channel.
+    pub email: Option<String>,
+    /// JSON object mapping categories to channels.
+    pub channels: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The preferences of `user_id`, if they were ever saved.
+pub async fn get(db: &SqlitePool, user_id: i64) -> sqlx::Result<Option<NotificationPreferences>> {
+    let row = sqlx::query(
+        r#"
+        SELECT email, channels, updated_at
+        FROM notification_preferences
+        WHERE user_id = ?
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| NotificationPreferences {
+        email: row.get("email"),
+        channels: row.get("channels"),
+        updated_at: row.get::<DateTime<Utc>, _>("updated_at"),
+    }))
+}
+
+/// Replace the preferences of `user_id`.
+pub async fn set(
+    db: &SqlitePool,
+    user_id: i64,
+    email: Option<&str>,
+    channels: &str,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO notification_preferences (user_id, email, channels, updated_at)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT (user_id) DO UPDATE SET
+          email = excluded.email,
+          channels = excluded.channels,
+          updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(channels)
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// A user a notification of some category is delivered to.
+#[derive(Debug, Clone)]
+pub struct Recipient {
+    pub user_id: i64,
+    pub subdomain: String,
+    pub base_domain: String,
+    pub email: Option<String>,
+}
+
+fn recipient_from(row: sqlx::sqlite::SqliteRow) -> Recipient {
+    Recipient {
+        user_id: row.get("id"),
+        subdomain: row.get("subdomain"),
+        base_domain: row.get("base_domain"),
+        email: row.get("email"),
+    }
+}
+
+/// Users with an address who route `category` to `email`.
+pub async fn list_email_recipients(
+    db: &SqlitePool,
+    category: &str,
+) -> sqlx::Result<Vec<Recipient>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT users.id, users.subdomain, users.base_domain, p.email
+        FROM users
+        JOIN notification_preferences p ON p.user_id = users.id
+        WHERE p.email IS NOT NULL AND json_extract(p.channels, '$.' || ?) = 'email'
+        ORDER BY users.id
+        "#,
+    )
+    .bind(category)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(recipient_from).collect())
+}
+
+/// Users with webhooks who route `category` to them, which is the default.
+pub async fn list_webhook_recipients(
+    db: &SqlitePool,
+    category: &str,
+) -> sqlx::Result<Vec<Recipient>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT users.id, users.subdomain, users.base_domain, p.email
+        FROM users
+        LEFT JOIN notification_preferences p ON p.user_id = users.id
+        WHERE COALESCE(json_extract(p.channels, '$.' || ?), 'webhook') = 'webhook'
+          AND EXISTS (SELECT 1 FROM webhooks WHERE webhooks.user_id = users.id)
+        ORDER BY users.id
+        "#,
+    )
+    .bind(category)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(recipient_from).collect())
+}
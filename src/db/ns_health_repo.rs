@@ -0,0 +1,96 @@
+//! Repository functions for external nameserver health.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+
+/// Latest probe result for one nameserver of a user.
+#[derive(Debug, Clone)]
+pub struct NsHealth {
+    pub hostname: String,
+    /// `ok`, `lame`, or `unreachable`.
+    pub status: String,
+    pub detail: Option<String>,
+    /// SOA serial from the last authoritative answer.
+    pub serial: Option<i64>,
+    pub consecutive_failures: i64,
+    pub failing_since: Option<DateTime<Utc>>,
+    /// Whether the user was told about the current failure streak.
+    pub notified: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+fn health_from_row(row: SqliteRow) -> NsHealth {
+    NsHealth {
+        hostname: row.get("hostname"),
+        status: row.get("status"),
+        detail: row.get("detail"),
+        serial: row.get("serial"),
+        consecutive_failures: row.get("consecutive_failures"),
+        failing_since: row.get::<Option<DateTime<Utc>>, _>("failing_since"),
+        notified: row.get::<i64, _>("notified") != 0,
+        checked_at: row.get::<DateTime<Utc>, _>("checked_at"),
+    }
+}
+
+/// Every recorded nameserver of a user, by hostname.
+pub async fn list_for_user(db: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<NsHealth>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT hostname, status, detail, serial, consecutive_failures, failing_since,
+               notified, checked_at
+        FROM ns_health
+        WHERE user_id = ?
+        ORDER BY hostname
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(health_from_row).collect())
+}
+
+/// Insert or replace the result for one nameserver.
+pub async fn upsert(db: &SqlitePool, user_id: i64, health: &NsHealth) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO ns_health (user_id, hostname, status, detail, serial,
+                               consecutive_failures, failing_since, notified, checked_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (user_id, hostname) DO UPDATE SET
+          status = excluded.status,
+          detail = excluded.detail,
+          serial = excluded.serial,
+          consecutive_failures = excluded.consecutive_failures,
+          failing_since = excluded.failing_since,
+          notified = excluded.notified,
+          checked_at = excluded.checked_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(&health.hostname)
+    .bind(&health.status)
+    .bind(&health.detail)
+    .bind(health.serial)
+    .bind(health.consecutive_failures)
+    .bind(health.failing_since)
+    .bind(health.notified)
+    .bind(health.checked_at)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Forget nameservers the user no longer delegates to.
+pub async fn retain(db: &SqlitePool, user_id: i64, hostnames: &[String]) -> sqlx::Result<()> {
+    let keep = serde_json::to_string(hostnames).unwrap_or_else(|_| "[]".into());
+    sqlx::query(
+        r#"
+        DELETE FROM ns_health
+        WHERE user_id = ? AND hostname NOT IN (SELECT value FROM json_each(?))
+        "#,
+    )
+    .bind(user_id)
+    .bind(keep)
+    .execute(db)
+    .await?;
+    Ok(())
+}
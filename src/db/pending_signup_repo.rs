@@ -0,0 +1,170 @@
+//! Repository functions for signups whose provisioning saga has not finished.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+
+use crate::db::user_repo;
+
+/// A signup holding its label while its zone and delegation are set up.
+#[derive(Debug, Clone)]
+pub struct PendingSignup {
+    pub id: i64,
+    pub subdomain: String,
+    pub base_domain: String,
+    pub password_hash: String,
+    /// Invite claimed by the signup, released again on rollback.
+    pub invite_code: Option<String>,
+    /// Abuse flag to raise once the user exists, e.g. `confusable_label`.
+    pub flag_reason: Option<String>,
+    /// Label the new one resembles, for the flag.
+    pub flag_similar: Option<String>,
+    /// Last completed step, see `signup_saga::Step`.
+    pub step: String,
+    /// Failed attempts at the next step.
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn pending_from_row(row: SqliteRow) -> PendingSignup {
+    PendingSignup {
+        id: row.get("id"),
+        subdomain: row.get("subdomain"),
+        base_domain: row.get("base_domain"),
+        password_hash: row.get("password_hash"),
+        invite_code: row.get("invite_code"),
+        flag_reason: row.get("flag_reason"),
+        flag_similar: row.get("flag_similar"),
+        step: row.get("step"),
+        attempts: row.get("attempts"),
+        last_error: row.get("last_error"),
+        created_at: row.get::<DateTime<Utc>, _>("created_at"),
+        updated_at: row.get::<DateTime<Utc>, _>("updated_at"),
+    }
+}
+
+/// Reserve a label for a new signup at `step`. Returns `None` when a user
+/// already has the label, and fails with a unique violation while another
+/// signup holds it.
+pub async fn insert(
+    db: &SqlitePool,
+    subdomain: &str,
+    base_domain: &str,
+    password_hash: &str,
+    invite_code: Option<&str>,
+    flag: Option<(&str, &str)>,
+    step: &str,
+) -> sqlx::Result<Option<PendingSignup>> {
+    let now = Utc::now();
+    let row = sqlx::query(
+        r#"
+        INSERT INTO pending_signups (
+            subdomain, base_domain, password_hash, invite_code,
+            flag_reason, flag_similar, step, created_at, updated_at
+        )
+        SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?
+        WHERE NOT EXISTS (SELECT 1 FROM users WHERE subdomain = ? AND base_domain = ?)
+        RETURNING id, subdomain, base_domain, password_hash, invite_code, flag_reason,
+                  flag_similar, step, attempts, last_error, created_at, updated_at
+        "#,
+    )
+    .bind(subdomain)
+    .bind(base_domain)
+    .bind(password_hash)
+    .bind(invite_code)
+    .bind(flag.map(|(reason, _)| reason))
+    .bind(flag.map(|(_, similar)| similar))
+    .bind(step)
+    .bind(now)
+    .bind(now)
+    .bind(subdomain)
+    .bind(base_domain)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(pending_from_row))
+}
+
+/// Whether a signup for the label is in progress.
+pub async fn exists(db: &SqlitePool, subdomain: &str, base_domain: &str) -> sqlx::Result<bool> {
+    let cnt: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM pending_signups WHERE subdomain = ? AND base_domain = ?",
+    )
+    .bind(subdomain)
+    .bind(base_domain)
+    .fetch_one(db)
+    .await?;
+    Ok(cnt.0 > 0)
+}
+
+/// Every unfinished signup, oldest first.
+pub async fn list(db: &SqlitePool) -> sqlx::Result<Vec<PendingSignup>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, subdomain, base_domain, password_hash, invite_code, flag_reason,
+               flag_similar, step, attempts, last_error, created_at, updated_at
+        FROM pending_signups
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(pending_from_row).collect())
+}
+
+/// Record that `step` completed; the attempt counter starts over.
+pub async fn set_step(db: &SqlitePool, id: i64, step: &str) -> sqlx::Result<()> {
+    sqlx::query(
+        "UPDATE pending_signups SET step = ?, attempts = 0, last_error = NULL, updated_at = ? WHERE id = ?",
+    )
+    .bind(step)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Count a failed attempt at the next step; returns the attempts so far.
+pub async fn record_failure(db: &SqlitePool, id: i64, error: &str) -> sqlx::Result<i64> {
+    sqlx::query_scalar(
+        r#"
+        UPDATE pending_signups
+        SET attempts = attempts + 1, last_error = ?, updated_at = ?
+        WHERE id = ?
+        RETURNING attempts
+        "#,
+    )
+    .bind(error)
+    .bind(Utc::now())
+    .bind(id)
+    .fetch_one(db)
+    .await
+}
+
+/// Create the user row and drop the pending signup in one transaction;
+/// returns the new user's id.
+pub async fn activate(db: &SqlitePool, signup: &PendingSignup) -> sqlx::Result<i64> {
+    let mut tx = db.begin().await?;
+    let user_id = user_repo::insert(
+        &mut *tx,
+        &signup.subdomain,
+        &signup.base_domain,
+        &signup.password_hash,
+    )
+    .await?;
+    sqlx::query("DELETE FROM pending_signups WHERE id = ?")
+        .bind(signup.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(user_id)
+}
+
+/// Drop a rolled-back signup, freeing its label.
+pub async fn delete(db: &SqlitePool, id: i64) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM pending_signups WHERE id = ?")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Repository functions for the notes and labels attached to RRsets.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// Note and labels of one RRset.
+#[derive(Debug, Clone)]
+pub struct RecordNote {
+    /// Lowercase owner name with trailing dot.
+    pub name: String,
+    pub rrtype: String,
+    pub note: Option<String>,
+    pub labels: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Every note of `user_id`.
+pub async fn list_for_user(db: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<RecordNote>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name, rrtype, note, labels, updated_at
+        FROM record_notes
+        WHERE user_id = ?
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| RecordNote {
+            name: row.get("name"),
+            rrtype: row.get("rrtype"),
+            note: row.get("note"),
+            labels: serde_json::from_str(row.get("labels")).unwrap_or_default(),
+            updated_at: row.get::<DateTime<Utc>, _>("updated_at"),
+        })
+        .collect())
+}
+
+/// Update the note and labels of an RRset. `None` keeps the stored value;
+/// an RRset left with neither is forgotten.
+pub async fn set(
+    db: &SqlitePool,
+    user_id: i64,
+    name: &str,
+    rrtype: &str,
+    note: Option<&str>,
+    labels: Option<&[String]>,
+) -> sqlx::Result<()> {
+    let name = name.to_ascii_lowercase();
+    let mut tx = db.begin().await?;
+    let stored: Option<(Option<String>, String)> = sqlx::query_as(
+        "SELECT note, labels FROM record_notes WHERE user_id = ? AND name = ? AND rrtype = ?",
+    )
+    .bind(user_id)
+    .bind(&name)
+    .bind(rrtype)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (stored_note, stored_labels) = stored.unwrap_or_default();
+    let note = match note {
+        Some(note) => (!note.is_empty()).then(|| note.to_string()),
+        None => stored_note,
+    };
+    let labels = match labels {
+        Some(labels) => serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()),
+        None if stored_labels.is_empty() => "[]".into(),
+        None => stored_labels,
+    };
+
+    if note.is_none() && labels == "[]" {
+        sqlx::query("DELETE FROM record_notes WHERE user_id = ? AND name = ? AND rrtype = ?")
+            .bind(user_id)
+            .bind(&name)
+            .bind(rrtype)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        sqlx::query(
+            r#"
+            INSERT INTO record_notes (user_id, name, rrtype, note, labels, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, name, rrtype) DO UPDATE SET
+              note = excluded.note,
+              labels = excluded.labels,
+              updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(&name)
+        .bind(rrtype)
+        .bind(note)
+        .bind(labels)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Forget the note of a deleted RRset.
+pub async fn delete(db: &SqlitePool, user_id: i64, name: &str, rrtype: &str) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM record_notes WHERE user_id = ? AND name = ? AND rrtype = ?")
+        .bind(user_id)
+        .bind(name.to_ascii_lowercase())
+        .bind(rrtype)
+        .execute(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Repository functions for the credentials that wrote each RRset.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// Who created and last modified one RRset.
+#[derive(Debug, Clone)]
+pub struct RecordProvenance {
+    /// Lowercase owner name with trailing dot.
+    pub name: String,
+    pub rrtype: String,
+    /// Credential kind, such as `password`.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub modified_by: String,
+    pub modified_at: DateTime<Utc>,
+}
+
+/// Provenance of every RRset of `user_id` written since it was tracked.
+pub async fn list_for_user(db: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<RecordProvenance>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name, rrtype, created_by, created_at, modified_by, modified_at
+        FROM record_provenance
+        WHERE user_id = ?
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| RecordProvenance {
+            name: row.get("name"),
+            rrtype: row.get("rrtype"),
+            created_by: row.get("created_by"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+            modified_by: row.get("modified_by"),
+            modified_at: row.get::<DateTime<Utc>, _>("modified_at"),
+        })
+        .collect())
+}
+
+/// Record that `credential` wrote an RRset; the first write also counts as
+/// its creation.
+pub async fn touch(
+    db: &SqlitePool,
+    user_id: i64,
+    name: &str,
+    rrtype: &str,
+    credential: &str,
+) -> sqlx::Result<()> {
+    let now = Utc::now();
+    sqlx::query(
+        r#"
+        INSERT INTO record_provenance
+          (user_id, name, rrtype, created_by, created_at, modified_by, modified_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (user_id, name, rrtype) DO UPDATE SET
+          modified_by = excluded.modified_by,
+          modified_at = excluded.modified_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(name.to_ascii_lowercase())
+    .bind(rrtype)
+    .bind(credential)
+    .bind(now)
+    .bind(credential)
+    .bind(now)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Forget the provenance of a deleted RRset.
+pub async fn delete(db: &SqlitePool, user_id: i64, name: &str, rrtype: &str) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM record_provenance WHERE user_id = ? AND name = ? AND rrtype = ?")
+        .bind(user_id)
+        .bind(name.to_ascii_lowercase())
+        .bind(rrtype)
+        .execute(db)
+        .await?;
+    Ok(())
+}
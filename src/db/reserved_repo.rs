@@ -0,0 +1,63 @@
+//! Repository functions for operator-managed label reservations.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// A label reserved by an operator at runtime.
+#[derive(Debug, Clone)]
+pub struct ReservedLabel {
+    pub label: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Determine whether the label is reserved in the database.
+pub async fn is_reserved(db: &SqlitePool, label: &str) -> sqlx::Result<bool> {
+    let cnt: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM reserved_labels WHERE label = ?")
+        .bind(label.to_ascii_lowercase())
+        .fetch_one(db)
+        .await?;
+    Ok(cnt.0 > 0)
+}
+
+/// List every stored reservation ordered by label.
+pub async fn list(db: &SqlitePool) -> sqlx::Result<Vec<ReservedLabel>> {
+    let rows = sqlx::query("SELECT label, reason, created_at FROM reserved_labels ORDER BY label")
+        .fetch_all(db)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ReservedLabel {
+            label: row.get("label"),
+            reason: row.get("reason"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+        })
+        .collect())
+}
+
+/// Reserve a label, updating the reason if it is already reserved.
+pub async fn upsert(db: &SqlitePool, label: &str, reason: Option<&str>) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO reserved_labels (label, reason, created_at)
+        VALUES (?, ?, ?)
+        ON CONFLICT (label) DO UPDATE SET reason = excluded.reason
+        "#,
+    )
+    .bind(label.to_ascii_lowercase())
+    .bind(reason)
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove a reservation; returns whether a row was deleted.
+pub async fn delete(db: &SqlitePool, label: &str) -> sqlx::Result<bool> {
+    let res = sqlx::query("DELETE FROM reserved_labels WHERE label = ?")
+        .bind(label.to_ascii_lowercase())
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
@@ -0,0 +1,94 @@
+//! Repository functions for pruning old rows of the append-only log tables.
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+/// A log table the retention job prunes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogTable {
+    AuditLog,
+    ChangeHistory,
+    WebhookDeliveries,
+}
+
+impl LogTable {
+    pub const ALL: [LogTable; 3] = [
+        LogTable::AuditLog,
+        LogTable::ChangeHistory,
+        LogTable::WebhookDeliveries,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogTable::AuditLog => "audit_log",
+            LogTable::ChangeHistory => "change_history",
+            LogTable::WebhookDeliveries => "webhook_deliveries",
+        }
+    }
+
+    /// Column holding the row's timestamp.
+    fn time_column(self) -> &'static str {
+        match self {
+            LogTable::WebhookDeliveries => "created_at",
+            _ => "occurred_at",
+        }
+    }
+
+    /// `json_object` arguments rendering a whole row.
+    fn json_columns(self) -> &'static str {
+        match self {
+            LogTable::AuditLog => {
+                "'id', id, 'actor', actor, 'action', action, 'target', target, \
+                 'details', json(details), 'occurred_at', occurred_at"
+            }
+            LogTable::ChangeHistory => {
+                "'id', id, 'user_id', user_id, 'zone', zone, 'event_type', event_type, \
+                 'payload', json(payload), 'occurred_at', occurred_at"
+            }
+            LogTable::WebhookDeliveries => {
+                "'id', id, 'webhook_id', webhook_id, 'event_type', event_type, \
+                 'attempt', attempt, 'status_code', status_code, 'error', error, \
+                 'created_at', created_at"
+            }
+        }
+    }
+}
+
+/// Up to `limit` rows older than `before`, oldest first, as `(id, JSON object)`.
+pub async fn list_expired(
+    db: &SqlitePool,
+    table: LogTable,
+    before: DateTime<Utc>,
+    limit: i64,
+) -> sqlx::Result<Vec<(i64, String)>> {
+    let sql = format!(
+        "SELECT id, json_object({}) FROM {} WHERE {} < ? ORDER BY id LIMIT ?",
+        table.json_columns(),
+        table.name(),
+        table.time_column(),
+    );
+    sqlx::query_as(&sql)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
+
+/// Delete the rows older than `before` up to and including `last_id`.
+pub async fn delete_expired(
+    db: &SqlitePool,
+    table: LogTable,
+    before: DateTime<Utc>,
+    last_id: i64,
+) -> sqlx::Result<u64> {
+    let sql = format!(
+        "DELETE FROM {} WHERE {} < ? AND id <= ?",
+        table.name(),
+        table.time_column(),
+    );
+    let result = sqlx::query(&sql)
+        .bind(before)
+        .bind(last_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,119 @@
+//! Repository functions for the signup source log behind the signup caps.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+/// Signups from one network within a window.
+#[derive(Debug, Clone)]
+pub struct SubnetSignups {
+    pub subnet: String,
+    pub signups: i64,
+    /// Distinct client addresses among them.
+    pub addresses: i64,
+    pub last_signup_at: DateTime<Utc>,
+}
+
+/// Log a signup, dropping entries older than `prune_before`.
+pub async fn record(
+    db: &SqlitePool,
+    client_ip: &str,
+    subnet: &str,
+    zone: &str,
+    prune_before: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM signup_sources WHERE created_at < ?")
+        .bind(prune_before)
+        .execute(db)
+        .await?;
+    sqlx::query(
+        "INSERT INTO signup_sources (client_ip, subnet, zone, created_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(client_ip)
+    .bind(subnet)
+    .bind(zone)
+    .bind(Utc::now())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Times of the newest `limit` signups from `client_ip` since `since`, newest first.
+pub async fn recent_by_ip(
+    db: &SqlitePool,
+    client_ip: &str,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> sqlx::Result<Vec<DateTime<Utc>>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT created_at FROM signup_sources
+        WHERE client_ip = ? AND created_at >= ?
+        ORDER BY created_at DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(client_ip)
+    .bind(since)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+/// Times of the newest `limit` signups from `subnet` since `since`, newest first.
+pub async fn recent_by_subnet(
+    db: &SqlitePool,
+    subnet: &str,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> sqlx::Result<Vec<DateTime<Utc>>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT created_at FROM signup_sources
+        WHERE subnet = ? AND created_at >= ?
+        ORDER BY created_at DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(subnet)
+    .bind(since)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+/// Signups from anywhere since `since`; at most a day back is kept.
+pub async fn count_since(db: &SqlitePool, since: DateTime<Utc>) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM signup_sources WHERE created_at >= ?")
+        .bind(since)
+        .fetch_one(db)
+        .await
+}
+
+/// Signups per network since `since`, busiest first.
+pub async fn count_by_subnet_since(
+    db: &SqlitePool,
+    since: DateTime<Utc>,
+) -> sqlx::Result<Vec<SubnetSignups>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT subnet, COUNT(*) AS signups, COUNT(DISTINCT client_ip) AS addresses,
+               MAX(created_at) AS last_signup_at
+        FROM signup_sources
+        WHERE created_at >= ?
+        GROUP BY subnet
+        ORDER BY signups DESC, last_signup_at DESC
+        "#,
+    )
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| SubnetSignups {
+            subnet: row.get("subnet"),
+            signups: row.get("signups"),
+            addresses: row.get("addresses"),
+            last_signup_at: row.get("last_signup_at"),
+        })
+        .collect())
+}
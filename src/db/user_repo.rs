@@ -1,57 +1,179 @@
 //! Repository functions for manipulating rows in the `users` table.
 use chrono::{DateTime, Utc};
-use sqlx::{Row, SqlitePool};
+use sqlx::{Row, SqliteExecutor, SqlitePool};
+use std::collections::HashMap;
 
 /// Application-level representation of a stored user.
 #[derive(Debug, Clone)]
 pub struct User {
     pub id: i64,
     pub subdomain: String,
+    pub base_domain: String,
     pub password_hash: String,
     pub external_ns: bool,
-    pub external_ns1: Option<String>,
-    pub external_ns2: Option<String>,
-    pub external_ns3: Option<String>,
-    pub external_ns4: Option<String>,
-    pub external_ns5: Option<String>,
-    pub external_ns6: Option<String>,
+    /// External nameservers in the order the user submitted them.
+    pub external_nameservers: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_login_at: Option<DateTime<Utc>>,
+    /// Client address of the last successful signin.
+    pub last_login_ip: Option<String>,
 }
 
-/// Determine whether a subdomain already has a user row.
-pub async fn exists(db: &SqlitePool, subdomain: &str) -> sqlx::Result<bool> {
-    let cnt: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE subdomain = ?")
-        .bind(subdomain)
-        .fetch_one(db)
+/// A registration as shown in the public feed.
+#[derive(Debug, Clone)]
+pub struct Delegation {
+    pub subdomain: String,
+    pub base_domain: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The most recently registered subdomains, newest first.
+pub async fn list_recent(db: &SqlitePool, limit: i64) -> sqlx::Result<Vec<Delegation>> {
+    let rows: Vec<(String, String, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT subdomain, base_domain, created_at FROM users ORDER BY created_at DESC, id DESC LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(subdomain, base_domain, created_at)| Delegation {
+            subdomain,
+            base_domain,
+            created_at,
+        })
+        .collect())
+}
+
+/// Registration counts for one base domain.
+#[derive(Debug, Clone)]
+pub struct DomainCounts {
+    pub base_domain: String,
+    pub total: i64,
+    pub external_ns: i64,
+    pub newest_created_at: Option<DateTime<Utc>>,
+}
+
+/// Registration counts grouped by base domain.
+pub async fn counts_by_domain(db: &SqlitePool) -> sqlx::Result<Vec<DomainCounts>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT base_domain,
+               COUNT(*) AS total,
+               SUM(CASE WHEN external_ns != 0 THEN 1 ELSE 0 END) AS external_ns,
+               MAX(created_at) AS newest_created_at
+        FROM users
+        GROUP BY base_domain
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| DomainCounts {
+            base_domain: row.get("base_domain"),
+            total: row.get("total"),
+            external_ns: row.get("external_ns"),
+            newest_created_at: row.get("newest_created_at"),
+        })
+        .collect())
+}
+
+/// Number of zones per known record count, for zones updated since record
+/// counts were introduced.
+pub async fn record_count_distribution(db: &SqlitePool) -> sqlx::Result<Vec<(i64, i64)>> {
+    sqlx::query_as(
+        r#"
+        SELECT record_count, COUNT(*)
+        FROM users
+        WHERE record_count IS NOT NULL
+        GROUP BY record_count
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Store the zone's user record count after a successful update.
+pub async fn set_record_count(
+    db: &SqlitePool,
+    user_id: i64,
+    record_count: usize,
+) -> sqlx::Result<()> {
+    sqlx::query("UPDATE users SET record_count = ? WHERE id = ?")
+        .bind(record_count as i64)
+        .bind(user_id)
+        .execute(db)
         .await?;
+    Ok(())
+}
+
+/// Determine whether a subdomain already has a user row.
+pub async fn exists(db: &SqlitePool, subdomain: &str, base_domain: &str) -> sqlx::Result<bool> {
+    let cnt: (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM users WHERE subdomain = ? AND base_domain = ?")
+            .bind(subdomain)
+            .bind(base_domain)
+            .fetch_one(db)
+            .await?;
     Ok(cnt.0 > 0)
 }
 
+/// Every registered label under `base_domain`.
+pub async fn list_subdomains(db: &SqlitePool, base_domain: &str) -> sqlx::Result<Vec<String>> {
+    sqlx::query_scalar("SELECT subdomain FROM users WHERE base_domain = ?")
+        .bind(base_domain)
+        .fetch_all(db)
+        .await
+}
+
+/// Labels under `base_domain` whose zones hold at least `min_records` records.
+pub async fn list_popular_subdomains(
+    db: &SqlitePool,
+    base_domain: &str,
+    min_records: usize,
+) -> sqlx::Result<Vec<String>> {
+    sqlx::query_scalar("SELECT subdomain FROM users WHERE base_domain = ? AND record_count >= ?")
+        .bind(base_domain)
+        .bind(min_records as i64)
+        .fetch_all(db)
+        .await
+}
+
+/// Assign rows created before multi-domain support to the default base domain.
+pub async fn backfill_base_domain(db: &SqlitePool, base_domain: &str) -> sqlx::Result<u64> {
+    let res = sqlx::query("UPDATE users SET base_domain = ? WHERE base_domain = ''")
+        .bind(base_domain)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected())
+}
+
 /// Fetch a user and all NS metadata for the given subdomain.
-pub async fn find_by_subdomain(db: &SqlitePool, subdomain: &str) -> sqlx::Result<Option<User>> {
+pub async fn find_by_subdomain(
+    db: &SqlitePool,
+    subdomain: &str,
+    base_domain: &str,
+) -> sqlx::Result<Option<User>> {
     let row = sqlx::query(
         r#"
         SELECT
             id,
             subdomain,
+            base_domain,
             password_hash,
             external_ns,
-            external_ns1,
-            external_ns2,
-            external_ns3,
-            external_ns4,
-            external_ns5,
-            external_ns6,
             created_at,
             updated_at,
-            last_login_at
+            last_login_at,
+            last_login_ip
         FROM users
-        WHERE subdomain = ?
+        WHERE subdomain = ? AND base_domain = ?
         "#,
     )
     .bind(subdomain)
+    .bind(base_domain)
     .fetch_optional(db)
     .await?;
 
@@ -59,46 +181,99 @@ pub async fn find_by_subdomain(db: &SqlitePool, subdomain: &str) -> sqlx::Result
         return Ok(None);
     };
 
+    let id: i64 = row.get("id");
+    let external_nameservers = list_external_ns(db, id).await?;
+
     Ok(Some(User {
-        id: row.get("id"),
+        id,
         subdomain: row.get("subdomain"),
+        base_domain: row.get("base_domain"),
         password_hash: row.get("password_hash"),
         external_ns: row.get::<i64, _>("external_ns") != 0,
-        external_ns1: row.get("external_ns1"),
-        external_ns2: row.get("external_ns2"),
-        external_ns3: row.get("external_ns3"),
-        external_ns4: row.get("external_ns4"),
-        external_ns5: row.get("external_ns5"),
-        external_ns6: row.get("external_ns6"),
+        external_nameservers,
         created_at: row.get::<DateTime<Utc>, _>("created_at"),
         updated_at: row.get::<DateTime<Utc>, _>("updated_at"),
         last_login_at: row.get("last_login_at"),
+        last_login_ip: row.get("last_login_ip"),
     }))
 }
 
+/// Every user with NS metadata, ordered by base domain and label.
+pub async fn list_all(db: &SqlitePool) -> sqlx::Result<Vec<User>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            id,
+            subdomain,
+            base_domain,
+            password_hash,
+            external_ns,
+            created_at,
+            updated_at,
+            last_login_at,
+            last_login_ip
+        FROM users
+        ORDER BY base_domain, subdomain
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut nameservers: HashMap<i64, Vec<String>> = HashMap::new();
+    let ns_rows: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT user_id, hostname FROM external_nameservers ORDER BY user_id, position",
+    )
+    .fetch_all(db)
+    .await?;
+    for (user_id, hostname) in ns_rows {
+        nameservers.entry(user_id).or_default().push(hostname);
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let id: i64 = row.get("id");
+            User {
+                id,
+                subdomain: row.get("subdomain"),
+                base_domain: row.get("base_domain"),
+                password_hash: row.get("password_hash"),
+                external_ns: row.get::<i64, _>("external_ns") != 0,
+                external_nameservers: nameservers.remove(&id).unwrap_or_default(),
+                created_at: row.get::<DateTime<Utc>, _>("created_at"),
+                updated_at: row.get::<DateTime<Utc>, _>("updated_at"),
+                last_login_at: row.get("last_login_at"),
+                last_login_ip: row.get("last_login_ip"),
+            }
+        })
+        .collect())
+}
+
 /// Create a new user row when signup completes successfully.
-pub async fn insert(db: &SqlitePool, subdomain: &str, password_hash: &str) -> sqlx::Result<i64> {
+pub async fn insert(
+    db: impl SqliteExecutor<'_>,
+    subdomain: &str,
+    base_domain: &str,
+    password_hash: &str,
+) -> sqlx::Result<i64> {
     let now = Utc::now();
 
     let res = sqlx::query(
         r#"
         INSERT INTO users (
             subdomain,
+            base_domain,
             password_hash,
             external_ns,
-            external_ns1,
-            external_ns2,
-            external_ns3,
-            external_ns4,
-            external_ns5,
-            external_ns6,
             created_at,
             updated_at,
-            last_login_at
-        ) VALUES (?, ?, 0, NULL, NULL, NULL, NULL, NULL, NULL, ?, ?, NULL)
+            last_login_at,
+            record_count
+        ) VALUES (?, ?, ?, 0, ?, ?, NULL, 0)
         "#,
     )
     .bind(subdomain)
+    .bind(base_domain)
     .bind(password_hash)
     .bind(now)
     .bind(now)
@@ -108,60 +283,65 @@ pub async fn insert(db: &SqlitePool, subdomain: &str, password_hash: &str) -> sq
     Ok(res.last_insert_rowid())
 }
 
-/// Persist the user's NS mode and up to six external nameservers.
+/// Load the user's external nameservers in submission order.
+pub async fn list_external_ns(db: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT hostname FROM external_nameservers WHERE user_id = ? ORDER BY position",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(|(hostname,)| hostname).collect())
+}
+
+/// Persist the user's NS mode and replace the stored external nameservers.
 pub async fn set_external_ns(
     db: &SqlitePool,
     user_id: i64,
     external_ns: bool,
-    ns1: Option<String>,
-    ns2: Option<String>,
-    ns3: Option<String>,
-    ns4: Option<String>,
-    ns5: Option<String>,
-    ns6: Option<String>,
+    nameservers: &[String],
 ) -> sqlx::Result<()> {
     let now = Utc::now();
-    sqlx::query(
-        r#"
-        UPDATE users
-        SET
-            external_ns = ?,
-            external_ns1 = ?,
-            external_ns2 = ?,
-            external_ns3 = ?,
-            external_ns4 = ?,
-            external_ns5 = ?,
-            external_ns6 = ?,
-            updated_at = ?
-        WHERE id = ?
-        "#,
-    )
-    .bind(if external_ns { 1 } else { 0 })
-    .bind(ns1)
-    .bind(ns2)
-    .bind(ns3)
-    .bind(ns4)
-    .bind(ns5)
-    .bind(ns6)
-    .bind(now)
-    .bind(user_id)
-    .execute(db)
-    .await?;
+    let mut tx = db.begin().await?;
 
-    Ok(())
+    sqlx::query("UPDATE users SET external_ns = ?, updated_at = ? WHERE id = ?")
+        .bind(if external_ns { 1 } else { 0 })
+        .bind(now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM external_nameservers WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for (position, hostname) in nameservers.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO external_nameservers (user_id, position, hostname) VALUES (?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(position as i64)
+        .bind(hostname)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
 }
 
-/// Update the user's last successful login timestamp.
-pub async fn update_last_login(db: &SqlitePool, user_id: i64) -> sqlx::Result<()> {
+/// Update the user's last successful login timestamp and client address.
+pub async fn update_last_login(db: &SqlitePool, user_id: i64, client_ip: &str) -> sqlx::Result<()> {
     let now = Utc::now();
     sqlx::query(
         r#"
         UPDATE users
-        SET last_login_at = ?, updated_at = ?
+        SET last_login_at = ?, last_login_ip = ?, updated_at = ?
         WHERE id = ?
         "#,
     )
     .bind(now)
+    .bind(client_ip)
     .bind(now)
     .bind(user_id)
     .execute(db)
@@ -192,3 +372,99 @@ pub async fn update_password(
 
     Ok(())
 }
+
+/// An account that has never been signed into or changed.
+#[derive(Debug, Clone)]
+pub struct UnusedAccount {
+    pub id: i64,
+    pub subdomain: String,
+    pub base_domain: String,
+    pub created_at: DateTime<Utc>,
+    pub expiry_reminded_at: Option<DateTime<Utc>>,
+}
+
+// shared by `list_unused` and `delete_unused`, so the delete re-checks the
+// same conditions the selection used
+const UNUSED_CONDITION: &str = r#"
+    last_login_at IS NULL
+    AND external_ns = 0
+    AND NOT EXISTS (
+        SELECT 1 FROM change_history
+        WHERE change_history.user_id = users.id
+          AND change_history.event_type <> 'account.expiry_reminder'
+    )
+"#;
+
+/// Accounts created before `created_before` with no sign-in, NS change, or
+/// recorded zone change, oldest first.
+pub async fn list_unused(
+    db: &SqlitePool,
+    created_before: DateTime<Utc>,
+) -> sqlx::Result<Vec<UnusedAccount>> {
+    let rows = sqlx::query(&format!(
+        "SELECT id, subdomain, base_domain, created_at, expiry_reminded_at FROM users \
+         WHERE created_at < ? AND {UNUSED_CONDITION} ORDER BY created_at"
+    ))
+    .bind(created_before)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| UnusedAccount {
+            id: row.get("id"),
+            subdomain: row.get("subdomain"),
+            base_domain: row.get("base_domain"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+            expiry_reminded_at: row.get::<Option<DateTime<Utc>>, _>("expiry_reminded_at"),
+        })
+        .collect())
+}
+
+/// Unused accounts created before `created_before` whose owner was not
+/// reminded of the expiry yet, oldest first.
+pub async fn list_unreminded(
+    db: &SqlitePool,
+    created_before: DateTime<Utc>,
+) -> sqlx::Result<Vec<UnusedAccount>> {
+    let rows = sqlx::query(&format!(
+        "SELECT id, subdomain, base_domain, created_at, expiry_reminded_at FROM users \
+         WHERE created_at < ? AND expiry_reminded_at IS NULL AND {UNUSED_CONDITION} \
+         ORDER BY created_at"
+    ))
+    .bind(created_before)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| UnusedAccount {
+            id: row.get("id"),
+            subdomain: row.get("subdomain"),
+            base_domain: row.get("base_domain"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+            expiry_reminded_at: row.get::<Option<DateTime<Utc>>, _>("expiry_reminded_at"),
+        })
+        .collect())
+}
+
+/// Note that the owner was reminded of the expiry.
+pub async fn mark_expiry_reminded(db: &SqlitePool, user_id: i64) -> sqlx::Result<()> {
+    sqlx::query("UPDATE users SET expiry_reminded_at = ? WHERE id = ?")
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Delete the account if it is still unused; returns whether a row was removed.
+pub async fn delete_unused(db: &SqlitePool, user_id: i64) -> sqlx::Result<bool> {
+    let res = sqlx::query(&format!(
+        "DELETE FROM users WHERE id = ? AND {UNUSED_CONDITION}"
+    ))
+    .bind(user_id)
+    .execute(db)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
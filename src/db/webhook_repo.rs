@@ -0,0 +1,156 @@
+//! Repository functions for user webhooks and their delivery log.
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+
+/// Delivery attempts kept per webhook; older rows are pruned on insert.
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+/// A webhook endpoint registered by a user.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: i64,
+    pub user_id: i64,
+    pub url: String,
+    /// HMAC key used to sign payloads.
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One delivery attempt of an event to a webhook.
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub id: i64,
+    pub event_type: String,
+    pub attempt: i64,
+    /// HTTP status returned by the endpoint, if it answered at all.
+    pub status_code: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn webhook_from_row(row: SqliteRow) -> Webhook {
+    Webhook {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        url: row.get("url"),
+        secret: row.get("secret"),
+        created_at: row.get::<DateTime<Utc>, _>("created_at"),
+    }
+}
+
+/// Register a webhook and return its id.
+pub async fn insert(db: &SqlitePool, user_id: i64, url: &str, secret: &str) -> sqlx::Result<i64> {
+    let res =
+        sqlx::query("INSERT INTO webhooks (user_id, url, secret, created_at) VALUES (?, ?, ?, ?)")
+            .bind(user_id)
+            .bind(url)
+            .bind(secret)
+            .bind(Utc::now())
+            .execute(db)
+            .await?;
+    Ok(res.last_insert_rowid())
+}
+
+/// Every webhook of a user, oldest first.
+pub async fn list_for_user(db: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<Webhook>> {
+    let rows = sqlx::query(
+        "SELECT id, user_id, url, secret, created_at FROM webhooks WHERE user_id = ? ORDER BY id",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(webhook_from_row).collect())
+}
+
+/// Fetch a webhook only if it belongs to the given user.
+pub async fn find_for_user(
+    db: &SqlitePool,
+    user_id: i64,
+    webhook_id: i64,
+) -> sqlx::Result<Option<Webhook>> {
+    let row = sqlx::query(
+        "SELECT id, user_id, url, secret, created_at FROM webhooks WHERE id = ? AND user_id = ?",
+    )
+    .bind(webhook_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(webhook_from_row))
+}
+
+/// Delete a user's webhook; returns whether a row was removed.
+pub async fn delete(db: &SqlitePool, user_id: i64, webhook_id: i64) -> sqlx::Result<bool> {
+    let res = sqlx::query("DELETE FROM webhooks WHERE id = ? AND user_id = ?")
+        .bind(webhook_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Append a delivery attempt and prune the log to the newest entries.
+pub async fn record_delivery(
+    db: &SqlitePool,
+    webhook_id: i64,
+    event_type: &str,
+    attempt: i64,
+    status_code: Option<u16>,
+    error: Option<&str>,
+) -> sqlx::Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, event_type, attempt, status_code, error, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(webhook_id)
+    .bind(event_type)
+    .bind(attempt)
+    .bind(status_code.map(i64::from))
+    .bind(error)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        DELETE FROM webhook_deliveries
+        WHERE webhook_id = ? AND id NOT IN (
+            SELECT id FROM webhook_deliveries WHERE webhook_id = ? ORDER BY id DESC LIMIT ?
+        )
+        "#,
+    )
+    .bind(webhook_id)
+    .bind(webhook_id)
+    .bind(DELIVERY_LOG_LIMIT)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Most recent delivery attempts of a webhook, newest first.
+pub async fn list_deliveries(db: &SqlitePool, webhook_id: i64) -> sqlx::Result<Vec<Delivery>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, event_type, attempt, status_code, error, created_at
+        FROM webhook_deliveries
+        WHERE webhook_id = ?
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(webhook_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Delivery {
+            id: row.get("id"),
+            event_type: row.get("event_type"),
+            attempt: row.get("attempt"),
+            status_code: row.get("status_code"),
+            error: row.get("error"),
+            created_at: row.get::<DateTime<Utc>, _>("created_at"),
+        })
+        .collect())
+}
@@ -0,0 +1,89 @@
+//! Changes to the NS RRsets of the parent zones, mirrored into the
+//! delegation inventory that the public listing and `/metrics` read.
+use anyhow::Context;
+use tokio::task::JoinHandle;
+
+use crate::db::delegation_repo;
+use crate::powerdns::types::PdnsRrset;
+use crate::{AppState, SharedState};
+
+/// Apply NS `rrsets` to the parent zone of `base_domain`, then record them
+/// in the inventory. An inventory write that fails is only logged; the
+/// next reconciliation scan corrects it.
+pub async fn patch(
+    state: &AppState,
+    base_domain: &str,
+    rrsets: &[PdnsRrset],
+) -> anyhow::Result<()> {
+    let parent_zone = state.config.parent_zone_name(base_domain);
+    state
+        .base_pdns_for(base_domain)
+        .patch_rrsets(&parent_zone, rrsets)
+        .await?;
+    for rrset in rrsets {
+        let result = if rrset.changetype.as_deref() == Some("DELETE") {
+            delegation_repo::delete(&state.db, &rrset.name).await
+        } else {
+            let nameservers: Vec<String> = rrset
+                .records
+                .iter()
+                .map(|record| record.content.clone())
+                .collect();
+            delegation_repo::upsert(&state.db, &rrset.name, base_domain, &nameservers).await
+        };
+        if let Err(err) = result {
+            tracing::warn!(
+                "failed to update delegation inventory for {}: {err:?}",
+                rrset.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replace the inventory of `base_domain` with the NS RRsets among
+/// `rrsets`, the contents of its parent zone.
+pub async fn sync(state: &AppState, base_domain: &str, rrsets: &[PdnsRrset]) -> sqlx::Result<()> {
+    let delegations: Vec<(String, Vec<String>)> = rrsets
+        .iter()
+        .filter(|rrset| rrset.rrtype.eq_ignore_ascii_case("NS"))
+        .map(|rrset| {
+            let nameservers = rrset
+                .records
+                .iter()
+                .map(|record| record.content.clone())
+                .collect();
+            (rrset.name.clone(), nameservers)
+        })
+        .collect();
+    delegation_repo::replace_all(&state.db, base_domain, &delegations).await
+}
+
+/// Read the parent zone of every served base domain into the inventory.
+pub async fn refresh(state: &AppState) -> anyhow::Result<()> {
+    for base_domain in state.config.base_domain_roots() {
+        let parent_zone = state.config.parent_zone_name(base_domain);
+        let parent = state
+            .base_pdns_for(base_domain)
+            .get_zone(&parent_zone)
+            .await
+            .with_context(|| format!("failed to load parent zone {parent_zone}"))?;
+        sync(
+            state,
+            base_domain,
+            parent.rrsets.as_deref().unwrap_or_default(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Fill the inventory once at startup, rather than leaving it to the first
+/// reconciliation scan.
+pub fn spawn_startup_refresh(state: SharedState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(err) = refresh(&state).await {
+            tracing::error!("failed to load the delegation inventory: {err:#}");
+        }
+    })
+}
@@ -0,0 +1,153 @@
+//! DKIM public key records (RFC 6376 section 3.6.1) built from user-supplied
+//! or freshly generated keys.
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rand_core::OsRng;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::{
+    DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding, ObjectIdentifier,
+    SubjectPublicKeyInfoRef,
+};
+use rsa::traits::PublicKeyParts;
+use rsa::{RsaPrivateKey, RsaPublicKey};
+
+/// Smallest RSA key verifiers must accept (RFC 8301 section 3.2).
+pub const MIN_RSA_BITS: usize = 1024;
+
+/// RSA sizes a key may be generated with.
+pub const GENERATED_RSA_BITS: &[usize] = &[2048, 3072, 4096];
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+const ED25519_KEY_LENGTH: usize = 32;
+
+/// Value of the `k=` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Rsa,
+    Ed25519,
+}
+
+impl KeyType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyType::Rsa => "rsa",
+            KeyType::Ed25519 => "ed25519",
+        }
+    }
+}
+
+/// A public key in the form the `p=` tag carries it.
+#[derive(Debug, Clone)]
+pub struct PublicKey {
+    pub key_type: KeyType,
+    /// SubjectPublicKeyInfo DER for RSA, the raw 32 octets for Ed25519 (RFC 8463).
+    pub data: Vec<u8>,
+}
+
+impl PublicKey {
+    /// Parse a PEM key (`PUBLIC KEY` or `RSA PUBLIC KEY`) or base64 DER.
+    /// Base64 of exactly 32 octets is taken as a raw Ed25519 key.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (label, body) = match text.strip_prefix("-----BEGIN ") {
+            Some(rest) => {
+                let (label, rest) = rest.split_once("-----").ok_or("malformed PEM header")?;
+                let end = format!("-----END {label}-----");
+                let body = rest
+                    .trim()
+                    .strip_suffix(end.as_str())
+                    .ok_or("malformed PEM footer")?;
+                (Some(label), body)
+            }
+            None => (None, text),
+        };
+        let body: String = body.split_whitespace().collect();
+        let der = BASE64
+            .decode(body)
+            .map_err(|_| "key is neither PEM nor base64 DER")?;
+
+        match label {
+            Some("PUBLIC KEY") => Self::from_spki(&der),
+            Some("RSA PUBLIC KEY") => Self::from_pkcs1(&der),
+            Some(label) => Err(format!("expected a public key, got {label}")),
+            None if der.len() == ED25519_KEY_LENGTH => Ok(PublicKey {
+                key_type: KeyType::Ed25519,
+                data: der,
+            }),
+            None => Self::from_spki(&der).or_else(|err| Self::from_pkcs1(&der).map_err(|_| err)),
+        }
+    }
+
+    fn from_spki(der: &[u8]) -> Result<Self, String> {
+        let info = SubjectPublicKeyInfoRef::try_from(der)
+            .map_err(|_| "key is not a SubjectPublicKeyInfo")?;
+        match info.algorithm.oid {
+            RSA_ENCRYPTION => {
+                let key = RsaPublicKey::from_public_key_der(der)
+                    .map_err(|_| "malformed RSA public key")?;
+                Self::rsa(&key)
+            }
+            ED25519 => {
+                let raw = info
+                    .subject_public_key
+                    .as_bytes()
+                    .filter(|raw| raw.len() == ED25519_KEY_LENGTH)
+                    .ok_or("malformed Ed25519 public key")?;
+                Ok(PublicKey {
+                    key_type: KeyType::Ed25519,
+                    data: raw.to_vec(),
+                })
+            }
+            oid => Err(format!(
+                "unsupported key algorithm {oid}; DKIM takes RSA or Ed25519"
+            )),
+        }
+    }
+
+    fn from_pkcs1(der: &[u8]) -> Result<Self, String> {
+        let key = RsaPublicKey::from_pkcs1_der(der).map_err(|_| "malformed RSA public key")?;
+        Self::rsa(&key)
+    }
+
+    fn rsa(key: &RsaPublicKey) -> Result<Self, String> {
+        let bits = key.size() * 8;
+        if bits < MIN_RSA_BITS {
+            return Err(format!(
+                "{bits}-bit RSA keys are too short; verifiers require at least {MIN_RSA_BITS}"
+            ));
+        }
+        let der = key
+            .to_public_key_der()
+            .map_err(|_| "malformed RSA public key")?;
+        Ok(PublicKey {
+            key_type: KeyType::Rsa,
+            data: der.as_bytes().to_vec(),
+        })
+    }
+
+    /// Text of the `selector._domainkey` TXT record.
+    pub fn record(&self) -> String {
+        format!(
+            "v=DKIM1; k={}; p={}",
+            self.key_type.as_str(),
+            BASE64.encode(&self.data)
+        )
+    }
+}
+
+/// Generate an RSA key pair; returns the public key and the private key as
+/// PKCS#8 PEM. Slow, so call it off the async runtime.
+pub fn generate_rsa(bits: usize) -> Result<(PublicKey, String), String> {
+    if !GENERATED_RSA_BITS.contains(&bits) {
+        return Err(format!(
+            "key size must be one of {GENERATED_RSA_BITS:?} bits"
+        ));
+    }
+    let private = RsaPrivateKey::new(&mut OsRng, bits).map_err(|err| err.to_string())?;
+    let pem = private
+        .to_pkcs8_pem(LineEnding::LF)
+        .map_err(|err| err.to_string())?;
+    let public = PublicKey::rsa(&RsaPublicKey::from(&private))?;
+    Ok((public, pem.to_string()))
+}
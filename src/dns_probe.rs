@@ -0,0 +1,326 @@
+//! Direct, non-recursive queries to a nameserver, to ask for a zone's SOA or
+//! NS set and tell whether it answers authoritatively, or to show what it
+//! serves for a name. The messages and transport are hickory-client's; its
+//! resolver is not used, since it has caches and hides the AA flag and the
+//! response code this module reports.
+use anyhow::{Context, bail};
+use hickory_client::client::Client;
+use hickory_proto::dnssec::rdata::DNSSECRData;
+use hickory_proto::op::{Query, ResponseCode};
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use hickory_proto::runtime::TokioRuntimeProvider;
+use hickory_proto::serialize::binary::{BinDecoder, BinEncodable, Restrict};
+use hickory_proto::udp::UdpClientStream;
+use hickory_proto::xfer::{DnsHandle, DnsRequestOptions, DnsResponse, FirstAnswer};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::rdata;
+
+/// How a nameserver answered a SOA query for a zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoaAnswer {
+    /// Authoritative answer carrying the zone's SOA.
+    Authoritative { serial: u32 },
+    /// The server answered but does not serve the zone (a lame delegation).
+    Lame(String),
+}
+
+/// How a nameserver answered an NS query for a zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NsAnswer {
+    /// Authoritative answer with the zone's NS set, lowercase with trailing dots.
+    Authoritative(Vec<String>),
+    /// The server answered but does not serve the zone.
+    Lame(String),
+}
+
+/// One answer-section record, with its data in presentation format.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnswerRecord {
+    pub name: String,
+    pub rrtype: String,
+    pub ttl: u32,
+    pub content: String,
+}
+
+impl From<&Record> for AnswerRecord {
+    fn from(record: &Record) -> Self {
+        Self {
+            name: record.name().to_lowercase().to_ascii(),
+            rrtype: rdata::type_name(record.record_type().into()),
+            ttl: record.ttl(),
+            content: record_text(record.data()),
+        }
+    }
+}
+
+/// A nameserver's response to a query for any type.
+#[derive(Debug, Clone, Serialize)]
+pub struct Response {
+    /// Response code, such as `NOERROR` or `NXDOMAIN`.
+    pub rcode: String,
+    pub authoritative: bool,
+    /// Whether the answer did not fit into a UDP response and was left out.
+    pub truncated: bool,
+    pub answers: Vec<AnswerRecord>,
+}
+
+/// Resolve `hostname` and query each address in turn until one answers.
+/// Unreachable servers are reported as errors.
+pub async fn query_soa_by_name(
+    hostname: &str,
+    zone: &str,
+    timeout: Duration,
+) -> anyhow::Result<SoaAnswer> {
+    let addrs = resolve(hostname, timeout).await?;
+    query_soa_any(&addrs, zone, timeout).await
+}
+
+/// Addresses of a nameserver `hostname`, with port 53.
+pub async fn resolve(hostname: &str, timeout: Duration) -> anyhow::Result<Vec<SocketAddr>> {
+    let host = hostname.trim_end_matches('.');
+    let addrs: Vec<SocketAddr> = tokio::time::timeout(timeout, tokio::net::lookup_host((host, 53)))
+        .await
+        .with_context(|| format!("resolving {host} timed out"))?
+        .with_context(|| format!("failed to resolve {host}"))?
+        .collect();
+    if addrs.is_empty() {
+        bail!("{host} has no addresses");
+    }
+    Ok(addrs)
+}
+
+/// Query each of `addrs` in turn until one answers.
+pub async fn query_soa_any(
+    addrs: &[SocketAddr],
+    zone: &str,
+    timeout: Duration,
+) -> anyhow::Result<SoaAnswer> {
+    let mut last_err = None;
+    for addr in addrs {
+        match query_soa(*addr, zone, timeout).await {
+            Ok(answer) => return Ok(answer),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.context("no addresses to query")?)
+}
+
+/// Query each of `addrs` in turn for the zone's NS set until one answers.
+pub async fn query_ns_any(
+    addrs: &[SocketAddr],
+    zone: &str,
+    timeout: Duration,
+) -> anyhow::Result<NsAnswer> {
+    let mut last_err = None;
+    for addr in addrs {
+        match query_ns(*addr, zone, timeout).await {
+            Ok(answer) => return Ok(answer),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.context("no addresses to query")?)
+}
+
+/// Query each of `addrs` in turn for `name` and `qtype` until one answers.
+pub async fn query_any(
+    addrs: &[SocketAddr],
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> anyhow::Result<Response> {
+    let mut last_err = None;
+    for addr in addrs {
+        match query(*addr, name, qtype, timeout).await {
+            Ok(response) => return Ok(response),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.context("no addresses to query")?)
+}
+
+/// Send one non-recursive query for `name` and `qtype` to `server`.
+pub async fn query(
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> anyhow::Result<Response> {
+    let response = exchange(server, name, RecordType::from(qtype), timeout).await?;
+    let truncated = response.truncated();
+    Ok(Response {
+        rcode: rcode_name(response.response_code()),
+        authoritative: response.authoritative(),
+        truncated,
+        answers: if truncated {
+            Vec::new()
+        } else {
+            response.answers().iter().map(AnswerRecord::from).collect()
+        },
+    })
+}
+
+/// Send one non-recursive SOA query for `zone` to `server`.
+pub async fn query_soa(
+    server: SocketAddr,
+    zone: &str,
+    timeout: Duration,
+) -> anyhow::Result<SoaAnswer> {
+    let response = exchange(server, zone, RecordType::SOA, timeout).await?;
+    if let Some(lame) = lame_reason(&response) {
+        return Ok(SoaAnswer::Lame(lame));
+    }
+    let serial = response
+        .answers()
+        .iter()
+        .find_map(|record| match record.data() {
+            RData::SOA(soa) => Some(soa.serial()),
+            _ => None,
+        });
+    Ok(match serial {
+        Some(serial) => SoaAnswer::Authoritative { serial },
+        None => SoaAnswer::Lame("authoritative answer without SOA".into()),
+    })
+}
+
+/// Send one non-recursive NS query for `zone` to `server`.
+pub async fn query_ns(
+    server: SocketAddr,
+    zone: &str,
+    timeout: Duration,
+) -> anyhow::Result<NsAnswer> {
+    let response = exchange(server, zone, RecordType::NS, timeout).await?;
+    if let Some(lame) = lame_reason(&response) {
+        return Ok(NsAnswer::Lame(lame));
+    }
+    let mut nameservers: Vec<String> = response
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            RData::NS(ns) => Some(ns.0.to_lowercase().to_ascii()),
+            _ => None,
+        })
+        .collect();
+    if nameservers.is_empty() {
+        return Ok(NsAnswer::Lame("authoritative answer without NS".into()));
+    }
+    nameservers.sort();
+    nameservers.dedup();
+    Ok(NsAnswer::Authoritative(nameservers))
+}
+
+/// Send a query with EDNS and without the RD flag, and wait for the answer.
+async fn exchange(
+    server: SocketAddr,
+    name: &str,
+    qtype: RecordType,
+    timeout: Duration,
+) -> anyhow::Result<DnsResponse> {
+    let Ok(name) = Name::from_ascii(name) else {
+        bail!("invalid name {name}");
+    };
+    let stream = UdpClientStream::builder(server, TokioRuntimeProvider::new())
+        .with_timeout(Some(timeout))
+        .build();
+    let (client, background) = Client::connect(stream)
+        .await
+        .with_context(|| format!("failed to query {server}"))?;
+    let background = tokio::spawn(background);
+    let mut options = DnsRequestOptions::default();
+    options.use_edns = true;
+    options.recursion_desired = false;
+    let response = client
+        .lookup(Query::query(name, qtype), options)
+        .first_answer()
+        .await;
+    background.abort();
+    response.with_context(|| format!("no answer from {server}"))
+}
+
+/// Why a response to a SOA or NS query does not show the server serving
+/// the zone, if it does not.
+fn lame_reason(response: &DnsResponse) -> Option<String> {
+    match response.response_code() {
+        ResponseCode::NoError if response.authoritative() => None,
+        ResponseCode::NoError => Some("answer is not authoritative".into()),
+        code => Some(rcode_name(code)),
+    }
+}
+
+/// Presentation format of record data. Strings are quoted and escaped, which
+/// hickory's `Display` does not do; types without a format here, and those
+/// hickory does not know, use the RFC 3597 generic form.
+fn record_text(data: &RData) -> String {
+    match data {
+        RData::A(_)
+        | RData::AAAA(_)
+        | RData::NS(_)
+        | RData::CNAME(_)
+        | RData::PTR(_)
+        | RData::MX(_)
+        | RData::SRV(_)
+        | RData::SOA(_)
+        | RData::SSHFP(_)
+        | RData::TLSA(_) => data.to_string().to_ascii_lowercase(),
+        RData::DNSSEC(ds @ DNSSECRData::DS(_)) => ds.to_string().to_ascii_lowercase(),
+        RData::TXT(txt) => txt
+            .txt_data()
+            .iter()
+            .map(|string| rdata::quote(string))
+            .collect::<Vec<_>>()
+            .join(" "),
+        RData::CAA(caa) => format!(
+            "{} {} {}",
+            caa.flags(),
+            caa.tag().as_str(),
+            rdata::quote(caa.raw_value())
+        ),
+        // DNAME and SPF carry the same data as CNAME and TXT
+        RData::Unknown { code, rdata } => {
+            let alias = match u16::from(*code) {
+                39 => Some(RecordType::CNAME),
+                99 => Some(RecordType::TXT),
+                _ => None,
+            };
+            let bytes = rdata.anything();
+            alias
+                .and_then(|rrtype| {
+                    let length = Restrict::new(u16::try_from(bytes.len()).ok()?);
+                    RData::read(&mut BinDecoder::new(bytes), rrtype, length).ok()
+                })
+                .map_or_else(|| generic_text(bytes), |data| record_text(&data))
+        }
+        _ => data
+            .to_bytes()
+            .map_or_else(|_| "\\# 0".to_string(), |bytes| generic_text(&bytes)),
+    }
+}
+
+/// Mnemonic of a response code, such as `NOERROR` or `NXDOMAIN`.
+pub(crate) fn rcode_name(rcode: ResponseCode) -> String {
+    match rcode {
+        ResponseCode::NoError => "NOERROR".into(),
+        ResponseCode::FormErr => "FORMERR".into(),
+        ResponseCode::ServFail => "SERVFAIL".into(),
+        ResponseCode::NXDomain => "NXDOMAIN".into(),
+        ResponseCode::NotImp => "NOTIMP".into(),
+        ResponseCode::Refused => "REFUSED".into(),
+        ResponseCode::NotAuth => "NOTAUTH".into(),
+        other => format!("RCODE{}", u16::from(other)),
+    }
+}
+
+/// RFC 3597 `\# <length> <hex>` form of RDATA.
+pub(crate) fn generic_text(data: &[u8]) -> String {
+    if data.is_empty() {
+        "\\# 0".to_string()
+    } else {
+        format!("\\# {} {}", data.len(), hex(data))
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -1,46 +1,303 @@
 //! Application error helpers and Axum integration.
+//!
+//! Errors are rendered as RFC 7807 `application/problem+json` documents whose
+//! `code` (and the matching `type` URI) stays stable across releases.
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
+use std::fmt;
 use thiserror::Error;
 
-/// Standard JSON error payload emitted by the API.
+use crate::i18n::{Locale, Message, MessageId};
+
+/// Media type of every error body.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Machine-readable error codes clients can branch on.
+///
+/// Codes are part of the API: new ones may appear, existing ones keep their meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidRequest,
+    InvalidSubdomain,
+    SubdomainReserved,
+    SubdomainConfusable,
+    SubdomainTaken,
+    UnknownBaseDomain,
+    ZoneChanged,
+    InvalidRecord,
+    NameTooLong,
+    RecordTypeNotAllowed,
+    ContentRejected,
+    RecordQuotaExceeded,
+    ZoneTooLarge,
+    InvalidNameservers,
+    NameserversNotReady,
+    TransferFailed,
+    WeakPassword,
+    EmailDomainRejected,
+    InvalidWebhook,
+    WebhookLimitReached,
+    Unauthorized,
+    InvalidCredentials,
+    SignupClosed,
+    InviteRequired,
+    InvalidInvite,
+    FeatureDisabled,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    TooManyRequests,
+    Maintenance,
+    Degraded,
+    NotReady,
+    Overloaded,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Wire name used in the `code` field and the `type` URI.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::InvalidSubdomain => "invalid_subdomain",
+            ErrorCode::SubdomainReserved => "subdomain_reserved",
+            ErrorCode::SubdomainConfusable => "subdomain_confusable",
+            ErrorCode::SubdomainTaken => "subdomain_taken",
+            ErrorCode::UnknownBaseDomain => "unknown_base_domain",
+            ErrorCode::ZoneChanged => "zone_changed",
+            ErrorCode::InvalidRecord => "invalid_record",
+            ErrorCode::NameTooLong => "name_too_long",
+            ErrorCode::RecordTypeNotAllowed => "record_type_not_allowed",
+            ErrorCode::ContentRejected => "content_rejected",
+            ErrorCode::RecordQuotaExceeded => "record_quota_exceeded",
+            ErrorCode::ZoneTooLarge => "zone_too_large",
+            ErrorCode::InvalidNameservers => "invalid_nameservers",
+            ErrorCode::NameserversNotReady => "nameservers_not_ready",
+            ErrorCode::TransferFailed => "transfer_failed",
+            ErrorCode::WeakPassword => "weak_password",
+            ErrorCode::EmailDomainRejected => "email_domain_rejected",
+            ErrorCode::InvalidWebhook => "invalid_webhook",
+            ErrorCode::WebhookLimitReached => "webhook_limit_reached",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::InvalidCredentials => "invalid_credentials",
+            ErrorCode::SignupClosed => "signup_closed",
+            ErrorCode::InviteRequired => "invite_required",
+            ErrorCode::InvalidInvite => "invalid_invite",
+            ErrorCode::FeatureDisabled => "feature_disabled",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::MethodNotAllowed => "method_not_allowed",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::UnsupportedMediaType => "unsupported_media_type",
+            ErrorCode::TooManyRequests => "too_many_requests",
+            ErrorCode::Maintenance => "maintenance",
+            ErrorCode::Degraded => "degraded",
+            ErrorCode::NotReady => "not_ready",
+            ErrorCode::Overloaded => "overloaded",
+            ErrorCode::Internal => "internal_error",
+        }
+    }
+
+    /// HTTP status the code is served with.
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::InvalidSubdomain
+            | ErrorCode::SubdomainReserved
+            | ErrorCode::SubdomainConfusable
+            | ErrorCode::UnknownBaseDomain
+            | ErrorCode::InvalidRecord
+            | ErrorCode::NameTooLong
+            | ErrorCode::RecordTypeNotAllowed
+            | ErrorCode::ContentRejected
+            | ErrorCode::RecordQuotaExceeded
+            | ErrorCode::InvalidNameservers
+            | ErrorCode::NameserversNotReady
+            | ErrorCode::TransferFailed
+            | ErrorCode::WeakPassword
+            | ErrorCode::EmailDomainRejected
+            | ErrorCode::InvalidWebhook
+            | ErrorCode::WebhookLimitReached => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized | ErrorCode::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ErrorCode::SignupClosed
+            | ErrorCode::InviteRequired
+            | ErrorCode::InvalidInvite
+            | ErrorCode::FeatureDisabled => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::SubdomainTaken | ErrorCode::ZoneChanged | ErrorCode::Conflict => {
+                StatusCode::CONFLICT
+            }
+            ErrorCode::ZoneTooLarge | ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Maintenance
+            | ErrorCode::Degraded
+            | ErrorCode::NotReady
+            | ErrorCode::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Generic code for a bare status produced outside the handlers
+    /// (extractor rejections, body limits, unmatched routes).
+    pub fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::TooManyRequests,
+            status if status.is_server_error() => ErrorCode::Internal,
+            _ => ErrorCode::InvalidRequest,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// RFC 7807 problem document emitted by the API.
 #[derive(Debug, Serialize)]
-pub struct ErrorResponseBody {
-    pub error: String,
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+    pub code: &'static str,
+    /// Extension members specific to the problem type.
+    #[serde(flatten)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+    /// What `detail` says, for translation; travels in the response extensions.
+    #[serde(skip)]
+    pub message: Option<Message>,
 }
 
-/// Common error cases surfaced to HTTP clients.
-#[derive(Debug, Error)]
-pub enum AppError {
-    #[error("bad request: {0}")]
-    BadRequest(String),
+impl Problem {
+    /// Build a problem for `code`, served with `status`.
+    pub fn new(status: StatusCode, code: ErrorCode, detail: impl Into<String>) -> Self {
+        Problem {
+            type_uri: format!("urn:satsuki:problem:{code}"),
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: detail.into(),
+            code: code.as_str(),
+            extensions: serde_json::Map::new(),
+            message: None,
+        }
+    }
 
-    #[error("unauthorized")]
-    Unauthorized,
+    /// Build a problem whose detail is a translatable message.
+    pub fn localized(status: StatusCode, code: ErrorCode, message: impl Into<Message>) -> Self {
+        let message = message.into();
+        Problem {
+            message: Some(message.clone()),
+            ..Problem::new(status, code, message.render(Locale::En))
+        }
+    }
+
+    /// Attach an extension member.
+    pub fn with(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.extensions.insert(key.to_string(), value.into());
+        self
+    }
+}
 
-    #[error("conflict: {0}")]
-    Conflict(String),
+impl IntoResponse for Problem {
+    fn into_response(mut self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let message = self.message.take();
+        let mut response = (status, Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        if let Some(message) = message {
+            response.extensions_mut().insert(message);
+        }
+        response
+    }
+}
 
-    #[error("not found")]
-    NotFound,
+/// Error type returned by every handler and extractor.
+#[derive(Debug, Error)]
+pub enum AppError {
+    /// A failure the client can act on.
+    #[error("{detail}")]
+    Api {
+        code: ErrorCode,
+        detail: String,
+        /// What `detail` says, if it can be translated.
+        message: Option<Message>,
+        /// Extension members added to the problem document.
+        extensions: serde_json::Map<String, serde_json::Value>,
+    },
 
+    /// Anything else; details are logged, never sent to the client.
     #[error("internal server error")]
     Internal(#[from] anyhow::Error),
 }
 
 impl AppError {
-    /// Convenience constructor for `400 Bad Request`.
+    /// Client-facing error with a stable code.
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> Self {
+        AppError::Api {
+            code,
+            detail: detail.into(),
+            message: None,
+            extensions: serde_json::Map::new(),
+        }
+    }
+
+    /// Client-facing error whose detail is translated per `Accept-Language`.
+    pub fn localized(code: ErrorCode, message: impl Into<Message>) -> Self {
+        let message = message.into();
+        AppError::Api {
+            code,
+            detail: message.render(Locale::En),
+            message: Some(message),
+            extensions: serde_json::Map::new(),
+        }
+    }
+
+    /// Attach an extension member to the problem document; internal errors
+    /// stay bare.
+    pub fn with(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        if let AppError::Api { extensions, .. } = &mut self {
+            extensions.insert(key.to_string(), value.into());
+        }
+        self
+    }
+
+    /// Convenience constructor for a generic `400 Bad Request`.
     pub fn bad_request(msg: impl Into<String>) -> Self {
-        AppError::BadRequest(msg.into())
+        AppError::new(ErrorCode::InvalidRequest, msg)
+    }
+
+    /// Convenience constructor for `401 Unauthorized`.
+    pub fn unauthorized(msg: impl Into<String>) -> Self {
+        AppError::new(ErrorCode::Unauthorized, msg)
     }
 
-    /// Convenience constructor for `409 Conflict`.
+    /// Convenience constructor for a generic `409 Conflict`.
     pub fn conflict(msg: impl Into<String>) -> Self {
-        AppError::Conflict(msg.into())
+        AppError::new(ErrorCode::Conflict, msg)
+    }
+
+    /// Convenience constructor for `404 Not Found`.
+    pub fn not_found() -> Self {
+        AppError::localized(ErrorCode::NotFound, MessageId::NotFound)
     }
 
     /// Wrap any error into `500 Internal Server Error`.
@@ -52,22 +309,50 @@ impl AppError {
     pub fn internal_anyhow(err: anyhow::Error) -> Self {
         AppError::Internal(err)
     }
+
+    /// Stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Api { code, .. } => *code,
+            AppError::Internal(_) => ErrorCode::Internal,
+        }
+    }
 }
 
+/// `map_err` helper turning any upstream failure into an internal error.
+pub(crate) fn internal<E: fmt::Debug>(err: E) -> AppError {
+    AppError::Internal(anyhow::anyhow!("{err:?}"))
+}
+
+/// A numeric `retry_after` extension is also sent as the `Retry-After` header.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, msg) = match self {
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized".into()),
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
-            AppError::NotFound => (StatusCode::NOT_FOUND, "not found".into()),
-            AppError::Internal(_) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "internal server error".into(),
-            ),
+        let code = self.code();
+        let (detail, message, extensions) = match self {
+            AppError::Api {
+                detail,
+                message,
+                extensions,
+                ..
+            } => (detail, message, extensions),
+            AppError::Internal(err) => {
+                tracing::error!("{err:#}");
+                let message = Message::from(MessageId::Internal);
+                (message.to_string(), Some(message), serde_json::Map::new())
+            }
         };
-
-        let body = Json(ErrorResponseBody { error: msg });
-        (status, body).into_response()
+        let retry_after = extensions
+            .get("retry_after")
+            .and_then(serde_json::Value::as_u64);
+        let mut problem = Problem::new(code.status(), code, detail);
+        problem.extensions = extensions;
+        problem.message = message;
+        let mut response = problem.into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
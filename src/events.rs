@@ -0,0 +1,137 @@
+//! In-process bus for user-visible change events (webhooks, streaming clients).
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::notifications::Category;
+
+/// Events buffered for slow subscribers before they start missing some.
+const EVENT_BUFFER: usize = 1024;
+
+/// A change to a user's zone or account.
+#[derive(Clone, Debug, Serialize)]
+pub struct Event {
+    /// Owner of the affected zone; not serialized to subscribers.
+    #[serde(skip)]
+    pub user_id: i64,
+    pub zone: String,
+    pub occurred_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What happened, tagged by `type` in the JSON payload.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum EventKind {
+    #[serde(rename = "records.changed")]
+    RecordsChanged {
+        rrsets: Vec<RrsetRef>,
+        /// Credential kind the change was made with, such as `password`.
+        credential: String,
+    },
+    #[serde(rename = "ns_mode.changed")]
+    NsModeChanged {
+        external: bool,
+        nameservers: Vec<String>,
+    },
+    #[serde(rename = "dnssec.toggled")]
+    DnssecToggled { enabled: bool },
+    /// External nameservers that kept failing SOA checks.
+    #[serde(rename = "ns_health.failing")]
+    NsHealthFailing { nameservers: Vec<String> },
+    /// Previously reported nameservers that answer authoritatively again.
+    #[serde(rename = "ns_health.recovered")]
+    NsHealthRecovered { nameservers: Vec<String> },
+    #[serde(rename = "account.password_changed")]
+    PasswordChanged,
+    /// A sign-in from a country none of the account's earlier sign-ins
+    /// came from, per the `--geoip-db` databases.
+    #[serde(rename = "account.signin_new_country")]
+    SigninNewCountry { country: String, client_ip: String },
+    /// The unused account is about to be removed by the `expiry` job.
+    #[serde(rename = "account.expiry_reminder")]
+    ExpiryReminder { expires_at: DateTime<Utc> },
+    /// A message from the operators to every user; delivered directly
+    /// rather than through the bus.
+    #[serde(rename = "announcement")]
+    Announcement { subject: String, text: String },
+}
+
+/// Identifies one RRset touched by a change.
+#[derive(Clone, Debug, Serialize)]
+pub struct RrsetRef {
+    pub name: String,
+    pub rrtype: String,
+}
+
+impl Event {
+    /// Build an event stamped with the current time.
+    pub fn new(user_id: i64, zone: impl Into<String>, kind: EventKind) -> Self {
+        Self {
+            user_id,
+            zone: zone.into(),
+            occurred_at: Utc::now(),
+            kind,
+        }
+    }
+
+    /// The `type` tag, e.g. `records.changed`.
+    pub fn type_name(&self) -> &'static str {
+        match self.kind {
+            EventKind::RecordsChanged { .. } => "records.changed",
+            EventKind::NsModeChanged { .. } => "ns_mode.changed",
+            EventKind::DnssecToggled { .. } => "dnssec.toggled",
+            EventKind::NsHealthFailing { .. } => "ns_health.failing",
+            EventKind::NsHealthRecovered { .. } => "ns_health.recovered",
+            EventKind::PasswordChanged => "account.password_changed",
+            EventKind::SigninNewCountry { .. } => "account.signin_new_country",
+            EventKind::ExpiryReminder { .. } => "account.expiry_reminder",
+            EventKind::Announcement { .. } => "announcement",
+        }
+    }
+}
+
+impl EventKind {
+    /// The category users route this kind of event by, if any.
+    pub fn category(&self) -> Option<Category> {
+        match self {
+            EventKind::PasswordChanged | EventKind::SigninNewCountry { .. } => {
+                Some(Category::Security)
+            }
+            EventKind::NsHealthFailing { .. } | EventKind::NsHealthRecovered { .. } => {
+                Some(Category::DelegationHealth)
+            }
+            EventKind::ExpiryReminder { .. } => Some(Category::Expiry),
+            EventKind::Announcement { .. } => Some(Category::Announcements),
+            EventKind::RecordsChanged { .. }
+            | EventKind::NsModeChanged { .. }
+            | EventKind::DnssecToggled { .. } => None,
+        }
+    }
+}
+
+/// Fan-out channel; publishing never blocks and events without subscribers are dropped.
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    /// Deliver an event to every current subscriber.
+    pub fn publish(&self, event: Event) {
+        // an error only means nobody is listening right now
+        let _ = self.sender.send(event);
+    }
+
+    /// Receive every event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
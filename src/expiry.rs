@@ -0,0 +1,167 @@
+//! Removal of accounts that were registered but never used, so squatted
+//! labels do not pile up.
+use async_trait::async_trait;
+use chrono::Utc;
+
+use crate::api::dns::is_server_managed;
+use crate::config::PolicyConfig;
+use crate::db::user_repo::{self, UnusedAccount};
+use crate::events::{Event, EventKind};
+use crate::jobs::{Job, Schedule};
+use crate::powerdns::types::PdnsRrset;
+use crate::{AppState, delegations};
+
+/// Scheduled sweeps, per `[expiry]` in the policy.
+pub struct ExpiryJob;
+
+#[async_trait]
+impl Job for ExpiryJob {
+    fn name(&self) -> &'static str {
+        "expiry"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.expiry;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let reminded = remind(state).await?;
+        let removed = sweep(state).await?;
+        Ok(format!(
+            "expired {removed} unused accounts, reminded {reminded} owners"
+        ))
+    }
+}
+
+/// Publish an `account.expiry_reminder` for every unused account within
+/// `expiry.remind_before_days` of removal, once per account. Returns how
+/// many owners were reminded.
+pub async fn remind(state: &AppState) -> anyhow::Result<usize> {
+    let (days, remind_before) = {
+        let policy = state.policy();
+        (
+            policy.expiry.unused_after_days,
+            policy.expiry.remind_before_days,
+        )
+    };
+    if remind_before == 0 {
+        return Ok(0);
+    }
+    let now = Utc::now();
+    let lifetime = chrono::Duration::days(i64::from(days));
+    let remind_period = chrono::Duration::days(i64::from(remind_before));
+    let cutoff = now - lifetime + remind_period;
+    let mut reminded = 0;
+    for account in user_repo::list_unreminded(&state.db, cutoff).await? {
+        // accounts with records are kept, so their owners need no warning
+        if !matches!(has_records(state, &account).await, Ok(false)) {
+            continue;
+        }
+        user_repo::mark_expiry_reminded(&state.db, account.id).await?;
+        state.events.publish(Event::new(
+            account.id,
+            state
+                .config
+                .user_zone_name(&account.subdomain, &account.base_domain),
+            EventKind::ExpiryReminder {
+                expires_at: (account.created_at + lifetime).max(now + remind_period),
+            },
+        ));
+        reminded += 1;
+    }
+    Ok(reminded)
+}
+
+/// Remove every unused account older than `expiry.unused_after_days` whose
+/// zone holds no user records, and whose owner was reminded at least
+/// `expiry.remind_before_days` ago. Returns how many were removed.
+pub async fn sweep(state: &AppState) -> anyhow::Result<usize> {
+    let (days, remind_before) = {
+        let policy = state.policy();
+        (
+            policy.expiry.unused_after_days,
+            policy.expiry.remind_before_days,
+        )
+    };
+    let now = Utc::now();
+    let cutoff = now - chrono::Duration::days(i64::from(days));
+    // owners get the full reminder period, even when reminded late
+    let reminded_before = now - chrono::Duration::days(i64::from(remind_before));
+    let mut removed = 0;
+    for account in user_repo::list_unused(&state.db, cutoff).await? {
+        if remind_before > 0
+            && account
+                .expiry_reminded_at
+                .is_none_or(|reminded_at| reminded_at > reminded_before)
+        {
+            continue;
+        }
+        match has_records(state, &account).await {
+            Ok(false) => {}
+            Ok(true) => continue,
+            Err(err) => {
+                tracing::warn!("skipping expiry of {}: {err:#}", account.subdomain);
+                continue;
+            }
+        }
+        if expire(state, &account).await? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+async fn has_records(state: &AppState, account: &UnusedAccount) -> anyhow::Result<bool> {
+    let zone_name = state
+        .config
+        .user_zone_name(&account.subdomain, &account.base_domain);
+    let zone = state
+        .sub_pdns_for(&account.base_domain)
+        .get_zone(&zone_name)
+        .await?;
+    Ok(zone
+        .rrsets
+        .unwrap_or_default()
+        .iter()
+        .any(|rr| !is_server_managed(rr, &zone_name)))
+}
+
+/// Delete the user row, then its delegation and zone. The row goes first and
+/// only if the account is still unused, so a user signing in meanwhile keeps it;
+/// leftover PowerDNS data is reported by the reconciler as orphaned.
+async fn expire(state: &AppState, account: &UnusedAccount) -> anyhow::Result<bool> {
+    let zone_name = state
+        .config
+        .user_zone_name(&account.subdomain, &account.base_domain);
+    let _guard = state.in_flight.begin(format!("expiring {zone_name}"));
+    if !user_repo::delete_unused(&state.db, account.id).await? {
+        return Ok(false);
+    }
+
+    let delegation = PdnsRrset {
+        name: zone_name.clone(),
+        rrtype: "NS".into(),
+        ttl: state.config.ns_ttl,
+        changetype: Some("DELETE".into()),
+        records: Vec::new(),
+        comments: Vec::new(),
+    };
+    if let Err(err) = delegations::patch(state, &account.base_domain, &[delegation]).await {
+        tracing::error!("failed to remove delegation of expired {zone_name}: {err:#}");
+    }
+    if let Err(err) = state
+        .sub_pdns_for(&account.base_domain)
+        .delete_zone(&zone_name)
+        .await
+    {
+        tracing::error!("failed to remove zone of expired {zone_name}: {err:#}");
+    }
+    tracing::info!(
+        "expired unused account {zone_name}, registered {}",
+        account.created_at
+    );
+    Ok(true)
+}
@@ -0,0 +1,106 @@
+//! Country and ASN lookups in MaxMind-format (`.mmdb`) databases, such as
+//! GeoLite2-Country and GeoLite2-ASN, for annotating sign-ins and signup
+//! sources. The files are read with the `maxminddb` crate.
+use anyhow::Context;
+use maxminddb::{Reader, geoip2};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// What the databases know about an address.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Location {
+    /// ISO 3166-1 alpha-2 code, e.g. `JP`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+}
+
+impl Location {
+    fn is_empty(&self) -> bool {
+        self.country.is_none() && self.asn.is_none() && self.as_org.is_none()
+    }
+}
+
+/// The databases given with `--geoip-db`, consulted in order.
+pub struct GeoIp {
+    databases: Vec<Reader<Vec<u8>>>,
+}
+
+impl GeoIp {
+    /// Load every database into memory.
+    pub fn open(paths: &[PathBuf]) -> anyhow::Result<Self> {
+        let databases = paths
+            .iter()
+            .map(|path| {
+                Reader::open_readfile(path)
+                    .with_context(|| format!("failed to load {}", path.display()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { databases })
+    }
+
+    /// Short description of the loaded databases for the startup log.
+    pub fn describe(&self) -> String {
+        self.databases
+            .iter()
+            .map(|db| db.metadata().database_type.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Country and ASN of `ip`; each field comes from the first database
+    /// that has it. `None` when no database knows the address.
+    pub fn lookup(&self, ip: IpAddr) -> Option<Location> {
+        // IPv4-mapped addresses from dual-stack listeners are IPv4 clients
+        let ip = ip.to_canonical();
+        let mut location = Location::default();
+        for db in &self.databases {
+            let database_type = &db.metadata().database_type;
+            let result = match db.lookup(ip) {
+                Ok(result) if result.has_data() => result,
+                Ok(_) => continue,
+                Err(err) => {
+                    tracing::debug!("GeoIP lookup of {ip} in {database_type} failed: {err}");
+                    continue;
+                }
+            };
+            if location.country.is_none() {
+                match result.decode::<geoip2::Country>() {
+                    Ok(record) => {
+                        location.country = record
+                            .and_then(|record| {
+                                record
+                                    .country
+                                    .iso_code
+                                    .or(record.registered_country.iso_code)
+                            })
+                            .map(str::to_string);
+                    }
+                    Err(err) => tracing::debug!(
+                        "GeoIP country of {ip} in {database_type} is unreadable: {err}"
+                    ),
+                }
+            }
+            if location.asn.is_none() || location.as_org.is_none() {
+                match result.decode::<geoip2::Asn>() {
+                    Ok(Some(record)) => {
+                        location.asn = location.asn.or(record.autonomous_system_number);
+                        location.as_org = location
+                            .as_org
+                            .take()
+                            .or_else(|| record.autonomous_system_organization.map(str::to_string));
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::debug!("GeoIP ASN of {ip} in {database_type} is unreadable: {err}")
+                    }
+                }
+            }
+        }
+        (!location.is_empty()).then_some(location)
+    }
+}
@@ -0,0 +1,253 @@
+//! Dependency checks shared by `--check-config` and readiness probes.
+use crate::db::{self, Db};
+use crate::powerdns::client::PowerDnsClient;
+use crate::powerdns::types::{PdnsZone, PdnsZoneCreate};
+use crate::{AppState, SharedState};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Pause between startup check rounds; also the `Retry-After` of API
+/// requests held until they pass.
+pub const STARTUP_RETRY: Duration = Duration::from_secs(2);
+
+/// Outcome of a single named check.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Ordered collection of check outcomes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    /// Record the outcome of a check; `Err` carries the failure reason.
+    pub fn record(&mut self, name: impl Into<String>, outcome: Result<String, String>) {
+        let (ok, detail) = match outcome {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        self.checks.push(CheckResult {
+            name: name.into(),
+            ok,
+            detail,
+        });
+    }
+
+    /// Whether every recorded check passed.
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+}
+
+/// Verify the database answers queries and its migrations match this binary.
+///
+/// With `require_current` set, pending migrations count as a failure.
+pub async fn check_database(db: &Db, require_current: bool) -> Result<String, String> {
+    sqlx::query("SELECT 1")
+        .execute(db)
+        .await
+        .map_err(|e| format!("query failed: {e}"))?;
+
+    let status = db::migration_status(db)
+        .await
+        .map_err(|e| format!("failed to read migration state: {e:#}"))?;
+    if !status.unknown.is_empty() {
+        return Err(format!(
+            "database has migrations unknown to this binary: {:?}",
+            status.unknown
+        ));
+    }
+    if !status.pending.is_empty() {
+        let msg = format!("{} pending migration(s)", status.pending.len());
+        return if require_current { Err(msg) } else { Ok(msg) };
+    }
+    Ok("reachable, migrations up to date".into())
+}
+
+/// Verify a PowerDNS API endpoint responds with valid credentials.
+pub async fn check_pdns(client: &PowerDnsClient) -> Result<String, String> {
+    let info = client.server_info().await.map_err(|e| format!("{e:#}"))?;
+    Ok(format!(
+        "{} {} ({})",
+        info.daemon_type, info.version, info.id
+    ))
+}
+
+/// Verify a zone exists on the given PowerDNS server.
+pub async fn check_zone(client: &PowerDnsClient, zone: &str) -> Result<String, String> {
+    let zone = client.get_zone(zone).await.map_err(|e| format!("{e:#}"))?;
+    let rrsets = zone.rrsets.map(|r| r.len()).unwrap_or_default();
+    Ok(format!("{} present ({} rrsets)", zone.name, rrsets))
+}
+
+/// Verify a parent zone exists and lists every internal nameserver at its
+/// apex, so delegations made at signup point at servers that answer for it.
+pub async fn check_parent_zone(
+    client: &PowerDnsClient,
+    zone: &str,
+    internal_ns: &[String],
+) -> Result<String, String> {
+    let found = client.find_zone(zone).await.map_err(|e| format!("{e:#}"))?;
+    let Some(found) = found else {
+        return Err(format!(
+            "{zone} does not exist on the base PowerDNS; create it with the internal \
+             nameservers at its apex, or start with --bootstrap-parent-zone"
+        ));
+    };
+    let missing = missing_apex_ns(&found, internal_ns);
+    if !missing.is_empty() {
+        return Err(format!(
+            "{zone} apex NS lacks {}; add them there or fix --internal-ns",
+            missing.join(", ")
+        ));
+    }
+    Ok(format!("{} present, apex NS complete", found.name))
+}
+
+/// Internal nameservers absent from the enabled NS records at the apex of `zone`.
+pub(crate) fn missing_apex_ns<'a>(zone: &PdnsZone, internal_ns: &'a [String]) -> Vec<&'a str> {
+    let apex: Vec<&str> = zone
+        .rrsets
+        .iter()
+        .flatten()
+        .filter(|rrset| rrset.rrtype == "NS" && rrset.name.eq_ignore_ascii_case(&zone.name))
+        .flat_map(|rrset| &rrset.records)
+        .filter(|record| !record.disabled)
+        .map(|record| record.content.trim_end_matches('.'))
+        .collect();
+    internal_ns
+        .iter()
+        .map(|ns| ns.as_str())
+        .filter(|ns| {
+            !apex
+                .iter()
+                .any(|present| present.eq_ignore_ascii_case(ns.trim_end_matches('.')))
+        })
+        .collect()
+}
+
+/// Check every parent zone at startup, logging what an operator has to fix.
+///
+/// With `bootstrap` set, a missing parent zone is created as a native zone
+/// with the internal nameservers at its apex. Failures never stop startup:
+/// PowerDNS may just not be up yet.
+pub async fn verify_parent_zones(state: &AppState, bootstrap: bool) {
+    let internal_ns = &state.config.internal_ns;
+    for base_domain in state.config.base_domain_roots() {
+        let zone = state.config.parent_zone_name(base_domain);
+        let client = state.base_pdns_for(base_domain);
+        match client.find_zone(&zone).await {
+            Ok(Some(found)) => {
+                let missing = missing_apex_ns(&found, internal_ns);
+                if missing.is_empty() {
+                    tracing::debug!(zone = %zone, "parent zone present, apex NS complete");
+                } else {
+                    tracing::error!(
+                        zone = %zone,
+                        missing = %missing.join(", "),
+                        "parent zone apex NS lacks internal nameservers; add them there \
+                         or fix --internal-ns, or delegations will not resolve"
+                    );
+                }
+            }
+            Ok(None) if bootstrap => {
+                let created = client
+                    .create_zone(&PdnsZoneCreate {
+                        name: zone.clone(),
+                        kind: "Native".into(),
+                        nameservers: internal_ns.clone(),
+                    })
+                    .await;
+                match created {
+                    Ok(()) => tracing::info!(zone = %zone, "created missing parent zone"),
+                    Err(err) => {
+                        tracing::error!(zone = %zone, "failed to create parent zone: {err:#}")
+                    }
+                }
+            }
+            Ok(None) => tracing::error!(
+                zone = %zone,
+                "parent zone does not exist on the base PowerDNS, so signups under it \
+                 will fail; create it with the internal nameservers at its apex, or \
+                 restart with --bootstrap-parent-zone"
+            ),
+            Err(err) => tracing::warn!(zone = %zone, "could not verify parent zone: {err:#}"),
+        }
+    }
+}
+
+/// Whether the database and every PowerDNS endpoint have passed a check
+/// since startup; until then the instance is not ready for traffic.
+#[derive(Default)]
+pub struct Startup {
+    ready: AtomicBool,
+}
+
+impl Startup {
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+}
+
+/// Check the database and PowerDNS until all pass once, then mark the
+/// instance ready.
+pub fn spawn_startup_checks(state: SharedState) {
+    tokio::spawn(async move {
+        let mut logged = false;
+        loop {
+            let mut report = CheckReport::default();
+            report.record("database", check_database(&state.db, true).await);
+            for (name, client) in state.pdns_endpoints() {
+                report.record(name, check_pdns(client).await);
+            }
+            if report.ok() {
+                state.startup.ready.store(true, Ordering::Release);
+                tracing::info!("startup checks passed, ready for traffic");
+                return;
+            }
+            if !logged {
+                for check in report.checks.iter().filter(|check| !check.ok) {
+                    tracing::warn!(
+                        check = %check.name,
+                        "not ready yet, retrying: {}",
+                        check.detail
+                    );
+                }
+                logged = true;
+            }
+            tokio::time::sleep(STARTUP_RETRY).await;
+        }
+    });
+}
+
+/// Run the readiness checks against a live application state.
+///
+/// Covers the startup checks, the database, every PowerDNS endpoint in use,
+/// and each parent zone.
+pub async fn readiness(state: &AppState) -> CheckReport {
+    let mut report = CheckReport::default();
+    if !state.startup.is_ready() {
+        report.record(
+            "startup",
+            Err("waiting for the database and PowerDNS to pass a first check".into()),
+        );
+    }
+    report.record("database", check_database(&state.db, true).await);
+    for (name, client) in state.pdns_endpoints() {
+        report.record(name, check_pdns(client).await);
+    }
+    for base_domain in state.config.base_domain_roots() {
+        let zone = state.config.parent_zone_name(base_domain);
+        report.record(
+            format!("parent_zone {zone}"),
+            check_zone(state.base_pdns_for(base_domain), &zone).await,
+        );
+    }
+    report
+}
@@ -0,0 +1,105 @@
+//! Persists events from the bus, and sign-ins, as each user's change history.
+use chrono::Utc;
+use std::net::IpAddr;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::SharedState;
+use crate::db::history_repo;
+use crate::db::user_repo::User;
+use crate::events::{Event, EventKind};
+
+/// Subscribe to the event bus and store every event in `change_history`.
+pub fn spawn_recorder(state: SharedState) -> JoinHandle<()> {
+    let mut events = state.events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("change history fell behind, {skipped} events not recorded");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let payload = match serde_json::to_string(&event) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    tracing::error!("failed to serialize event: {err:?}");
+                    continue;
+                }
+            };
+            let _guard = state.in_flight.begin(format!(
+                "recording {} for {}",
+                event.type_name(),
+                event.zone
+            ));
+            if let Err(err) = history_repo::insert(
+                &state.db,
+                event.user_id,
+                &event.zone,
+                event.type_name(),
+                &payload,
+                event.occurred_at,
+            )
+            .await
+            {
+                tracing::error!("failed to record change history: {err:?}");
+            }
+        }
+    })
+}
+
+/// Type recorded for successful `POST /api/signin` calls.
+pub const SIGNED_IN: &str = "account.signed_in";
+
+/// Record a sign-in in the user's history; not published on the event bus.
+/// With `--geoip-db`, the entry carries the country and ASN of `client_ip`,
+/// and a sign-in from a country earlier entries do not show publishes
+/// [`EventKind::SigninNewCountry`].
+pub async fn record_signin(
+    state: &SharedState,
+    user: &User,
+    client_ip: IpAddr,
+) -> sqlx::Result<()> {
+    let zone = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let occurred_at = Utc::now();
+    let mut payload = serde_json::json!({
+        "zone": zone,
+        "occurred_at": occurred_at,
+        "type": SIGNED_IN,
+        "client_ip": client_ip.to_string(),
+    });
+    let location = state
+        .geoip
+        .as_ref()
+        .and_then(|geoip| geoip.lookup(client_ip));
+    if let Some(location) = &location {
+        payload["geo"] = serde_json::json!(location);
+    }
+    if let Some(country) = location.and_then(|location| location.country) {
+        let known = history_repo::list_countries(&state.db, user.id, SIGNED_IN).await?;
+        // the first located sign-in sets the baseline
+        if !known.is_empty() && !known.contains(&country) {
+            state.events.publish(Event::new(
+                user.id,
+                zone.clone(),
+                EventKind::SigninNewCountry {
+                    country,
+                    client_ip: client_ip.to_string(),
+                },
+            ));
+        }
+    }
+    history_repo::insert(
+        &state.db,
+        user.id,
+        &zone,
+        SIGNED_IN,
+        &payload.to_string(),
+        occurred_at,
+    )
+    .await
+}
@@ -0,0 +1,394 @@
+//! Message catalogs for user-facing error text, negotiated via `Accept-Language`.
+//!
+//! Errors that can be translated carry a [`Message`]: a catalog entry with
+//! the values of its placeholders, or a [`ValidationError`]. Both are keyed
+//! by variant, so English wording can change without breaking translations.
+//! Error codes are never translated.
+use axum::http::StatusCode;
+use std::fmt;
+
+use crate::validation::ValidationError;
+
+/// Languages with a catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Ja,
+}
+
+impl Locale {
+    /// Every supported locale, in order of preference when weights tie.
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Ja];
+
+    /// BCP 47 tag, as sent in `Content-Language`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+
+    /// Best supported locale for an `Accept-Language` header; English otherwise.
+    pub fn negotiate(accept_language: Option<&str>) -> Locale {
+        let Some(header) = accept_language else {
+            return Locale::En;
+        };
+        let mut best = (Locale::En, 0.0_f32);
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let range = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let weight = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let primary = range.split('-').next().unwrap_or_default();
+            let Some(locale) = Locale::ALL
+                .into_iter()
+                .find(|locale| locale.tag() == primary)
+            else {
+                continue;
+            };
+            if weight > best.1 {
+                best = (locale, weight);
+            }
+        }
+        best.0
+    }
+
+    /// Localized problem `title` for a status; English uses the reason phrase.
+    pub fn title(self, status: StatusCode) -> Option<&'static str> {
+        match self {
+            Locale::En => status.canonical_reason(),
+            Locale::Ja => JA_TITLES
+                .iter()
+                .find(|(code, _)| *code == status.as_u16())
+                .map(|(_, title)| *title),
+        }
+    }
+}
+
+/// Catalog entries, each with an English and a Japanese template. Templates
+/// refer to the values of a [`Message`] as `{0}`, `{1}`, and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageId {
+    // subdomain labels
+    SubdomainReserved,
+    /// `{0}`: the label it resembles.
+    SubdomainConfusable,
+    SubdomainTaken,
+    UnknownBaseDomain,
+    MissingNameParameter,
+    // signup and credentials
+    SignupClosed,
+    InviteRequired,
+    InvalidInvite,
+    InvalidCredentials,
+    MissingAuthorization,
+    InvalidAuthorization,
+    ExpectedBasicAuth,
+    ExpectedBearerToken,
+    InvalidAdminToken,
+    AuthenticationRequired,
+    WeakPassword,
+    WrongPassword,
+    /// `{0}`: the feature.
+    FeatureDisabled,
+    /// `{0}`: the rejected domain.
+    EmailDomainRejected,
+    // records
+    OwnerOutsideZone,
+    SoaManaged,
+    /// `{0}`: the type.
+    RecordTypeNotAllowed,
+    ApexNsManaged,
+    /// `{0}`, `{1}`: owner and type.
+    ConflictingTtls,
+    /// `{0}`, `{1}`, `{2}`: type, address, and its range, e.g. `private`.
+    PrivateAddress,
+    /// `{0}`, `{1}`: type and target.
+    TargetNotAllowed,
+    /// `{0}`: the limit in bytes.
+    TxtTooLong,
+    /// `{0}`, `{1}`: records after the update and the limit.
+    TooManyRecords,
+    /// `{0}`, `{1}`: RRsets after the update and the limit; `{2}`, `{3}`,
+    /// `{4}`: current RRsets, records, and bytes.
+    TooManyRrsets,
+    /// `{0}`, `{1}`: bytes after the update and the limit; `{2}`, `{3}`,
+    /// `{4}`: current RRsets, records, and bytes.
+    ZoneTooLarge,
+    ZoneChanged,
+    // nameservers
+    /// `{0}`: the minimum.
+    TooFewNameservers,
+    /// `{0}`: the maximum.
+    TooManyNameservers,
+    NameserverNotAbsolute,
+    /// `{0}`: the nameserver.
+    DuplicateNameserver,
+    NameserversNotReady,
+    // webhooks
+    /// `{0}`: the maximum.
+    TooManyWebhooks,
+    InvalidWebhookUrl,
+    WebhookScheme,
+    WebhookNotPublic,
+    WebhookCredentials,
+    // generic
+    NotFound,
+    SoaNotFound,
+    Maintenance,
+    Degraded,
+    NotReady,
+    Overloaded,
+    Internal,
+}
+
+impl MessageId {
+    fn english(self) -> &'static str {
+        match self {
+            MessageId::SubdomainReserved => "requested subdomain is reserved",
+            MessageId::SubdomainConfusable => "requested subdomain is too similar to {0}",
+            MessageId::SubdomainTaken => "already exists",
+            MessageId::UnknownBaseDomain => "unknown base domain",
+            MessageId::MissingNameParameter => "missing 'name' parameter",
+            MessageId::SignupClosed => "registrations are currently closed, please try again later",
+            MessageId::InviteRequired => "registrations currently require an invite code",
+            MessageId::InvalidInvite => "invite code is invalid or has already been used",
+            MessageId::InvalidCredentials => "invalid credentials",
+            MessageId::MissingAuthorization => "missing Authorization header",
+            MessageId::InvalidAuthorization => "invalid Authorization header",
+            MessageId::ExpectedBasicAuth => "expected Basic auth",
+            MessageId::ExpectedBearerToken => "expected Bearer token",
+            MessageId::InvalidAdminToken => "invalid admin token",
+            MessageId::AuthenticationRequired => "authentication required",
+            MessageId::WeakPassword => "new password must be at least 8 characters",
+            MessageId::WrongPassword => "current password is incorrect",
+            MessageId::FeatureDisabled => "feature '{0}' is disabled on this instance",
+            MessageId::EmailDomainRejected => "email addresses at {0} are not accepted here",
+            MessageId::OwnerOutsideZone => "record name must be within your zone",
+            MessageId::SoaManaged => "SOA records are managed automatically and cannot be modified",
+            MessageId::RecordTypeNotAllowed => "record type {0} is not allowed on this instance",
+            MessageId::ApexNsManaged => "apex NS records must be managed via NS-mode endpoints",
+            MessageId::ConflictingTtls => "conflicting TTLs for {0} {1}",
+            MessageId::PrivateAddress => "{0} record {1} points at a {2} address",
+            MessageId::TargetNotAllowed => "{0} target {1} is not allowed",
+            MessageId::TxtTooLong => "TXT record exceeds the {0} byte limit",
+            MessageId::TooManyRecords => "zone would contain {0} records, the limit is {1}",
+            MessageId::TooManyRrsets => {
+                "zone would contain {0} rrsets, the limit is {1} (current usage: {2} rrsets, {3} records, {4} bytes)"
+            }
+            MessageId::ZoneTooLarge => {
+                "zone would contain {0} bytes, the limit is {1} (current usage: {2} rrsets, {3} records, {4} bytes)"
+            }
+            MessageId::ZoneChanged => "zone changed since the plan was computed; plan again",
+            MessageId::TooFewNameservers => "at least {0} nameserver(s) required",
+            MessageId::TooManyNameservers => "up to {0} nameservers supported",
+            MessageId::NameserverNotAbsolute => "nameservers must end with '.'",
+            MessageId::DuplicateNameserver => "duplicate nameserver {0}",
+            MessageId::NameserversNotReady => {
+                "the nameservers do not serve the zone yet; fix them or resubmit with force"
+            }
+            MessageId::TooManyWebhooks => "up to {0} webhooks supported",
+            MessageId::InvalidWebhookUrl => "invalid webhook URL",
+            MessageId::WebhookScheme => "webhook URL must use http or https",
+            MessageId::WebhookNotPublic => "webhook URL must point at a public host",
+            MessageId::WebhookCredentials => "webhook URL must not contain credentials",
+            MessageId::NotFound => "not found",
+            MessageId::SoaNotFound => "SOA record not found",
+            MessageId::Maintenance => "service is under maintenance",
+            MessageId::Degraded => "PowerDNS is unreachable; changes are paused until it recovers",
+            MessageId::NotReady => "service is starting; try again shortly",
+            MessageId::Overloaded => "too many concurrent requests, please retry shortly",
+            MessageId::Internal => "internal server error",
+        }
+    }
+
+    fn japanese(self) -> &'static str {
+        match self {
+            MessageId::SubdomainReserved => "このサブドメインは予約されています",
+            MessageId::SubdomainConfusable => {
+                "このサブドメインは {0} と紛らわしいため使用できません"
+            }
+            MessageId::SubdomainTaken => "このサブドメインはすでに登録されています",
+            MessageId::UnknownBaseDomain => "不明なベースドメインです",
+            MessageId::MissingNameParameter => "'name' パラメーターがありません",
+            MessageId::SignupClosed => {
+                "現在、新規登録を受け付けていません。しばらくしてから再度お試しください"
+            }
+            MessageId::InviteRequired => "現在、登録には招待コードが必要です",
+            MessageId::InvalidInvite => "招待コードが無効か、すでに使用されています",
+            MessageId::InvalidCredentials => "ユーザー名またはパスワードが正しくありません",
+            MessageId::MissingAuthorization => "Authorization ヘッダーがありません",
+            MessageId::InvalidAuthorization => "Authorization ヘッダーが不正です",
+            MessageId::ExpectedBasicAuth => "Basic 認証が必要です",
+            MessageId::ExpectedBearerToken => "Bearer トークンが必要です",
+            MessageId::InvalidAdminToken => "管理トークンが正しくありません",
+            MessageId::AuthenticationRequired => "認証が必要です",
+            MessageId::WeakPassword => "新しいパスワードは 8 文字以上にしてください",
+            MessageId::WrongPassword => "現在のパスワードが正しくありません",
+            MessageId::FeatureDisabled => "このインスタンスでは機能 '{0}' は無効です",
+            MessageId::EmailDomainRejected => {
+                "{0} のメールアドレスはこのサービスでは使用できません"
+            }
+            MessageId::OwnerOutsideZone => "レコード名は自分のゾーン内にする必要があります",
+            MessageId::SoaManaged => "SOA レコードは自動で管理されるため変更できません",
+            MessageId::RecordTypeNotAllowed => {
+                "このインスタンスではレコードタイプ {0} は使用できません"
+            }
+            MessageId::ApexNsManaged => {
+                "ゾーン頂点の NS レコードは NS モードの API で管理してください"
+            }
+            MessageId::ConflictingTtls => "{0} {1} の TTL が一致しません",
+            MessageId::PrivateAddress => {
+                "{0} レコード {1} は公開されていないアドレス（{2}）を指しています"
+            }
+            MessageId::TargetNotAllowed => "{0} の向き先 {1} は許可されていません",
+            MessageId::TxtTooLong => "TXT レコードが上限の {0} バイトを超えています",
+            MessageId::TooManyRecords => "ゾーンのレコードが {0} 件になります（上限は {1} 件）",
+            MessageId::TooManyRrsets => {
+                "ゾーンの RRset が {0} 個になります（上限は {1} 個、現在の使用量: RRset {2} 個、レコード {3} 件、{4} バイト）"
+            }
+            MessageId::ZoneTooLarge => {
+                "ゾーンが {0} バイトになります（上限は {1} バイト、現在の使用量: RRset {2} 個、レコード {3} 件、{4} バイト）"
+            }
+            MessageId::ZoneChanged => {
+                "プランの作成後にゾーンが変更されました。もう一度プランを作成してください"
+            }
+            MessageId::TooFewNameservers => "ネームサーバーを {0} 個以上指定してください",
+            MessageId::TooManyNameservers => "ネームサーバーは {0} 個まで指定できます",
+            MessageId::NameserverNotAbsolute => "ネームサーバー名は '.' で終わる必要があります",
+            MessageId::DuplicateNameserver => "ネームサーバー {0} が重複しています",
+            MessageId::NameserversNotReady => {
+                "ネームサーバーがまだこのゾーンを提供していません。修正するか force を指定して再送信してください"
+            }
+            MessageId::TooManyWebhooks => "Webhook は {0} 個まで登録できます",
+            MessageId::InvalidWebhookUrl => "Webhook の URL が正しくありません",
+            MessageId::WebhookScheme => "Webhook の URL には http か https を使用してください",
+            MessageId::WebhookNotPublic => "Webhook の URL は公開ホストを指す必要があります",
+            MessageId::WebhookCredentials => "Webhook の URL に認証情報を含めることはできません",
+            MessageId::NotFound => "見つかりません",
+            MessageId::SoaNotFound => "SOA レコードが見つかりません",
+            MessageId::Maintenance => "メンテナンス中です",
+            MessageId::Degraded => {
+                "PowerDNS に接続できないため、復旧するまで変更を受け付けていません"
+            }
+            MessageId::NotReady => "起動中です。しばらくしてから再度お試しください",
+            MessageId::Overloaded => {
+                "リクエストが集中しています。しばらくしてから再度お試しください"
+            }
+            MessageId::Internal => "サーバー内部エラーが発生しました",
+        }
+    }
+}
+
+/// A translatable client-facing message.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// A catalog entry and the values of its placeholders, in order.
+    Catalog(MessageId, Vec<String>),
+    /// A failed name check, translated by variant.
+    Validation(ValidationError),
+}
+
+impl Message {
+    /// Catalog entry `id` with placeholder values `args`.
+    pub fn new<T: ToString>(id: MessageId, args: impl IntoIterator<Item = T>) -> Self {
+        Message::Catalog(id, args.into_iter().map(|arg| arg.to_string()).collect())
+    }
+
+    /// The message in `locale`.
+    pub fn render(&self, locale: Locale) -> String {
+        match (self, locale) {
+            (Message::Catalog(id, args), Locale::En) => fill(id.english(), args),
+            (Message::Catalog(id, args), Locale::Ja) => fill(id.japanese(), args),
+            (Message::Validation(err), Locale::En) => err.to_string(),
+            (Message::Validation(err), Locale::Ja) => validation_ja(err),
+        }
+    }
+}
+
+/// English, the language of logs and of responses without a preference.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Locale::En))
+    }
+}
+
+impl From<MessageId> for Message {
+    fn from(id: MessageId) -> Self {
+        Message::Catalog(id, Vec::new())
+    }
+}
+
+impl From<ValidationError> for Message {
+    fn from(err: ValidationError) -> Self {
+        Message::Validation(err)
+    }
+}
+
+/// Substitute `{0}`, `{1}`, ... in `template`; values are inserted as is.
+fn fill(template: &str, values: &[String]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let value = rest[start + 1..].split_once('}').and_then(|(index, _)| {
+            let value = values.get(index.parse::<usize>().ok()?)?;
+            Some((value, index.len() + 2))
+        });
+        text.push_str(&rest[..start]);
+        match value {
+            Some((value, len)) => {
+                text.push_str(value);
+                rest = &rest[start + len..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn validation_ja(err: &ValidationError) -> String {
+    match err {
+        ValidationError::Empty => "サブドメインが空です".into(),
+        ValidationError::TooLong => "サブドメインが長すぎます（最大 63 文字）".into(),
+        ValidationError::InvalidCharacters => {
+            "サブドメインに使用できない文字が含まれています（a-z、0-9、'-' のみ使用できます）"
+                .into()
+        }
+        ValidationError::LeadingOrTrailingHyphen => {
+            "サブドメインの先頭と末尾に '-' は使用できません".into()
+        }
+        ValidationError::DoubleHyphen => "サブドメインに '--' を含めることはできません".into(),
+        ValidationError::InvalidIdn => "サブドメインが国際化ラベルとして正しくありません".into(),
+        ValidationError::MixedScript => "サブドメインに複数の文字体系の文字が混在しています".into(),
+        ValidationError::NameTooLong { name, len } => {
+            format!("名前 {name} は {len} オクテットあります（最大 253）")
+        }
+        ValidationError::LabelTooLong { label, len } => {
+            format!("ラベル {label} は {len} オクテットあります（最大 63）")
+        }
+        ValidationError::EmptyLabel { name } => format!("名前 {name} に空のラベルが含まれています"),
+        ValidationError::InvalidOwnerLabel { label } => {
+            format!("レコード名のラベル {label} には a-z、0-9、'-' と先頭の '_' のみ使用できます")
+        }
+    }
+}
+
+const JA_TITLES: &[(u16, &str)] = &[
+    (400, "不正なリクエスト"),
+    (401, "認証が必要です"),
+    (403, "許可されていません"),
+    (404, "見つかりません"),
+    (405, "許可されていないメソッド"),
+    (409, "競合"),
+    (413, "リクエストが大きすぎます"),
+    (415, "サポートされていないメディアタイプ"),
+    (429, "リクエストが多すぎます"),
+    (500, "サーバー内部エラー"),
+    (503, "サービス利用不可"),
+];
@@ -0,0 +1,426 @@
+//! Scheduler shared by the periodic background workers: each job runs on an
+//! interval or cron schedule taken from the policy, and every run is recorded
+//! in the `jobs` table for `GET /api/admin/jobs`.
+use anyhow::{Context, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, TimeZone, Timelike, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::alerts::{self, Alert, AlertKind};
+use crate::config::PolicyConfig;
+use crate::db::job_repo;
+use crate::{AppState, SharedState};
+
+/// How often a switched-off job checks whether a reload enabled it.
+const DISABLED_RECHECK: Duration = Duration::from_secs(60);
+
+/// A periodic background task.
+#[async_trait]
+pub trait Job: Send + Sync + 'static {
+    /// Stable identifier, stored in the `jobs` table.
+    fn name(&self) -> &'static str;
+
+    /// When to run under `policy`; `None` while the job is switched off.
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule>;
+
+    /// Do one run, returning a short summary for the run history.
+    async fn run(&self, state: &AppState) -> anyhow::Result<String>;
+}
+
+/// When a job fires.
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    /// Fixed delay between the end of one run and the start of the next.
+    Every(Duration),
+    /// Five-field cron expression, evaluated in UTC.
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// The cron expression if one is configured, otherwise every `interval_secs`.
+    pub fn configured(interval_secs: u64, cron: Option<&str>) -> Self {
+        match cron.map(Cron::parse) {
+            Some(Ok(cron)) => Schedule::Cron(cron),
+            Some(Err(err)) => {
+                // policy loading validates the expression, so this is not expected
+                tracing::warn!("ignoring invalid schedule: {err:#}");
+                Schedule::Every(Duration::from_secs(interval_secs))
+            }
+            None => Schedule::Every(Duration::from_secs(interval_secs)),
+        }
+    }
+
+    /// The first firing time after `now`.
+    pub fn next_after(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => Some(now + ChronoDuration::from_std(*interval).ok()?),
+            Schedule::Cron(cron) => cron.next_after(now),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(interval) => write!(f, "every {}s", interval.as_secs()),
+            Schedule::Cron(cron) => write!(f, "cron {}", cron.expr),
+        }
+    }
+}
+
+/// Parsed `minute hour day-of-month month day-of-week` expression, with `*`,
+/// lists, ranges, and steps, plus `@hourly`, `@daily`, and `@weekly`.
+#[derive(Clone, Debug)]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // standard cron: when both day fields are restricted, either may match
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    /// Parse and check that the expression fires at least once.
+    pub fn parse(expr: &str) -> anyhow::Result<Self> {
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!("cron expression {expr:?} must have five fields");
+        };
+        let mut weekday_bits = cron_field(weekdays, 0, 7).context("in day-of-week")?;
+        // both 0 and 7 mean Sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        let cron = Cron {
+            expr: expr.trim().to_string(),
+            minutes: cron_field(minutes, 0, 59).context("in minute")?,
+            hours: cron_field(hours, 0, 23).context("in hour")?,
+            days: cron_field(days, 1, 31).context("in day-of-month")?,
+            months: cron_field(months, 1, 12).context("in month")?,
+            weekdays: weekday_bits,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+        };
+        if cron.next_after(Utc::now()).is_none() {
+            bail!("cron expression {expr:?} never fires");
+        }
+        Ok(cron)
+    }
+
+    fn day_matches(&self, time: DateTime<Utc>) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute after `now`, searching about five years ahead.
+    pub fn next_after(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = now.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = now + ChronoDuration::days(5 * 366);
+        while time < limit {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = midnight(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.day_matches(time) {
+                time = midnight(time.date_naive().succ_opt()?);
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += ChronoDuration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight exists"))
+}
+
+/// Bit set of the values one cron field allows.
+fn cron_field(spec: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut bits = 0u64;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("invalid step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("step must be at least 1");
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                // `5/15` means every 15 starting at 5
+                None if part.contains('/') => (range.parse()?, max),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            bail!("{part:?} is outside {min}-{max}");
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// Live status of a registered job.
+#[derive(Clone, Debug, Default)]
+pub struct JobStatus {
+    /// Description of the current schedule; `None` while switched off.
+    pub schedule: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub running_since: Option<DateTime<Utc>>,
+}
+
+/// Status of every spawned job, keyed by name.
+#[derive(Default)]
+pub struct Registry {
+    jobs: Mutex<BTreeMap<&'static str, JobStatus>>,
+}
+
+impl Registry {
+    /// Snapshot of every job, by name.
+    pub fn snapshot(&self) -> Vec<(&'static str, JobStatus)> {
+        let jobs = self.jobs.lock().expect("job registry poisoned");
+        jobs.iter()
+            .map(|(name, status)| (*name, status.clone()))
+            .collect()
+    }
+
+    fn update(&self, name: &'static str, apply: impl FnOnce(&mut JobStatus)) {
+        let mut jobs = self.jobs.lock().expect("job registry poisoned");
+        apply(jobs.entry(name).or_default());
+    }
+}
+
+/// Run `job` on its schedule for as long as the process lives.
+pub fn spawn(state: SharedState, job: impl Job) -> JoinHandle<()> {
+    let name = job.name();
+    state.jobs.update(name, |_| {});
+    tokio::spawn(async move {
+        loop {
+            let next = job
+                .schedule(&state.policy())
+                .and_then(|schedule| Some((schedule.next_after(Utc::now())?, schedule)));
+            let Some((next, schedule)) = next else {
+                state.jobs.update(name, |status| {
+                    status.schedule = None;
+                    status.next_run_at = None;
+                });
+                tokio::time::sleep(DISABLED_RECHECK).await;
+                continue;
+            };
+            state.jobs.update(name, |status| {
+                status.schedule = Some(schedule.to_string());
+                status.next_run_at = Some(next);
+            });
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            // a reload may have switched the job off while it was waiting
+            if job.schedule(&state.policy()).is_none() {
+                continue;
+            }
+            run(&state, &job).await;
+        }
+    })
+}
+
+async fn run(state: &AppState, job: &impl Job) {
+    let name = job.name();
+    let started_at = Utc::now();
+    state.jobs.update(name, |status| {
+        status.running_since = Some(started_at);
+        status.next_run_at = None;
+    });
+    let result = job.run(state).await;
+    let finished_at = Utc::now();
+    state
+        .jobs
+        .update(name, |status| status.running_since = None);
+
+    let (outcome, detail) = match &result {
+        Ok(summary) => {
+            tracing::info!("job {name} finished: {summary}");
+            ("ok", summary.clone())
+        }
+        Err(err) => {
+            tracing::error!("job {name} failed: {err:#}");
+            alerts::raise(
+                state,
+                Alert::new(
+                    AlertKind::JobFailed,
+                    name,
+                    format!("job {name} failed: {err:#}"),
+                    serde_json::json!({ "job": name, "error": format!("{err:#}") }),
+                ),
+            );
+            ("failed", format!("{err:#}"))
+        }
+    };
+    if let Err(err) = job_repo::record(
+        &state.db,
+        name,
+        started_at,
+        finished_at,
+        outcome,
+        Some(&detail),
+    )
+    .await
+    {
+        tracing::error!("failed to record run of job {name}: {err:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn next(expr: &str, now: DateTime<Utc>) -> DateTime<Utc> {
+        Cron::parse(expr).unwrap().next_after(now).unwrap()
+    }
+
+    fn bits(values: &[u32]) -> u64 {
+        values.iter().fold(0, |bits, value| bits | 1 << value)
+    }
+
+    #[test]
+    fn fields() {
+        for (spec, min, max, want) in [
+            ("*", 1, 12, (1..=12).collect::<Vec<_>>()),
+            ("7", 0, 59, vec![7]),
+            ("1-5,10", 0, 59, vec![1, 2, 3, 4, 5, 10]),
+            ("*/20", 0, 59, vec![0, 20, 40]),
+            ("1-10/3", 0, 59, vec![1, 4, 7, 10]),
+            ("5/15", 0, 59, vec![5, 20, 35, 50]),
+            ("22/1", 0, 23, vec![22, 23]),
+        ] {
+            assert_eq!(cron_field(spec, min, max).unwrap(), bits(&want), "{spec}");
+        }
+        for (spec, min, max) in [
+            ("60", 0, 59),
+            ("0", 1, 31),
+            ("5-1", 0, 59),
+            ("*/0", 0, 59),
+            ("5/", 0, 59),
+            ("x", 0, 59),
+            ("", 0, 59),
+        ] {
+            assert!(cron_field(spec, min, max).is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        assert_eq!(Cron::parse("0 0 * * 7").unwrap().weekdays, bits(&[0]));
+        assert_eq!(
+            Cron::parse("0 0 * * 5-7").unwrap().weekdays,
+            bits(&[0, 5, 6])
+        );
+        // from Thursday 2026-10-15 to Sunday 2026-10-18
+        let now = at(2026, 10, 15, 12, 0);
+        assert_eq!(next("0 0 * * 7", now), at(2026, 10, 18, 0, 0));
+        assert_eq!(next("0 0 * * 0", now), at(2026, 10, 18, 0, 0));
+        assert_eq!(next("@weekly", now), at(2026, 10, 18, 0, 0));
+        assert!(Cron::parse("0 0 * * 8").is_err());
+    }
+
+    #[test]
+    fn either_day_field_matches_when_both_are_restricted() {
+        // the 1st or a Monday: Sunday 2026-11-01 comes before Monday the 2nd
+        let now = at(2026, 10, 28, 0, 0);
+        assert_eq!(next("0 0 1 * 1", now), at(2026, 11, 1, 0, 0));
+        assert_eq!(
+            next("0 0 1 * 1", at(2026, 11, 1, 0, 0)),
+            at(2026, 11, 2, 0, 0)
+        );
+        // and a Monday before the 1st
+        assert_eq!(
+            next("0 0 1 * 1", at(2026, 10, 15, 0, 0)),
+            at(2026, 10, 19, 0, 0)
+        );
+    }
+
+    #[test]
+    fn one_restricted_day_field_applies_alone() {
+        let now = at(2026, 10, 28, 0, 0);
+        assert_eq!(next("0 0 1 * *", now), at(2026, 11, 1, 0, 0));
+        assert_eq!(next("0 0 * * 1", now), at(2026, 11, 2, 0, 0));
+        // months without a 31st are skipped
+        assert_eq!(
+            next("0 0 31 * *", at(2026, 11, 1, 0, 0)),
+            at(2026, 12, 31, 0, 0)
+        );
+    }
+
+    #[test]
+    fn next_is_strictly_later() {
+        assert_eq!(
+            next("5/15 * * * *", at(2026, 10, 15, 10, 6)),
+            at(2026, 10, 15, 10, 20)
+        );
+        assert_eq!(
+            next("5/15 * * * *", at(2026, 10, 15, 10, 20)),
+            at(2026, 10, 15, 10, 35)
+        );
+        assert_eq!(
+            next("5/15 * * * *", at(2026, 10, 15, 10, 50)),
+            at(2026, 10, 15, 11, 5)
+        );
+        assert_eq!(
+            next("@daily", at(2026, 12, 31, 23, 59)),
+            at(2027, 1, 1, 0, 0)
+        );
+        assert_eq!(
+            next("@hourly", at(2026, 10, 15, 10, 0)),
+            at(2026, 10, 15, 11, 0)
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expr in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "61 * * * *",
+            "0 0 30 2 *",
+            "@yearly",
+        ] {
+            assert!(Cron::parse(expr).is_err(), "{expr:?}");
+        }
+    }
+}
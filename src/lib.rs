@@ -1,25 +1,157 @@
 //! Crate entrypoint wiring together configuration, DB, PowerDNS, and APIs.
 
+pub mod abuse;
+#[cfg(feature = "acme")]
+pub mod acme;
+pub mod alerts;
 pub mod api;
 pub mod auth;
+pub mod axfr;
+pub mod client;
+pub mod client_ip;
 pub mod config;
+pub mod dangling;
 pub mod db;
+pub mod delegations;
+pub mod dkim;
+pub mod dns_probe;
 pub mod error;
+pub mod events;
+pub mod expiry;
+pub mod geoip;
+pub mod health;
+pub mod history;
+pub mod i18n;
+pub mod jobs;
+pub mod mail;
+pub mod notifications;
+pub mod ns_health;
+pub mod ns_preflight;
 pub mod powerdns;
+pub mod provision;
+pub mod rate_limit;
+pub mod rdata;
+pub mod reconcile;
+pub mod record_data;
+pub mod reputation;
+pub mod retention;
+pub mod s3;
+pub mod serial_check;
+pub mod shutdown;
+pub mod signup_saga;
+pub mod snapshots;
+pub mod spf;
+pub mod stats;
+pub mod systemd;
+pub mod tar;
+#[cfg(feature = "tls")]
+pub mod tls;
 pub mod validation;
+pub mod webhooks;
 
-use config::AppConfig;
+use arc_swap::{ArcSwap, ArcSwapOption, Guard};
+use config::{AppConfig, PolicyConfig};
 use db::Db;
+use events::EventBus;
 use powerdns::client::PowerDnsClient;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// PowerDNS pair used for one base domain: parent zones and user zones.
+pub struct PdnsBackend {
+    pub base: PowerDnsClient,
+    pub sub: PowerDnsClient,
+}
+
 /// Complete application dependencies shared across handlers.
 pub struct AppState {
     pub config: AppConfig,
+    pub policy: ArcSwap<PolicyConfig>,
     pub db: Db,
     pub base_pdns: PowerDnsClient,
     pub sub_pdns: PowerDnsClient,
+    /// Overrides keyed by base domain root; other domains use `base_pdns`/`sub_pdns`.
+    pub domain_backends: HashMap<String, PdnsBackend>,
+    /// Durations of the calls of every PowerDNS client above.
+    pub pdns_latency: Arc<powerdns::latency::Latency>,
+    /// Change notifications consumed by webhooks and streaming clients.
+    pub events: EventBus,
+    /// Latest aggregate numbers, refreshed by `stats::spawn_refresher`.
+    pub stats: ArcSwapOption<stats::InstanceStats>,
+    /// Mutations and background jobs a graceful shutdown waits for.
+    pub in_flight: shutdown::InFlight,
+    /// Latest reconciliation report and action counters.
+    pub reconcile: reconcile::Tracker,
+    /// Latest SOA serial comparison with the internal nameservers.
+    pub serial_check: serial_check::Tracker,
+    /// Schedule and run status of the background jobs.
+    pub jobs: jobs::Registry,
+    /// Zone updates per user, against the policy's rate limits.
+    pub zone_updates: rate_limit::ZoneUpdateLimiter,
+    /// Cooldowns of the operator alerts.
+    pub alerts: alerts::Alerter,
+    /// PowerDNS endpoints currently down, which puts the API in degraded mode.
+    pub pdns_health: alerts::PdnsHealth,
+    /// Set once the startup checks pass; `/readyz` fails until then.
+    pub startup: health::Startup,
+    /// Country/ASN databases from `--geoip-db`, if any.
+    pub geoip: Option<geoip::GeoIp>,
+}
+
+impl AppState {
+    /// Snapshot of the currently active reloadable policy.
+    pub fn policy(&self) -> Guard<Arc<PolicyConfig>> {
+        self.policy.load()
+    }
+
+    /// Whether any reloadable policy source is configured.
+    pub fn has_policy_sources(&self) -> bool {
+        self.config.policy_file.is_some() || self.config.reserved_labels_file.is_some()
+    }
+
+    /// Re-read the policy sources (if configured) and atomically swap them in.
+    pub fn reload_policy(&self) -> anyhow::Result<()> {
+        if !self.has_policy_sources() {
+            return Ok(());
+        }
+        let policy = PolicyConfig::from_sources(&self.config)?;
+        self.policy.store(Arc::new(policy));
+        tracing::info!("reloaded policy");
+        Ok(())
+    }
+
+    /// PowerDNS client holding the parent zone of `base_domain`.
+    pub fn base_pdns_for(&self, base_domain: &str) -> &PowerDnsClient {
+        self.domain_backends
+            .get(base_domain)
+            .map_or(&self.base_pdns, |backend| &backend.base)
+    }
+
+    /// PowerDNS client hosting user zones under `base_domain`.
+    pub fn sub_pdns_for(&self, base_domain: &str) -> &PowerDnsClient {
+        self.domain_backends
+            .get(base_domain)
+            .map_or(&self.sub_pdns, |backend| &backend.sub)
+    }
+
+    /// Every PowerDNS client, named as in `/readyz`.
+    pub fn pdns_endpoints(&self) -> Vec<(String, &PowerDnsClient)> {
+        let mut endpoints = vec![
+            ("base_pdns".to_string(), &self.base_pdns),
+            ("sub_pdns".to_string(), &self.sub_pdns),
+        ];
+        for (domain, backend) in &self.domain_backends {
+            endpoints.push((format!("base_pdns {domain}"), &backend.base));
+            endpoints.push((format!("sub_pdns {domain}"), &backend.sub));
+        }
+        endpoints
+    }
+
+    /// Check a label against both the static reserved list and the live policy.
+    pub fn is_disallowed_subdomain(&self, label: &str) -> bool {
+        self.config.is_disallowed_subdomain(label) || self.policy().is_disallowed_subdomain(label)
+    }
 }
 
 /// Arc-wrapped version of `AppState` passed into Axum extensions.
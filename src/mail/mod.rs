@@ -0,0 +1,177 @@
+//! Outgoing mail: templates are rendered into the `mail_outbox` table, and
+//! a background sender delivers them through the configured transport,
+//! retrying with backoff. Flows that mail users only call [`enqueue`].
+pub mod smtp;
+pub mod templates;
+
+use chrono::Utc;
+use lettre::Message;
+use lettre::address::AddressError;
+use lettre::message::SinglePart;
+use rand_core::{OsRng, RngCore};
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::config::MailTransport;
+use crate::db::mail_outbox_repo::{self, OutboxMessage};
+use crate::validation::is_email_address;
+use crate::{AppState, SharedState};
+use smtp::SmtpError;
+pub use templates::Template;
+
+/// Attempts before a message is given up on.
+pub const MAX_ATTEMPTS: i64 = 8;
+/// Pause after the first failed attempt; doubled after each further one.
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+/// How often the outbox is checked for retries that came due.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// Messages taken from the outbox per pass.
+const BATCH: i64 = 50;
+
+/// Wakes the sender when a message is queued.
+static QUEUED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Why a message could not be queued.
+#[derive(Debug, thiserror::Error)]
+pub enum EnqueueError {
+    #[error("invalid email address {0:?}")]
+    InvalidAddress(String),
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// Render `template` for `to` and queue it; returns the outbox id.
+pub async fn enqueue(
+    state: &AppState,
+    to: &str,
+    template: Template<'_>,
+) -> Result<i64, EnqueueError> {
+    let to = to.trim();
+    if !is_email_address(to) {
+        return Err(EnqueueError::InvalidAddress(to.to_string()));
+    }
+    let rendered = template.render(&service_name(state));
+    let id = mail_outbox_repo::insert(
+        &state.db,
+        to,
+        template.name(),
+        &rendered.subject,
+        &rendered.body,
+    )
+    .await?;
+    QUEUED.notify_one();
+    Ok(id)
+}
+
+/// Name of the instance used in subjects and signatures.
+fn service_name(state: &AppState) -> String {
+    state
+        .policy()
+        .service_name
+        .clone()
+        .unwrap_or_else(|| state.config.base_domain_root().to_string())
+}
+
+/// Deliver queued messages until the process exits.
+pub fn spawn_sender(state: SharedState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = send_due(&state).await {
+                tracing::error!("failed to read the mail outbox: {err:?}");
+            }
+            tokio::select! {
+                _ = QUEUED.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    })
+}
+
+/// Attempt every message that is due.
+async fn send_due(state: &AppState) -> sqlx::Result<()> {
+    loop {
+        let due = mail_outbox_repo::list_due(&state.db, Utc::now(), BATCH).await?;
+        if due.is_empty() {
+            return Ok(());
+        }
+        for message in due {
+            let _guard = state.in_flight.begin(format!(
+                "sending mail {} to {}",
+                message.id, message.recipient
+            ));
+            match deliver(state, &message).await {
+                Ok(()) => mail_outbox_repo::delete(&state.db, message.id).await?,
+                Err(err) => record_failure(state, &message, err).await?,
+            }
+        }
+    }
+}
+
+async fn record_failure(
+    state: &AppState,
+    message: &OutboxMessage,
+    err: SmtpError,
+) -> sqlx::Result<()> {
+    let attempts = message.attempts + 1;
+    if matches!(err, SmtpError::Permanent(_)) || attempts >= MAX_ATTEMPTS {
+        tracing::error!(
+            "giving up on mail {} to {} after {attempts} attempt(s): {err}",
+            message.id,
+            message.recipient
+        );
+        return mail_outbox_repo::mark_failed(&state.db, message.id, &err.to_string()).await;
+    }
+    let delay = FIRST_RETRY * 2u32.pow(attempts as u32 - 1);
+    tracing::warn!(
+        "mail {} to {} failed, retrying in {}s: {err}",
+        message.id,
+        message.recipient,
+        delay.as_secs()
+    );
+    let next = Utc::now() + chrono::Duration::seconds(delay.as_secs() as i64);
+    mail_outbox_repo::reschedule(&state.db, message.id, &err.to_string(), next).await
+}
+
+/// Hand one message to the transport.
+async fn deliver(state: &AppState, message: &OutboxMessage) -> Result<(), SmtpError> {
+    let from = &state.config.mail.from;
+    match &state.config.mail.transport {
+        MailTransport::Log => {
+            tracing::info!(
+                to = %message.recipient,
+                subject = %message.subject,
+                "mail not sent (log transport):\n{}",
+                message.body
+            );
+            Ok(())
+        }
+        MailTransport::Smtp(config) => {
+            let helo = from
+                .rsplit_once('@')
+                .map_or("localhost", |(_, domain)| domain);
+            smtp::send(config, helo, build_message(from, message)?).await
+        }
+    }
+}
+
+/// RFC 5322 message with a UTF-8 text body.
+fn build_message(from: &str, message: &OutboxMessage) -> Result<Message, SmtpError> {
+    let domain = from
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain);
+    let mut id = [0u8; 12];
+    OsRng.fill_bytes(&mut id);
+    let id: String = id.iter().map(|byte| format!("{byte:02x}")).collect();
+    let invalid = |err: AddressError| SmtpError::Permanent(format!("invalid address: {err}"));
+
+    Message::builder()
+        .from(from.parse().map_err(invalid)?)
+        .to(message.recipient.parse().map_err(invalid)?)
+        // line breaks are dropped so they cannot start new headers
+        .subject(message.subject.replace(['\r', '\n'], ""))
+        .message_id(Some(format!("<{id}.{}@{domain}>", message.id)))
+        .singlepart(SinglePart::plain(message.body.clone()))
+        .map_err(|err| SmtpError::Permanent(err.to_string()))
+}
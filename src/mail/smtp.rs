@@ -0,0 +1,94 @@
+//! SMTP submission through lettre: `STARTTLS` or implicit TLS, `AUTH PLAIN`,
+//! and one message per connection.
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::transport::smtp::extension::ClientId;
+use lettre::transport::smtp::{AsyncSmtpTransportBuilder, Error};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+use crate::config::SmtpConfig;
+
+/// Limit on connecting and on each command's reply.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Why a message was not sent.
+#[derive(Debug, thiserror::Error)]
+pub enum SmtpError {
+    /// A `5xx` reply; retrying the same message will not help.
+    #[error("rejected: {0}")]
+    Permanent(String),
+    /// Connection problems and `4xx` replies.
+    #[error("{0}")]
+    Transient(String),
+}
+
+impl From<Error> for SmtpError {
+    fn from(err: Error) -> Self {
+        if err.is_permanent() {
+            SmtpError::Permanent(err.to_string())
+        } else {
+            SmtpError::Transient(err.to_string())
+        }
+    }
+}
+
+/// Deliver one message to the recipients of its envelope.
+pub async fn send(config: &SmtpConfig, helo: &str, message: Message) -> Result<(), SmtpError> {
+    let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+        .port(config.port)
+        .hello_name(ClientId::Domain(helo.to_string()))
+        .timeout(Some(TIMEOUT));
+    builder = tls::configure(builder, config)?;
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        builder = builder
+            .credentials(Credentials::new(username.clone(), password.clone()))
+            .authentication(vec![Mechanism::Plain]);
+    }
+    builder.build().send(message).await?;
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use super::{AsyncSmtpTransportBuilder, SmtpError};
+    use lettre::transport::smtp::client::{Tls, TlsParameters};
+
+    use crate::config::{SmtpConfig, SmtpSecurity};
+
+    /// Require TLS as configured, trusting the Mozilla roots.
+    pub fn configure(
+        builder: AsyncSmtpTransportBuilder,
+        config: &SmtpConfig,
+    ) -> Result<AsyncSmtpTransportBuilder, SmtpError> {
+        if config.security == SmtpSecurity::Insecure {
+            return Ok(builder);
+        }
+        let parameters = TlsParameters::new_rustls(config.host.clone()).map_err(|err| {
+            SmtpError::Permanent(format!("invalid server name {}: {err}", config.host))
+        })?;
+        Ok(builder.tls(match config.security {
+            SmtpSecurity::Tls => Tls::Wrapper(parameters),
+            _ => Tls::Required(parameters),
+        }))
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+mod tls {
+    use super::{AsyncSmtpTransportBuilder, SmtpError};
+
+    use crate::config::{SmtpConfig, SmtpSecurity};
+
+    pub fn configure(
+        builder: AsyncSmtpTransportBuilder,
+        config: &SmtpConfig,
+    ) -> Result<AsyncSmtpTransportBuilder, SmtpError> {
+        if config.security != SmtpSecurity::Insecure {
+            return Err(SmtpError::Permanent(
+                "SMTP over TLS needs the `tls` feature; use smtp+insecure:// for a local relay"
+                    .into(),
+            ));
+        }
+        Ok(builder)
+    }
+}
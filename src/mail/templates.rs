@@ -0,0 +1,80 @@
+//! Plain-text messages the server sends, rendered when they are queued.
+
+/// A message and the values it is rendered with.
+#[derive(Clone, Debug)]
+pub enum Template<'a> {
+    /// Confirm that the recipient controls the address.
+    Verification { account: &'a str, link: &'a str },
+    /// Let the recipient choose a new password.
+    PasswordReset { account: &'a str, link: &'a str },
+    /// A change or warning concerning the recipient's account.
+    Notification {
+        account: &'a str,
+        subject: &'a str,
+        text: &'a str,
+    },
+    /// Sent by `POST /api/admin/mail/test` to check the transport.
+    Test,
+}
+
+/// Subject and body of a rendered message.
+#[derive(Clone, Debug)]
+pub struct Rendered {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Template<'_> {
+    /// Name stored with the queued message, e.g. `password_reset`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Template::Verification { .. } => "verification",
+            Template::PasswordReset { .. } => "password_reset",
+            Template::Notification { .. } => "notification",
+            Template::Test => "test",
+        }
+    }
+
+    /// Render for an instance called `service`.
+    pub fn render(&self, service: &str) -> Rendered {
+        let (subject, body) = match self {
+            Template::Verification { account, link } => (
+                format!("[{service}] Confirm your email address"),
+                format!(
+                    "Hello,\n\n\
+                     this address was given for the account {account} on {service}.\n\
+                     Open the link below to confirm it:\n\n\
+                     {link}\n\n\
+                     If you did not expect this message, you can ignore it."
+                ),
+            ),
+            Template::PasswordReset { account, link } => (
+                format!("[{service}] Reset your password"),
+                format!(
+                    "Hello,\n\n\
+                     a password reset was requested for the account {account} on {service}.\n\
+                     Open the link below to choose a new password:\n\n\
+                     {link}\n\n\
+                     If you did not request it, you can ignore this message; \
+                     your password stays unchanged."
+                ),
+            ),
+            Template::Notification {
+                account,
+                subject,
+                text,
+            } => (
+                format!("[{service}] {subject}"),
+                format!("Hello,\n\nabout your account {account} on {service}:\n\n{text}"),
+            ),
+            Template::Test => (
+                format!("[{service}] Test message"),
+                format!("This test message shows that {service} can send mail."),
+            ),
+        };
+        Rendered {
+            subject,
+            body: format!("{body}\n\n-- \n{service}\n"),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Per-user routing of notable events. Each [`Category`] lands in the
+//! user's inbox and is also delivered by email, to the user's webhooks, or
+//! nowhere else, as the user chose in `/api/profile/notifications`. Events
+//! outside the categories, such as record changes, always go to webhooks.
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::db::{inbox_repo, notification_repo};
+use crate::events::{Event, EventKind};
+use crate::mail::{self, Template};
+use crate::{AppState, SharedState, webhooks};
+
+/// Kinds of events users can route.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Changes to the account's credentials and sign-ins from new countries.
+    Security,
+    /// Failing and recovered external nameservers.
+    DelegationHealth,
+    /// Warnings before an unused account is removed.
+    Expiry,
+    /// Messages from the operators.
+    Announcements,
+}
+
+impl Category {
+    /// Name used in the API and the database, e.g. `delegation_health`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Security => "security",
+            Category::DelegationHealth => "delegation_health",
+            Category::Expiry => "expiry",
+            Category::Announcements => "announcements",
+        }
+    }
+}
+
+/// Where the events of a category go.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Email,
+    #[default]
+    Webhook,
+    None,
+}
+
+/// The channel of each category.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Channels {
+    pub security: Channel,
+    pub delegation_health: Channel,
+    pub expiry: Channel,
+    pub announcements: Channel,
+}
+
+impl Channels {
+    pub fn get(&self, category: Category) -> Channel {
+        match category {
+            Category::Security => self.security,
+            Category::DelegationHealth => self.delegation_health,
+            Category::Expiry => self.expiry,
+            Category::Announcements => self.announcements,
+        }
+    }
+
+    /// Whether any category is delivered by email.
+    pub fn uses_email(&self) -> bool {
+        [
+            self.security,
+            self.delegation_health,
+            self.expiry,
+            self.announcements,
+        ]
+        .contains(&Channel::Email)
+    }
+}
+
+/// A user's preferences; the defaults for users who never saved any.
+#[derive(Clone, Debug, Default)]
+pub struct Preferences {
+    pub email: Option<String>,
+    pub channels: Channels,
+}
+
+/// Load the preferences of `user_id`.
+pub async fn load(state: &AppState, user_id: i64) -> sqlx::Result<Preferences> {
+    let Some(stored) = notification_repo::get(&state.db, user_id).await? else {
+        return Ok(Preferences::default());
+    };
+    let channels = serde_json::from_str(&stored.channels).unwrap_or_else(|err| {
+        tracing::warn!("ignoring unreadable notification channels of user {user_id}: {err}");
+        Channels::default()
+    });
+    Ok(Preferences {
+        email: stored.email,
+        channels,
+    })
+}
+
+/// Whether the owner's webhooks should receive `event`.
+pub async fn wants_webhook(state: &AppState, event: &Event) -> sqlx::Result<bool> {
+    let Some(category) = event.kind.category() else {
+        return Ok(true);
+    };
+    Ok(load(state, event.user_id).await?.channels.get(category) == Channel::Webhook)
+}
+
+/// Subscribe to the event bus, fill the inboxes, and mail the events users
+/// route to email.
+pub fn spawn_dispatcher(state: SharedState) -> JoinHandle<()> {
+    let mut events = state.events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Err(err) = dispatch(&state, &event).await {
+                        tracing::error!(
+                            "failed to notify user {} of {}: {err}",
+                            event.user_id,
+                            event.type_name()
+                        );
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("notification dispatcher fell behind, skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+async fn dispatch(state: &AppState, event: &Event) -> Result<(), mail::EnqueueError> {
+    let (Some(category), Some((subject, text))) = (event.kind.category(), describe(&event.kind))
+    else {
+        return Ok(());
+    };
+    inbox_repo::insert(
+        &state.db,
+        event.user_id,
+        category.as_str(),
+        event.type_name(),
+        &subject,
+        &text,
+    )
+    .await?;
+    let preferences = load(state, event.user_id).await?;
+    if let (Channel::Email, Some(email)) = (preferences.channels.get(category), &preferences.email)
+    {
+        send_email(state, email, &event.zone, &subject, &text).await?;
+    }
+    Ok(())
+}
+
+async fn send_email(
+    state: &AppState,
+    to: &str,
+    zone: &str,
+    subject: &str,
+    text: &str,
+) -> Result<(), mail::EnqueueError> {
+    let template = Template::Notification {
+        account: zone.trim_end_matches('.'),
+        subject,
+        text,
+    };
+    mail::enqueue(state, to, template).await?;
+    Ok(())
+}
+
+/// Put a message from the operators into every inbox, then mail it and
+/// send it to webhooks in the background, per each user's preferences.
+/// Returns how many inboxes received it.
+pub async fn announce(state: &SharedState, subject: String, text: String) -> sqlx::Result<u64> {
+    let category = Category::Announcements;
+    let delivered = inbox_repo::insert_for_all(
+        &state.db,
+        category.as_str(),
+        "announcement",
+        &subject,
+        &text,
+    )
+    .await?;
+    let email = notification_repo::list_email_recipients(&state.db, category.as_str()).await?;
+    let webhook = notification_repo::list_webhook_recipients(&state.db, category.as_str()).await?;
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let _guard = state.in_flight.begin(format!("announcing {subject:?}"));
+        for recipient in email {
+            let zone = state
+                .config
+                .user_zone_name(&recipient.subdomain, &recipient.base_domain);
+            let Some(to) = &recipient.email else {
+                continue;
+            };
+            if let Err(err) = send_email(&state, to, &zone, &subject, &text).await {
+                tracing::warn!(
+                    "failed to mail announcement to user {}: {err}",
+                    recipient.user_id
+                );
+            }
+        }
+        for recipient in webhook {
+            let zone = state
+                .config
+                .user_zone_name(&recipient.subdomain, &recipient.base_domain);
+            let kind = EventKind::Announcement {
+                subject: subject.clone(),
+                text: text.clone(),
+            };
+            webhooks::dispatch(&state, Event::new(recipient.user_id, zone, kind)).await;
+        }
+    });
+    Ok(delivered)
+}
+
+/// Subject and text of the message sent for an event of a category.
+fn describe(kind: &EventKind) -> Option<(String, String)> {
+    let message = match kind {
+        EventKind::PasswordChanged => (
+            "Your password was changed".into(),
+            "The password of your account was changed. If this was not you, \
+             contact the operators of this service."
+                .into(),
+        ),
+        EventKind::SigninNewCountry { country, client_ip } => (
+            format!("New sign-in from {country}"),
+            format!(
+                "Your account was signed in to from {client_ip}, located in {country}, \
+                 a country it was not used from before. If this was not you, change \
+                 your password and contact the operators of this service."
+            ),
+        ),
+        EventKind::NsHealthFailing { nameservers } => (
+            "Your nameservers are not answering".into(),
+            format!(
+                "These nameservers keep failing SOA checks for your zone:\n\n{}\n\n\
+                 Your domain may not resolve until they answer again.",
+                nameservers.join("\n")
+            ),
+        ),
+        EventKind::NsHealthRecovered { nameservers } => (
+            "Your nameservers answer again".into(),
+            format!(
+                "These nameservers answer authoritatively for your zone again:\n\n{}",
+                nameservers.join("\n")
+            ),
+        ),
+        EventKind::ExpiryReminder { expires_at } => (
+            "Your unused account will be removed".into(),
+            format!(
+                "Your account was never used and will be removed after {}. \
+                 Sign in or add records to keep it.",
+                expires_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+        ),
+        EventKind::Announcement { subject, text } => (subject.clone(), text.clone()),
+        EventKind::RecordsChanged { .. }
+        | EventKind::NsModeChanged { .. }
+        | EventKind::DnssecToggled { .. } => return None,
+    };
+    Some(message)
+}
@@ -0,0 +1,146 @@
+//! Periodic SOA checks of external nameservers, so lame or dead delegations
+//! under the base domains are noticed and their owners told.
+use async_trait::async_trait;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::AppState;
+use crate::config::PolicyConfig;
+use crate::db::ns_health_repo::{self, NsHealth};
+use crate::db::user_repo::{self, User};
+use crate::dns_probe::{self, SoaAnswer};
+use crate::events::{Event, EventKind};
+use crate::jobs::{Job, Schedule};
+
+/// Scheduled checks, per `[ns_health]` in the policy.
+pub struct NsHealthJob;
+
+#[async_trait]
+impl Job for NsHealthJob {
+    fn name(&self) -> &'static str {
+        "ns_health"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.ns_health;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let checked = check_all(state).await?;
+        Ok(format!("checked external nameservers of {checked} users"))
+    }
+}
+
+/// One round over every user in external NS mode; returns how many were checked.
+pub async fn check_all(state: &AppState) -> anyhow::Result<usize> {
+    let users = user_repo::list_all(&state.db).await?;
+    let mut checked = 0;
+    for user in users {
+        if !user.external_ns {
+            ns_health_repo::retain(&state.db, user.id, &[]).await?;
+            continue;
+        }
+        check_user(state, &user).await?;
+        checked += 1;
+    }
+    Ok(checked)
+}
+
+/// Probe each of the user's nameservers, store the results, and publish an
+/// event for nameservers that crossed `notify_after` or recovered.
+async fn check_user(state: &AppState, user: &User) -> anyhow::Result<()> {
+    let (timeout, notify_after) = {
+        let policy = state.policy();
+        (
+            Duration::from_secs(policy.ns_health.timeout_secs),
+            i64::from(policy.ns_health.notify_after),
+        )
+    };
+    let zone = state
+        .config
+        .user_zone_name(&user.subdomain, &user.base_domain);
+    let previous = ns_health_repo::list_for_user(&state.db, user.id).await?;
+
+    let mut probes = JoinSet::new();
+    for hostname in user.external_nameservers.clone() {
+        let zone = zone.clone();
+        probes.spawn(async move {
+            let answer = dns_probe::query_soa_by_name(&hostname, &zone, timeout).await;
+            (hostname, answer)
+        });
+    }
+
+    let mut failing = Vec::new();
+    let mut recovered = Vec::new();
+    while let Some(joined) = probes.join_next().await {
+        let (hostname, answer) = joined?;
+        let before = previous.iter().find(|health| health.hostname == hostname);
+        let now = Utc::now();
+        let mut health = NsHealth {
+            hostname: hostname.clone(),
+            status: "ok".into(),
+            detail: None,
+            serial: None,
+            consecutive_failures: 0,
+            failing_since: None,
+            notified: false,
+            checked_at: now,
+        };
+        let failure = match answer {
+            Ok(SoaAnswer::Authoritative { serial }) => {
+                health.serial = Some(i64::from(serial));
+                if before.is_some_and(|before| before.notified) {
+                    recovered.push(hostname.clone());
+                }
+                None
+            }
+            Ok(SoaAnswer::Lame(detail)) => Some(("lame", detail)),
+            Err(err) => Some(("unreachable", format!("{err:#}"))),
+        };
+        if let Some((status, detail)) = failure {
+            health.status = status.into();
+            health.detail = Some(detail);
+            health.serial = before.and_then(|before| before.serial);
+            health.consecutive_failures =
+                before.map_or(0, |before| before.consecutive_failures) + 1;
+            health.failing_since = before.and_then(|before| before.failing_since).or(Some(now));
+            health.notified = before.is_some_and(|before| before.notified);
+            if !health.notified && health.consecutive_failures >= notify_after {
+                health.notified = true;
+                failing.push(hostname);
+            }
+        }
+        ns_health_repo::upsert(&state.db, user.id, &health).await?;
+    }
+    ns_health_repo::retain(&state.db, user.id, &user.external_nameservers).await?;
+
+    if !failing.is_empty() {
+        failing.sort();
+        tracing::warn!(
+            "{zone}: external nameservers keep failing: {}",
+            failing.join(", ")
+        );
+        state.events.publish(Event::new(
+            user.id,
+            zone.clone(),
+            EventKind::NsHealthFailing {
+                nameservers: failing,
+            },
+        ));
+    }
+    if !recovered.is_empty() {
+        recovered.sort();
+        state.events.publish(Event::new(
+            user.id,
+            zone,
+            EventKind::NsHealthRecovered {
+                nameservers: recovered,
+            },
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Checks of the nameservers a user submits before the parent delegation is
+//! switched to them, so a zone is not delegated to servers that do not
+//! serve it yet.
+use serde::Serialize;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::dns_probe::{self, NsAnswer, SoaAnswer};
+
+/// Findings for one submitted nameserver.
+#[derive(Debug, Clone, Serialize)]
+pub struct NameserverCheck {
+    pub hostname: String,
+    /// SOA serial of an authoritative answer.
+    pub serial: Option<u32>,
+    /// NS set the server returned for the zone.
+    pub nameservers: Vec<String>,
+    /// What keeps the server from taking over; empty when it is ready.
+    pub problems: Vec<String>,
+}
+
+/// Result of checking every submitted nameserver.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// Whether every nameserver is ready.
+    pub ok: bool,
+    pub nameservers: Vec<NameserverCheck>,
+}
+
+/// Check that each of `submitted` (lowercase, with trailing dots) answers
+/// authoritatively for `zone` with a SOA and the submitted NS set.
+pub async fn run(zone: &str, submitted: &[String], timeout: Duration) -> Report {
+    let mut probes = JoinSet::new();
+    for (index, hostname) in submitted.iter().cloned().enumerate() {
+        let zone = zone.to_string();
+        let expected = submitted.to_vec();
+        probes.spawn(async move { (index, check(hostname, &zone, expected, timeout).await) });
+    }
+    let mut checks = Vec::with_capacity(submitted.len());
+    while let Some(joined) = probes.join_next().await {
+        if let Ok(check) = joined {
+            checks.push(check);
+        }
+    }
+    checks.sort_by_key(|(index, _)| *index);
+
+    let nameservers: Vec<NameserverCheck> = checks.into_iter().map(|(_, check)| check).collect();
+    Report {
+        ok: nameservers.len() == submitted.len()
+            && nameservers.iter().all(|check| check.problems.is_empty()),
+        nameservers,
+    }
+}
+
+async fn check(
+    hostname: String,
+    zone: &str,
+    mut expected: Vec<String>,
+    timeout: Duration,
+) -> NameserverCheck {
+    let mut result = NameserverCheck {
+        hostname,
+        serial: None,
+        nameservers: Vec::new(),
+        problems: Vec::new(),
+    };
+    let addrs = match dns_probe::resolve(&result.hostname, timeout).await {
+        Ok(addrs) => addrs,
+        Err(err) => {
+            result.problems.push(format!("{err:#}"));
+            return result;
+        }
+    };
+
+    match dns_probe::query_soa_any(&addrs, zone, timeout).await {
+        Ok(SoaAnswer::Authoritative { serial }) => result.serial = Some(serial),
+        Ok(SoaAnswer::Lame(detail)) => {
+            result
+                .problems
+                .push(format!("does not serve {zone}: {detail}"));
+            return result;
+        }
+        Err(err) => {
+            result.problems.push(format!("{err:#}"));
+            return result;
+        }
+    }
+
+    match dns_probe::query_ns_any(&addrs, zone, timeout).await {
+        Ok(NsAnswer::Authoritative(nameservers)) => {
+            expected.sort();
+            if nameservers != expected {
+                result.problems.push(format!(
+                    "serves NS {} instead of {}",
+                    nameservers.join(" "),
+                    expected.join(" ")
+                ));
+            }
+            result.nameservers = nameservers;
+        }
+        Ok(NsAnswer::Lame(detail)) => result
+            .problems
+            .push(format!("no NS set for {zone}: {detail}")),
+        Err(err) => result.problems.push(format!("{err:#}")),
+    }
+    result
+}
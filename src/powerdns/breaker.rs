@@ -0,0 +1,156 @@
+//! Circuit breaker for one PowerDNS endpoint: after repeated failures,
+//! calls fail at once for a cooldown instead of each waiting on a broken
+//! backend; then a single trial call decides whether it is back.
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::powerdns::client::PowerDnsClient;
+
+/// Returned instead of calling an endpoint whose circuit is open.
+#[derive(Debug, thiserror::Error)]
+#[error("PowerDNS at {server} failed repeatedly; not calling it for another {retry_in_secs}s")]
+pub struct CircuitOpen {
+    pub server: String,
+    pub retry_in_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    /// Calls go through; `failures` in a row so far.
+    Closed { failures: u32 },
+    /// Calls are rejected until `until`.
+    Open { until: Instant },
+    /// One trial call, started at `since`, is under way; others are rejected.
+    HalfOpen { since: Instant },
+}
+
+impl State {
+    /// Value of the `satsuki_pdns_circuit_state` gauge.
+    fn gauge(self) -> u8 {
+        match self {
+            State::Closed { .. } => 0,
+            State::Open { .. } => 1,
+            State::HalfOpen { .. } => 2,
+        }
+    }
+}
+
+pub struct Breaker {
+    /// Failures in a row that open the circuit.
+    threshold: u32,
+    /// How long the circuit stays open before a trial call.
+    cooldown: Duration,
+    state: Mutex<State>,
+    opened_total: AtomicU64,
+    rejected_total: AtomicU64,
+}
+
+impl Breaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+            opened_total: AtomicU64::new(0),
+            rejected_total: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether a call to `server` may go ahead now.
+    pub(crate) fn admit(&self, server: &str) -> Result<(), CircuitOpen> {
+        let mut state = self.state.lock().expect("circuit breaker poisoned");
+        let now = Instant::now();
+        let retry_at = match *state {
+            State::Closed { .. } => return Ok(()),
+            State::Open { until } if now >= until => {
+                *state = State::HalfOpen { since: now };
+                return Ok(());
+            }
+            // a trial whose caller gave up never reports back
+            State::HalfOpen { since } if now >= since + self.cooldown => {
+                *state = State::HalfOpen { since: now };
+                return Ok(());
+            }
+            State::Open { until } => until,
+            State::HalfOpen { since } => since + self.cooldown,
+        };
+        self.rejected_total.fetch_add(1, Ordering::Relaxed);
+        Err(CircuitOpen {
+            server: server.to_string(),
+            retry_in_secs: retry_at.saturating_duration_since(now).as_secs().max(1),
+        })
+    }
+
+    /// Count the outcome of an admitted call.
+    pub(crate) fn record(&self, server: &str, ok: bool) {
+        let mut state = self.state.lock().expect("circuit breaker poisoned");
+        let next = match (*state, ok) {
+            (State::HalfOpen { .. }, true) => {
+                tracing::info!(server, "PowerDNS answers again, circuit closed");
+                State::Closed { failures: 0 }
+            }
+            (_, true) => State::Closed { failures: 0 },
+            (State::Closed { failures }, false) if failures + 1 < self.threshold => State::Closed {
+                failures: failures + 1,
+            },
+            // a call admitted before the circuit opened failed too
+            (State::Open { until }, false) => State::Open { until },
+            (_, false) => {
+                self.opened_total.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    server,
+                    cooldown_secs = self.cooldown.as_secs(),
+                    "PowerDNS keeps failing, circuit opened"
+                );
+                State::Open {
+                    until: Instant::now() + self.cooldown,
+                }
+            }
+        };
+        *state = next;
+    }
+}
+
+/// Prometheus lines for the breakers of `clients`, named as in `/readyz`.
+pub fn metrics(clients: &[(String, &PowerDnsClient)]) -> String {
+    let breakers: Vec<_> = clients
+        .iter()
+        .filter_map(|(name, client)| Some((name, client.breaker()?)))
+        .collect();
+    if breakers.is_empty() {
+        return String::new();
+    }
+    let mut body = String::from(
+        "# TYPE satsuki_pdns_circuit_state gauge\n\
+# HELP satsuki_pdns_circuit_state PowerDNS circuit breaker: 0 closed, 1 open, 2 half-open\n",
+    );
+    for (name, breaker) in &breakers {
+        let state = *breaker.state.lock().expect("circuit breaker poisoned");
+        body.push_str(&format!(
+            "satsuki_pdns_circuit_state{{endpoint=\"{name}\"}} {}\n",
+            state.gauge()
+        ));
+    }
+    body.push_str(
+        "# TYPE satsuki_pdns_circuit_opened_total counter\n\
+# HELP satsuki_pdns_circuit_opened_total Times the PowerDNS circuit breaker opened\n",
+    );
+    for (name, breaker) in &breakers {
+        body.push_str(&format!(
+            "satsuki_pdns_circuit_opened_total{{endpoint=\"{name}\"}} {}\n",
+            breaker.opened_total.load(Ordering::Relaxed)
+        ));
+    }
+    body.push_str(
+        "# TYPE satsuki_pdns_circuit_rejected_total counter\n\
+# HELP satsuki_pdns_circuit_rejected_total PowerDNS calls failed at once by an open circuit\n",
+    );
+    for (name, breaker) in &breakers {
+        body.push_str(&format!(
+            "satsuki_pdns_circuit_rejected_total{{endpoint=\"{name}\"}} {}\n",
+            breaker.rejected_total.load(Ordering::Relaxed)
+        ));
+    }
+    body
+}
@@ -1,7 +1,11 @@
 //! Thin async client for the PowerDNS HTTP API.
+use crate::powerdns::breaker::Breaker;
+use crate::powerdns::latency::Latency;
 use crate::powerdns::types::*;
 use reqwest::Client;
 use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Convenience wrapper around reqwest with PowerDNS-specific helpers.
 #[derive(Clone)]
@@ -10,6 +14,10 @@ pub struct PowerDnsClient {
     base_url: String, // e.g. "http://127.0.0.1:8081/api/v1"
     api_key: String,
     server_id: String, // usually "localhost"
+    /// Log write requests instead of sending them.
+    dry_run: bool,
+    latency: Option<Arc<Latency>>,
+    breaker: Option<Arc<Breaker>>,
 }
 
 impl PowerDnsClient {
@@ -24,12 +32,74 @@ impl PowerDnsClient {
             base_url: base_url.into(),
             api_key: api_key.into(),
             server_id: server_id.into(),
+            dry_run: false,
+            latency: None,
+            breaker: None,
         }
     }
 
-    /// Attach the configured API key to the request.
-    fn auth_header(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        req.header("X-API-Key", &self.api_key)
+    /// Make every write method log its request body and report success
+    /// without contacting PowerDNS; reads still go through.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Record the duration of every call in `latency`.
+    pub fn latency(mut self, latency: Arc<Latency>) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Fail calls at once for `cooldown` after `failures` in a row; `0`
+    /// failures leaves the client without a breaker.
+    pub fn circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.breaker = (failures > 0).then(|| Arc::new(Breaker::new(failures, cooldown)));
+        self
+    }
+
+    pub(crate) fn breaker(&self) -> Option<&Breaker> {
+        self.breaker.as_deref()
+    }
+
+    /// Run one API call, unless the circuit is open, recording how long it took.
+    async fn timed<T>(
+        &self,
+        op: &'static str,
+        zone: Option<&str>,
+        call: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        if let Some(breaker) = &self.breaker {
+            breaker.admit(&self.base_url)?;
+        }
+        let started = Instant::now();
+        let result = call.await;
+        if let Some(latency) = &self.latency {
+            latency.observe(&self.base_url, op, zone, started.elapsed());
+        }
+        result
+    }
+
+    /// In dry-run mode, log the write that would have been sent and return `true`.
+    fn skip_write(&self, body: &impl Serialize) -> bool {
+        if self.dry_run {
+            let body = serde_json::to_string(body).unwrap_or_default();
+            tracing::info!(body = %body, "dry run: PowerDNS write not sent");
+        }
+        self.dry_run
+    }
+
+    /// Send the request with the configured API key; transport errors and
+    /// 5xx answers count against the circuit breaker.
+    async fn send(&self, req: reqwest::RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let result = req.header("X-API-Key", &self.api_key).send().await;
+        if let Some(breaker) = &self.breaker {
+            let ok = result
+                .as_ref()
+                .is_ok_and(|res| !res.status().is_server_error());
+            breaker.record(&self.base_url, ok);
+        }
+        Ok(result?)
     }
 
     /// Build a fully-qualified PDNS URL for the provided path.
@@ -42,55 +112,175 @@ impl PowerDnsClient {
         )
     }
 
+    /// Fetch the server description, verifying URL, server id, and API key.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "server_info", server = %self.base_url)
+    )]
+    pub async fn server_info(&self) -> anyhow::Result<PdnsServerInfo> {
+        self.timed("server_info", None, async {
+            let url = format!("{}/servers/{}", self.base_url, self.server_id);
+            let res = self.send(self.http.get(url)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS server_info failed with {}", res.status());
+            }
+            Ok(res.json::<PdnsServerInfo>().await?)
+        })
+        .await
+    }
+
     /// Fetch the authoritative view of a zone including rrsets.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "get_zone", server = %self.base_url, zone = %name)
+    )]
     pub async fn get_zone(&self, name: &str) -> anyhow::Result<PdnsZone> {
-        let url = self.url(&format!("zones/{}", name));
-        let res = self.auth_header(self.http.get(url)).send().await?;
-        if !res.status().is_success() {
-            anyhow::bail!("PowerDNS get_zone failed with {}", res.status());
-        }
-        Ok(res.json::<PdnsZone>().await?)
+        self.timed("get_zone", Some(name), async {
+            let url = self.url(&format!("zones/{}", name));
+            let res = self.send(self.http.get(url)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS get_zone failed with {}", res.status());
+            }
+            Ok(res.json::<PdnsZone>().await?)
+        })
+        .await
+    }
+
+    /// Like [`get_zone`](Self::get_zone), but `None` when the server has no such zone.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "find_zone", server = %self.base_url, zone = %name)
+    )]
+    pub async fn find_zone(&self, name: &str) -> anyhow::Result<Option<PdnsZone>> {
+        self.timed("find_zone", Some(name), async {
+            let url = self.url(&format!("zones/{}", name));
+            let res = self.send(self.http.get(url)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if res.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS find_zone failed with {}", res.status());
+            }
+            Ok(Some(res.json::<PdnsZone>().await?))
+        })
+        .await
+    }
+
+    /// List every zone on this server, without rrsets.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "list_zones", server = %self.base_url)
+    )]
+    pub async fn list_zones(&self) -> anyhow::Result<Vec<PdnsZone>> {
+        self.timed("list_zones", None, async {
+            let url = self.url("zones");
+            let res = self.send(self.http.get(url)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS list_zones failed with {}", res.status());
+            }
+            Ok(res.json::<Vec<PdnsZone>>().await?)
+        })
+        .await
+    }
+
+    /// Fetch a zone in BIND zone file format.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "export_zone", server = %self.base_url, zone = %name)
+    )]
+    pub async fn export_zone(&self, name: &str) -> anyhow::Result<String> {
+        self.timed("export_zone", Some(name), async {
+            let url = self.url(&format!("zones/{}/export", name));
+            let res = self.send(self.http.get(url)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS export_zone failed with {}", res.status());
+            }
+            Ok(res.text().await?)
+        })
+        .await
     }
 
     /// Create a brand new zone managed by this PDNS server.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "create_zone", server = %self.base_url, zone = %z.name)
+    )]
     pub async fn create_zone(&self, z: &PdnsZoneCreate) -> anyhow::Result<()> {
-        let url = self.url("zones");
-        let res = self.auth_header(self.http.post(url)).json(z).send().await?;
-        if !res.status().is_success() {
-            anyhow::bail!("PowerDNS create_zone failed with {}", res.status());
+        if self.skip_write(z) {
+            return Ok(());
         }
-        Ok(())
+        self.timed("create_zone", Some(&z.name), async {
+            let url = self.url("zones");
+            let res = self.send(self.http.post(url).json(z)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS create_zone failed with {}", res.status());
+            }
+            Ok(())
+        })
+        .await
     }
 
     /// Atomically apply RRset changes to the given zone.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "patch_rrsets", server = %self.base_url, zone = %zone_name)
+    )]
     pub async fn patch_rrsets(&self, zone_name: &str, rrsets: &[PdnsRrset]) -> anyhow::Result<()> {
         #[derive(Serialize)]
         struct PatchBody<'a> {
             rrsets: &'a [PdnsRrset],
         }
 
-        let url = self.url(&format!("zones/{}", zone_name));
         let body = PatchBody { rrsets };
-        let res = self
-            .auth_header(self.http.patch(url))
-            .json(&body)
-            .send()
-            .await?;
-        if !res.status().is_success() {
-            let status = res.status();
-            let text = res.text().await.unwrap_or_default();
-            anyhow::bail!("PowerDNS patch_rrsets failed with {} {}", status, text);
+        if self.skip_write(&body) {
+            return Ok(());
         }
-        Ok(())
+        self.timed("patch_rrsets", Some(zone_name), async {
+            let url = self.url(&format!("zones/{}", zone_name));
+            let res = self.send(self.http.patch(url).json(&body)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                let status = res.status();
+                let text = res.text().await.unwrap_or_default();
+                anyhow::bail!("PowerDNS patch_rrsets failed with {} {}", status, text);
+            }
+            Ok(())
+        })
+        .await
     }
 
     /// Delete a zone and all of its data.
+    #[tracing::instrument(
+        name = "pdns",
+        skip_all,
+        fields(op = "delete_zone", server = %self.base_url, zone = %name)
+    )]
     pub async fn delete_zone(&self, name: &str) -> anyhow::Result<()> {
-        let url = self.url(&format!("zones/{}", name));
-        let res = self.auth_header(self.http.delete(url)).send().await?;
-        if !res.status().is_success() {
-            anyhow::bail!("PowerDNS delete_zone failed with {}", res.status());
+        if self.skip_write(&()) {
+            return Ok(());
         }
-        Ok(())
+        self.timed("delete_zone", Some(name), async {
+            let url = self.url(&format!("zones/{}", name));
+            let res = self.send(self.http.delete(url)).await?;
+            tracing::debug!(status = res.status().as_u16(), "PowerDNS responded");
+            if !res.status().is_success() {
+                anyhow::bail!("PowerDNS delete_zone failed with {}", res.status());
+            }
+            Ok(())
+        })
+        .await
     }
 }
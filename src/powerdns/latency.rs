@@ -0,0 +1,89 @@
+//! Durations of PowerDNS API calls: a histogram for `/metrics`, and a
+//! warning for each call slower than `--pdns-slow-ms`, so a degrading
+//! backend shows up before requests start timing out.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds in seconds of the `satsuki_pdns_request_duration_seconds` buckets.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Shared by every PowerDNS client of the instance.
+#[derive(Default)]
+pub struct Latency {
+    /// Calls taking longer are logged at WARN; `None` logs none.
+    slow_after: Option<Duration>,
+    /// Keyed by server URL and operation.
+    histograms: Mutex<BTreeMap<(String, &'static str), DurationHistogram>>,
+}
+
+#[derive(Default)]
+struct DurationHistogram {
+    /// Calls that took at most `DURATION_BUCKETS[i]` seconds.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Latency {
+    /// `slow_after_ms` of `0` turns the warnings off.
+    pub fn new(slow_after_ms: u64) -> Self {
+        Self {
+            slow_after: (slow_after_ms > 0).then(|| Duration::from_millis(slow_after_ms)),
+            histograms: Mutex::default(),
+        }
+    }
+
+    /// Record one call to `server`, warning if it was slow.
+    pub fn observe(&self, server: &str, op: &'static str, zone: Option<&str>, elapsed: Duration) {
+        if self
+            .slow_after
+            .is_some_and(|slow_after| elapsed > slow_after)
+        {
+            tracing::warn!(
+                server,
+                op,
+                zone = zone.unwrap_or("-"),
+                duration_ms = elapsed.as_millis() as u64,
+                "slow PowerDNS call"
+            );
+        }
+        let seconds = elapsed.as_secs_f64();
+        let mut histograms = self.histograms.lock().expect("PowerDNS latency poisoned");
+        let histogram = histograms.entry((server.to_string(), op)).or_default();
+        histogram.count += 1;
+        histogram.sum += seconds;
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Prometheus lines for the duration histogram.
+    pub fn metrics(&self) -> String {
+        let mut body = String::from(
+            "# TYPE satsuki_pdns_request_duration_seconds histogram\n\
+# HELP satsuki_pdns_request_duration_seconds Duration of PowerDNS API calls, failed ones included\n",
+        );
+        let histograms = self.histograms.lock().expect("PowerDNS latency poisoned");
+        for ((server, op), histogram) in histograms.iter() {
+            let labels = format!("server=\"{server}\",op=\"{op}\"");
+            for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                body.push_str(&format!(
+                    "satsuki_pdns_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {count}\n"
+                ));
+            }
+            body.push_str(&format!(
+                "satsuki_pdns_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {count}\n\
+satsuki_pdns_request_duration_seconds_sum{{{labels}}} {sum}\n\
+satsuki_pdns_request_duration_seconds_count{{{labels}}} {count}\n",
+                count = histogram.count,
+                sum = histogram.sum,
+            ));
+        }
+        body
+    }
+}
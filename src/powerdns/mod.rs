@@ -1,4 +1,6 @@
 //! Lightweight PowerDNS HTTP API bindings.
 
+pub mod breaker;
 pub mod client;
+pub mod latency;
 pub mod types;
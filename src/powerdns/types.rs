@@ -9,6 +9,9 @@ pub struct PdnsZone {
     #[serde(rename = "type", default)]
     pub zone_type: Option<String>, // "Zone"
     pub kind: String, // "Native", etc.
+    /// SOA serial as PDNS currently serves it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<u32>,
     pub rrsets: Option<Vec<PdnsRrset>>,
 }
 
@@ -48,3 +51,13 @@ pub struct PdnsZoneCreate {
     pub kind: String,             // "Native"
     pub nameservers: Vec<String>, // ["ns1.example.net.", "ns2.example.net."]
 }
+
+/// Server description returned by `GET /servers/{id}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdnsServerInfo {
+    pub id: String,
+    #[serde(default)]
+    pub daemon_type: String, // "authoritative"
+    #[serde(default)]
+    pub version: String,
+}
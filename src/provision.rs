@@ -0,0 +1,218 @@
+//! The `init` subcommand: set up the base PowerDNS for a new installation,
+//! i.e. each parent zone with its SOA, the internal nameservers at its apex,
+//! and glue for the nameservers inside it.
+use std::net::IpAddr;
+
+use crate::AppState;
+use crate::api::public::{build_apex_ns_rrset, build_apex_soa_rrset};
+use crate::health::{self, CheckReport, missing_apex_ns};
+use crate::powerdns::client::PowerDnsClient;
+use crate::powerdns::types::{PdnsRecord, PdnsRrset, PdnsZone, PdnsZoneCreate};
+
+/// Provision every parent zone, reporting each step.
+///
+/// Nothing is written unless every PowerDNS endpoint accepts its key and
+/// every `glue` name is an internal nameserver inside a parent zone.
+/// Existing zones are completed, never reset: missing apex NS records are
+/// added next to the others and the SOA is left alone. `glue` maps internal
+/// nameservers to their addresses.
+pub async fn init(state: &AppState, glue: &[(String, IpAddr)]) -> CheckReport {
+    let mut report = CheckReport::default();
+    for (name, client) in state.pdns_endpoints() {
+        report.record(name, health::check_pdns(client).await);
+    }
+    for (name, _) in glue {
+        let inside = state.config.base_domain_roots().any(|base_domain| {
+            let zone = state.config.parent_zone_name(base_domain);
+            in_zone(&state.config.internal_ns, &zone).any(|ns| ns.eq_ignore_ascii_case(name))
+        });
+        if !inside {
+            report.record(
+                format!("glue {name}"),
+                Err("not an internal nameserver inside a parent zone".into()),
+            );
+        }
+    }
+    if !report.ok() {
+        return report;
+    }
+
+    for base_domain in state.config.base_domain_roots() {
+        let zone_name = state.config.parent_zone_name(base_domain);
+        let client = state.base_pdns_for(base_domain);
+        let zone = match client.find_zone(&zone_name).await {
+            Ok(zone) => zone,
+            Err(err) => {
+                report.record(format!("parent_zone {zone_name}"), Err(format!("{err:#}")));
+                continue;
+            }
+        };
+        let Some(zone) = zone else {
+            let created = create_parent_zone(state, client, &zone_name).await;
+            let ok = created.is_ok();
+            report.record(format!("parent_zone {zone_name}"), created);
+            if ok {
+                glue_nameservers(state, client, &zone_name, None, glue, &mut report).await;
+            }
+            continue;
+        };
+        report.record(
+            format!("parent_zone {zone_name}"),
+            Ok("exists, SOA kept".into()),
+        );
+        report.record(
+            format!("apex_ns {zone_name}"),
+            complete_apex_ns(state, client, &zone).await,
+        );
+        glue_nameservers(state, client, &zone_name, Some(&zone), glue, &mut report).await;
+    }
+    report
+}
+
+/// Create the zone with the internal nameservers and this instance's SOA.
+async fn create_parent_zone(
+    state: &AppState,
+    client: &PowerDnsClient,
+    zone: &str,
+) -> Result<String, String> {
+    client
+        .create_zone(&PdnsZoneCreate {
+            name: zone.to_string(),
+            kind: "Native".into(),
+            nameservers: state.config.internal_ns.clone(),
+        })
+        .await
+        .map_err(|e| format!("{e:#}"))?;
+    client
+        .patch_rrsets(
+            zone,
+            &[
+                build_apex_ns_rrset(&state.config, zone),
+                build_apex_soa_rrset(&state.config, zone),
+            ],
+        )
+        .await
+        .map_err(|e| format!("created, but setting its SOA and NS failed: {e:#}"))?;
+    Ok("created with SOA and apex NS".into())
+}
+
+/// Add the internal nameservers missing from the apex NS RRset of `zone`.
+async fn complete_apex_ns(
+    state: &AppState,
+    client: &PowerDnsClient,
+    zone: &PdnsZone,
+) -> Result<String, String> {
+    let missing = missing_apex_ns(zone, &state.config.internal_ns);
+    if missing.is_empty() {
+        return Ok("complete".into());
+    }
+    let existing = zone
+        .rrsets
+        .iter()
+        .flatten()
+        .find(|rrset| rrset.rrtype == "NS" && rrset.name.eq_ignore_ascii_case(&zone.name));
+    let mut rrset = build_apex_ns_rrset(&state.config, &zone.name);
+    if let Some(existing) = existing {
+        rrset.ttl = existing.ttl;
+        rrset.records = existing
+            .records
+            .iter()
+            .map(|record| PdnsRecord {
+                content: record.content.clone(),
+                disabled: record.disabled,
+            })
+            .chain(missing.iter().map(|ns| PdnsRecord {
+                content: ns.to_string(),
+                disabled: false,
+            }))
+            .collect();
+    }
+    client
+        .patch_rrsets(&zone.name, &[rrset])
+        .await
+        .map_err(|e| format!("{e:#}"))?;
+    Ok(format!("added {}", missing.join(", ")))
+}
+
+/// Set the A/AAAA records of the internal nameservers inside `zone_name`;
+/// `zone` is its current content, `None` for a zone just created.
+async fn glue_nameservers(
+    state: &AppState,
+    client: &PowerDnsClient,
+    zone_name: &str,
+    zone: Option<&PdnsZone>,
+    glue: &[(String, IpAddr)],
+    report: &mut CheckReport,
+) {
+    for ns in in_zone(&state.config.internal_ns, zone_name) {
+        let addrs: Vec<IpAddr> = glue
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(ns))
+            .map(|(_, addr)| *addr)
+            .collect();
+        let outcome = if addrs.is_empty() {
+            let current = zone
+                .map(|zone| current_addresses(zone, ns))
+                .unwrap_or_default();
+            if current.is_empty() {
+                Err(format!(
+                    "{ns} is inside {zone_name} and needs glue; pass --ns-address {ns}=ADDR"
+                ))
+            } else {
+                Ok(format!("kept {}", current.join(", ")))
+            }
+        } else {
+            let rrsets: Vec<PdnsRrset> = [("A", true), ("AAAA", false)]
+                .into_iter()
+                .filter_map(|(rrtype, v4)| {
+                    let records: Vec<PdnsRecord> = addrs
+                        .iter()
+                        .filter(|addr| addr.is_ipv4() == v4)
+                        .map(|addr| PdnsRecord {
+                            content: addr.to_string(),
+                            disabled: false,
+                        })
+                        .collect();
+                    (!records.is_empty()).then(|| PdnsRrset {
+                        name: ns.to_string(),
+                        rrtype: rrtype.into(),
+                        ttl: state.config.ns_ttl,
+                        changetype: Some("REPLACE".into()),
+                        records,
+                        comments: Vec::new(),
+                    })
+                })
+                .collect();
+            let listed: Vec<String> = addrs.iter().map(IpAddr::to_string).collect();
+            client
+                .patch_rrsets(zone_name, &rrsets)
+                .await
+                .map(|()| format!("set to {}", listed.join(", ")))
+                .map_err(|e| format!("{e:#}"))
+        };
+        report.record(format!("glue {ns}"), outcome);
+    }
+}
+
+/// Internal nameservers whose names lie inside `zone`, so resolvers need glue.
+fn in_zone<'a>(internal_ns: &'a [String], zone: &str) -> impl Iterator<Item = &'a str> {
+    let suffix = format!(".{}", zone.to_ascii_lowercase());
+    internal_ns
+        .iter()
+        .map(String::as_str)
+        .filter(move |ns| ns.to_ascii_lowercase().ends_with(&suffix))
+}
+
+/// Enabled A and AAAA contents at `name` in `zone`.
+fn current_addresses(zone: &PdnsZone, name: &str) -> Vec<String> {
+    zone.rrsets
+        .iter()
+        .flatten()
+        .filter(|rrset| {
+            matches!(rrset.rrtype.as_str(), "A" | "AAAA") && rrset.name.eq_ignore_ascii_case(name)
+        })
+        .flat_map(|rrset| &rrset.records)
+        .filter(|record| !record.disabled)
+        .map(|record| record.content.clone())
+        .collect()
+}
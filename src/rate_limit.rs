@@ -0,0 +1,125 @@
+//! Per-user counters of zone updates, so one runaway client cannot drive
+//! the PowerDNS API as hard as it likes. Counters live in memory and start
+//! over when the process restarts.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(86_400);
+
+/// Updates counted since the start of a fixed window.
+#[derive(Clone, Copy)]
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+impl Window {
+    /// The count, or zero once `length` has passed since the window started.
+    fn current(&self, now: Instant, length: Duration) -> u32 {
+        if now.duration_since(self.start) < length {
+            self.count
+        } else {
+            0
+        }
+    }
+
+    fn hit(&mut self, now: Instant, length: Duration) {
+        if now.duration_since(self.start) >= length {
+            *self = Window {
+                start: now,
+                count: 0,
+            };
+        }
+        self.count += 1;
+    }
+
+    fn remaining(&self, now: Instant, length: Duration) -> Duration {
+        length.saturating_sub(now.duration_since(self.start))
+    }
+}
+
+/// Updates a user made in the current minute and day windows.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Usage {
+    pub minute: u32,
+    pub day: u32,
+}
+
+/// An update refused because a window is full.
+#[derive(Clone, Copy, Debug)]
+pub struct Exceeded {
+    /// `"minute"` or `"day"`.
+    pub window: &'static str,
+    pub limit: u32,
+    pub usage: Usage,
+    /// Time until the full window starts over.
+    pub retry_after: Duration,
+}
+
+/// Zone update counters of every user seen since startup.
+#[derive(Default)]
+pub struct ZoneUpdateLimiter {
+    users: Mutex<HashMap<i64, (Window, Window)>>,
+}
+
+impl ZoneUpdateLimiter {
+    /// Count one update by `user_id`, unless it would exceed `per_minute`
+    /// or `per_day`; refused updates are not counted.
+    pub fn acquire(
+        &self,
+        user_id: i64,
+        per_minute: Option<u32>,
+        per_day: Option<u32>,
+    ) -> Result<Usage, Exceeded> {
+        let now = Instant::now();
+        let mut users = self.users.lock().expect("rate limiter lock poisoned");
+        let fresh = Window {
+            start: now,
+            count: 0,
+        };
+        let (minute, day) = users.entry(user_id).or_insert((fresh, fresh));
+        let usage = Usage {
+            minute: minute.current(now, MINUTE),
+            day: day.current(now, DAY),
+        };
+
+        for (name, window, length, limit, used) in [
+            ("day", *day, DAY, per_day, usage.day),
+            ("minute", *minute, MINUTE, per_minute, usage.minute),
+        ] {
+            if let Some(limit) = limit
+                && used >= limit
+            {
+                return Err(Exceeded {
+                    window: name,
+                    limit,
+                    usage,
+                    retry_after: window.remaining(now, length),
+                });
+            }
+        }
+
+        minute.hit(now, MINUTE);
+        day.hit(now, DAY);
+        Ok(Usage {
+            minute: minute.count,
+            day: day.count,
+        })
+    }
+
+    /// Updates `user_id` made in the current windows.
+    pub fn usage(&self, user_id: i64) -> Usage {
+        let now = Instant::now();
+        let users = self.users.lock().expect("rate limiter lock poisoned");
+        users
+            .get(&user_id)
+            .map(|(minute, day)| Usage {
+                minute: minute.current(now, MINUTE),
+                day: day.current(now, DAY),
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,850 @@
+//! Presentation-format record data encoded into wire format, so malformed
+//! content is rejected with a reason before it is sent to PowerDNS.
+//! hickory-proto parses the types it knows; TXT, URI, LOC, and the RFC 3597
+//! generic form are handled here.
+use hickory_proto::ProtoErrorKind;
+use hickory_proto::rr::rdata::{HTTPS, SVCB, TXT};
+use hickory_proto::rr::{RData, RecordType};
+use hickory_proto::serialize::binary::BinEncodable;
+use hickory_proto::serialize::txt::{ParseError, ParseErrorKind, RDataParser};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::validation;
+
+/// Largest RDATA the wire format can carry.
+const MAX_RDATA_LENGTH: usize = 65535;
+/// Largest character-string, e.g. one quoted string of a TXT record.
+const MAX_STRING_LENGTH: usize = 255;
+/// Issuer Critical flag of a CAA property.
+const CAA_CRITICAL: u8 = 128;
+
+/// Record types hickory has no mnemonic for, by mnemonic.
+const EXTRA_TYPES: &[(&str, u16)] = &[
+    ("RP", 17),
+    ("AFSDB", 18),
+    ("LOC", 29),
+    ("KX", 36),
+    ("DNAME", 39),
+    ("APL", 42),
+    ("IPSECKEY", 45),
+    ("DHCID", 49),
+    ("SMIMEA", 53),
+    ("HIP", 55),
+    ("ZONEMD", 63),
+    ("SPF", 99),
+    ("EUI48", 108),
+    ("EUI64", 109),
+    ("URI", 256),
+];
+
+/// Types whose data has domain names, which must be given in ASCII.
+const NAME_TYPES: &[&str] = &[
+    "CNAME", "DNAME", "NS", "PTR", "MX", "SRV", "NAPTR", "SOA", "SVCB", "HTTPS",
+];
+
+/// Encode the content of an `rrtype` record into wire-format RDATA.
+///
+/// Types without a parser are passed through unchecked (`Ok(None)`) unless
+/// given in the RFC 3597 `\# <length> <hex>` form, which works for every
+/// type.
+pub fn to_wire(rrtype: &str, content: &str) -> Result<Option<Vec<u8>>, String> {
+    let tokens = tokenize(content)?;
+    let mut fields = Fields {
+        tokens: tokens.into_iter(),
+        wire: Vec::new(),
+    };
+    if fields.peek_raw() == Some("\\#") {
+        fields.next("\\#")?;
+        generic(&mut fields)?;
+    } else {
+        match rrtype {
+            "TXT" | "SPF" => txt(&mut fields)?,
+            "URI" => uri(&mut fields)?,
+            "LOC" => loc(&mut fields)?,
+            _ => match parsed_type(rrtype) {
+                Some(record_type) => parsed(&mut fields, rrtype, record_type)?,
+                None => return Ok(None),
+            },
+        }
+    }
+    fields.finish()
+}
+
+/// The hickory type whose parser reads `rrtype` records. Types with the
+/// same data as another share its parser.
+fn parsed_type(rrtype: &str) -> Option<RecordType> {
+    Some(match rrtype {
+        "A" => RecordType::A,
+        "AAAA" => RecordType::AAAA,
+        "CNAME" | "DNAME" => RecordType::CNAME,
+        "NS" => RecordType::NS,
+        "PTR" => RecordType::PTR,
+        "MX" => RecordType::MX,
+        "SRV" => RecordType::SRV,
+        "SOA" => RecordType::SOA,
+        "HINFO" => RecordType::HINFO,
+        "NAPTR" => RecordType::NAPTR,
+        "CAA" => RecordType::CAA,
+        "CERT" => RecordType::CERT,
+        "CSYNC" => RecordType::CSYNC,
+        "DS" | "CDS" => RecordType::DS,
+        "SSHFP" => RecordType::SSHFP,
+        "TLSA" | "SMIMEA" => RecordType::TLSA,
+        "OPENPGPKEY" => RecordType::OPENPGPKEY,
+        "SVCB" => RecordType::SVCB,
+        "HTTPS" => RecordType::HTTPS,
+        _ => return None,
+    })
+}
+
+/// Record data read by hickory's parser, with the checks it leaves out.
+fn parsed(fields: &mut Fields, rrtype: &str, record_type: RecordType) -> Result<(), String> {
+    let names = NAME_TYPES.contains(&rrtype);
+    let mut values = Vec::new();
+    for token in fields.tokens.by_ref() {
+        if token.quoted || !names {
+            let value = String::from_utf8(unescape(&token.raw)?)
+                .map_err(|_| format!("{} is not valid UTF-8", token.raw))?;
+            values.push(value);
+        } else if token.raw.is_ascii() {
+            // names keep their escapes, which hickory resolves
+            values.push(token.raw);
+        } else {
+            return Err(format!(
+                "name {} contains non-ASCII characters; use its punycode form",
+                token.raw
+            ));
+        }
+    }
+    match rrtype {
+        // hickory takes these as one field, zone files often split them
+        "SSHFP" if values.len() > 3 => {
+            let fingerprint = values.split_off(2).concat();
+            values.push(fingerprint);
+        }
+        "OPENPGPKEY" if values.len() > 1 => values = vec![values.concat()],
+        // hickory ignores an odd digit at the end
+        "DS" | "CDS" if values.len() > 3 => {
+            let digest = values[3..].concat();
+            if !digest.len().is_multiple_of(2) || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err("digest is not valid hex".into());
+            }
+        }
+        "CAA" if values.len() >= 3 => check_caa(&values[0], &values[1], &values[2])?,
+        _ => {}
+    }
+
+    let mut tokens = values.iter().map(String::as_str);
+    let data = RData::parse(record_type, tokens.by_ref(), None)
+        .map_err(|err| parse_error(rrtype, &err))?;
+    if let Some(extra) = tokens.next() {
+        return Err(format!("unexpected trailing data {extra}"));
+    }
+    let data = match data {
+        RData::SVCB(svcb) => RData::SVCB(sorted_params(&svcb)?),
+        RData::HTTPS(HTTPS(svcb)) => RData::HTTPS(HTTPS(sorted_params(&svcb)?)),
+        data => data,
+    };
+    fields.emit(&data)
+}
+
+/// A hickory parse error, naming the field where callers need it.
+fn parse_error(rrtype: &str, err: &ParseError) -> String {
+    match err.kind() {
+        ParseErrorKind::MissingToken(what) => format!("missing {what}"),
+        ParseErrorKind::AddrParse(err) => err.to_string(),
+        ParseErrorKind::ParseInt(err) => format!("invalid number: {err}"),
+        ParseErrorKind::Proto(err) => match err.kind() {
+            ProtoErrorKind::LabelBytesTooLong(len) => {
+                format!("name has a label of {len} octets, over 63")
+            }
+            ProtoErrorKind::DomainNameTooLong(len) => format!("name of {len} octets is too long"),
+            ProtoErrorKind::Msg(msg) => match msg.strip_prefix("Malformed label: ") {
+                Some("") => "name has an empty label".into(),
+                Some(label) => format!("name has a malformed label {label}"),
+                None => msg.clone(),
+            },
+            _ => err.to_string(),
+        },
+        ParseErrorKind::DataEncoding(_) => match rrtype {
+            "SSHFP" => "fingerprint is not valid hex".into(),
+            "TLSA" | "SMIMEA" => "certificate association data is not valid hex".into(),
+            "OPENPGPKEY" => "public key is not valid base64".into(),
+            _ => err.to_string(),
+        },
+        _ => err.to_string(),
+    }
+}
+
+/// SVCB parameters in key order, as the wire format wants them.
+fn sorted_params(svcb: &SVCB) -> Result<SVCB, String> {
+    let mut params = svcb.svc_params().to_vec();
+    params.sort_by_key(|(key, _)| u16::from(*key));
+    if let Some(pair) = params.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(format!("parameter {} is given twice", pair[0].0));
+    }
+    Ok(SVCB::new(
+        svcb.svc_priority(),
+        svcb.target_name().clone(),
+        params,
+    ))
+}
+
+/// One or more character-strings. Built from bytes rather than parsed by
+/// hickory, which only takes UTF-8.
+fn txt(fields: &mut Fields) -> Result<(), String> {
+    let mut strings = vec![unescape(&fields.next("text")?.raw)?];
+    for token in fields.tokens.by_ref() {
+        strings.push(unescape(&token.raw)?);
+    }
+    if let Some(long) = strings
+        .iter()
+        .find(|string| string.len() > MAX_STRING_LENGTH)
+    {
+        return Err(format!(
+            "text string of {} octets exceeds {MAX_STRING_LENGTH}; split it into several quoted strings",
+            long.len()
+        ));
+    }
+    let txt = TXT::from_bytes(strings.iter().map(Vec::as_slice).collect());
+    fields.emit(&RData::TXT(txt))
+}
+
+fn uri(fields: &mut Fields) -> Result<(), String> {
+    fields.u16("priority")?;
+    fields.u16("weight")?;
+    let target = unescape(&fields.next("target")?.raw)?;
+    if target.is_empty() {
+        return Err("empty target".into());
+    }
+    fields.wire.extend_from_slice(&target);
+    Ok(())
+}
+
+/// Split the strings of TXT content that exceed 255 octets into consecutive
+/// quoted strings of at most 255 octets. Content without long strings is
+/// returned unchanged.
+pub fn chunk_txt(content: &str) -> Result<Cow<'_, str>, String> {
+    let strings = tokenize(content)?
+        .iter()
+        .map(|token| unescape(&token.raw))
+        .collect::<Result<Vec<_>, _>>()?;
+    if strings
+        .iter()
+        .all(|string| string.len() <= MAX_STRING_LENGTH)
+    {
+        return Ok(Cow::Borrowed(content));
+    }
+    let chunks: Vec<String> = strings
+        .iter()
+        .flat_map(|string| {
+            if string.is_empty() {
+                vec![quote(string)]
+            } else {
+                string.chunks(MAX_STRING_LENGTH).map(quote).collect()
+            }
+        })
+        .collect();
+    Ok(Cow::Owned(chunks.join(" ")))
+}
+
+/// Undo `chunk_txt`: content whose strings are all 255 octets long except
+/// the last is returned as one quoted string. Anything else, including
+/// strings split deliberately at other lengths, is returned unchanged.
+pub fn join_txt(content: &str) -> Cow<'_, str> {
+    let Ok(tokens) = tokenize(content) else {
+        return Cow::Borrowed(content);
+    };
+    if tokens.len() < 2 {
+        return Cow::Borrowed(content);
+    }
+    let Ok(strings) = tokens
+        .iter()
+        .map(|token| unescape(&token.raw))
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return Cow::Borrowed(content);
+    };
+    let (last, full) = strings.split_last().expect("at least two strings");
+    if last.len() > MAX_STRING_LENGTH || full.iter().any(|string| string.len() != MAX_STRING_LENGTH)
+    {
+        return Cow::Borrowed(content);
+    }
+    Cow::Owned(quote(&strings.concat()))
+}
+
+/// The strings of TXT content concatenated, as receivers of SPF, DKIM, or
+/// DMARC records read them.
+pub fn txt_text(content: &str) -> Result<Vec<u8>, String> {
+    let mut text = Vec::new();
+    for token in tokenize(content)? {
+        text.extend(unescape(&token.raw)?);
+    }
+    Ok(text)
+}
+
+/// The fields of presentation-format content with quotes removed and
+/// escapes resolved.
+pub fn fields(content: &str) -> Result<Vec<Vec<u8>>, String> {
+    tokenize(content)?
+        .iter()
+        .map(|token| unescape(&token.raw))
+        .collect()
+}
+
+/// A quoted string, escaping quotes, backslashes, and non-printable or
+/// non-ASCII octets.
+pub(crate) fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::with_capacity(bytes.len() + 2);
+    quoted.push('"');
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(b as char);
+            }
+            0x20..=0x7e => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\{b:03}")),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+/// One whitespace-separated field, with escapes still in place.
+struct Token {
+    raw: String,
+    /// Whether the field was a quoted string.
+    quoted: bool,
+}
+
+/// Split presentation format into fields. Quoted strings may contain
+/// whitespace; an unquoted field may quote its value after `=`, as in
+/// `alpn="h2,h3"`.
+fn tokenize(content: &str) -> Result<Vec<Token>, String> {
+    let bytes = content.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+        let quoted = bytes[pos] == b'"';
+        let start = if quoted { pos + 1 } else { pos };
+        let mut end = start;
+        let mut in_quotes = quoted;
+        loop {
+            match bytes.get(end) {
+                None if in_quotes => return Err("unterminated quoted string".into()),
+                None => break,
+                Some(b'\\') if end + 1 == bytes.len() => {
+                    return Err("dangling backslash at the end".into());
+                }
+                Some(b'\\') => end += 2,
+                Some(b'"') if quoted => break,
+                Some(b'"') if in_quotes => {
+                    in_quotes = false;
+                    end += 1;
+                }
+                Some(b'"') if end > start && bytes[end - 1] == b'=' => {
+                    in_quotes = true;
+                    end += 1;
+                }
+                Some(b'"') => return Err("unexpected quote".into()),
+                Some(b) if !in_quotes && b.is_ascii_whitespace() => break,
+                Some(_) => end += 1,
+            }
+        }
+        tokens.push(Token {
+            raw: content[start..end].to_string(),
+            quoted,
+        });
+        pos = if quoted { end + 1 } else { end };
+    }
+    Ok(tokens)
+}
+
+/// Bytes of a field with `\X` and `\DDD` escapes resolved.
+fn unescape(raw: &str) -> Result<Vec<u8>, String> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1..i + 4) {
+            Some(digits) if digits.iter().all(u8::is_ascii_digit) => {
+                let value = digits
+                    .iter()
+                    .fold(0u16, |acc, d| acc * 10 + u16::from(d - b'0'));
+                let value =
+                    u8::try_from(value).map_err(|_| format!("escape \\{value} is out of range"))?;
+                out.push(value);
+                i += 4;
+            }
+            _ => {
+                let escaped = *bytes.get(i + 1).ok_or("dangling backslash")?;
+                out.push(escaped);
+                i += 2;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The fields of one record, consumed in order while the RDATA is built.
+struct Fields {
+    tokens: std::vec::IntoIter<Token>,
+    wire: Vec<u8>,
+}
+
+impl Fields {
+    fn peek_raw(&self) -> Option<&str> {
+        self.tokens
+            .as_slice()
+            .first()
+            .map(|token| token.raw.as_str())
+    }
+
+    fn next(&mut self, what: &str) -> Result<Token, String> {
+        self.tokens.next().ok_or_else(|| format!("missing {what}"))
+    }
+
+    fn parse<T: FromStr>(&mut self, what: &str) -> Result<T, String> {
+        let token = self.next(what)?;
+        token
+            .raw
+            .parse()
+            .map_err(|_| format!("invalid {what} {}", token.raw))
+    }
+
+    fn u16(&mut self, what: &str) -> Result<u16, String> {
+        let value: u16 = self.parse(what)?;
+        self.wire.extend_from_slice(&value.to_be_bytes());
+        Ok(value)
+    }
+
+    /// All remaining fields joined, for data that may contain spaces.
+    fn rest(&mut self, what: &str) -> Result<String, String> {
+        let joined: String = self.tokens.by_ref().map(|token| token.raw).collect();
+        if joined.is_empty() {
+            return Err(format!("missing {what}"));
+        }
+        Ok(joined)
+    }
+
+    fn hex_rest(&mut self, what: &str) -> Result<(), String> {
+        let text = self.rest(what)?;
+        let bytes = decode_hex(&text).ok_or_else(|| format!("{what} is not valid hex"))?;
+        self.wire.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Append the wire format of data hickory holds.
+    fn emit(&mut self, data: &RData) -> Result<(), String> {
+        let bytes = data.to_bytes().map_err(|err| err.to_string())?;
+        self.wire.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Option<Vec<u8>>, String> {
+        if let Some(extra) = self.tokens.next() {
+            return Err(format!("unexpected trailing data {}", extra.raw));
+        }
+        if self.wire.len() > MAX_RDATA_LENGTH {
+            return Err("record data exceeds 65535 octets".into());
+        }
+        Ok(Some(self.wire))
+    }
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// RFC 3597 `\# <length> <hex>`, after the `\#`.
+fn generic(fields: &mut Fields) -> Result<(), String> {
+    let length: usize = fields.parse("length")?;
+    if length == 0 {
+        return Ok(());
+    }
+    fields.hex_rest("data")?;
+    if fields.wire.len() != length {
+        return Err(format!(
+            "data is {} octets but the length says {length}",
+            fields.wire.len()
+        ));
+    }
+    Ok(())
+}
+/// This service's rules for CAA properties, stricter than hickory's: flags
+/// other than Issuer Critical and malformed values are rejected.
+fn check_caa(flags: &str, tag: &str, value: &str) -> Result<(), String> {
+    let flags: u8 = flags
+        .parse()
+        .map_err(|_| format!("invalid flags {flags}"))?;
+    if flags & !CAA_CRITICAL != 0 {
+        return Err(format!(
+            "flags must be 0, or {CAA_CRITICAL} for a critical property"
+        ));
+    }
+    if tag.is_empty() || tag.len() > 15 || !tag.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Err(format!("invalid tag {tag}"));
+    }
+    check_caa_value(
+        &tag.to_ascii_lowercase(),
+        value.as_bytes(),
+        flags & CAA_CRITICAL != 0,
+    )
+}
+
+/// Checks of the property values defined by RFC 8659 and its extensions.
+fn check_caa_value(tag: &str, value: &[u8], critical: bool) -> Result<(), String> {
+    let value =
+        std::str::from_utf8(value).map_err(|_| format!("{tag} value is not valid UTF-8"))?;
+    match tag {
+        "issue" | "issuewild" | "issuemail" | "issuevmc" => {
+            // "<issuer domain>; key=value; ..." where an empty domain forbids issuance
+            let (domain, parameters) = value.split_once(';').unwrap_or((value, ""));
+            let domain = domain.trim();
+            if !domain.is_empty() && !validation::is_hostname(domain) {
+                return Err(format!("{tag} names an invalid CA domain {domain}"));
+            }
+            for parameter in parameters
+                .split(';')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+            {
+                let valid = parameter.split_once('=').is_some_and(|(key, value)| {
+                    let key = key.trim();
+                    !key.is_empty()
+                        && key.bytes().all(|b| b.is_ascii_alphanumeric())
+                        && !value.trim().is_empty()
+                });
+                if !valid {
+                    return Err(format!("{tag} parameter {parameter} is not key=value"));
+                }
+            }
+        }
+        "iodef" => {
+            let url =
+                url::Url::parse(value).map_err(|_| format!("iodef value {value} is not a URL"))?;
+            if !matches!(url.scheme(), "mailto" | "http" | "https") {
+                return Err(format!(
+                    "iodef URL {value} must use mailto:, http:, or https:"
+                ));
+            }
+        }
+        "contactemail" | "contactphone" => {}
+        _ if critical => {
+            return Err(format!(
+                "unknown critical tag {tag} would forbid all issuance"
+            ));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+/// Numeric type of a mnemonic such as `MX`, or of the RFC 3597 `TYPE<n>` form.
+pub fn type_code(mnemonic: &str) -> Option<u16> {
+    let upper = mnemonic.to_ascii_uppercase();
+    if let Some(number) = upper.strip_prefix("TYPE") {
+        return number.parse().ok();
+    }
+    if let Ok(known) = RecordType::from_str(&upper) {
+        return Some(known.into());
+    }
+    EXTRA_TYPES
+        .iter()
+        .find(|(name, _)| *name == upper)
+        .map(|(_, code)| *code)
+}
+
+/// Mnemonic of a numeric type, `TYPE<n>` for types without one.
+pub fn type_name(code: u16) -> String {
+    match RecordType::from(code) {
+        RecordType::Unknown(_) | RecordType::ZERO => EXTRA_TYPES
+            .iter()
+            .find(|(_, known)| *known == code)
+            .map_or_else(|| format!("TYPE{code}"), |(name, _)| name.to_string()),
+        known => known.to_string(),
+    }
+}
+
+/// A decimal with up to `scale` fraction digits, scaled to an integer.
+fn decimal(text: &str, scale: u32) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if whole.is_empty()
+        || fraction.len() > scale as usize
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let mut value: i64 = whole.parse().ok()?;
+    for i in 0..scale as usize {
+        let digit = fraction
+            .as_bytes()
+            .get(i)
+            .map_or(0, |d| i64::from(d - b'0'));
+        value = value.checked_mul(10)?.checked_add(digit)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Meters with an optional `m` suffix, in centimeters.
+fn centimeters(text: &str) -> Option<i64> {
+    decimal(text.strip_suffix(['m', 'M']).unwrap_or(text), 2)
+}
+
+/// A LOC size or precision as mantissa and power of ten (RFC 1876).
+fn precision(text: &str) -> Result<u8, String> {
+    let mut mantissa = centimeters(text)
+        .filter(|cm| *cm >= 0)
+        .ok_or_else(|| format!("invalid size or precision {text}"))?;
+    let mut exponent = 0;
+    while mantissa >= 10 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    if exponent > 9 {
+        return Err(format!("size or precision {text} is too large"));
+    }
+    Ok(((mantissa as u8) << 4) | exponent)
+}
+
+/// Latitude or longitude in thousandths of an arc second offset by 2^31.
+fn coordinate(
+    fields: &mut Fields,
+    positive: &str,
+    negative: &str,
+    max_degrees: i64,
+) -> Result<u32, String> {
+    let what = if positive == "N" {
+        "latitude"
+    } else {
+        "longitude"
+    };
+    let invalid = || format!("invalid {what}");
+    let degrees = decimal(&fields.next(what)?.raw, 0)
+        .filter(|d| (0..=max_degrees).contains(d))
+        .ok_or_else(invalid)?;
+    let mut minutes = 0;
+    let mut millis = 0;
+    let mut token = fields.next(what)?.raw;
+    let is_hemisphere =
+        |token: &str| token.eq_ignore_ascii_case(positive) || token.eq_ignore_ascii_case(negative);
+    if !is_hemisphere(&token) {
+        minutes = decimal(&token, 0)
+            .filter(|m| (0..60).contains(m))
+            .ok_or_else(invalid)?;
+        token = fields.next(what)?.raw;
+        if !is_hemisphere(&token) {
+            millis = decimal(&token, 3)
+                .filter(|ms| (0..60_000).contains(ms))
+                .ok_or_else(invalid)?;
+            token = fields.next(what)?.raw;
+        }
+    }
+    if !is_hemisphere(&token) {
+        return Err(format!("{what} needs {positive} or {negative}"));
+    }
+    let value = (degrees * 3600 + minutes * 60) * 1000 + millis;
+    if value > max_degrees * 3600 * 1000 {
+        return Err(invalid());
+    }
+    let offset = 1i64 << 31;
+    let encoded = if token.eq_ignore_ascii_case(positive) {
+        offset + value
+    } else {
+        offset - value
+    };
+    Ok(encoded as u32)
+}
+
+fn loc(fields: &mut Fields) -> Result<(), String> {
+    let latitude = coordinate(fields, "N", "S", 90)?;
+    let longitude = coordinate(fields, "E", "W", 180)?;
+    let altitude = fields.next("altitude")?.raw;
+    let altitude = centimeters(&altitude)
+        .filter(|cm| (-10_000_000..=4_284_967_295).contains(cm))
+        .ok_or_else(|| format!("invalid altitude {altitude}"))?;
+    // size 1m, horizontal precision 10000m, vertical precision 10m
+    let mut precisions = [0x12, 0x16, 0x13];
+    for slot in &mut precisions {
+        match fields.tokens.next() {
+            Some(token) => *slot = precision(&token.raw)?,
+            None => break,
+        }
+    }
+    fields.wire.push(0);
+    fields.wire.extend_from_slice(&precisions);
+    fields.wire.extend_from_slice(&latitude.to_be_bytes());
+    fields.wire.extend_from_slice(&longitude.to_be_bytes());
+    fields
+        .wire
+        .extend_from_slice(&((altitude + 10_000_000) as u32).to_be_bytes());
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn encodes_every_supported_type() {
+        let cases = [
+            ("A", "192.0.2.1", "c0000201"),
+            ("AAAA", "2001:db8::1", "20010db8000000000000000000000001"),
+            (
+                "CNAME",
+                "www.example.com.",
+                "03777777076578616d706c6503636f6d00",
+            ),
+            ("DNAME", "example.net.", "076578616d706c65036e657400"),
+            (
+                "NS",
+                "ns1.example.com.",
+                "036e7331076578616d706c6503636f6d00",
+            ),
+            ("PTR", "host.example.", "04686f7374076578616d706c6500"),
+            ("PTR", ".", "00"),
+            ("MX", "10 mail.example.", "000a046d61696c076578616d706c6500"),
+            (
+                "SRV",
+                "0 5 5060 sip.example.",
+                "0000000513c403736970076578616d706c6500",
+            ),
+            (
+                "TXT",
+                r#""v=spf1 -all" "x""#,
+                "0b763d73706631202d616c6c0178",
+            ),
+            ("TXT", r#""a\"b\255""#, "04612262ff"),
+            ("SPF", r#""""#, "00"),
+            (
+                "CAA",
+                r#"0 issue "letsencrypt.org""#,
+                "000569737375656c657473656e63727970742e6f7267",
+            ),
+            (
+                "CAA",
+                r#"128 iodef "mailto:ca@example.com""#,
+                "8005696f6465666d61696c746f3a6361406578616d706c652e636f6d",
+            ),
+            ("DS", "12345 13 2 ABCDEF01", "30390d02abcdef01"),
+            ("CDS", "12345 13 2 ab cd", "30390d02abcd"),
+            ("SSHFP", "1 2 ab cd", "0102abcd"),
+            ("TLSA", "3 1 1 0123", "0301010123"),
+            ("SMIMEA", "3 0 1 ff", "030001ff"),
+            ("OPENPGPKEY", "AQID BA==", "01020304"),
+            (
+                "NAPTR",
+                r#"100 10 "S" "SIP+D2U" "" _sip._udp.example."#,
+                "0064000a0153075349502b44325500045f736970045f756470076578616d706c6500",
+            ),
+            (
+                "URI",
+                r#"10 1 "https://example.com/""#,
+                "000a000168747470733a2f2f6578616d706c652e636f6d2f",
+            ),
+            ("CSYNC", "66 3 A NS AAAA", "000000420003000460000008"),
+            (
+                "SVCB",
+                "1 . alpn=h2,h3 port=8443",
+                "000100000100060268320268330003000220fb",
+            ),
+            (
+                "HTTPS",
+                "1 . ipv4hint=192.0.2.1 mandatory=ipv4hint",
+                "00010000000002000400040004c0000201",
+            ),
+            ("HTTPS", "0 svc.example.", "000003737663076578616d706c6500"),
+            (
+                "LOC",
+                "52 22 23 N 4 53 32 E -2m",
+                "001216138b3cf018810cbce0009895b8",
+            ),
+            (
+                "LOC",
+                "42 21 54 S 71 06 18 W -24m 30m",
+                "0033161376e8d23070be15f000988d20",
+            ),
+            ("A", r"\# 4 c0000201", "c0000201"),
+            ("HINFO", r#""PC" "Linux""#, "025043054c696e7578"),
+            ("RP", r"\# 0", ""),
+        ];
+        for (rrtype, content, expected) in cases {
+            let wire = to_wire(rrtype, content)
+                .unwrap_or_else(|err| panic!("{rrtype} {content}: {err}"))
+                .unwrap_or_else(|| panic!("{rrtype} {content}: not encoded"));
+            assert_eq!(hex(&wire), expected, "{rrtype} {content}");
+        }
+    }
+
+    #[test]
+    fn passes_unknown_types_through() {
+        assert_eq!(to_wire("RP", "admin.example. ."), Ok(None));
+    }
+
+    #[test]
+    fn rejects_malformed_content() {
+        let long_label = format!("{}.example.", "a".repeat(64));
+        let long_string = format!(r#""{}""#, "a".repeat(256));
+        let cases = [
+            ("A", "192.0.2", "invalid IPv4 address"),
+            ("A", "192.0.2.1 192.0.2.2", "unexpected trailing data"),
+            ("AAAA", "192.0.2.1", "invalid IPv6 address"),
+            ("MX", "10", "missing exchange"),
+            ("MX", "x mail.example.", "invalid number"),
+            ("MX", "10 a..example.", "empty label"),
+            ("CNAME", long_label.as_str(), "over 63"),
+            ("CNAME", "bücher.example.", "use its punycode form"),
+            ("TXT", long_string.as_str(), "exceeds 255"),
+            ("TXT", r#""abc"#, "unterminated quoted string"),
+            ("CAA", r#"1 issue "ca.example""#, "flags must be 0"),
+            ("CAA", r#"128 future "x""#, "unknown critical tag"),
+            ("CAA", r#"0 iodef "ftp://example.com/""#, "must use mailto"),
+            ("DS", "1 2 3 xyz", "not valid hex"),
+            ("DS", "1 2 3 abc", "not valid hex"),
+            ("TLSA", "3 1 1 zz", "not valid hex"),
+            ("OPENPGPKEY", "!!!", "not valid base64"),
+            ("URI", r#"10 1 """#, "empty target"),
+            ("CSYNC", "1 0 BOGUS", "record type string unknown"),
+            ("SVCB", "1 . port=1 port=2", "given twice"),
+            ("SVCB", "1 . no-default-alpn=h2", "no value expected"),
+            ("HTTPS", "1 . alpn", "expected at least one ALPN"),
+            ("SRV", "0 5 5060", "missing target"),
+            ("LOC", "91 0 0 N 0 E 0m", "invalid latitude"),
+            ("LOC", "90 1 0 N 0 E 0m", "invalid latitude"),
+            ("LOC", "99999999999999999 N 0 E 0m", "invalid latitude"),
+            ("LOC", "0 N 181 E 0m", "invalid longitude"),
+            ("LOC", "0 0 0 X 0 E 0m", "latitude needs N or S"),
+            ("LOC", "0 N 0 E 0m 1e3m", "invalid size or precision"),
+            ("A", r"\# 4 c00002", "the length says 4"),
+        ];
+        for (rrtype, content, expected) in cases {
+            let err = to_wire(rrtype, content).expect_err(content);
+            assert!(err.contains(expected), "{rrtype} {content}: {err}");
+        }
+    }
+}
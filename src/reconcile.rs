@@ -0,0 +1,595 @@
+//! Comparison of user rows with the delegations in the parent zones and the
+//! zones on sub-PDNS, and repair of the drift the DB is authoritative for.
+use anyhow::Context;
+use arc_swap::ArcSwapOption;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::alerts::{self, Alert, AlertKind};
+use crate::api::public::{build_apex_ns_rrset, build_apex_soa_rrset};
+use crate::config::{OrphanPolicy, PolicyConfig};
+use crate::db::pending_signup_repo;
+use crate::db::user_repo::{self, User};
+use crate::jobs::{Job, Schedule};
+use crate::powerdns::types::{PdnsRecord, PdnsRrset, PdnsZoneCreate};
+use crate::{AppState, delegations};
+
+/// One way PowerDNS disagrees with the database.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DriftKind {
+    /// The user's zone does not exist on sub-PDNS.
+    MissingZone,
+    /// The parent zone has no NS rrset for the user.
+    MissingDelegation,
+    /// The delegation points somewhere other than the user's NS mode says.
+    WrongDelegation {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    /// A parent-zone delegation one label below the base domain with no user.
+    OrphanDelegation,
+    /// A zone on sub-PDNS one label below the base domain with no user.
+    OrphanZone,
+}
+
+impl DriftKind {
+    /// All tags, as used in the JSON `kind` and metric labels.
+    pub const TAGS: [&'static str; 5] = [
+        "missing_zone",
+        "missing_delegation",
+        "wrong_delegation",
+        "orphan_delegation",
+        "orphan_zone",
+    ];
+
+    /// Whether `repair` fixes it; orphans are only reported.
+    pub fn repairable(&self) -> bool {
+        !self.is_orphan()
+    }
+
+    /// A delegation or zone without a user.
+    pub fn is_orphan(&self) -> bool {
+        matches!(self, DriftKind::OrphanDelegation | DriftKind::OrphanZone)
+    }
+
+    /// The JSON `kind` tag.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            DriftKind::MissingZone => "missing_zone",
+            DriftKind::MissingDelegation => "missing_delegation",
+            DriftKind::WrongDelegation { .. } => "wrong_delegation",
+            DriftKind::OrphanDelegation => "orphan_delegation",
+            DriftKind::OrphanZone => "orphan_zone",
+        }
+    }
+}
+
+/// Drift found for one zone.
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    /// Fully qualified zone name with trailing dot.
+    pub zone: String,
+    pub base_domain: String,
+    #[serde(flatten)]
+    pub kind: DriftKind,
+}
+
+impl Drift {
+    /// Identity across scans: the zone and the kind of drift.
+    fn key(&self) -> (String, &'static str) {
+        (self.zone.clone(), self.kind.tag())
+    }
+}
+
+/// Outcome of a scan over every served base domain.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub users_checked: usize,
+    pub drift: Vec<Drift>,
+}
+
+/// Compare every user of a served base domain with PowerDNS.
+pub async fn scan(state: &AppState) -> anyhow::Result<Report> {
+    let users = user_repo::list_all(&state.db)
+        .await
+        .context("failed to load users")?;
+    let pending = pending_signup_repo::list(&state.db)
+        .await
+        .context("failed to load pending signups")?;
+    let mut report = Report::default();
+
+    for base_domain in state.config.base_domain_roots() {
+        let users: Vec<&User> = users
+            .iter()
+            .filter(|user| user.base_domain == base_domain)
+            .collect();
+        report.users_checked += users.len();
+
+        let parent_zone = state.config.parent_zone_name(base_domain);
+        let parent = state
+            .base_pdns_for(base_domain)
+            .get_zone(&parent_zone)
+            .await
+            .with_context(|| format!("failed to load parent zone {parent_zone}"))?;
+        // keeps the inventory behind the public listing honest
+        delegations::sync(
+            state,
+            base_domain,
+            parent.rrsets.as_deref().unwrap_or_default(),
+        )
+        .await
+        .context("failed to update the delegation inventory")?;
+        let delegations: HashMap<String, Vec<String>> = parent
+            .rrsets
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|rrset| rrset.rrtype == "NS")
+            .map(|rrset| {
+                let targets = rrset.records.into_iter().map(|r| r.content).collect();
+                (normalize(&rrset.name), targets)
+            })
+            .collect();
+        let zones: HashSet<String> = state
+            .sub_pdns_for(base_domain)
+            .list_zones()
+            .await
+            .context("failed to list sub-PDNS zones")?
+            .into_iter()
+            .map(|zone| normalize(&zone.name))
+            .collect();
+
+        // zones of signups still in progress are not orphans
+        let mut known: HashSet<String> = pending
+            .iter()
+            .filter(|signup| signup.base_domain == base_domain)
+            .map(|signup| normalize(&state.config.user_zone_name(&signup.subdomain, base_domain)))
+            .collect();
+        for user in users {
+            let zone = state.config.user_zone_name(&user.subdomain, base_domain);
+            let key = normalize(&zone);
+            let mut push = |kind| {
+                report.drift.push(Drift {
+                    zone: zone.clone(),
+                    base_domain: base_domain.to_string(),
+                    kind,
+                })
+            };
+            if !zones.contains(&key) {
+                push(DriftKind::MissingZone);
+            }
+            let expected = expected_nameservers(state, user);
+            match delegations.get(&key) {
+                None => push(DriftKind::MissingDelegation),
+                Some(actual) if name_set(actual) != name_set(&expected) => {
+                    push(DriftKind::WrongDelegation {
+                        expected,
+                        actual: actual.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+            known.insert(key);
+        }
+
+        let suffix = format!(".{}", normalize(&parent_zone));
+        let orphans = |names: Vec<&String>, kind: DriftKind| {
+            let mut names: Vec<String> = names
+                .into_iter()
+                .filter(|name| !known.contains(*name))
+                .filter(|name| {
+                    name.strip_suffix(&suffix)
+                        .is_some_and(|label| !label.is_empty() && !label.contains('.'))
+                })
+                .cloned()
+                .collect();
+            names.sort();
+            names.into_iter().map(move |name| Drift {
+                zone: format!("{name}."),
+                base_domain: base_domain.to_string(),
+                kind: kind.clone(),
+            })
+        };
+        report.drift.extend(orphans(
+            delegations.keys().collect(),
+            DriftKind::OrphanDelegation,
+        ));
+        report
+            .drift
+            .extend(orphans(zones.iter().collect(), DriftKind::OrphanZone));
+    }
+
+    Ok(report)
+}
+
+/// Bring PowerDNS back in line with the database for one repairable drift.
+pub async fn repair(state: &AppState, drift: &Drift) -> anyhow::Result<()> {
+    let base_domain = drift.base_domain.as_str();
+    match &drift.kind {
+        DriftKind::MissingZone => {
+            let sub_pdns = state.sub_pdns_for(base_domain);
+            sub_pdns
+                .create_zone(&PdnsZoneCreate {
+                    name: drift.zone.clone(),
+                    kind: "Native".into(),
+                    nameservers: state.config.internal_ns.clone(),
+                })
+                .await?;
+            sub_pdns
+                .patch_rrsets(
+                    &drift.zone,
+                    &[
+                        build_apex_ns_rrset(&state.config, &drift.zone),
+                        build_apex_soa_rrset(&state.config, &drift.zone),
+                    ],
+                )
+                .await
+        }
+        DriftKind::MissingDelegation | DriftKind::WrongDelegation { .. } => {
+            let label = drift
+                .zone
+                .strip_suffix(&format!(".{}", state.config.parent_zone_name(base_domain)))
+                .context("zone is not directly below its base domain")?;
+            let user = user_repo::find_by_subdomain(&state.db, label, base_domain)
+                .await?
+                .context("user was deleted since the scan")?;
+            let rrset = PdnsRrset {
+                name: drift.zone.clone(),
+                rrtype: "NS".into(),
+                ttl: state.config.ns_ttl,
+                changetype: Some("REPLACE".into()),
+                records: expected_nameservers(state, &user)
+                    .into_iter()
+                    .map(|content| PdnsRecord {
+                        content,
+                        disabled: false,
+                    })
+                    .collect(),
+                comments: Vec::new(),
+            };
+            delegations::patch(state, base_domain, &[rrset]).await
+        }
+        DriftKind::OrphanDelegation | DriftKind::OrphanZone => {
+            anyhow::bail!("orphans are not repaired automatically")
+        }
+    }
+}
+
+/// Delete a delegation or zone that has no user.
+pub async fn remove_orphan(state: &AppState, drift: &Drift) -> anyhow::Result<()> {
+    let base_domain = drift.base_domain.as_str();
+    match drift.kind {
+        DriftKind::OrphanDelegation => {
+            let rrset = PdnsRrset {
+                name: drift.zone.clone(),
+                rrtype: "NS".into(),
+                ttl: state.config.ns_ttl,
+                changetype: Some("DELETE".into()),
+                records: Vec::new(),
+                comments: Vec::new(),
+            };
+            delegations::patch(state, base_domain, &[rrset]).await
+        }
+        DriftKind::OrphanZone => {
+            state
+                .sub_pdns_for(base_domain)
+                .delete_zone(&drift.zone)
+                .await
+        }
+        _ => anyhow::bail!("not an orphan"),
+    }
+}
+
+/// Time between the two scans of a run; only drift seen by both is acted on,
+/// so signups and NS-mode changes caught halfway are left alone.
+const CONFIRM_DELAY: Duration = Duration::from_secs(30);
+
+/// What a run did about one drift.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Reported,
+    Repaired,
+    Removed,
+    Failed,
+}
+
+/// One drift and how it was handled.
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftOutcome {
+    #[serde(flatten)]
+    pub drift: Drift,
+    pub action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of one periodic or operator-triggered run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub users_checked: usize,
+    pub drift: Vec<DriftOutcome>,
+    /// Set when the scan itself failed, e.g. PowerDNS was unreachable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reconciler state kept across runs.
+#[derive(Default)]
+pub struct Tracker {
+    /// Report of the latest finished run.
+    pub last: ArcSwapOption<RunReport>,
+    running: tokio::sync::Mutex<()>,
+    /// Orphans of the previous run; only those found again are removed.
+    previous_orphans: Mutex<HashSet<(String, &'static str)>>,
+    pub repaired_total: AtomicU64,
+    pub removed_total: AtomicU64,
+    pub failed_total: AtomicU64,
+}
+
+impl Tracker {
+    /// Prometheus lines describing the latest run and the action counters.
+    pub fn metrics(&self) -> String {
+        let mut body = String::from(
+            "# TYPE satsuki_reconcile_actions_total counter\n\
+# HELP satsuki_reconcile_actions_total Drift repaired, removed, or failed to fix\n",
+        );
+        for (action, counter) in [
+            ("repaired", &self.repaired_total),
+            ("removed", &self.removed_total),
+            ("failed", &self.failed_total),
+        ] {
+            body.push_str(&format!(
+                "satsuki_reconcile_actions_total{{action=\"{action}\"}} {}\n",
+                counter.load(Ordering::Relaxed)
+            ));
+        }
+        let Some(last) = self.last.load_full() else {
+            return body;
+        };
+        body.push_str(
+            "# TYPE satsuki_reconcile_drift gauge\n\
+# HELP satsuki_reconcile_drift Drift found by the latest reconciliation run\n",
+        );
+        for tag in DriftKind::TAGS {
+            let count = last
+                .drift
+                .iter()
+                .filter(|outcome| outcome.drift.kind.tag() == tag)
+                .count();
+            body.push_str(&format!(
+                "satsuki_reconcile_drift{{kind=\"{tag}\"}} {count}\n"
+            ));
+        }
+        body.push_str(&format!(
+            "# TYPE satsuki_reconcile_last_run_timestamp_seconds gauge\n\
+satsuki_reconcile_last_run_timestamp_seconds {}\n\
+# TYPE satsuki_reconcile_last_run_success gauge\n\
+satsuki_reconcile_last_run_success {}\n",
+            last.started_at.timestamp(),
+            u8::from(last.error.is_none())
+        ));
+        body
+    }
+}
+
+/// Scan twice, act on the confirmed drift per policy, and store the report.
+/// Returns `None` if another run is in progress.
+pub async fn run(state: &AppState) -> Option<Arc<RunReport>> {
+    let _running = state.reconcile.running.try_lock().ok()?;
+    let _guard = state.in_flight.begin("reconciliation run");
+    let policy = state.policy().reconcile.clone();
+    let started_at = Utc::now();
+    let clock = Instant::now();
+
+    let scanned = async {
+        let first = scan(state).await?;
+        if first.drift.is_empty() {
+            return Ok(first);
+        }
+        tokio::time::sleep(CONFIRM_DELAY).await;
+        let seen: HashSet<_> = first.drift.iter().map(Drift::key).collect();
+        let mut second = scan(state).await?;
+        second.drift.retain(|drift| seen.contains(&drift.key()));
+        Ok::<_, anyhow::Error>(second)
+    }
+    .await;
+
+    let mut report = RunReport {
+        started_at,
+        duration_ms: 0,
+        users_checked: 0,
+        drift: Vec::new(),
+        error: None,
+    };
+    match scanned {
+        Err(err) => {
+            tracing::error!("reconciliation scan failed: {err:#}");
+            report.error = Some(format!("{err:#}"));
+        }
+        Ok(scan) => {
+            report.users_checked = scan.users_checked;
+            let previous = std::mem::take(
+                &mut *state
+                    .reconcile
+                    .previous_orphans
+                    .lock()
+                    .expect("orphan set poisoned"),
+            );
+            let mut orphans = HashSet::new();
+            for drift in scan.drift {
+                let outcome = handle(state, &policy, &previous, drift).await;
+                if outcome.drift.kind.is_orphan() {
+                    orphans.insert(outcome.drift.key());
+                }
+                report.drift.push(outcome);
+            }
+            *state
+                .reconcile
+                .previous_orphans
+                .lock()
+                .expect("orphan set poisoned") = orphans;
+        }
+    }
+    report.duration_ms = clock.elapsed().as_millis() as u64;
+    alert_new_drift(state, &report);
+
+    let report = Arc::new(report);
+    state.reconcile.last.store(Some(report.clone()));
+    Some(report)
+}
+
+/// Alert the operators of drift that is left unfixed and was not in the
+/// previous report, so a standing drift is only reported once.
+fn alert_new_drift(state: &AppState, report: &RunReport) {
+    let previous: HashSet<_> = state
+        .reconcile
+        .last
+        .load()
+        .as_ref()
+        .map(|last| {
+            last.drift
+                .iter()
+                .map(|outcome| outcome.drift.key())
+                .collect()
+        })
+        .unwrap_or_default();
+    let new: Vec<_> = report
+        .drift
+        .iter()
+        .filter(|outcome| matches!(outcome.action, Action::Reported | Action::Failed))
+        .filter(|outcome| !previous.contains(&outcome.drift.key()))
+        .collect();
+    if new.is_empty() {
+        return;
+    }
+    let listed: Vec<_> = new
+        .iter()
+        .map(|outcome| format!("{} ({})", outcome.drift.zone, outcome.drift.kind.tag()))
+        .collect();
+    alerts::raise(
+        state,
+        Alert::new(
+            AlertKind::ReconcileDrift,
+            listed.join(", "),
+            format!(
+                "reconciliation found {} new drift(s): {}",
+                new.len(),
+                listed.join(", ")
+            ),
+            serde_json::to_value(&new).unwrap_or_default(),
+        ),
+    );
+}
+
+/// Apply the policy to one confirmed drift.
+async fn handle(
+    state: &AppState,
+    policy: &crate::config::ReconcileConfig,
+    previous_orphans: &HashSet<(String, &'static str)>,
+    drift: Drift,
+) -> DriftOutcome {
+    let attempt = if drift.kind.repairable() && policy.repair {
+        Some((repair(state, &drift).await, Action::Repaired))
+    } else if drift.kind.is_orphan()
+        && policy.orphans == OrphanPolicy::Remove
+        && previous_orphans.contains(&drift.key())
+    {
+        Some((remove_orphan(state, &drift).await, Action::Removed))
+    } else {
+        None
+    };
+
+    let tracker = &state.reconcile;
+    let (action, error) = match attempt {
+        None => (Action::Reported, None),
+        Some((Ok(()), action)) => {
+            let (verb, counter) = match action {
+                Action::Removed => ("removed", &tracker.removed_total),
+                _ => ("repaired", &tracker.repaired_total),
+            };
+            tracing::warn!(
+                "reconciliation {verb} {} ({})",
+                drift.zone,
+                drift.kind.tag()
+            );
+            counter.fetch_add(1, Ordering::Relaxed);
+            (action, None)
+        }
+        Some((Err(err), _)) => {
+            tracing::error!(
+                "reconciliation: failed to fix {} {}: {err:#}",
+                drift.zone,
+                drift.kind.tag()
+            );
+            tracker.failed_total.fetch_add(1, Ordering::Relaxed);
+            (Action::Failed, Some(format!("{err:#}")))
+        }
+    };
+    DriftOutcome {
+        drift,
+        action,
+        error,
+    }
+}
+
+/// Scheduled reconciliation, per `[reconcile]` in the policy.
+pub struct ReconcileJob;
+
+#[async_trait]
+impl Job for ReconcileJob {
+    fn name(&self) -> &'static str {
+        "reconcile"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.reconcile;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let Some(report) = run(state).await else {
+            return Ok("skipped, another run was in progress".into());
+        };
+        if let Some(err) = &report.error {
+            anyhow::bail!("{err}");
+        }
+        let failed = report
+            .drift
+            .iter()
+            .filter(|outcome| matches!(outcome.action, Action::Failed))
+            .count();
+        Ok(format!(
+            "checked {} users, found {} drift(s), {failed} not fixed",
+            report.users_checked,
+            report.drift.len()
+        ))
+    }
+}
+
+/// Nameservers the parent delegation should list for the user's NS mode.
+fn expected_nameservers(state: &AppState, user: &User) -> Vec<String> {
+    if user.external_ns {
+        user.external_nameservers.clone()
+    } else {
+        state.config.internal_ns.clone()
+    }
+}
+
+fn name_set(names: &[String]) -> BTreeSet<String> {
+    names.iter().map(|name| normalize(name)).collect()
+}
+
+/// Lowercase without the trailing dot, for comparisons.
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
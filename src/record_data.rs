@@ -0,0 +1,140 @@
+//! Structured record data for the common record types, accepted and returned
+//! next to the presentation-format `content` of the record API.
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::rdata;
+
+/// Record data tagged by record type. Other types are only available as
+/// presentation-format content.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "UPPERCASE", deny_unknown_fields)]
+pub enum RecordData {
+    A {
+        address: Ipv4Addr,
+    },
+    Aaaa {
+        address: Ipv6Addr,
+    },
+    Cname {
+        target: String,
+    },
+    Ns {
+        host: String,
+    },
+    Ptr {
+        target: String,
+    },
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    /// The character-strings of the record; receivers concatenate them.
+    Txt {
+        strings: Vec<String>,
+    },
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Caa {
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+}
+
+impl RecordData {
+    /// Record type mnemonic.
+    pub fn rrtype(&self) -> &'static str {
+        match self {
+            RecordData::A { .. } => "A",
+            RecordData::Aaaa { .. } => "AAAA",
+            RecordData::Cname { .. } => "CNAME",
+            RecordData::Ns { .. } => "NS",
+            RecordData::Ptr { .. } => "PTR",
+            RecordData::Mx { .. } => "MX",
+            RecordData::Txt { .. } => "TXT",
+            RecordData::Srv { .. } => "SRV",
+            RecordData::Caa { .. } => "CAA",
+        }
+    }
+
+    /// Presentation format, validated like any submitted content afterwards.
+    pub fn to_content(&self) -> String {
+        match self {
+            RecordData::A { address } => address.to_string(),
+            RecordData::Aaaa { address } => address.to_string(),
+            RecordData::Cname { target } | RecordData::Ptr { target } => target.trim().to_string(),
+            RecordData::Ns { host } => host.trim().to_string(),
+            RecordData::Mx {
+                preference,
+                exchange,
+            } => format!("{preference} {}", exchange.trim()),
+            RecordData::Txt { strings } => strings
+                .iter()
+                .map(|string| rdata::quote(string.as_bytes()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            RecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => format!("{priority} {weight} {port} {}", target.trim()),
+            RecordData::Caa { flags, tag, value } => {
+                format!("{flags} {} {}", tag.trim(), rdata::quote(value.as_bytes()))
+            }
+        }
+    }
+
+    /// Structured form of stored content, or `None` for other types and
+    /// content that does not fit, such as TXT strings that are not UTF-8.
+    pub fn parse(rrtype: &str, content: &str) -> Option<Self> {
+        let mut fields = rdata::fields(content)
+            .ok()?
+            .into_iter()
+            .map(String::from_utf8)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        if rrtype == "TXT" {
+            return Some(RecordData::Txt { strings: fields });
+        }
+        let data = match (rrtype, fields.as_mut_slice()) {
+            ("A", [address]) => RecordData::A {
+                address: address.parse().ok()?,
+            },
+            ("AAAA", [address]) => RecordData::Aaaa {
+                address: address.parse().ok()?,
+            },
+            ("CNAME", [target]) => RecordData::Cname {
+                target: std::mem::take(target),
+            },
+            ("NS", [host]) => RecordData::Ns {
+                host: std::mem::take(host),
+            },
+            ("PTR", [target]) => RecordData::Ptr {
+                target: std::mem::take(target),
+            },
+            ("MX", [preference, exchange]) => RecordData::Mx {
+                preference: preference.parse().ok()?,
+                exchange: std::mem::take(exchange),
+            },
+            ("SRV", [priority, weight, port, target]) => RecordData::Srv {
+                priority: priority.parse().ok()?,
+                weight: weight.parse().ok()?,
+                port: port.parse().ok()?,
+                target: std::mem::take(target),
+            },
+            ("CAA", [flags, tag, value]) => RecordData::Caa {
+                flags: flags.parse().ok()?,
+                tag: std::mem::take(tag),
+                value: std::mem::take(value),
+            },
+            _ => return None,
+        };
+        Some(data)
+    }
+}
@@ -0,0 +1,217 @@
+//! Blocklist lookups of the addresses and hostnames that new records point
+//! at, so the shared parent domain is not lent to known spam or phishing
+//! infrastructure.
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::config::ReputationConfig;
+use crate::validation::is_public_ip;
+
+const SAFE_BROWSING_URL: &str = "https://safebrowsing.googleapis.com/v4/threatMatches:find";
+
+/// Parent domains of a hostname looked up besides the hostname itself.
+const MAX_PARENT_LOOKUPS: usize = 3;
+
+/// An address or hostname a record points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Address(IpAddr),
+    /// Lowercase, without the trailing dot.
+    Host(String),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Address(ip) => ip.fmt(f),
+            Target::Host(host) => f.write_str(host),
+        }
+    }
+}
+
+/// What a record of `rrtype` points at, if it points anywhere.
+pub fn target(rrtype: &str, content: &str) -> Option<Target> {
+    let name = match rrtype {
+        "A" | "AAAA" => return content.trim().parse().ok().map(Target::Address),
+        "CNAME" | "DNAME" | "NS" | "PTR" => content.trim(),
+        // the target comes after the priority (and weight and port)
+        "MX" | "SRV" => content.split_whitespace().last()?,
+        "HTTPS" | "SVCB" => content.split_whitespace().nth(1)?,
+        _ => return None,
+    };
+    let host = name.trim_end_matches('.').to_ascii_lowercase();
+    (!host.is_empty()).then_some(Target::Host(host))
+}
+
+/// A target found on a blocklist.
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit {
+    pub target: String,
+    /// Blocklist zone, or `safe_browsing:<threat type>`.
+    pub list: String,
+}
+
+/// Look `targets` up on every configured list. Lookups still running when
+/// the timeout expires, and lookups that fail, count as clean.
+pub async fn check(config: &ReputationConfig, targets: Vec<Target>) -> Vec<Hit> {
+    let mut lookups: JoinSet<Vec<Hit>> = JoinSet::new();
+    let mut hosts = Vec::new();
+    for target in targets {
+        let queries: Vec<(String, &String)> = match &target {
+            Target::Address(ip) if is_public_ip(*ip) => config
+                .ip_blocklists
+                .iter()
+                .map(|list| (format!("{}.{list}", reversed(*ip)), list))
+                .collect(),
+            Target::Address(_) => continue,
+            Target::Host(host) => {
+                hosts.push(host.clone());
+                config
+                    .domain_blocklists
+                    .iter()
+                    .flat_map(|list| {
+                        candidates(host).map(move |name| (format!("{name}.{list}"), list))
+                    })
+                    .collect()
+            }
+        };
+        for (query, list) in queries {
+            let hit = Hit {
+                target: target.to_string(),
+                list: list.clone(),
+            };
+            lookups.spawn(async move {
+                if is_listed(&query).await {
+                    vec![hit]
+                } else {
+                    Vec::new()
+                }
+            });
+        }
+    }
+    if let Some(key) = config.safe_browsing_key.clone()
+        && !hosts.is_empty()
+    {
+        lookups.spawn(async move {
+            safe_browsing(&key, &hosts).await.unwrap_or_else(|err| {
+                tracing::warn!("Safe Browsing lookup failed: {err:#}");
+                Vec::new()
+            })
+        });
+    }
+
+    let mut hits = Vec::new();
+    let collect = async {
+        while let Some(joined) = lookups.join_next().await {
+            if let Ok(found) = joined {
+                hits.extend(found);
+            }
+        }
+    };
+    if tokio::time::timeout(Duration::from_secs(config.timeout_secs), collect)
+        .await
+        .is_err()
+    {
+        tracing::debug!("reputation lookups timed out; unfinished ones count as clean");
+    }
+    hits
+}
+
+/// The hostname and up to [`MAX_PARENT_LOOKUPS`] of its parents with at
+/// least two labels, since domain blocklists list registered domains.
+fn candidates(host: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(host);
+    std::iter::from_fn(move || {
+        let current = rest?;
+        rest = current
+            .split_once('.')
+            .map(|(_, parent)| parent)
+            .filter(|parent| parent.contains('.'));
+        Some(current)
+    })
+    .take(MAX_PARENT_LOOKUPS + 1)
+}
+
+/// The address in DNSBL order: reversed octets, or reversed nibbles for IPv6.
+fn reversed(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            format!("{d}.{c}.{b}.{a}")
+        }
+        IpAddr::V6(v6) => v6
+            .octets()
+            .iter()
+            .rev()
+            .flat_map(|byte| [byte & 0x0f, byte >> 4])
+            .map(|nibble| format!("{nibble:x}"))
+            .collect::<Vec<_>>()
+            .join("."),
+    }
+}
+
+/// Whether a DNSBL query name resolves to a listing. Answers in
+/// 127.255.255.0/24 are the lists' error codes, such as "query refused".
+async fn is_listed(query: &str) -> bool {
+    let Ok(addrs) = tokio::net::lookup_host((query, 0)).await else {
+        return false;
+    };
+    addrs.into_iter().any(|addr| match addr.ip() {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            a == 127 && (b, c) != (255, 255)
+        }
+        IpAddr::V6(_) => false,
+    })
+}
+
+/// Ask the Google Safe Browsing v4 Lookup API about `hosts`.
+async fn safe_browsing(key: &str, hosts: &[String]) -> anyhow::Result<Vec<Hit>> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let client = CLIENT.get_or_init(reqwest::Client::new);
+
+    let entries: Vec<_> = hosts
+        .iter()
+        .map(|host| json!({ "url": format!("http://{host}/") }))
+        .collect();
+    let body = json!({
+        "client": { "clientId": "satsuki", "clientVersion": env!("CARGO_PKG_VERSION") },
+        "threatInfo": {
+            "threatTypes": ["MALWARE", "SOCIAL_ENGINEERING", "UNWANTED_SOFTWARE", "POTENTIALLY_HARMFUL_APPLICATION"],
+            "platformTypes": ["ANY_PLATFORM"],
+            "threatEntryTypes": ["URL"],
+            "threatEntries": entries,
+        },
+    });
+    let response: serde_json::Value = client
+        .post(SAFE_BROWSING_URL)
+        // a header keeps the key out of URLs in error messages
+        .header("x-goog-api-key", key)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let matches = response["matches"].as_array().cloned().unwrap_or_default();
+    Ok(matches
+        .iter()
+        .filter_map(|found| {
+            let url = found["threat"]["url"].as_str()?;
+            let host = url.strip_prefix("http://")?.trim_end_matches('/');
+            Some(Hit {
+                target: host.to_string(),
+                list: format!(
+                    "safe_browsing:{}",
+                    found["threatType"].as_str().unwrap_or("UNKNOWN")
+                ),
+            })
+        })
+        .collect())
+}
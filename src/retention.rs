@@ -0,0 +1,121 @@
+//! Retention of the append-only log tables: rows older than their table's
+//! `retention.*_days` are archived as gzipped NDJSON, to a directory and/or
+//! an S3-compatible bucket, and then deleted, so busy instances keep a
+//! bounded SQLite file.
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write;
+
+use crate::AppState;
+use crate::config::{PolicyConfig, RetentionConfig};
+use crate::db::retention_repo::{self, LogTable};
+use crate::jobs::{Job, Schedule};
+use crate::s3;
+
+/// Rows per archive file, and deleted per transaction.
+const BATCH_ROWS: i64 = 10_000;
+
+/// Scheduled pruning, per `[retention]` in the policy.
+pub struct RetentionJob;
+
+#[async_trait]
+impl Job for RetentionJob {
+    fn name(&self) -> &'static str {
+        "retention"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.retention;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let config = state.policy().retention.clone();
+        let mut summary = Vec::new();
+        for table in LogTable::ALL {
+            let Some(days) = retention_days(&config, table) else {
+                continue;
+            };
+            let before = Utc::now() - Duration::days(i64::from(days));
+            let pruned = prune(state, &config, table, before).await?;
+            summary.push(format!("{pruned} from {}", table.name()));
+        }
+        if summary.is_empty() {
+            return Ok("no retention periods configured".into());
+        }
+        Ok(format!("pruned {}", summary.join(", ")))
+    }
+}
+
+fn retention_days(config: &RetentionConfig, table: LogTable) -> Option<u32> {
+    match table {
+        LogTable::AuditLog => config.audit_log_days,
+        LogTable::ChangeHistory => config.change_history_days,
+        LogTable::WebhookDeliveries => config.webhook_deliveries_days,
+    }
+}
+
+/// Archive and delete the rows of `table` older than `before`, a batch at
+/// a time. A batch is only deleted once every archive target holds it.
+async fn prune(
+    state: &AppState,
+    config: &RetentionConfig,
+    table: LogTable,
+    before: chrono::DateTime<Utc>,
+) -> anyhow::Result<u64> {
+    let mut pruned = 0;
+    loop {
+        let rows = retention_repo::list_expired(&state.db, table, before, BATCH_ROWS).await?;
+        let (Some((first_id, _)), Some((last_id, _))) = (rows.first(), rows.last()) else {
+            return Ok(pruned);
+        };
+        let name = format!(
+            "{}-{first_id}-{last_id}-{}.ndjson.gz",
+            table.name(),
+            Utc::now().format("%Y%m%dT%H%M%SZ")
+        );
+        let mut ndjson = Vec::new();
+        for (_, row) in &rows {
+            ndjson.extend_from_slice(row.as_bytes());
+            ndjson.push(b'\n');
+        }
+        archive(config, &name, gzip(&ndjson)?).await?;
+        pruned += retention_repo::delete_expired(&state.db, table, before, *last_id).await?;
+        if (rows.len() as i64) < BATCH_ROWS {
+            return Ok(pruned);
+        }
+    }
+}
+
+/// `data` as one gzip member.
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Store one archive file in every configured target.
+async fn archive(config: &RetentionConfig, name: &str, data: Vec<u8>) -> anyhow::Result<()> {
+    if let Some(dir) = &config.archive_dir {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        // written under a temporary name so a crash never leaves a partial archive
+        let partial = dir.join(format!("{name}.partial"));
+        tokio::fs::write(&partial, &data)
+            .await
+            .with_context(|| format!("failed to write {}", partial.display()))?;
+        tokio::fs::rename(&partial, dir.join(name))
+            .await
+            .with_context(|| format!("failed to finish archive {name}"))?;
+    }
+    if let Some(bucket) = &config.s3 {
+        s3::put_object(bucket, name, "application/gzip", data).await?;
+    }
+    Ok(())
+}
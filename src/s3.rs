@@ -0,0 +1,37 @@
+//! Uploads to S3-compatible object storage with the `rust-s3` client,
+//! addressed path-style (`endpoint/bucket/key`), which MinIO, Garage, and
+//! the other common implementations accept.
+use anyhow::Context;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+
+use crate::config::S3Config;
+
+/// Store `body` as `key` (below the configured prefix) in the bucket.
+pub async fn put_object(
+    config: &S3Config,
+    key: &str,
+    content_type: &str,
+    body: Vec<u8>,
+) -> anyhow::Result<()> {
+    let region = Region::Custom {
+        region: config.region.clone(),
+        endpoint: config.endpoint.trim_end_matches('/').to_string(),
+    };
+    let credentials = Credentials::new(
+        Some(&config.access_key_id),
+        Some(&config.secret_access_key),
+        None,
+        None,
+        None,
+    )
+    .context("invalid S3 credentials")?;
+    let bucket = Bucket::new(&config.bucket, region, credentials)
+        .context("invalid S3 bucket")?
+        .with_path_style();
+    bucket
+        .put_object_with_content_type(format!("{}{key}", config.prefix), &body, content_type)
+        .await
+        .with_context(|| format!("S3 upload of {key} failed"))?;
+    Ok(())
+}
@@ -0,0 +1,275 @@
+//! Comparison of the SOA serial each internal nameserver serves for user
+//! zones with the serial on sub-PDNS, so secondaries that stopped
+//! replicating are noticed.
+use arc_swap::ArcSwapOption;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+use crate::AppState;
+use crate::config::PolicyConfig;
+use crate::db::user_repo;
+use crate::dns_probe::{self, SoaAnswer};
+use crate::jobs::{Job, Schedule};
+
+/// Time before mismatches are checked again; only those still there are
+/// reported, so a change that is still being transferred is not flagged.
+const CONFIRM_DELAY: Duration = Duration::from_secs(60);
+
+/// A nameserver serving a zone with another serial than sub-PDNS.
+#[derive(Debug, Clone, Serialize)]
+pub struct Mismatch {
+    /// Fully qualified zone name with trailing dot.
+    pub zone: String,
+    pub nameserver: String,
+    /// Serial on sub-PDNS.
+    pub expected: u32,
+    /// Serial the nameserver answered with; `None` when it gave no SOA.
+    pub actual: Option<u32>,
+    /// Why there is no serial: a lame answer or a query error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Result of one run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub zones_checked: usize,
+    pub nameservers: Vec<String>,
+    /// Nameservers that could not be resolved, with the error; they were skipped.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub unresolved: BTreeMap<String, String>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Checker state kept across runs.
+#[derive(Default)]
+pub struct Tracker {
+    /// Report of the latest finished run.
+    pub last: ArcSwapOption<Report>,
+}
+
+impl Tracker {
+    /// Prometheus lines describing the latest run, if any.
+    pub fn metrics(&self) -> String {
+        let Some(last) = self.last.load_full() else {
+            return String::new();
+        };
+        let mut body = String::from(
+            "# TYPE satsuki_soa_serial_mismatches gauge\n\
+# HELP satsuki_soa_serial_mismatches Zones whose serial on an internal nameserver differs from sub-PDNS\n",
+        );
+        for nameserver in &last.nameservers {
+            let count = last
+                .mismatches
+                .iter()
+                .filter(|mismatch| mismatch.nameserver == *nameserver)
+                .count();
+            body.push_str(&format!(
+                "satsuki_soa_serial_mismatches{{nameserver=\"{nameserver}\"}} {count}\n"
+            ));
+        }
+        body.push_str(&format!(
+            "# TYPE satsuki_soa_serial_check_last_run_timestamp_seconds gauge\n\
+satsuki_soa_serial_check_last_run_timestamp_seconds {}\n",
+            last.started_at.timestamp()
+        ));
+        body
+    }
+}
+
+/// Scheduled checks, per `[serial_check]` in the policy.
+pub struct SerialCheckJob;
+
+#[async_trait]
+impl Job for SerialCheckJob {
+    fn name(&self) -> &'static str {
+        "serial_check"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.serial_check;
+        config
+            .enabled
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let report = run(state).await?;
+        if report.unresolved.len() == report.nameservers.len() {
+            anyhow::bail!("none of the internal nameservers could be resolved");
+        }
+        Ok(format!(
+            "checked {} zones on {} nameservers, {} mismatch(es)",
+            report.zones_checked,
+            report.nameservers.len() - report.unresolved.len(),
+            report.mismatches.len()
+        ))
+    }
+}
+
+/// Check every (or a sample of) user zone on every internal nameserver and
+/// store the report.
+pub async fn run(state: &AppState) -> anyhow::Result<Arc<Report>> {
+    let (timeout, sample) = {
+        let policy = state.policy();
+        (
+            Duration::from_secs(policy.serial_check.timeout_secs),
+            policy.serial_check.sample,
+        )
+    };
+    let started_at = Utc::now();
+    let clock = Instant::now();
+
+    let mut zones: Vec<(String, String)> = user_repo::list_all(&state.db)
+        .await?
+        .into_iter()
+        .map(|user| {
+            let zone = state
+                .config
+                .user_zone_name(&user.subdomain, &user.base_domain);
+            (zone, user.base_domain)
+        })
+        .collect();
+    if let Some(sample) = sample {
+        choose(&mut zones, sample);
+    }
+
+    let mut nameservers = Vec::new();
+    let mut unresolved = BTreeMap::new();
+    for hostname in &state.config.internal_ns {
+        match dns_probe::resolve(hostname, timeout).await {
+            Ok(addrs) => nameservers.push((hostname.clone(), addrs)),
+            Err(err) => {
+                tracing::warn!("serial check: {err:#}");
+                unresolved.insert(hostname.clone(), format!("{err:#}"));
+            }
+        }
+    }
+
+    let expected = expected_serials(state, &zones).await?;
+    let targets: Vec<(String, u32)> = zones
+        .iter()
+        .filter_map(|(zone, _)| Some((zone.clone(), *expected.get(&normalize(zone))?)))
+        .collect();
+    let mut mismatches = check(&targets, &nameservers, timeout).await;
+
+    if !mismatches.is_empty() {
+        tokio::time::sleep(CONFIRM_DELAY).await;
+        let expected = expected_serials(state, &zones).await?;
+        let mut confirmed = Vec::new();
+        for mismatch in mismatches {
+            let Some(serial) = expected.get(&normalize(&mismatch.zone)) else {
+                continue;
+            };
+            let nameserver = nameservers
+                .iter()
+                .filter(|(hostname, _)| *hostname == mismatch.nameserver)
+                .cloned()
+                .collect::<Vec<_>>();
+            confirmed.extend(check(&[(mismatch.zone, *serial)], &nameserver, timeout).await);
+        }
+        mismatches = confirmed;
+    }
+
+    mismatches.sort_by(|a, b| (&a.nameserver, &a.zone).cmp(&(&b.nameserver, &b.zone)));
+    let mut per_nameserver: BTreeMap<&str, usize> = BTreeMap::new();
+    for mismatch in &mismatches {
+        *per_nameserver.entry(&mismatch.nameserver).or_default() += 1;
+    }
+    for (nameserver, count) in per_nameserver {
+        tracing::warn!("{nameserver} serves {count} zone(s) with a serial differing from sub-PDNS");
+    }
+
+    let report = Arc::new(Report {
+        started_at,
+        duration_ms: clock.elapsed().as_millis() as u64,
+        zones_checked: targets.len(),
+        nameservers: state.config.internal_ns.clone(),
+        unresolved,
+        mismatches,
+    });
+    state.serial_check.last.store(Some(report.clone()));
+    Ok(report)
+}
+
+/// Serials on sub-PDNS of the zones' base domains, keyed by normalized zone name.
+async fn expected_serials(
+    state: &AppState,
+    zones: &[(String, String)],
+) -> anyhow::Result<HashMap<String, u32>> {
+    let mut base_domains: Vec<&str> = zones.iter().map(|(_, base)| base.as_str()).collect();
+    base_domains.sort_unstable();
+    base_domains.dedup();
+
+    let mut serials = HashMap::new();
+    for base_domain in base_domains {
+        for zone in state.sub_pdns_for(base_domain).list_zones().await? {
+            if let Some(serial) = zone.serial {
+                serials.insert(normalize(&zone.name), serial);
+            }
+        }
+    }
+    Ok(serials)
+}
+
+/// Ask every nameserver for each zone's SOA and collect the differences.
+async fn check(
+    targets: &[(String, u32)],
+    nameservers: &[(String, Vec<SocketAddr>)],
+    timeout: Duration,
+) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    for (zone, expected) in targets {
+        let mut probes = JoinSet::new();
+        for (hostname, addrs) in nameservers.iter().cloned() {
+            let zone = zone.clone();
+            probes.spawn(async move {
+                let answer = dns_probe::query_soa_any(&addrs, &zone, timeout).await;
+                (hostname, answer)
+            });
+        }
+        while let Some(joined) = probes.join_next().await {
+            let Ok((nameserver, answer)) = joined else {
+                continue;
+            };
+            let (actual, detail) = match answer {
+                Ok(SoaAnswer::Authoritative { serial }) if serial == *expected => continue,
+                Ok(SoaAnswer::Authoritative { serial }) => (Some(serial), None),
+                Ok(SoaAnswer::Lame(detail)) => (None, Some(detail)),
+                Err(err) => (None, Some(format!("{err:#}"))),
+            };
+            mismatches.push(Mismatch {
+                zone: zone.clone(),
+                nameserver,
+                expected: *expected,
+                actual,
+                detail,
+            });
+        }
+    }
+    mismatches
+}
+
+/// Keep `count` randomly chosen entries of `items`.
+fn choose<T>(items: &mut Vec<T>, count: usize) {
+    let len = items.len();
+    for i in 0..count.min(len) {
+        let j = i + (OsRng.next_u64() % (len - i) as u64) as usize;
+        items.swap(i, j);
+    }
+    items.truncate(count);
+}
+
+/// Lowercase without the trailing dot, for comparisons.
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
@@ -0,0 +1,155 @@
+//! Graceful shutdown: waiting for SIGTERM/Ctrl-C and draining in-flight
+//! mutations and background jobs, bounded by a deadline.
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::systemd;
+
+/// How often `drain` re-checks the in-flight set.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Operations that must not be cut off halfway, keyed by a ticket number.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    inner: Arc<InFlightInner>,
+}
+
+#[derive(Default)]
+struct InFlightInner {
+    next: AtomicU64,
+    operations: Mutex<BTreeMap<u64, String>>,
+}
+
+/// Marks an operation as running until dropped.
+pub struct InFlightGuard {
+    inner: Arc<InFlightInner>,
+    ticket: u64,
+}
+
+impl InFlight {
+    /// Register an operation, described for the log should the drain time out.
+    pub fn begin(&self, description: impl Into<String>) -> InFlightGuard {
+        let ticket = self.inner.next.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .operations
+            .lock()
+            .expect("in-flight set poisoned")
+            .insert(ticket, description.into());
+        InFlightGuard {
+            inner: self.inner.clone(),
+            ticket,
+        }
+    }
+
+    /// Descriptions of the operations still running, oldest first.
+    pub fn pending(&self) -> Vec<String> {
+        let operations = self
+            .inner
+            .operations
+            .lock()
+            .expect("in-flight set poisoned");
+        operations.values().cloned().collect()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.inner
+            .operations
+            .lock()
+            .expect("in-flight set poisoned")
+            .is_empty()
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut operations) = self.inner.operations.lock() {
+            operations.remove(&self.ticket);
+        }
+    }
+}
+
+/// Broadcast of the shutdown deadline, set once a stop signal arrives.
+#[derive(Clone)]
+pub struct Shutdown {
+    deadline: watch::Receiver<Option<Instant>>,
+}
+
+impl Shutdown {
+    /// Start listening for SIGTERM and Ctrl-C; the drain gets `timeout` from then.
+    pub fn on_signal(timeout: Duration) -> Self {
+        let (sender, deadline) = watch::channel(None);
+        tokio::spawn(async move {
+            signal().await;
+            let _ = sender.send(Some(Instant::now() + timeout));
+        });
+        Self { deadline }
+    }
+
+    /// Resolve once shutdown was requested, yielding the drain deadline.
+    pub async fn requested(&self) -> Instant {
+        let mut deadline = self.deadline.clone();
+        let requested = deadline.wait_for(Option::is_some).await.map(|value| *value);
+        match requested {
+            Ok(Some(deadline)) => deadline,
+            // the signal task is gone, so no shutdown will ever be requested
+            _ => std::future::pending().await,
+        }
+    }
+
+    /// Resolve when the drain deadline passes.
+    pub async fn expired(&self) {
+        tokio::time::sleep_until(self.requested().await).await;
+    }
+}
+
+/// Wait for SIGTERM (sent by `systemctl stop`) or Ctrl-C.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to install CTRL+C handler: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut stream) => {
+                stream.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("failed to install SIGTERM handler: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("interrupt received, shutting down"),
+        _ = terminate => tracing::info!("SIGTERM received, shutting down"),
+    }
+    systemd::notify("STOPPING=1");
+}
+
+/// Wait until no tracked operation is running, or log the leftovers once
+/// `deadline` passes. Returns whether everything finished.
+pub async fn drain(in_flight: &InFlight, deadline: Instant) -> bool {
+    let idle = async {
+        while !in_flight.is_idle() {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    };
+    if tokio::time::timeout_at(deadline, idle).await.is_ok() {
+        return true;
+    }
+    for operation in in_flight.pending() {
+        tracing::warn!("shutdown deadline reached, abandoning {operation}");
+    }
+    false
+}
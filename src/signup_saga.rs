@@ -0,0 +1,341 @@
+//! Signup as a saga of persisted steps: reserve the label, create the sub
+//! zone, write its apex, delegate it in the parent zone, and activate the
+//! user. Each completed step is stored in `pending_signups`, so a signup
+//! that fails midway is resumed by the `signup_recovery` job, and rolled
+//! back once one step failed `signup_recovery.max_attempts` times.
+use anyhow::Context;
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use crate::api::public::{build_apex_ns_rrset, build_apex_soa_rrset, flag_lookalike};
+use crate::config::PolicyConfig;
+use crate::db::pending_signup_repo::{self, PendingSignup};
+use crate::db::{audit_repo, invite_repo, user_repo};
+use crate::jobs::{Job, Schedule};
+use crate::powerdns::types::{PdnsRrset, PdnsZoneCreate};
+use crate::{AppState, delegations};
+
+/// Audit log actor for rollbacks made by the recovery job.
+const ACTOR: &str = "signup_recovery";
+
+/// Labels whose saga is being advanced by this process, as
+/// `(subdomain, base_domain)`.
+static IN_FLIGHT: Mutex<BTreeSet<(String, String)>> = Mutex::new(BTreeSet::new());
+
+/// Last completed step of a signup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Reserved,
+    ZoneCreated,
+    ApexWritten,
+    Delegated,
+}
+
+impl Step {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Step::Reserved => "reserved",
+            Step::ZoneCreated => "zone_created",
+            Step::ApexWritten => "apex_written",
+            Step::Delegated => "delegated",
+        }
+    }
+
+    fn parse(step: &str) -> Option<Self> {
+        [
+            Step::Reserved,
+            Step::ZoneCreated,
+            Step::ApexWritten,
+            Step::Delegated,
+        ]
+        .into_iter()
+        .find(|known| known.as_str() == step)
+    }
+}
+
+/// Exclusive right of this task to advance one label's saga; released on drop.
+pub struct Claim {
+    key: (String, String),
+}
+
+impl Claim {
+    /// Claim the saga of `subdomain` under `base_domain`, unless another
+    /// task is already advancing it.
+    fn take(subdomain: &str, base_domain: &str) -> Option<Self> {
+        let key = (subdomain.to_string(), base_domain.to_string());
+        let mut in_flight = IN_FLIGHT.lock().expect("signup saga lock poisoned");
+        in_flight.insert(key.clone()).then_some(Claim { key })
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().expect("signup saga lock poisoned");
+        in_flight.remove(&self.key);
+    }
+}
+
+/// A claimed signup, ready to be advanced.
+pub struct Saga {
+    signup: PendingSignup,
+    _claim: Claim,
+}
+
+/// Why a label could not be reserved.
+#[derive(Debug)]
+pub enum ReserveError {
+    /// A user or another signup holds the label.
+    Taken,
+    Db(sqlx::Error),
+}
+
+/// First step: hold the label in the database. `flag` is the abuse reason
+/// and the resembled label, raised once the user exists.
+pub async fn reserve(
+    state: &AppState,
+    subdomain: &str,
+    base_domain: &str,
+    password_hash: &str,
+    invite_code: Option<&str>,
+    flag: Option<(&str, &str)>,
+) -> Result<Saga, ReserveError> {
+    let claim = Claim::take(subdomain, base_domain).ok_or(ReserveError::Taken)?;
+    let signup = pending_signup_repo::insert(
+        &state.db,
+        subdomain,
+        base_domain,
+        password_hash,
+        invite_code,
+        flag,
+        Step::Reserved.as_str(),
+    )
+    .await
+    .map_err(|err| match &err {
+        sqlx::Error::Database(db_err) if db_err.message().contains("UNIQUE") => ReserveError::Taken,
+        _ => ReserveError::Db(err),
+    })?
+    .ok_or(ReserveError::Taken)?;
+    Ok(Saga {
+        signup,
+        _claim: claim,
+    })
+}
+
+/// Run the remaining steps. Returns the new user's id, or the error of the
+/// step that failed, which is counted against the signup.
+pub async fn advance(state: &AppState, mut saga: Saga) -> anyhow::Result<i64> {
+    let zone_name = state
+        .config
+        .user_zone_name(&saga.signup.subdomain, &saga.signup.base_domain);
+    loop {
+        let step = Step::parse(&saga.signup.step)
+            .with_context(|| format!("unknown signup step {:?}", saga.signup.step))?;
+        let result = match step {
+            Step::Reserved => create_zone(state, &saga.signup, &zone_name).await,
+            Step::ZoneCreated => write_apex(state, &saga.signup, &zone_name).await,
+            Step::ApexWritten => delegate(state, &saga.signup, &zone_name).await,
+            Step::Delegated => {
+                return match activate(state, &saga.signup, &zone_name).await {
+                    Ok(user_id) => Ok(user_id),
+                    Err(err) => Err(fail(state, &saga.signup, err).await),
+                };
+            }
+        };
+        let next = match result {
+            Ok(next) => next,
+            Err(err) => return Err(fail(state, &saga.signup, err).await),
+        };
+        pending_signup_repo::set_step(&state.db, saga.signup.id, next.as_str()).await?;
+        saga.signup.step = next.as_str().to_string();
+        saga.signup.attempts = 0;
+    }
+}
+
+/// Count a failed attempt and hand the error back.
+async fn fail(state: &AppState, signup: &PendingSignup, err: anyhow::Error) -> anyhow::Error {
+    if let Err(db_err) =
+        pending_signup_repo::record_failure(&state.db, signup.id, &format!("{err:#}")).await
+    {
+        tracing::error!("failed to record signup failure: {db_err:?}");
+    }
+    err.context(format!(
+        "signup of {}.{} failed after step {}",
+        signup.subdomain, signup.base_domain, signup.step
+    ))
+}
+
+async fn create_zone(
+    state: &AppState,
+    signup: &PendingSignup,
+    zone_name: &str,
+) -> anyhow::Result<Step> {
+    let sub_pdns = state.sub_pdns_for(&signup.base_domain);
+    let zone = PdnsZoneCreate {
+        name: zone_name.to_string(),
+        kind: "Native".into(),
+        nameservers: state.config.internal_ns.clone(),
+    };
+    if let Err(err) = sub_pdns.create_zone(&zone).await {
+        // an earlier attempt may have created the zone before it failed
+        if signup.attempts == 0 || sub_pdns.get_zone(zone_name).await.is_err() {
+            return Err(err);
+        }
+    }
+    Ok(Step::ZoneCreated)
+}
+
+async fn write_apex(
+    state: &AppState,
+    signup: &PendingSignup,
+    zone_name: &str,
+) -> anyhow::Result<Step> {
+    let rrsets = [
+        build_apex_ns_rrset(&state.config, zone_name),
+        build_apex_soa_rrset(&state.config, zone_name),
+    ];
+    state
+        .sub_pdns_for(&signup.base_domain)
+        .patch_rrsets(zone_name, &rrsets)
+        .await?;
+    Ok(Step::ApexWritten)
+}
+
+async fn delegate(
+    state: &AppState,
+    signup: &PendingSignup,
+    zone_name: &str,
+) -> anyhow::Result<Step> {
+    let rrset = build_apex_ns_rrset(&state.config, zone_name);
+    delegations::patch(state, &signup.base_domain, &[rrset]).await?;
+    Ok(Step::Delegated)
+}
+
+async fn activate(
+    state: &AppState,
+    signup: &PendingSignup,
+    zone_name: &str,
+) -> anyhow::Result<i64> {
+    let user_id = pending_signup_repo::activate(&state.db, signup).await?;
+    if let (Some(reason), Some(similar)) = (&signup.flag_reason, &signup.flag_similar)
+        && let Err(err) = flag_lookalike(
+            state,
+            user_id,
+            zone_name,
+            &signup.subdomain,
+            similar,
+            reason,
+        )
+        .await
+    {
+        tracing::error!("failed to flag lookalike label {zone_name}: {err:?}");
+    }
+    Ok(user_id)
+}
+
+/// Undo the DNS steps of a signup, release its invite, and free its label.
+async fn roll_back(state: &AppState, signup: &PendingSignup) -> anyhow::Result<()> {
+    let zone_name = state
+        .config
+        .user_zone_name(&signup.subdomain, &signup.base_domain);
+    let step = Step::parse(&signup.step).unwrap_or(Step::Delegated);
+    // never touch DNS a user owns, however its row came about
+    let owned = user_repo::exists(&state.db, &signup.subdomain, &signup.base_domain).await?;
+
+    if !owned && step >= Step::ApexWritten {
+        let delete_rrset = PdnsRrset {
+            name: zone_name.clone(),
+            rrtype: "NS".into(),
+            ttl: state.config.ns_ttl,
+            changetype: Some("DELETE".into()),
+            records: Vec::new(),
+            comments: Vec::new(),
+        };
+        delegations::patch(state, &signup.base_domain, &[delete_rrset]).await?;
+    }
+    if !owned && step >= Step::ZoneCreated {
+        let sub_pdns = state.sub_pdns_for(&signup.base_domain);
+        if let Err(err) = sub_pdns.delete_zone(&zone_name).await
+            && sub_pdns.get_zone(&zone_name).await.is_ok()
+        {
+            return Err(err);
+        }
+    }
+    if let Some(code) = &signup.invite_code {
+        invite_repo::release(&state.db, code).await?;
+    }
+    pending_signup_repo::delete(&state.db, signup.id).await?;
+
+    let details = serde_json::json!({
+        "step": signup.step,
+        "attempts": signup.attempts,
+        "error": signup.last_error,
+    });
+    audit_repo::insert(&state.db, ACTOR, "signup.rolled_back", &zone_name, &details).await?;
+    tracing::warn!(
+        "rolled back signup of {zone_name} after step {}",
+        signup.step
+    );
+    Ok(())
+}
+
+/// Scheduled recovery, per `[signup_recovery]` in the policy.
+pub struct SignupRecoveryJob;
+
+#[async_trait]
+impl Job for SignupRecoveryJob {
+    fn name(&self) -> &'static str {
+        "signup_recovery"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.signup_recovery;
+        Some(Schedule::configured(
+            config.interval_secs,
+            config.schedule.as_deref(),
+        ))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let max_attempts = i64::from(state.policy().signup_recovery.max_attempts);
+        let (mut completed, mut rolled_back, mut pending) = (0, 0, 0);
+        for signup in pending_signup_repo::list(&state.db).await? {
+            // signups still in their request are left to it
+            let Some(claim) = Claim::take(&signup.subdomain, &signup.base_domain) else {
+                continue;
+            };
+            if signup.attempts >= max_attempts {
+                match roll_back(state, &signup).await {
+                    Ok(()) => rolled_back += 1,
+                    Err(err) => {
+                        tracing::error!(
+                            "failed to roll back signup of {}.{}: {err:#}",
+                            signup.subdomain,
+                            signup.base_domain
+                        );
+                        pending += 1;
+                    }
+                }
+                continue;
+            }
+            let label = format!("{}.{}", signup.subdomain, signup.base_domain);
+            let saga = Saga {
+                signup,
+                _claim: claim,
+            };
+            match advance(state, saga).await {
+                Ok(_) => {
+                    tracing::info!("completed signup of {label}");
+                    completed += 1;
+                }
+                Err(err) => {
+                    tracing::warn!("{err:#}");
+                    pending += 1;
+                }
+            }
+        }
+        Ok(format!(
+            "completed {completed} signup(s), rolled back {rolled_back}, {pending} still pending"
+        ))
+    }
+}
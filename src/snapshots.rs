@@ -0,0 +1,143 @@
+//! BIND-format exports of every user zone: on demand for the `export`
+//! subcommand, and as rotated point-in-time snapshots taken by a job.
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use std::path::{Path, PathBuf};
+
+use crate::AppState;
+use crate::config::PolicyConfig;
+use crate::db::user_repo;
+use crate::jobs::{Job, Schedule};
+
+/// Directory name format of one snapshot, e.g. `20250101T030000Z`.
+const SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Zones written by `export_all`.
+#[derive(Debug, Default)]
+pub struct Export {
+    pub exported: usize,
+    /// Zones that could not be exported, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Write every user zone as `<dir>/<zone>zone`.
+pub async fn export_all(state: &AppState, dir: &Path) -> anyhow::Result<Export> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut export = Export::default();
+    for user in user_repo::list_all(&state.db).await? {
+        let zone = state
+            .config
+            .user_zone_name(&user.subdomain, &user.base_domain);
+        let path = dir.join(format!("{zone}zone"));
+        let outcome = async {
+            let text = state
+                .sub_pdns_for(&user.base_domain)
+                .export_zone(&zone)
+                .await?;
+            tokio::fs::write(&path, text)
+                .await
+                .with_context(|| format!("failed to write {}", path.display()))
+        };
+        match outcome.await {
+            Ok(()) => export.exported += 1,
+            Err(err) => export.failed.push((zone, format!("{err:#}"))),
+        }
+    }
+    Ok(export)
+}
+
+/// Scheduled snapshots, per `[snapshots]` in the policy.
+pub struct SnapshotJob;
+
+#[async_trait]
+impl Job for SnapshotJob {
+    fn name(&self) -> &'static str {
+        "snapshots"
+    }
+
+    fn schedule(&self, policy: &PolicyConfig) -> Option<Schedule> {
+        let config = &policy.snapshots;
+        (config.enabled && config.dir.is_some())
+            .then(|| Schedule::configured(config.interval_secs, config.schedule.as_deref()))
+    }
+
+    async fn run(&self, state: &AppState) -> anyhow::Result<String> {
+        let (dir, keep) = {
+            let policy = state.policy();
+            let dir = policy
+                .snapshots
+                .dir
+                .clone()
+                .context("snapshots.dir is not set")?;
+            (dir, policy.snapshots.keep)
+        };
+        take_snapshot(state, &dir, keep).await
+    }
+}
+
+/// Export into a new timestamped directory under `root`, then delete all
+/// but the `keep` newest snapshots.
+pub async fn take_snapshot(state: &AppState, root: &Path, keep: usize) -> anyhow::Result<String> {
+    let name = Utc::now().format(SNAPSHOT_FORMAT).to_string();
+    // written under a temporary name so a crash never leaves a partial snapshot
+    let partial = root.join(format!("{name}.partial"));
+    let export = export_all(state, &partial).await?;
+    for (zone, err) in &export.failed {
+        tracing::warn!("snapshot {name}: failed to export {zone}: {err}");
+    }
+    tokio::fs::rename(&partial, root.join(&name))
+        .await
+        .with_context(|| format!("failed to finish snapshot {name}"))?;
+
+    let removed = rotate(root, keep).await?;
+    let summary = format!(
+        "snapshot {name}: {} zone(s) exported, {} failed, {removed} old snapshot(s) removed",
+        export.exported,
+        export.failed.len()
+    );
+    if export.failed.is_empty() {
+        Ok(summary)
+    } else {
+        anyhow::bail!("{summary}")
+    }
+}
+
+/// Remove the oldest snapshot directories beyond `keep`, and leftovers of
+/// interrupted runs. Other files in `root` are left alone.
+async fn rotate(root: &Path, keep: usize) -> anyhow::Result<usize> {
+    let mut snapshots: Vec<PathBuf> = Vec::new();
+    let mut stale: Vec<PathBuf> = Vec::new();
+    let mut entries = tokio::fs::read_dir(root)
+        .await
+        .with_context(|| format!("failed to list {}", root.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str() else {
+            continue;
+        };
+        let snapshot = name.strip_suffix(".partial").unwrap_or(name);
+        if NaiveDateTime::parse_from_str(snapshot, SNAPSHOT_FORMAT).is_err() {
+            continue;
+        }
+        if snapshot == name {
+            snapshots.push(entry.path());
+        } else {
+            stale.push(entry.path());
+        }
+    }
+    // the names sort chronologically
+    snapshots.sort();
+    let excess = snapshots.len().saturating_sub(keep);
+    let mut removed = 0;
+    for path in snapshots.into_iter().take(excess).chain(stale) {
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => removed += 1,
+            Err(err) => tracing::warn!("failed to remove snapshot {}: {err}", path.display()),
+        }
+    }
+    Ok(removed)
+}
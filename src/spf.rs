@@ -0,0 +1,193 @@
+//! Syntax check of SPF policies (RFC 7208) published in TXT records.
+use ipnet::{Ipv4Net, Ipv6Net};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Most DNS-querying terms an SPF evaluation may need (RFC 7208 section 4.6.4).
+const MAX_DNS_LOOKUPS: usize = 10;
+
+/// Findings for one SPF record.
+#[derive(Debug, Default)]
+pub struct Lint {
+    /// Problems that make receivers treat the record as a permanent error.
+    pub errors: Vec<String>,
+    /// Likely mistakes that still evaluate.
+    pub warnings: Vec<String>,
+}
+
+/// Whether the text of a TXT record is an SPF policy.
+pub fn is_spf(text: &str) -> bool {
+    let version = text.split_ascii_whitespace().next().unwrap_or_default();
+    version.eq_ignore_ascii_case("v=spf1")
+}
+
+/// Check an SPF policy. DNS lookups are counted for this record only;
+/// those of included records come on top.
+pub fn lint(text: &str) -> Lint {
+    let mut lint = Lint::default();
+    let mut lookups = 0;
+    let mut seen: Vec<String> = Vec::new();
+    let mut all_seen = false;
+    let mut after_all = false;
+    let mut redirect = false;
+    let mut exp = false;
+
+    for term in text.split_ascii_whitespace().skip(1) {
+        let lower = term.to_ascii_lowercase();
+        if let Some((name, value)) = modifier(&lower) {
+            match name {
+                "redirect" | "exp" => {
+                    let once = if name == "redirect" {
+                        &mut redirect
+                    } else {
+                        &mut exp
+                    };
+                    if *once {
+                        lint.errors
+                            .push(format!("\"{name}\" is given more than once"));
+                    }
+                    *once = true;
+                    if value.is_empty() {
+                        lint.errors.push(format!("\"{name}\" needs a domain"));
+                    }
+                    if name == "redirect" {
+                        lookups += 1;
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if all_seen && !after_all {
+            after_all = true;
+            lint.warnings.push(format!(
+                "mechanisms after \"all\" are never evaluated, starting at \"{term}\""
+            ));
+        }
+        let (qualifier, body) = match lower.chars().next() {
+            Some(q @ ('+' | '-' | '~' | '?')) => (q, &lower[1..]),
+            _ => ('+', lower.as_str()),
+        };
+        let split = body.find([':', '/']).unwrap_or(body.len());
+        let (mechanism, argument) = body.split_at(split);
+        let domain = argument.strip_prefix(':');
+        match mechanism {
+            "all" => {
+                if !argument.is_empty() {
+                    lint.errors
+                        .push(format!("\"all\" takes no argument in \"{term}\""));
+                }
+                if qualifier == '+' {
+                    lint.warnings.push(
+                        "\"+all\" lets every server on the internet send mail as this domain"
+                            .to_string(),
+                    );
+                }
+                all_seen = true;
+            }
+            "include" | "exists" => {
+                if domain.is_none_or(str::is_empty) {
+                    lint.errors
+                        .push(format!("\"{mechanism}\" needs a domain in \"{term}\""));
+                }
+                lookups += 1;
+            }
+            "a" | "mx" => {
+                if let Err(err) = check_dual_cidr(argument) {
+                    lint.errors.push(format!("{err} in \"{term}\""));
+                }
+                lookups += 1;
+            }
+            "ptr" => {
+                lint.warnings.push(
+                    "\"ptr\" is slow and unreliable; receivers may ignore it (RFC 7208 section 5.5)"
+                        .to_string(),
+                );
+                lookups += 1;
+            }
+            "ip4" => {
+                let valid = domain.is_some_and(|net| {
+                    net.parse::<Ipv4Addr>().is_ok() || net.parse::<Ipv4Net>().is_ok()
+                });
+                if !valid {
+                    lint.errors
+                        .push(format!("invalid IPv4 address or network in \"{term}\""));
+                }
+            }
+            "ip6" => {
+                let valid = domain.is_some_and(|net| {
+                    net.parse::<Ipv6Addr>().is_ok() || net.parse::<Ipv6Net>().is_ok()
+                });
+                if !valid {
+                    lint.errors
+                        .push(format!("invalid IPv6 address or network in \"{term}\""));
+                }
+            }
+            _ => {
+                lint.errors.push(format!("unknown mechanism \"{term}\""));
+                continue;
+            }
+        }
+
+        if seen.iter().any(|previous| previous == body) {
+            lint.warnings
+                .push(format!("\"{body}\" is listed more than once"));
+        } else {
+            seen.push(body.to_string());
+        }
+    }
+
+    if lookups > MAX_DNS_LOOKUPS {
+        lint.errors.push(format!(
+            "needs {lookups} DNS lookups, more than the limit of {MAX_DNS_LOOKUPS}"
+        ));
+    }
+    match (all_seen, redirect) {
+        (false, false) => lint.warnings.push(
+            "no \"all\" at the end; add \"-all\" or \"~all\" so other servers are not left neutral"
+                .to_string(),
+        ),
+        (true, true) => lint
+            .warnings
+            .push("\"redirect\" is ignored because the record has an \"all\"".to_string()),
+        _ => {}
+    }
+    lint
+}
+
+/// Name and value of a `name=value` modifier.
+fn modifier(term: &str) -> Option<(&str, &str)> {
+    let (name, value) = term.split_once('=')?;
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid_name.then_some((name, value))
+}
+
+/// `[:domain][/ip4-cidr][//ip6-cidr]` of the `a` and `mx` mechanisms.
+fn check_dual_cidr(argument: &str) -> Result<(), String> {
+    let (domain, cidr) = match argument.find('/') {
+        Some(at) => argument.split_at(at),
+        None => (argument, ""),
+    };
+    if domain == ":" {
+        return Err("empty domain".into());
+    }
+    if cidr.is_empty() {
+        return Ok(());
+    }
+    let (v4, v6) = match cidr.strip_prefix("//") {
+        Some(v6) => ("", v6),
+        None => {
+            let cidr = &cidr[1..];
+            cidr.split_once("//").unwrap_or((cidr, ""))
+        }
+    };
+    let in_range =
+        |prefix: &str, max: u8| prefix.is_empty() || prefix.parse::<u8>().is_ok_and(|p| p <= max);
+    if !in_range(v4, 32) || !in_range(v6, 128) {
+        return Err("invalid prefix length".into());
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! Aggregate instance statistics, recomputed from the database on a schedule.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::SharedState;
+use crate::db::user_repo;
+
+/// How often the cached statistics are recomputed.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Upper bounds of the `satsuki_zone_records` histogram buckets.
+const RECORD_BUCKETS: [i64; 10] = [0, 1, 5, 10, 25, 50, 100, 250, 500, 1000];
+
+/// Registration numbers for one base domain.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DomainStats {
+    pub delegations: i64,
+    pub internal_ns: i64,
+    pub external_ns: i64,
+}
+
+/// Instance-wide registration numbers.
+#[derive(Clone, Debug, Serialize)]
+pub struct InstanceStats {
+    pub delegations: i64,
+    pub internal_ns: i64,
+    pub external_ns: i64,
+    /// Keyed by base domain; every served domain is listed, even without users.
+    pub domains: BTreeMap<String, DomainStats>,
+    pub newest_signup_at: Option<DateTime<Utc>>,
+    /// Age of the newest signup at `computed_at`.
+    pub newest_signup_age_secs: Option<i64>,
+    pub computed_at: DateTime<Utc>,
+    /// Only exported as metrics.
+    #[serde(skip)]
+    pub zone_records: RecordHistogram,
+}
+
+/// Cumulative distribution of user records per zone, over the zones whose
+/// record count is known.
+#[derive(Clone, Debug, Default)]
+pub struct RecordHistogram {
+    /// Zones with at most `RECORD_BUCKETS[i]` records.
+    pub buckets: [i64; RECORD_BUCKETS.len()],
+    pub count: i64,
+    pub sum: i64,
+}
+
+impl RecordHistogram {
+    fn from_distribution(distribution: &[(i64, i64)]) -> Self {
+        let mut histogram = Self::default();
+        for &(records, zones) in distribution {
+            histogram.count += zones;
+            histogram.sum += records * zones;
+            for (bucket, bound) in histogram.buckets.iter_mut().zip(RECORD_BUCKETS) {
+                if records <= bound {
+                    *bucket += zones;
+                }
+            }
+        }
+        histogram
+    }
+}
+
+impl InstanceStats {
+    /// Prometheus lines for the delegation gauges and the record histogram.
+    pub fn metrics(&self) -> String {
+        let mut body = String::from(
+            "# TYPE satsuki_delegations gauge\n\
+# HELP satsuki_delegations Registered delegations by NS mode, from the database\n",
+        );
+        for (domain, stats) in &self.domains {
+            for (mode, count) in [
+                ("internal", stats.internal_ns),
+                ("external", stats.external_ns),
+            ] {
+                body.push_str(&format!(
+                    "satsuki_delegations{{domain=\"{domain}\",ns_mode=\"{mode}\"}} {count}\n"
+                ));
+            }
+        }
+
+        let histogram = &self.zone_records;
+        body.push_str(
+            "# TYPE satsuki_zone_records histogram\n\
+# HELP satsuki_zone_records User records per zone, as of each zone's last update\n",
+        );
+        for (bound, count) in RECORD_BUCKETS.iter().zip(histogram.buckets) {
+            body.push_str(&format!(
+                "satsuki_zone_records_bucket{{le=\"{bound}\"}} {count}\n"
+            ));
+        }
+        body.push_str(&format!(
+            "satsuki_zone_records_bucket{{le=\"+Inf\"}} {count}\n\
+satsuki_zone_records_sum {}\n\
+satsuki_zone_records_count {count}\n\
+# TYPE satsuki_stats_computed_timestamp_seconds gauge\n\
+satsuki_stats_computed_timestamp_seconds {}\n",
+            histogram.sum,
+            self.computed_at.timestamp(),
+            count = histogram.count,
+        ));
+        body
+    }
+}
+
+/// Count registrations in the database.
+pub async fn compute(state: &SharedState) -> sqlx::Result<InstanceStats> {
+    let counts = user_repo::counts_by_domain(&state.db).await?;
+    let distribution = user_repo::record_count_distribution(&state.db).await?;
+    let computed_at = Utc::now();
+
+    let mut domains: BTreeMap<String, DomainStats> = state
+        .config
+        .base_domain_roots()
+        .map(|domain| (domain.to_string(), DomainStats::default()))
+        .collect();
+    let mut newest_signup_at: Option<DateTime<Utc>> = None;
+    for row in counts {
+        newest_signup_at = newest_signup_at.max(row.newest_created_at);
+        let entry = domains.entry(row.base_domain).or_default();
+        entry.delegations = row.total;
+        entry.external_ns = row.external_ns;
+        entry.internal_ns = row.total - row.external_ns;
+    }
+
+    Ok(InstanceStats {
+        delegations: domains.values().map(|domain| domain.delegations).sum(),
+        internal_ns: domains.values().map(|domain| domain.internal_ns).sum(),
+        external_ns: domains.values().map(|domain| domain.external_ns).sum(),
+        domains,
+        newest_signup_at,
+        newest_signup_age_secs: newest_signup_at
+            .map(|created_at| (computed_at - created_at).num_seconds().max(0)),
+        computed_at,
+        zone_records: RecordHistogram::from_distribution(&distribution),
+    })
+}
+
+/// Recompute and cache the statistics now.
+pub async fn refresh(state: &SharedState) -> sqlx::Result<Arc<InstanceStats>> {
+    let stats = Arc::new(compute(state).await?);
+    state.stats.store(Some(stats.clone()));
+    Ok(stats)
+}
+
+/// Refresh the cached statistics every `REFRESH_INTERVAL`.
+pub fn spawn_refresher(state: SharedState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = refresh(&state).await {
+                tracing::error!("failed to refresh instance statistics: {err:?}");
+            }
+        }
+    })
+}
@@ -0,0 +1,97 @@
+//! systemd supervision: readiness and watchdog notifications (`sd_notify`)
+//! and socket activation (`LISTEN_FDS`). Everything is a no-op when the
+//! process was not started by systemd.
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// First file descriptor passed by socket activation (`SD_LISTEN_FDS_START`).
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Send a state line such as `READY=1` to the service manager.
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    if let Err(err) = notify_unix(state) {
+        tracing::warn!("sd_notify {state:?} failed: {err}");
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+#[cfg(unix)]
+fn notify_unix(state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path.as_ref())?;
+        }
+    }
+    Ok(())
+}
+
+/// The listening sockets handed over by systemd socket activation, in the
+/// order of the unit's `Listen*=` lines; empty when there are none.
+pub fn take_listeners() -> std::io::Result<Vec<std::net::TcpListener>> {
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+
+        let for_us = std::env::var("LISTEN_PID")
+            .ok()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .is_some_and(|pid| pid == std::process::id());
+        let count = std::env::var("LISTEN_FDS")
+            .ok()
+            .and_then(|fds| fds.parse::<i32>().ok())
+            .unwrap_or(0);
+        if !for_us || count < 1 {
+            return Ok(Vec::new());
+        }
+        (LISTEN_FDS_START..LISTEN_FDS_START + count)
+            .map(|fd| {
+                // SAFETY: systemd guarantees LISTEN_FDS descriptors starting at 3 are
+                // open and owned by this process, and nothing else takes ownership of them.
+                let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .collect()
+    }
+    #[cfg(not(unix))]
+    Ok(Vec::new())
+}
+
+/// Ping the watchdog at half the interval systemd asked for (`WatchdogSec=`).
+pub fn spawn_watchdog() -> Option<JoinHandle<()>> {
+    let for_us = std::env::var("WATCHDOG_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_none_or(|pid| pid == std::process::id());
+    let usec = std::env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.parse::<u64>().ok())
+        .filter(|usec| *usec > 0)?;
+    if !for_us {
+        return None;
+    }
+    let period = Duration::from_micros(usec) / 2;
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            notify("WATCHDOG=1");
+        }
+    }))
+}
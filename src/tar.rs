@@ -0,0 +1,60 @@
+//! Minimal writer of uncompressed POSIX ustar archives, for downloads
+//! assembled in memory.
+
+/// Size of a header or data block.
+const BLOCK: usize = 512;
+
+/// An archive of regular files, built in memory.
+pub struct TarBuilder {
+    buf: Vec<u8>,
+    mtime: u64,
+}
+
+impl TarBuilder {
+    /// Start an empty archive whose entries are stamped with `mtime`
+    /// (seconds since the epoch).
+    pub fn new(mtime: u64) -> Self {
+        Self {
+            buf: Vec::new(),
+            mtime,
+        }
+    }
+
+    /// Append a regular file. `path` must be shorter than 100 bytes.
+    pub fn append(&mut self, path: &str, data: &[u8]) {
+        assert!(path.len() < 100, "tar entry name too long: {path}");
+        let mut header = [0u8; BLOCK];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        write_octal(&mut header[100..108], 0o644);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], data.len() as u64);
+        write_octal(&mut header[136..148], self.mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // the checksum is computed with its own field set to spaces
+        header[148..156].fill(b' ');
+        let checksum: u64 = header.iter().map(|&byte| u64::from(byte)).sum();
+        write_octal(&mut header[148..155], checksum);
+
+        self.buf.extend_from_slice(&header);
+        self.buf.extend_from_slice(data);
+        let padding = (BLOCK - data.len() % BLOCK) % BLOCK;
+        self.buf.resize(self.buf.len() + padding, 0);
+    }
+
+    /// The archive, closed by the two zero blocks that mark its end.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buf.resize(self.buf.len() + 2 * BLOCK, 0);
+        self.buf
+    }
+}
+
+/// Zero-padded octal digits filling `field` but its last byte, which stays NUL.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}", width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
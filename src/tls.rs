@@ -0,0 +1,68 @@
+//! Native TLS termination with rustls, for deployments without a reverse proxy.
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+/// How often the certificate and key files are checked for changes.
+pub const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// PEM certificate chain and private key served by the listener.
+#[derive(Clone, Debug)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    /// Build the rustls configuration; ALPN offers `h2` and `http/1.1`.
+    pub async fn load(&self) -> anyhow::Result<RustlsConfig> {
+        // several rustls providers may be linked in; pin ring before first use
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        RustlsConfig::from_pem_file(&self.cert, &self.key)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to load TLS certificate {} / key {}",
+                    self.cert.display(),
+                    self.key.display()
+                )
+            })
+    }
+
+    /// Newest modification time of the two files.
+    fn modified(&self) -> Option<SystemTime> {
+        let mtime = |path: &Path| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        mtime(&self.cert).max(mtime(&self.key))
+    }
+
+    /// Swap in renewed files whenever either changes on disk. A bad pair is
+    /// logged and the previous certificate keeps being served.
+    pub fn spawn_reloader(self, config: RustlsConfig) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut loaded = self.modified();
+            let mut interval = tokio::time::interval(RELOAD_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let current = self.modified();
+                if current == loaded {
+                    continue;
+                }
+                match config.reload_from_pem_file(&self.cert, &self.key).await {
+                    Ok(()) => {
+                        tracing::info!("reloaded TLS certificate {}", self.cert.display());
+                        loaded = current;
+                    }
+                    Err(err) => {
+                        tracing::error!("TLS certificate reload failed, keeping previous: {err}")
+                    }
+                }
+            }
+        })
+    }
+}
@@ -1,8 +1,12 @@
 //! Validation helpers for user-provided labels and FQDNs.
 use regex::Regex;
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::config::IdnMode;
 
 /// Human-friendly validation errors surfaced to clients.
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, Clone)]
 pub enum ValidationError {
     #[error("subdomain is empty")]
     Empty,
@@ -14,8 +18,35 @@ pub enum ValidationError {
     LeadingOrTrailingHyphen,
     #[error("subdomain must not contain consecutive '--'")]
     DoubleHyphen,
+    #[error("subdomain is not a valid internationalized label")]
+    InvalidIdn,
+    #[error("subdomain mixes characters from several scripts")]
+    MixedScript,
+    #[error("name {name} is {len} octets long (max 253)")]
+    NameTooLong { name: String, len: usize },
+    #[error("label {label} is {len} octets long (max 63)")]
+    LabelTooLong { label: String, len: usize },
+    #[error("name {name} contains an empty label")]
+    EmptyLabel { name: String },
+    #[error("record name label {label} may only contain a-z, 0-9, '-', and a leading '_'")]
+    InvalidOwnerLabel { label: String },
+}
+
+impl ValidationError {
+    /// Whether a name broke the RFC 1035 length limits.
+    pub fn is_length_limit(&self) -> bool {
+        matches!(
+            self,
+            ValidationError::NameTooLong { .. } | ValidationError::LabelTooLong { .. }
+        )
+    }
 }
 
+/// Longest name in presentation form without the trailing dot (255 octets on the wire).
+pub const MAX_NAME_LENGTH: usize = 253;
+/// Longest single label.
+pub const MAX_LABEL_LENGTH: usize = 63;
+
 lazy_static::lazy_static! {
     /// Only lowercase letters, digits and '-'
     static ref SUBDOMAIN_RE: Regex = Regex::new(r"^[a-z0-9-]+$").unwrap();
@@ -41,6 +72,349 @@ pub fn validate_subdomain_name(name: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+/// Validate a requested subdomain label and return the form stored in DNS.
+///
+/// Unicode labels and `xn--` labels are accepted per `mode` and returned as
+/// lowercase ACE (punycode); other ASCII labels must pass
+/// `validate_subdomain_name` and are returned unchanged.
+pub fn normalize_subdomain_label(input: &str, mode: IdnMode) -> Result<String, ValidationError> {
+    let is_ace = input
+        .get(..4)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("xn--"));
+    if mode == IdnMode::Off || (input.is_ascii() && !is_ace) {
+        validate_subdomain_name(input)?;
+        return Ok(input.to_string());
+    }
+    if input.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+    if input.contains(['.', '\u{3002}', '\u{ff0e}', '\u{ff61}']) {
+        return Err(ValidationError::InvalidCharacters);
+    }
+    let ace = idna::domain_to_ascii_strict(input).map_err(|_| ValidationError::InvalidIdn)?;
+    if ace.len() > 63 {
+        return Err(ValidationError::TooLong);
+    }
+    let (unicode, decoded) = idna::domain_to_unicode(&ace);
+    decoded.map_err(|_| ValidationError::InvalidIdn)?;
+    if mode == IdnMode::Restricted && !is_single_script(&unicode) {
+        return Err(ValidationError::MixedScript);
+    }
+    Ok(ace)
+}
+
+/// Best-effort ACE form of a login name's label, for looking up accounts
+/// registered with a Unicode label; unconvertible input is returned as is.
+pub fn label_to_ascii(label: &str) -> Cow<'_, str> {
+    if label.is_ascii() {
+        return Cow::Borrowed(label);
+    }
+    idna::domain_to_ascii_strict(label).map_or(Cow::Borrowed(label), Cow::Owned)
+}
+
+/// Unicode form of a stored label; labels without punycode come back unchanged.
+pub fn label_to_unicode(label: &str) -> String {
+    match idna::domain_to_unicode(label) {
+        (unicode, Ok(())) => unicode,
+        (_, Err(_)) => label.to_string(),
+    }
+}
+
+/// Letters from other scripts, or with diacritics, that pass for a plain
+/// Latin letter.
+const CONFUSABLES: &[(char, char)] = &[
+    ('а', 'a'),
+    ('α', 'a'),
+    ('ɑ', 'a'),
+    ('à', 'a'),
+    ('á', 'a'),
+    ('â', 'a'),
+    ('ã', 'a'),
+    ('ä', 'a'),
+    ('å', 'a'),
+    ('ā', 'a'),
+    ('ь', 'b'),
+    ('с', 'c'),
+    ('ϲ', 'c'),
+    ('ç', 'c'),
+    ('ԁ', 'd'),
+    ('е', 'e'),
+    ('ё', 'e'),
+    ('è', 'e'),
+    ('é', 'e'),
+    ('ê', 'e'),
+    ('ë', 'e'),
+    ('ē', 'e'),
+    ('ɡ', 'g'),
+    ('һ', 'h'),
+    ('і', 'i'),
+    ('ı', 'i'),
+    ('ι', 'i'),
+    ('ì', 'i'),
+    ('í', 'i'),
+    ('î', 'i'),
+    ('ï', 'i'),
+    ('ī', 'i'),
+    ('ј', 'j'),
+    ('κ', 'k'),
+    ('ӏ', 'l'),
+    ('ℓ', 'l'),
+    ('ո', 'n'),
+    ('ñ', 'n'),
+    ('о', 'o'),
+    ('ο', 'o'),
+    ('σ', 'o'),
+    ('ò', 'o'),
+    ('ó', 'o'),
+    ('ô', 'o'),
+    ('õ', 'o'),
+    ('ö', 'o'),
+    ('ø', 'o'),
+    ('ō', 'o'),
+    ('р', 'p'),
+    ('ρ', 'p'),
+    ('ԛ', 'q'),
+    ('ѕ', 's'),
+    ('т', 't'),
+    ('ս', 'u'),
+    ('υ', 'u'),
+    ('ù', 'u'),
+    ('ú', 'u'),
+    ('û', 'u'),
+    ('ü', 'u'),
+    ('ū', 'u'),
+    ('ν', 'v'),
+    ('ѵ', 'v'),
+    ('ԝ', 'w'),
+    ('х', 'x'),
+    ('χ', 'x'),
+    ('у', 'y'),
+    ('ү', 'y'),
+    ('ý', 'y'),
+    ('ÿ', 'y'),
+    ('ᴢ', 'z'),
+];
+
+/// Digits written in place of letters ("leet speak").
+const DIGIT_SUBSTITUTES: &[(char, char)] = &[
+    ('0', 'o'),
+    ('1', 'l'),
+    ('3', 'e'),
+    ('4', 'a'),
+    ('5', 's'),
+    ('7', 't'),
+    ('8', 'b'),
+    ('9', 'g'),
+];
+
+/// Skeleton of a label for homograph checks: lookalike letters and digits
+/// become plain Latin letters, `i` and `l` merge, and `rn` and `vv` read as
+/// `m` and `w`. Labels with the same skeleton are easily mistaken for each other.
+pub fn confusable_skeleton(label: &str) -> String {
+    let skeleton: String = label_to_unicode(label)
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            let c = CONFUSABLES
+                .iter()
+                .chain(DIGIT_SUBSTITUTES)
+                .find_map(|(from, to)| (*from == c).then_some(*to))
+                .unwrap_or(c);
+            if c == 'i' { 'l' } else { c }
+        })
+        .collect();
+    skeleton.replace("rn", "m").replace("vv", "w")
+}
+
+/// Whether `a` becomes `b` by one insertion, deletion, substitution, or swap
+/// of adjacent characters; equal strings do not count.
+pub fn one_edit_apart(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+    if short.len() < long.len() {
+        return short[prefix..] == long[prefix + 1..];
+    }
+    if prefix == short.len() {
+        return false;
+    }
+    short[prefix + 1..] == long[prefix + 1..]
+        || (prefix + 1 < short.len()
+            && short[prefix] == long[prefix + 1]
+            && short[prefix + 1] == long[prefix]
+            && short[prefix + 2..] == long[prefix + 2..])
+}
+
+/// Writing systems told apart by the mixed-script check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Thai,
+    Georgian,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Bopomofo,
+    Han,
+    /// Anything else, identified by its 128-code-point block.
+    Other(u32),
+}
+
+/// Script of a letter; digits, hyphens, and combining marks have none.
+fn script_of(c: char) -> Option<Script> {
+    let script = match c as u32 {
+        0x30..=0x39 | 0x2d | 0x300..=0x36f | 0x30fc => return None,
+        0x61..=0x7a
+        | 0xc0..=0x24f
+        | 0x1e00..=0x1eff
+        | 0x2c60..=0x2c7f
+        | 0xa720..=0xa7ff
+        | 0xab30..=0xab6f => Script::Latin,
+        0x370..=0x3ff | 0x1f00..=0x1fff => Script::Greek,
+        0x400..=0x52f | 0x1c80..=0x1c8f | 0x2de0..=0x2dff | 0xa640..=0xa69f => Script::Cyrillic,
+        0x530..=0x58f => Script::Armenian,
+        0x590..=0x5ff => Script::Hebrew,
+        0x600..=0x6ff | 0x750..=0x77f | 0x8a0..=0x8ff => Script::Arabic,
+        0x900..=0x97f => Script::Devanagari,
+        0x980..=0x9ff => Script::Bengali,
+        0xe00..=0xe7f => Script::Thai,
+        0x10a0..=0x10ff => Script::Georgian,
+        0x1100..=0x11ff | 0x3130..=0x318f | 0xac00..=0xd7af => Script::Hangul,
+        0x3040..=0x309f => Script::Hiragana,
+        0x30a0..=0x30ff | 0x31f0..=0x31ff => Script::Katakana,
+        0x3100..=0x312f | 0x31a0..=0x31bf => Script::Bopomofo,
+        0x3005
+        | 0x3007
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xf900..=0xfaff
+        | 0x20000..=0x3ffff => Script::Han,
+        other => Script::Other(other >> 7),
+    };
+    Some(script)
+}
+
+/// Whether a label uses one script, or one of the combinations usual for
+/// Japanese, Chinese, and Korean (the UTS #39 "highly restrictive" level).
+fn is_single_script(label: &str) -> bool {
+    const COMBINATIONS: &[&[Script]] = &[
+        &[
+            Script::Latin,
+            Script::Han,
+            Script::Hiragana,
+            Script::Katakana,
+        ],
+        &[Script::Latin, Script::Han, Script::Bopomofo],
+        &[Script::Latin, Script::Han, Script::Hangul],
+    ];
+    let mut scripts: Vec<Script> = Vec::new();
+    for script in label.chars().filter_map(script_of) {
+        if !scripts.contains(&script) {
+            scripts.push(script);
+        }
+    }
+    scripts.len() <= 1
+        || COMBINATIONS
+            .iter()
+            .any(|allowed| scripts.iter().all(|script| allowed.contains(script)))
+}
+
+/// Check the RFC 1035 limits of a dotted name: at most 253 octets without
+/// the trailing dot, and 1 to 63 octets per label.
+pub fn validate_name_length(name: &str) -> Result<(), ValidationError> {
+    let trimmed = name.strip_suffix('.').unwrap_or(name);
+    if trimmed.len() > MAX_NAME_LENGTH {
+        return Err(ValidationError::NameTooLong {
+            name: name.to_string(),
+            len: trimmed.len(),
+        });
+    }
+    for label in trimmed.split('.') {
+        if label.is_empty() {
+            return Err(ValidationError::EmptyLabel {
+                name: name.to_string(),
+            });
+        }
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(ValidationError::LabelTooLong {
+                label: label.to_string(),
+                len: label.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Validate the labels a record owner adds below its zone (`www`,
+/// `_dmarc`, `_443._tcp`, `*.dev`). Unlike `validate_subdomain_name`, a label
+/// may start with `_` and contain `--` (as punycode does), and the leftmost
+/// label may be the wildcard `*`.
+pub fn validate_owner_labels(relative: &str) -> Result<(), ValidationError> {
+    for (index, label) in relative.split('.').enumerate() {
+        if index == 0 && label == "*" {
+            continue;
+        }
+        let rest = label.strip_prefix('_').unwrap_or(label);
+        let valid = !rest.is_empty()
+            && !rest.starts_with('-')
+            && !rest.ends_with('-')
+            && rest.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        if !valid {
+            return Err(ValidationError::InvalidOwnerLabel {
+                label: label.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether `domain` is a hostname made of letters, digits, and hyphens.
+pub fn is_hostname(domain: &str) -> bool {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+/// Whether `address` looks like a deliverable `local@domain` email address.
+pub fn is_email_address(address: &str) -> bool {
+    address.rsplit_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && local.len() <= 64
+            && !local.chars().any(|c| {
+                c.is_whitespace() || c.is_control() || matches!(c, '"' | '<' | '>' | ',' | ';')
+            })
+            && domain.contains('.')
+            && is_hostname(domain)
+    })
+}
+
 /// Validate that each label of the dotted name is a valid ASCII subdomain.
 pub fn validate_fqdn_ascii(domain: &str) -> Result<(), ValidationError> {
     // require trailing dot for clarity, or add it yourself
@@ -48,8 +422,77 @@ pub fn validate_fqdn_ascii(domain: &str) -> Result<(), ValidationError> {
     if d.is_empty() {
         return Err(ValidationError::Empty);
     }
+    validate_name_length(domain)?;
     for label in d.split('.') {
         validate_subdomain_name(label)?;
     }
     Ok(())
 }
+
+/// Whether an address is globally routable, i.e. not private, loopback, or another bogon.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    special_range(ip).is_none()
+}
+
+/// Kind of the special-purpose range holding a non-public address, such as
+/// `"private"` or `"loopback"`; `None` for globally routable addresses.
+pub fn special_range(ip: IpAddr) -> Option<&'static str> {
+    match ip {
+        IpAddr::V4(v4) => special_range_v4(v4),
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return special_range_v4(v4);
+            }
+            let segments = v6.segments();
+            if v6.is_unspecified() {
+                Some("unspecified")
+            } else if v6.is_loopback() {
+                Some("loopback")
+            } else if v6.is_multicast() {
+                Some("multicast")
+            } else if v6.is_unique_local() {
+                Some("private")
+            } else if v6.is_unicast_link_local() {
+                Some("link-local")
+            } else if segments[0] == 0x2001 && segments[1] == 0x0db8 {
+                // 2001:db8::/32
+                Some("documentation")
+            } else if segments[0] == 0x0100 && segments[1..4] == [0, 0, 0] {
+                // 100::/64
+                Some("discard-only")
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn special_range_v4(v4: Ipv4Addr) -> Option<&'static str> {
+    let [a, b, ..] = v4.octets();
+    if v4.is_unspecified() || a == 0 {
+        Some("unspecified")
+    } else if v4.is_private() {
+        Some("private")
+    } else if v4.is_loopback() {
+        Some("loopback")
+    } else if v4.is_link_local() {
+        Some("link-local")
+    } else if v4.is_broadcast() {
+        Some("broadcast")
+    } else if v4.is_documentation() {
+        Some("documentation")
+    } else if v4.is_multicast() {
+        Some("multicast")
+    } else if a == 100 && (b & 0xc0) == 64 {
+        // 100.64.0.0/10
+        Some("carrier-grade NAT")
+    } else if a == 198 && (b & 0xfe) == 18 {
+        // 198.18.0.0/15
+        Some("benchmarking")
+    } else if a >= 240 {
+        // 240.0.0.0/4
+        Some("reserved")
+    } else {
+        None
+    }
+}
@@ -0,0 +1,218 @@
+//! Signed delivery of change events to user-registered webhook endpoints.
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::config::Feature;
+use crate::db::webhook_repo::{self, Webhook};
+use crate::events::Event;
+use crate::validation::special_range;
+use crate::{SharedState, notifications};
+
+/// Header carrying `sha256=<hex HMAC of the body>`.
+pub const SIGNATURE_HEADER: &str = "x-satsuki-signature";
+/// Header carrying the event `type`.
+pub const EVENT_HEADER: &str = "x-satsuki-event";
+
+/// Pause before each retry; an event is attempted `RETRY_DELAYS.len() + 1` times.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(60),
+    Duration::from_secs(600),
+];
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Subscribe to the event bus and deliver every event to the owner's webhooks.
+pub fn spawn_dispatcher(state: SharedState) -> JoinHandle<()> {
+    let mut events = state.events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => dispatch(&state, event).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("webhook dispatcher fell behind, skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Resolver refusing hosts with any non-public address, so a webhook cannot
+/// make this server call its own loopback or LAN. The client connects to
+/// the addresses checked here, not to a second lookup.
+struct PublicAddrsOnly;
+
+impl Resolve for PublicAddrsOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            if let Some(range) = addrs.iter().find_map(|addr| special_range(addr.ip())) {
+                return Err(format!("{host} resolves to a {range} address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Client shared by all deliveries.
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            // a proxy would resolve the host itself, unchecked
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicAddrsOnly))
+            .build()
+            .expect("static reqwest configuration is valid")
+    });
+    &CLIENT
+}
+
+/// Fan an event out to each of the user's webhooks in the background. Also
+/// used directly for events not published on the bus.
+pub async fn dispatch(state: &SharedState, event: Event) {
+    if !state.policy().features.is_enabled(Feature::Webhooks) {
+        return;
+    }
+    match notifications::wants_webhook(state, &event).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            tracing::error!(
+                "failed to load notification preferences of user {}: {err:?}",
+                event.user_id
+            );
+            return;
+        }
+    }
+    let hooks = match webhook_repo::list_for_user(&state.db, event.user_id).await {
+        Ok(hooks) => hooks,
+        Err(err) => {
+            tracing::error!(
+                "failed to load webhooks for user {}: {err:?}",
+                event.user_id
+            );
+            return;
+        }
+    };
+    if hooks.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_vec(&event) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::error!("failed to serialize event: {err:?}");
+            return;
+        }
+    };
+    for hook in hooks {
+        tokio::spawn(deliver(
+            state.clone(),
+            hook,
+            event.type_name(),
+            body.clone(),
+        ));
+    }
+}
+
+/// POST one payload, retrying with backoff and logging every attempt.
+async fn deliver(state: SharedState, hook: Webhook, event_type: &'static str, body: Vec<u8>) {
+    let signature = sign(&hook.secret, &body);
+    for attempt in 1..=RETRY_DELAYS.len() + 1 {
+        // held for the attempt only; a pending retry is visible in the delivery log
+        let attempt_guard = state
+            .in_flight
+            .begin(format!("{event_type} delivery to webhook {}", hook.id));
+        let outcome = match literal_target(&hook.url) {
+            Some(error) => Err(error),
+            None => http_client()
+                .post(&hook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event_type)
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(|err| error_chain(&err)),
+        };
+        let (status_code, error) = match outcome {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                Some(format!("endpoint answered {}", response.status())),
+            ),
+            Err(error) => (None, Some(error)),
+        };
+
+        if let Err(err) = webhook_repo::record_delivery(
+            &state.db,
+            hook.id,
+            event_type,
+            attempt as i64,
+            status_code,
+            error.as_deref(),
+        )
+        .await
+        {
+            // the webhook was most likely deleted while we were retrying
+            tracing::debug!("failed to log delivery for webhook {}: {err:?}", hook.id);
+            return;
+        }
+        drop(attempt_guard);
+
+        match (error, RETRY_DELAYS.get(attempt - 1)) {
+            (None, _) => return,
+            (Some(_), Some(delay)) => tokio::time::sleep(*delay).await,
+            (Some(error), None) => {
+                tracing::warn!(
+                    "giving up on webhook {} after {attempt} attempts: {error}",
+                    hook.id
+                );
+            }
+        }
+    }
+}
+
+/// Why a URL naming its host by a non-public address may not be called;
+/// such hosts never reach the resolver. URLs stored before registration
+/// checked this are caught here.
+fn literal_target(url: &str) -> Option<String> {
+    let ip = match reqwest::Url::parse(url).ok()?.host()? {
+        url::Host::Ipv4(ip) => ip.into(),
+        url::Host::Ipv6(ip) => ip.into(),
+        url::Host::Domain(_) => return None,
+    };
+    special_range(ip).map(|range| format!("{ip} is a {range} address"))
+}
+
+/// An error with its causes, which reqwest's `Display` leaves out.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut text = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        text.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    text
+}
+
+/// Compute the signature header value for a payload.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("sha256={hex}")
+}